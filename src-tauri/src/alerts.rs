@@ -0,0 +1,53 @@
+use std::sync::Arc;
+use tauri::AppHandle;
+use crate::db::{AlertSeverity, Database};
+use crate::events;
+use crate::i18n::{Message, DEFAULT_LANG};
+use crate::ipc;
+use crate::routing;
+use crate::scripts::{self, ScriptEvent};
+use crate::state::AppState;
+use crate::tray;
+
+/// Guardar una alerta y notificarla al frontend con el evento `usb-alert`
+pub fn raise_alert(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    device_id: Option<&str>,
+    activity_log_id: Option<i64>,
+    alert_type: &str,
+    severity: AlertSeverity,
+    message: Message,
+) -> Option<i64> {
+    println!("[Alerts] {} ({}): {}", alert_type, severity.as_str(), message.render(DEFAULT_LANG));
+
+    let alert = match db
+        .create_alert(device_id, activity_log_id, alert_type, severity, &message)
+        .and_then(|id| db.get_alert(id))
+    {
+        Ok(alert) => alert,
+        Err(e) => {
+            println!("[DB] Error creating alert: {}", e);
+            return None;
+        }
+    };
+
+    ipc::publish("usb-alert", &alert);
+    scripts::fire(db, ScriptEvent::Alert, alert.device_id.as_deref(), &alert);
+    if let Some(app_handle) = app_handle {
+        events::emit(app_handle, "usb-alert", &alert);
+        tray::refresh(app_handle);
+    }
+    routing::dispatch(db, app_handle, &alert);
+
+    Some(alert.id)
+}
+
+#[tauri::command]
+pub async fn get_alerts(state: tauri::State<'_, AppState>, limit: i64, include_acknowledged: Option<bool>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_alerts(limit, include_acknowledged.unwrap_or(false)) {
+        Ok(alerts) => Ok(serde_json::json!({ "success": true, "alerts": alerts })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Day,
+    Week,
+    Month,
+}
+
+impl Granularity {
+    // Formato strftime de SQLite que define cada bucket
+    fn bucket_format(&self) -> &'static str {
+        match self {
+            Granularity::Day => "%Y-%m-%d",
+            Granularity::Week => "%Y-W%W",
+            Granularity::Month => "%Y-%m",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct DateRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[tauri::command]
+pub async fn get_activity_analytics(
+    state: tauri::State<'_, AppState>,
+    granularity: Granularity,
+    range: Option<DateRange>,
+) -> Result<serde_json::Value, String> {
+    let range = range.unwrap_or_default();
+
+    let db = &state.db;
+    match db.get_activity_buckets(granularity.bucket_format(), range.from, range.to) {
+        Ok(buckets) => Ok(serde_json::json!({ "success": true, "buckets": buckets })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Matriz 7x24 (día de la semana x hora local) de conexiones, global o de un dispositivo
+#[tauri::command]
+pub async fn get_activity_heatmap(state: tauri::State<'_, AppState>, device_id: Option<String>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_connection_hour_counts(device_id.as_deref()) {
+        Ok(counts) => {
+            let mut matrix = [[0i64; 24]; 7];
+            for (day, hour, count) in counts {
+                if day < 7 && hour < 24 {
+                    matrix[day][hour] = count;
+                }
+            }
+            let max = matrix.iter().flatten().copied().max().unwrap_or(0);
+
+            Ok(serde_json::json!({
+                "success": true,
+                "device_id": device_id,
+                "days": ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"],
+                "matrix": matrix,
+                "max": max,
+            }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Serie temporal de bytes usados por escaneo de un dispositivo
+#[tauri::command]
+pub async fn get_device_growth(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_device_growth(&device_id) {
+        Ok(series) => {
+            let change_bytes = match (series.first(), series.last()) {
+                (Some(first), Some(last)) => last.total_bytes - first.total_bytes,
+                _ => 0,
+            };
+
+            Ok(serde_json::json!({
+                "success": true,
+                "device_id": device_id,
+                "series": series,
+                "change_bytes": change_bytes,
+            }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
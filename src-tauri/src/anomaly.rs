@@ -0,0 +1,231 @@
+use chrono::{Local, Timelike};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::db::{AlertSeverity, Database, RuleKind};
+use crate::i18n::Message;
+use crate::rules;
+use crate::state::AppState;
+
+pub const ANOMALY_SETTINGS_KEY: &str = "anomaly";
+const ANOMALY_ALERT: &str = "ANOMALOUS_USAGE";
+
+/// Umbrales globales; `min_hour_share` y `volume_z_score` se pueden sobrescribir por dispositivo (ver rules.rs)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AnomalySettings {
+    pub enabled: bool,
+    /// Conexiones o escaneos previos necesarios antes de evaluar un dispositivo
+    pub min_samples: i64,
+    /// Fracción mínima de conexiones previas en la franja de ±1 hora para considerarla habitual
+    pub min_hour_share: f64,
+    /// Desviaciones estándar sobre la media de bytes escaneados a partir de las cuales se alerta
+    pub volume_z_score: f64,
+}
+
+impl Default for AnomalySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_samples: 10,
+            min_hour_share: 0.05,
+            volume_z_score: 3.0,
+        }
+    }
+}
+
+impl AnomalySettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(ANOMALY_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+/// Patrón aprendido de un dispositivo a partir de su historial en la base de datos
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DeviceBaseline {
+    /// Conexiones por hora local (0-23)
+    pub hours: [i64; 24],
+    pub connections: i64,
+    pub scans: i64,
+    pub mean_bytes: f64,
+    pub stddev_bytes: f64,
+}
+
+pub fn load_baseline(db: &Database, device_id: &str, exclude_activity_id: i64) -> Result<DeviceBaseline, String> {
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+
+    let mut baseline = DeviceBaseline::default();
+    for (_, hour, count) in db.get_connection_hour_counts(Some(device_id)).map_err(db_err)? {
+        baseline.hours[hour % 24] += count;
+        baseline.connections += count;
+    }
+
+    let (scans, mean, stddev) = db.get_scan_volume_stats(device_id, exclude_activity_id).map_err(db_err)?;
+    baseline.scans = scans;
+    baseline.mean_bytes = mean;
+    baseline.stddev_bytes = stddev;
+
+    Ok(baseline)
+}
+
+/// Comprobar una conexión recién registrada contra el patrón del dispositivo (hora y equipo).
+/// La propia conexión se descuenta del patrón.
+pub fn check_connection(db: &Arc<Database>, app_handle: Option<&AppHandle>, device_id: &str, activity_id: i64) {
+    let settings = AnomalySettings::load(db);
+    if !settings.enabled {
+        return;
+    }
+
+    check_connection_hour(db, app_handle, &settings, device_id, activity_id);
+    check_connection_machine(db, app_handle, &settings, device_id, activity_id);
+}
+
+// Alertar si el dispositivo se conecta a una hora en la que casi nunca lo hace
+fn check_connection_hour(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    settings: &AnomalySettings,
+    device_id: &str,
+    activity_id: i64,
+) {
+    let mut baseline = match load_baseline(db, device_id, activity_id) {
+        Ok(b) => b,
+        Err(e) => {
+            println!("[Anomaly] {}", e);
+            return;
+        }
+    };
+
+    let hour = Local::now().hour() as usize;
+    baseline.hours[hour] = (baseline.hours[hour] - 1).max(0);
+    let previous = baseline.connections - 1;
+    if previous < settings.min_samples {
+        return;
+    }
+
+    let min_share = match rules::threshold(db, device_id, RuleKind::UnusualHourShare, Some(settings.min_hour_share)) {
+        Some(min_share) => min_share,
+        None => return,
+    };
+
+    let window = baseline.hours[(hour + 23) % 24] + baseline.hours[hour] + baseline.hours[(hour + 1) % 24];
+    let share = window as f64 / previous as f64;
+    if share < min_share {
+        raise_alert(
+            db,
+            app_handle,
+            Some(device_id),
+            Some(activity_id),
+            ANOMALY_ALERT,
+            AlertSeverity::Medium,
+            Message::new(
+                "alert.unusual_hour",
+                serde_json::json!({
+                    "device": device_id,
+                    "hour": format!("{:02}", hour),
+                    "window": window,
+                    "previous": previous,
+                }),
+            ),
+        );
+    }
+}
+
+// Alertar si un dispositivo con historial suficiente aparece por primera vez en este equipo
+fn check_connection_machine(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    settings: &AnomalySettings,
+    device_id: &str,
+    activity_id: i64,
+) {
+    let machine = crate::machine::current();
+    let (previous, on_machine) = match db.count_machine_connections(device_id, &machine.machine_id, activity_id) {
+        Ok(counts) => counts,
+        Err(e) => {
+            println!("[Anomaly] Database error: {}", e);
+            return;
+        }
+    };
+
+    if previous >= settings.min_samples && on_machine == 0 {
+        raise_alert(
+            db,
+            app_handle,
+            Some(device_id),
+            Some(activity_id),
+            ANOMALY_ALERT,
+            AlertSeverity::Medium,
+            Message::new(
+                "alert.new_machine",
+                serde_json::json!({
+                    "device": device_id,
+                    "machine": machine.hostname.as_deref().unwrap_or(&machine.machine_id),
+                    "previous": previous,
+                }),
+            ),
+        );
+    }
+}
+
+/// Alertar si el volumen de datos de un escaneo se aleja mucho de lo habitual en el dispositivo
+pub fn check_scan_volume(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    device_id: &str,
+    activity_id: i64,
+    total_bytes: i64,
+) {
+    let settings = AnomalySettings::load(db);
+    if !settings.enabled {
+        return;
+    }
+
+    let (scans, mean, stddev) = match db.get_scan_volume_stats(device_id, activity_id) {
+        Ok(stats) => stats,
+        Err(e) => {
+            println!("[Anomaly] Database error: {}", e);
+            return;
+        }
+    };
+    // Sin variación previa no hay forma razonable de medir la desviación
+    if scans < settings.min_samples || stddev <= 0.0 {
+        return;
+    }
+
+    let max_z = match rules::threshold(db, device_id, RuleKind::ScanVolumeZScore, Some(settings.volume_z_score)) {
+        Some(max_z) => max_z,
+        None => return,
+    };
+
+    let z = (total_bytes as f64 - mean) / stddev;
+    if z >= max_z {
+        raise_alert(
+            db,
+            app_handle,
+            Some(device_id),
+            Some(activity_id),
+            ANOMALY_ALERT,
+            AlertSeverity::Medium,
+            Message::new(
+                "alert.scan_volume",
+                serde_json::json!({
+                    "device": device_id,
+                    "bytes": total_bytes,
+                    "mean": mean.round() as i64,
+                    "z": (z * 10.0).round() / 10.0,
+                }),
+            ),
+        );
+    }
+}
+
+#[tauri::command]
+pub async fn get_device_baseline(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    // -1 no coincide con ningún activity_log_id: se incluyen todos los escaneos
+    let baseline = load_baseline(&db, &device_id, -1)?;
+    Ok(serde_json::json!({ "success": true, "device_id": device_id, "baseline": baseline }))
+}
@@ -0,0 +1,274 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::audit;
+use crate::db::{Database, TrustLevel};
+use crate::kiosk;
+use crate::secrets::{self, SecretFields};
+use crate::state::AppState;
+
+pub const APPROVAL_SETTINGS_KEY: &str = "approval";
+const APPROVED_AUDIT_ACTION: &str = "TRUST_CHANGE_APPROVED";
+const DENIED_AUDIT_ACTION: &str = "TRUST_CHANGE_DENIED";
+const CONFIGURED_AUDIT_ACTION: &str = "APPROVAL_CONFIGURED";
+const PBKDF2_ROUNDS: u32 = 100_000;
+const TOTP_STEP_SECS: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Modo estricto: confiar en un dispositivo o desbloquearlo exige un código de una segunda persona
+/// (frase de administrador o TOTP). Solo se cambia con `configure_approval`, nunca con `set_setting`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ApprovalSettings {
+    pub enabled: bool,
+    /// PBKDF2-SHA256 de la frase de administrador, "<salt hex>$<hash hex>"
+    pub passphrase_hash: Option<String>,
+    /// Secreto TOTP en base32 (RFC 6238: SHA-1, 6 dígitos, 30 s)
+    pub totp_secret: Option<String>,
+}
+
+impl SecretFields for ApprovalSettings {
+    fn secret_fields(&mut self) -> Vec<(&'static str, &mut String)> {
+        self.totp_secret.iter_mut().map(|secret| ("approval.totp_secret", secret)).collect()
+    }
+}
+
+impl ApprovalSettings {
+    pub fn load(db: &Database) -> Self {
+        let mut settings: Self = db.get_setting(APPROVAL_SETTINGS_KEY).ok().flatten().unwrap_or_default();
+        secrets::reveal_all(&mut settings);
+        settings.totp_secret = settings.totp_secret.filter(|s| !s.is_empty());
+        settings
+    }
+
+    // El secreto TOTP se guarda en el llavero del sistema
+    fn save(&self, db: &Database) -> rusqlite::Result<()> {
+        let mut sealed = self.clone();
+        secrets::seal_all(db, &mut sealed);
+        db.set_setting(APPROVAL_SETTINGS_KEY, &sealed)
+    }
+
+    // Método con el que se ha aceptado el código, si alguno lo acepta
+    fn verify(&self, code: &str) -> Option<&'static str> {
+        let code = code.trim();
+        if let Some(secret) = self.totp_secret.as_deref().and_then(base32_decode) {
+            let step = chrono::Utc::now().timestamp() as u64 / TOTP_STEP_SECS;
+            // Un paso de margen a cada lado por desajuste de reloj
+            if (step.saturating_sub(1)..=step + 1).any(|s| constant_time_eq(totp(&secret, s).as_bytes(), code.as_bytes())) {
+                return Some("totp");
+            }
+        }
+        if let Some((salt, hash)) = self.passphrase_hash.as_deref().and_then(|h| h.split_once('$')) {
+            if constant_time_eq(hash_passphrase(code, salt).as_bytes(), hash.as_bytes()) {
+                return Some("passphrase");
+            }
+        }
+        None
+    }
+}
+
+/// Código de aprobación que acompaña a un cambio de confianza
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Approval {
+    pub code: String,
+    /// Quién aprueba (la segunda persona), solo para el registro de auditoría
+    #[serde(default)]
+    pub approver: Option<String>,
+}
+
+fn hash_passphrase(passphrase: &str, salt: &str) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS, &mut hash);
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// RFC 4226/6238: HMAC-SHA1 del contador y truncado dinámico
+fn totp(secret: &[u8], step: u64) -> String {
+    let mut mac = Hmac::<sha1::Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([digest[offset] & 0x7f, digest[offset + 1], digest[offset + 2], digest[offset + 3]]);
+    format!("{:0width$}", value % 10u32.pow(TOTP_DIGITS), width = TOTP_DIGITS as usize)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for chunk in bytes.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = u64::from_be_bytes([0, 0, 0, buffer[0], buffer[1], buffer[2], buffer[3], buffer[4]]);
+        let chars = (chunk.len() * 8).div_ceil(5);
+        for i in 0..chars {
+            output.push(BASE32_ALPHABET[((bits >> (35 - i * 5)) & 0x1f) as usize] as char);
+        }
+    }
+    output
+}
+
+// Admite minúsculas, espacios y relleno '=' como los muestran las apps de autenticación
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace() && *c != '=') {
+        let value = BASE32_ALPHABET.iter().position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    (!bytes.is_empty()).then_some(bytes)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Bytes aleatorios del generador del sistema, para claves, sales y nonces
+pub fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes).expect("System random number generator unavailable");
+    bytes
+}
+
+/// Si pasar `device_ids` a `level` necesita aprobación: confiar en ellos o desbloquearlos, con la aprobación activada
+pub fn needs_approval(db: &Database, device_ids: &[String], level: TrustLevel) -> bool {
+    ApprovalSettings::load(db).enabled
+        && (level == TrustLevel::Trusted
+            || (level != TrustLevel::Blocked
+                && device_ids.iter().any(|id| db.get_device_trust(id).unwrap_or_default() == TrustLevel::Blocked)))
+}
+
+/// Comprobar la aprobación de un cambio de confianza antes de aplicarlo. Hace falta si el modo está activo y
+/// algún dispositivo pasa a Trusted o deja de estar Blocked. Devuelve el método usado (None si no hacía falta).
+pub fn authorize(
+    db: &Database,
+    device_ids: &[String],
+    level: TrustLevel,
+    approval: Option<&Approval>,
+) -> Result<Option<&'static str>, String> {
+    if !needs_approval(db, device_ids, level) {
+        return Ok(None);
+    }
+
+    let settings = ApprovalSettings::load(db);
+    let approval = approval.ok_or("Approval code required to trust or unblock devices")?;
+    match settings.verify(&approval.code) {
+        Some(method) => Ok(Some(method)),
+        None => {
+            audit::record(
+                db,
+                DENIED_AUDIT_ACTION,
+                None,
+                &serde_json::json!({ "devices": device_ids, "to": level.as_str(), "approver": approval.approver }),
+            );
+            Err("Invalid approval code".to_string())
+        }
+    }
+}
+
+/// Registrar en auditoría cada dispositivo de un cambio aprobado (`previous`: confianza anterior de cada uno)
+pub fn record_approved(
+    db: &Database,
+    previous: &[(String, TrustLevel)],
+    level: TrustLevel,
+    method: &str,
+    approval: Option<&Approval>,
+) {
+    for (device_id, from) in previous {
+        audit::record(
+            db,
+            APPROVED_AUDIT_ACTION,
+            Some(device_id),
+            &serde_json::json!({
+                "from": from.as_str(),
+                "to": level.as_str(),
+                "method": method,
+                "approver": approval.and_then(|a| a.approver.clone()),
+                "os_user": crate::os_session::OsSession::current().user,
+            }),
+        );
+    }
+}
+
+/// `set_setting` no puede tocar la configuración de aprobación: bastaría con desactivarla
+pub fn check_writable(key: &str) -> Result<(), String> {
+    if key == APPROVAL_SETTINGS_KEY {
+        return Err("Approval settings can only be changed with configure_approval".to_string());
+    }
+    Ok(())
+}
+
+/// Ocultar el hash y el secreto al listar los ajustes
+pub fn redact(settings: &mut serde_json::Map<String, serde_json::Value>) {
+    settings.remove(APPROVAL_SETTINGS_KEY);
+}
+
+#[tauri::command]
+pub async fn get_approval_status(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    let settings = ApprovalSettings::load(db);
+    Ok(serde_json::json!({
+        "success": true,
+        "enabled": settings.enabled,
+        "passphrase": settings.passphrase_hash.is_some(),
+        "totp": settings.totp_secret.is_some(),
+    }))
+}
+
+/// Activar, cambiar o desactivar el modo estricto. Si ya está activo hace falta un código válido.
+/// Con `generate_totp` se crea un secreto nuevo, que solo se devuelve esta vez (para la app de autenticación).
+#[tauri::command]
+pub async fn configure_approval(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+    passphrase: Option<String>,
+    generate_totp: Option<bool>,
+    current_code: Option<String>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let mut settings = ApprovalSettings::load(&db);
+
+    if settings.enabled && settings.verify(current_code.as_deref().unwrap_or_default()).is_none() {
+        audit::record(&db, DENIED_AUDIT_ACTION, None, &serde_json::json!({ "configure": true }));
+        return Err("Invalid approval code".to_string());
+    }
+
+    if let Some(passphrase) = passphrase.filter(|p| !p.is_empty()) {
+        let salt: String = random_bytes(16).iter().map(|b| format!("{:02x}", b)).collect();
+        settings.passphrase_hash = Some(format!("{}${}", salt, hash_passphrase(&passphrase, &salt)));
+    }
+    let totp_secret = generate_totp.unwrap_or(false).then(|| base32_encode(&random_bytes(20)));
+    if let Some(ref secret) = totp_secret {
+        settings.totp_secret = Some(secret.clone());
+    }
+    if enabled && settings.passphrase_hash.is_none() && settings.totp_secret.is_none() {
+        return Err("Set a passphrase or generate a TOTP secret to enable approvals".to_string());
+    }
+    settings.enabled = enabled;
+
+    settings.save(&db).map_err(|e| format!("Database error: {}", e))?;
+    audit::record(
+        &db,
+        CONFIGURED_AUDIT_ACTION,
+        None,
+        &serde_json::json!({
+            "enabled": enabled,
+            "passphrase": settings.passphrase_hash.is_some(),
+            "totp": settings.totp_secret.is_some(),
+        }),
+    );
+
+    Ok(serde_json::json!({
+        "success": true,
+        "enabled": enabled,
+        "totp_secret": totp_secret,
+        "totp_uri": totp_secret.as_ref().map(|s| format!("otpauth://totp/USB%20Manager:approval?secret={}&issuer=USB%20Manager", s)),
+    }))
+}
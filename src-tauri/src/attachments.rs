@@ -0,0 +1,150 @@
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use crate::audit;
+use crate::db::{Database, DeviceAttachment};
+use crate::hashing::sha256_file;
+use crate::kiosk;
+use crate::state::AppState;
+
+const ATTACHMENTS_DIR: &str = "attachments";
+// Adjuntos pequeños: fotos, formularios escaneados
+const MAX_ATTACHMENT_BYTES: u64 = 20 * 1024 * 1024;
+const ADDED_AUDIT_ACTION: &str = "ATTACHMENT_ADDED";
+const DELETED_AUDIT_ACTION: &str = "ATTACHMENT_DELETED";
+
+// Tipo MIME por extensión, para que la interfaz sepa si puede mostrarlo
+fn mime_type(file_name: &str) -> Option<String> {
+    let extension = Path::new(file_name).extension()?.to_str()?.to_lowercase();
+    let mime = match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "heic" => "image/heic",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        _ => return None,
+    };
+    Some(mime.to_string())
+}
+
+// Copiar el archivo a <datos>/attachments/<dispositivo>/<fecha>_<nombre>
+fn store_copy(db: &Database, device_id: &str, path: &Path, file_name: &str) -> Result<PathBuf, String> {
+    let data_dir = db.path().parent().map(Path::to_path_buf).unwrap_or_default();
+    let folder: String = device_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '~' | '.') { c } else { '_' })
+        .collect();
+    let dir = data_dir.join(ATTACHMENTS_DIR).join(folder);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create attachments folder: {}", e))?;
+
+    let target = dir.join(format!("{}_{}", chrono::Utc::now().format("%Y%m%d%H%M%S"), file_name));
+    std::fs::copy(path, &target).map_err(|e| format!("Failed to copy attachment: {}", e))?;
+    Ok(target)
+}
+
+/// Adjuntar un archivo pequeño a la ficha de un dispositivo. Se guarda una copia con su hash.
+#[tauri::command]
+pub async fn add_device_attachment(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+    path: String,
+    description: Option<String>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    db.get_device(&device_id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Device not found: {}", device_id))?;
+
+    let source = Path::new(&path);
+    let metadata = std::fs::metadata(source).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    if !metadata.is_file() {
+        return Err(format!("Not a file: {}", path));
+    }
+    if metadata.len() > MAX_ATTACHMENT_BYTES {
+        return Err(format!("Attachments are limited to {} MB", MAX_ATTACHMENT_BYTES / (1024 * 1024)));
+    }
+
+    let file_name = source.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let stored = store_copy(&db, &device_id, source, &file_name)?;
+    let mut attachment = DeviceAttachment {
+        id: None,
+        device_id: device_id.clone(),
+        mime_type: mime_type(&file_name),
+        file_name,
+        stored_path: stored.to_string_lossy().to_string(),
+        file_size: metadata.len() as i64,
+        sha256: sha256_file(&stored).ok(),
+        description: description.filter(|d| !d.trim().is_empty()),
+        created_at: None,
+    };
+
+    match db.insert_device_attachment(&attachment) {
+        Ok(id) => attachment.id = Some(id),
+        Err(e) => {
+            let _ = std::fs::remove_file(&stored);
+            return Err(format!("Database error: {}", e));
+        }
+    }
+    audit::record(
+        &db,
+        ADDED_AUDIT_ACTION,
+        Some(&device_id),
+        &serde_json::json!({ "id": attachment.id, "file_name": attachment.file_name, "sha256": attachment.sha256 }),
+    );
+
+    Ok(serde_json::json!({ "success": true, "attachment": attachment }))
+}
+
+#[tauri::command]
+pub async fn get_device_attachments(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let attachments = db
+        .get_device_attachments(&device_id)
+        .map_err(|e| format!("Database error: {}", e))?;
+    Ok(serde_json::json!({ "success": true, "attachments": attachments }))
+}
+
+/// Contenido de un adjunto para mostrarlo. `intact` indica si su hash sigue siendo el del momento de adjuntarlo.
+#[tauri::command]
+pub async fn get_device_attachment_content(state: tauri::State<'_, AppState>, id: i64) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let attachment = db
+        .get_device_attachment(id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Attachment not found: {}", id))?;
+
+    let content = std::fs::read(&attachment.stored_path)
+        .map_err(|e| format!("Failed to read attachment {}: {}", attachment.stored_path, e))?;
+    let intact = attachment
+        .sha256
+        .as_ref()
+        .map(|expected| *expected == format!("{:x}", Sha256::digest(&content)));
+
+    Ok(serde_json::json!({ "success": true, "attachment": attachment, "intact": intact, "content": content }))
+}
+
+#[tauri::command]
+pub async fn delete_device_attachment(state: tauri::State<'_, AppState>, id: i64) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let attachment = db
+        .get_device_attachment(id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Attachment not found: {}", id))?;
+
+    db.delete_device_attachment(id).map_err(|e| format!("Database error: {}", e))?;
+    if let Err(e) = std::fs::remove_file(&attachment.stored_path) {
+        println!("[Attachments] Failed to remove {}: {}", attachment.stored_path, e);
+    }
+    audit::record(
+        &db,
+        DELETED_AUDIT_ACTION,
+        Some(&attachment.device_id),
+        &serde_json::json!({ "id": id, "file_name": attachment.file_name, "sha256": attachment.sha256 }),
+    );
+
+    Ok(serde_json::json!({ "success": true }))
+}
@@ -0,0 +1,18 @@
+use crate::db::Database;
+use crate::state::AppState;
+
+/// Guardar una acción en el registro de auditoría; los errores solo se registran en consola
+pub fn record(db: &Database, action: &str, device_id: Option<&str>, details: &serde_json::Value) {
+    if let Err(e) = db.insert_audit_entry(action, device_id, details) {
+        println!("[DB] Error writing audit entry: {}", e);
+    }
+}
+
+#[tauri::command]
+pub async fn get_audit_log(state: tauri::State<'_, AppState>, limit: i64, action: Option<String>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_audit_log(limit, action.as_deref()) {
+        Ok(entries) => Ok(serde_json::json!({ "success": true, "entries": entries })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,167 @@
+use crate::audit;
+use crate::kiosk;
+use crate::state::AppState;
+
+/// Argumento con el que se lanza al iniciar sesión: la ventana arranca oculta en la bandeja
+pub const AUTOSTART_ARG: &str = "--autostart";
+const ENABLED_AUDIT_ACTION: &str = "AUTOSTART_ENABLED";
+const DISABLED_AUDIT_ACTION: &str = "AUTOSTART_DISABLED";
+
+/// Si esta instancia la ha lanzado el inicio de sesión
+pub fn launched_at_login() -> bool {
+    std::env::args().any(|arg| arg == AUTOSTART_ARG)
+}
+
+fn executable() -> Result<String, String> {
+    std::env::current_exe()
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| format!("Failed to locate the executable: {}", e))
+}
+
+// Valor en HKCU\...\Run: solo para el usuario actual, sin permisos de administrador
+#[cfg(windows)]
+mod platform {
+    use winreg::enums::{HKEY_CURRENT_USER, KEY_READ, KEY_SET_VALUE};
+    use winreg::RegKey;
+
+    const RUN_KEY: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+    const VALUE_NAME: &str = "USBManager";
+
+    pub fn is_enabled() -> bool {
+        RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey_with_flags(RUN_KEY, KEY_READ)
+            .and_then(|key| key.get_value::<String, _>(VALUE_NAME))
+            .is_ok()
+    }
+
+    pub fn enable(executable: &str) -> Result<(), String> {
+        let (key, _) = RegKey::predef(HKEY_CURRENT_USER)
+            .create_subkey(RUN_KEY)
+            .map_err(|e| format!("Failed to open the Run key: {}", e))?;
+        key.set_value(VALUE_NAME, &format!("\"{}\" {}", executable, super::AUTOSTART_ARG))
+            .map_err(|e| format!("Failed to register autostart: {}", e))
+    }
+
+    pub fn disable() -> Result<(), String> {
+        let key = RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey_with_flags(RUN_KEY, KEY_SET_VALUE)
+            .map_err(|e| format!("Failed to open the Run key: {}", e))?;
+        match key.delete_value(VALUE_NAME) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove autostart: {}", e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+// LaunchAgent del usuario con RunAtLoad
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::path::PathBuf;
+
+    const LABEL: &str = "com.ale-z17.usb-manager";
+
+    fn agent_path() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME")?;
+        Some(PathBuf::from(home).join("Library/LaunchAgents").join(format!("{}.plist", LABEL)))
+    }
+
+    pub fn is_enabled() -> bool {
+        agent_path().is_some_and(|path| path.exists())
+    }
+
+    pub fn enable(executable: &str) -> Result<(), String> {
+        let path = agent_path().ok_or("HOME is not set")?;
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+            LABEL,
+            executable.replace('&', "&amp;").replace('<', "&lt;"),
+            super::AUTOSTART_ARG
+        );
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(&path, plist).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn disable() -> Result<(), String> {
+        match agent_path().map(std::fs::remove_file) {
+            Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove autostart: {}", e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Entrada .desktop en el autostart de XDG (~/.config/autostart)
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::path::PathBuf;
+
+    const DESKTOP_FILE: &str = "usb-manager.desktop";
+
+    fn desktop_path() -> Option<PathBuf> {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config.join("autostart").join(DESKTOP_FILE))
+    }
+
+    pub fn is_enabled() -> bool {
+        desktop_path().is_some_and(|path| path.exists())
+    }
+
+    pub fn enable(executable: &str) -> Result<(), String> {
+        let path = desktop_path().ok_or("HOME is not set")?;
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName=USB Manager\nExec=\"{}\" {}\nTerminal=false\nX-GNOME-Autostart-enabled=true\n",
+            executable.replace('\\', "\\\\").replace('"', "\\\""),
+            super::AUTOSTART_ARG
+        );
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        std::fs::write(&path, entry).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn disable() -> Result<(), String> {
+        match desktop_path().map(std::fs::remove_file) {
+            Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to remove autostart: {}", e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Arrancar la aplicación (oculta en la bandeja) al iniciar sesión el usuario actual
+#[tauri::command]
+pub async fn set_autostart(state: tauri::State<'_, AppState>, enabled: bool) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    if enabled {
+        platform::enable(&executable()?)?;
+    } else {
+        platform::disable()?;
+    }
+    println!("[Autostart] {}", if enabled { "Enabled" } else { "Disabled" });
+
+    let action = if enabled { ENABLED_AUDIT_ACTION } else { DISABLED_AUDIT_ACTION };
+    audit::record(&state.db, action, None, &serde_json::json!({}));
+    Ok(serde_json::json!({ "success": true, "enabled": platform::is_enabled() }))
+}
+
+#[tauri::command]
+pub async fn get_autostart_status() -> Result<serde_json::Value, String> {
+    Ok(serde_json::json!({ "success": true, "enabled": platform::is_enabled(), "launched_at_login": launched_at_login() }))
+}
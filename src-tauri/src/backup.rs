@@ -0,0 +1,469 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+use walkdir::WalkDir;
+use crate::alerts::raise_alert;
+use crate::db::{
+    AlertSeverity, BackupMode, BackupProfile, BackupRun, BackupStatus, ConflictPolicy, Database, SyncEntry,
+    TrustLevel,
+};
+use crate::events;
+use crate::hashing::sha256_file;
+use crate::i18n::Message;
+use crate::ipc;
+use crate::kiosk;
+use crate::state::AppState;
+
+const SYNC_CONFLICT_ALERT: &str = "SYNC_CONFLICT";
+// Archivos copiados entre dos eventos de progreso
+const PROGRESS_EVERY: i64 = 25;
+// FAT y exFAT guardan la fecha de modificación con 2 segundos de resolución
+const MTIME_TOLERANCE: Duration = Duration::from_secs(2);
+
+// Dispositivos con una copia en curso, para no lanzar dos a la vez
+static RUNNING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// `a` es posterior a `b` por más de la tolerancia de la fecha
+fn newer_than(a: SystemTime, b: SystemTime) -> bool {
+    a.duration_since(b).map(|d| d > MTIME_TOLERANCE).unwrap_or(false)
+}
+
+fn unchanged(source: &Path, dest: &Path) -> bool {
+    let size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).ok();
+    match (modified(source), modified(dest)) {
+        (Some(a), Some(b)) => size(source) == size(dest) && !newer_than(a, b) && !newer_than(b, a),
+        _ => false,
+    }
+}
+
+// "informe.pdf" -> "informe (1).pdf": el primer nombre libre o el que ya tiene esta versión.
+// Devuelve también si la versión ya estaba guardada, para no repetirla en cada copia.
+fn keep_both_path(source: &Path, dest: &Path) -> (PathBuf, bool) {
+    let stem = dest.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let extension = dest.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    (1..)
+        .map(|n| dest.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists() || unchanged(source, candidate))
+        .map(|candidate| {
+            let saved = candidate.exists();
+            (candidate, saved)
+        })
+        .unwrap_or_else(|| (dest.to_path_buf(), false))
+}
+
+// Copiar conservando la fecha de modificación, para reconocer el archivo sin cambios en la próxima copia
+fn copy_file(source: &Path, dest: &Path) -> std::io::Result<u64> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let bytes = std::fs::copy(source, dest)?;
+    if let Some(mtime) = modified(source) {
+        File::options().write(true).open(dest)?.set_modified(mtime)?;
+    }
+    Ok(bytes)
+}
+
+struct Progress<'a> {
+    app_handle: Option<&'a AppHandle>,
+    files_total: i64,
+    files_done: i64,
+}
+
+impl Progress<'_> {
+    fn advance(&mut self, run: &BackupRun, current: &Path) {
+        self.files_done += 1;
+        if self.files_done % PROGRESS_EVERY == 0 || self.files_done == self.files_total {
+            let payload = serde_json::json!({
+                "run_id": run.id,
+                "device_id": run.device_id,
+                "files_done": self.files_done,
+                "files_total": self.files_total,
+                "bytes_copied": run.bytes_copied,
+                "current": current.to_string_lossy(),
+            });
+            ipc::publish("backup-progress", &payload);
+            if let Some(app_handle) = self.app_handle {
+                events::emit(app_handle, "backup-progress", payload);
+            }
+        }
+    }
+}
+
+fn file_state(relative_path: String, path: &Path, sha256: Option<String>) -> Option<SyncEntry> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some(SyncEntry { relative_path, size: metadata.len() as i64, modified, sha256 })
+}
+
+// Si el archivo ha cambiado respecto a la última copia; con la fecha distinta pero el mismo hash no cuenta
+fn changed_since(path: &Path, base: &SyncEntry) -> bool {
+    let current = match file_state(String::new(), path, None) {
+        Some(current) => current,
+        None => return true,
+    };
+    if current.size != base.size {
+        return true;
+    }
+    if (current.modified - base.modified).unsigned_abs() <= MTIME_TOLERANCE.as_secs() {
+        return false;
+    }
+    match &base.sha256 {
+        Some(expected) => sha256_file(path).map(|actual| &actual != expected).unwrap_or(true),
+        None => true,
+    }
+}
+
+// Ruta relativa al destino de la copia, con '/' como separador
+fn sync_key(dest: &Path, target_root: &Path) -> String {
+    dest.strip_prefix(target_root).unwrap_or(dest).to_string_lossy().replace('\\', "/")
+}
+
+fn record_conflict(
+    db: &Database,
+    run: &mut BackupRun,
+    new_conflicts: &mut Vec<String>,
+    key: &str,
+    host_path: &str,
+    dest: &Path,
+    host_deleted: bool,
+) {
+    run.files_conflicted += 1;
+    match db.insert_sync_conflict(&run.device_id, run.id, key, host_path, &dest.to_string_lossy(), host_deleted) {
+        Ok(true) => new_conflicts.push(key.to_string()),
+        Ok(false) => {}
+        Err(e) => println!("[DB] Error saving sync conflict: {}", e),
+    }
+}
+
+/// Copiar las carpetas del perfil a `<montaje>/<target_dir>/<carpeta>`. Bloquea hasta terminar.
+pub fn run_backup(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    profile: &BackupProfile,
+    mount_point: &str,
+    activity_id: Option<i64>,
+) -> Result<BackupRun, String> {
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+    let mut run = BackupRun {
+        id: db.start_backup_run(&profile.device_id, activity_id).map_err(db_err)?,
+        device_id: profile.device_id.clone(),
+        activity_log_id: activity_id,
+        ..Default::default()
+    };
+    println!("[Backup] Run {} started for {}", run.id, profile.device_id);
+
+    let target_root = Path::new(mount_point).join(&profile.target_dir);
+    let sources: Vec<PathBuf> = profile.folders.iter().map(PathBuf::from).collect();
+    let walk_files = |root: &Path| {
+        WalkDir::new(root)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| e.into_path())
+            .collect::<Vec<_>>()
+    };
+
+    // Estado de la copia anterior, solo si se detectan conflictos
+    let base_state = if profile.detect_conflicts {
+        db.get_sync_state(&profile.device_id).map_err(db_err)?
+    } else {
+        HashMap::new()
+    };
+    let mut new_state: Vec<SyncEntry> = Vec::new();
+    // Conflictos que no estaban ya pendientes, para la alerta
+    let mut new_conflicts: Vec<String> = Vec::new();
+
+    let mut progress = Progress {
+        app_handle,
+        files_total: sources.iter().map(|s| walk_files(s).len() as i64).sum(),
+        files_done: 0,
+    };
+    let mut errors = Vec::new();
+
+    for source_root in &sources {
+        let folder_name = match source_root.file_name() {
+            Some(name) if source_root.is_dir() => name.to_os_string(),
+            _ => {
+                errors.push(format!("Not a folder: {}", source_root.display()));
+                continue;
+            }
+        };
+        let dest_root = target_root.join(folder_name);
+        let mut expected: HashSet<PathBuf> = HashSet::new();
+
+        for source in walk_files(source_root) {
+            let relative = source.strip_prefix(source_root).unwrap_or(&source);
+            let mut dest = dest_root.join(relative);
+            let key = sync_key(&dest, &target_root);
+            let base = base_state.get(&key);
+            expected.insert(dest.clone());
+
+            let copy = if !dest.exists() {
+                true
+            } else if unchanged(&source, &dest) {
+                // Ya están iguales: se conserva el hash conocido si sigue siendo el mismo archivo
+                let sha256 = base.filter(|b| !changed_since(&dest, b)).and_then(|b| b.sha256.clone());
+                new_state.extend(file_state(key.clone(), &dest, sha256));
+                false
+            } else if let Some(base) = base {
+                // Comparación a tres bandas con el estado de la última copia
+                match (changed_since(&source, base), changed_since(&dest, base)) {
+                    (true, true) => {
+                        record_conflict(db, &mut run, &mut new_conflicts, &key, &source.to_string_lossy(), &dest, false);
+                        new_state.push(base.clone());
+                        false
+                    }
+                    // Cambiado solo en el dispositivo (en el otro equipo): no se pisa
+                    (false, true) => {
+                        new_state.push(base.clone());
+                        false
+                    }
+                    (true, false) => true,
+                    (false, false) => false,
+                }
+            } else {
+                match profile.conflict {
+                    ConflictPolicy::Overwrite => true,
+                    ConflictPolicy::Skip => false,
+                    ConflictPolicy::Newer => match (modified(&source), modified(&dest)) {
+                        (Some(a), Some(b)) => newer_than(a, b),
+                        _ => true,
+                    },
+                    ConflictPolicy::KeepBoth => {
+                        let (path, saved) = keep_both_path(&source, &dest);
+                        dest = path;
+                        expected.insert(dest.clone());
+                        !saved
+                    }
+                }
+            };
+
+            if copy {
+                match copy_file(&source, &dest) {
+                    Ok(bytes) => {
+                        run.files_copied += 1;
+                        run.bytes_copied += bytes as i64;
+                        if profile.detect_conflicts {
+                            new_state.extend(file_state(sync_key(&dest, &target_root), &dest, sha256_file(&source).ok()));
+                        }
+                    }
+                    Err(e) => errors.push(format!("{}: {}", source.display(), e)),
+                }
+            } else {
+                run.files_skipped += 1;
+            }
+            progress.advance(&run, &source);
+        }
+
+        // Espejo: quitar del dispositivo lo que ya no está en el origen. Detectando conflictos, solo se
+        // borra lo que no ha cambiado desde la última copia; lo nuevo del dispositivo se deja.
+        if profile.mode == BackupMode::Mirror {
+            for dest in walk_files(&dest_root).into_iter().filter(|d| !expected.contains(d)) {
+                if profile.detect_conflicts {
+                    let key = sync_key(&dest, &target_root);
+                    match base_state.get(&key) {
+                        None => continue,
+                        Some(base) if changed_since(&dest, base) => {
+                            record_conflict(db, &mut run, &mut new_conflicts, &key, "", &dest, true);
+                            new_state.push(base.clone());
+                            continue;
+                        }
+                        Some(_) => {}
+                    }
+                }
+                match std::fs::remove_file(&dest) {
+                    Ok(_) => run.files_deleted += 1,
+                    Err(e) => errors.push(format!("{}: {}", dest.display(), e)),
+                }
+            }
+        }
+    }
+
+    if profile.detect_conflicts {
+        db.replace_sync_state(&profile.device_id, &new_state).map_err(db_err)?;
+    }
+    if !new_conflicts.is_empty() {
+        raise_alert(
+            db,
+            app_handle,
+            Some(&profile.device_id),
+            activity_id,
+            SYNC_CONFLICT_ALERT,
+            AlertSeverity::Medium,
+            Message::new(
+                "alert.sync_conflict",
+                serde_json::json!({ "device": profile.device_id, "count": new_conflicts.len(), "paths": new_conflicts.join(", ") }),
+            ),
+        );
+    }
+
+    run.status = Some(if errors.is_empty() { BackupStatus::Completed } else { BackupStatus::Failed });
+    if !errors.is_empty() {
+        run.error = Some(format!("{} errors, first: {}", errors.len(), errors[0]));
+    }
+    db.finish_backup_run(&run).map_err(db_err)?;
+
+    println!(
+        "[Backup] Run {} finished: {} copied, {} skipped, {} deleted, {} conflicts, {} bytes, {} errors",
+        run.id,
+        run.files_copied,
+        run.files_skipped,
+        run.files_deleted,
+        run.files_conflicted,
+        run.bytes_copied,
+        errors.len()
+    );
+    ipc::publish("backup-complete", &run);
+    if let Some(app_handle) = app_handle {
+        events::emit(app_handle, "backup-complete", &run);
+    }
+
+    Ok(run)
+}
+
+// Ejecutar la copia marcando el dispositivo como ocupado mientras dura
+fn run_exclusive(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    profile: &BackupProfile,
+    mount_point: &str,
+    activity_id: Option<i64>,
+) -> Result<BackupRun, String> {
+    {
+        let mut running = RUNNING.lock().unwrap();
+        if running.contains(&profile.device_id) {
+            return Err(format!("A backup is already running for {}", profile.device_id));
+        }
+        running.push(profile.device_id.clone());
+    }
+
+    let result = run_backup(db, app_handle, profile, mount_point, activity_id);
+    RUNNING.lock().unwrap().retain(|d| d != &profile.device_id);
+    result
+}
+
+/// Lanzar en segundo plano la copia de un dispositivo de confianza recién conectado, si tiene perfil
+pub fn on_connect(db: &Arc<Database>, app_handle: Option<&AppHandle>, device_id: &str, mount_point: &str, activity_id: i64) {
+    if db.get_device_trust(device_id).unwrap_or_default() != TrustLevel::Trusted {
+        return;
+    }
+    let profile = match db.get_backup_profile(device_id) {
+        Ok(Some(profile)) if profile.enabled && !profile.folders.is_empty() => profile,
+        _ => return,
+    };
+
+    let db = db.clone();
+    let app_handle = app_handle.cloned();
+    let mount_point = mount_point.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = run_exclusive(&db, app_handle.as_ref(), &profile, &mount_point, Some(activity_id)) {
+            println!("[Backup] {}", e);
+        }
+    });
+}
+
+fn validate(profile: &BackupProfile) -> Result<(), String> {
+    if profile.folders.iter().any(|f| f.trim().is_empty()) {
+        return Err("Backup folders cannot be empty".to_string());
+    }
+    let target = Path::new(&profile.target_dir);
+    if profile.target_dir.trim().is_empty()
+        || target.is_absolute()
+        || target.components().any(|c| matches!(c, std::path::Component::ParentDir))
+    {
+        return Err("Target folder must be a path inside the device".to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_backup_profile(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_backup_profile(&device_id) {
+        Ok(profile) => Ok(serde_json::json!({ "success": true, "profile": profile })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn set_backup_profile(state: tauri::State<'_, AppState>, profile: BackupProfile) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    validate(&profile)?;
+
+    let db = &state.db;
+    match db.set_backup_profile(&profile) {
+        Ok(_) => Ok(serde_json::json!({ "success": true, "profile": profile })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn delete_backup_profile(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = &state.db;
+    match db.delete_backup_profile(&device_id) {
+        Ok(deleted) => Ok(serde_json::json!({ "success": true, "deleted": deleted })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn get_sync_conflicts(state: tauri::State<'_, AppState>, device_id: Option<String>, include_resolved: Option<bool>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_sync_conflicts(device_id.as_deref(), include_resolved.unwrap_or(false)) {
+        Ok(conflicts) => Ok(serde_json::json!({ "success": true, "conflicts": conflicts })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Marcar un conflicto como resuelto una vez el usuario ha decidido qué versión conservar
+#[tauri::command]
+pub async fn resolve_sync_conflict(state: tauri::State<'_, AppState>, id: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.resolve_sync_conflict(id) {
+        Ok(resolved) => Ok(serde_json::json!({ "success": resolved })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn get_backup_history(state: tauri::State<'_, AppState>, device_id: Option<String>, limit: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_backup_runs(device_id.as_deref(), limit) {
+        Ok(runs) => Ok(serde_json::json!({ "success": true, "runs": runs })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Ejecutar ahora la copia de un dispositivo conectado, sin esperar a la próxima conexión
+#[tauri::command]
+pub async fn run_backup_now(
+    state: tauri::State<'_, AppState>,
+    app_handle: AppHandle,
+    device_id: String,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let mount_point = state.monitor
+        .mount_point_for(&device_id)
+        .ok_or_else(|| format!("Device is not connected: {}", device_id))?;
+    let profile = db
+        .get_backup_profile(&device_id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("No backup profile for {}", device_id))?;
+    let activity_id = state.monitor.session_map.lock().unwrap().get(&device_id).copied();
+
+    let run = tauri::async_runtime::spawn_blocking(move || {
+        run_exclusive(&db, Some(&app_handle), &profile, &mount_point, activity_id)
+    })
+    .await
+    .map_err(|e| format!("Backup task failed: {}", e))??;
+
+    Ok(serde_json::json!({ "success": run.error.is_none(), "run": run }))
+}
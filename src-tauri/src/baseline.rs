@@ -0,0 +1,167 @@
+use std::sync::Arc;
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::audit;
+use crate::compare::{diff_snapshots, scan_root, SnapshotDiff};
+use crate::db::{AlertSeverity, Database, ScanInfo};
+use crate::i18n::Message;
+use crate::kiosk;
+use crate::state::AppState;
+
+const BASELINE_DEVIATION_ALERT: &str = "BASELINE_DEVIATION";
+const BASELINE_SET_AUDIT_ACTION: &str = "DEVICE_BASELINE_SET";
+const BASELINE_CLEARED_AUDIT_ACTION: &str = "DEVICE_BASELINE_CLEARED";
+
+// Escaneo de referencia del dispositivo; error si no tiene o si ya no existe
+fn load_baseline(db: &Database, device_id: &str) -> Result<ScanInfo, String> {
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+    let activity_id = db
+        .get_device_baseline(device_id)
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Device {} has no baseline scan", device_id))?;
+    db.get_scan_info(activity_id)
+        .map_err(db_err)?
+        .ok_or_else(|| format!("Baseline scan {} no longer exists", activity_id))
+}
+
+// Diferencias de un escaneo respecto a la referencia (A = referencia, B = escaneo)
+fn diff_against(db: &Database, baseline: &ScanInfo, scan: &ScanInfo) -> Result<SnapshotDiff, String> {
+    let load = |scan: &ScanInfo| -> Result<_, String> {
+        let snapshots = db
+            .get_file_snapshots(scan.activity_log_id)
+            .map_err(|e| format!("Database error: {}", e))?;
+        let root = scan_root(scan, &snapshots);
+        Ok((snapshots, root))
+    };
+    let (baseline_snaps, baseline_root) = load(baseline)?;
+    let (scan_snaps, scan_root) = load(scan)?;
+    Ok(diff_snapshots(&baseline_snaps, &baseline_root, &scan_snaps, &scan_root))
+}
+
+fn has_deviation(diff: &SnapshotDiff) -> bool {
+    !diff.only_in_a.is_empty() || !diff.only_in_b.is_empty() || !diff.modified.is_empty()
+}
+
+/// Comparar un escaneo recién terminado con la referencia del dispositivo (si tiene) y alertar
+/// de cualquier diferencia: en unidades de herramientas o de arranque el contenido no debería cambiar
+pub fn check_scan(db: &Arc<Database>, app_handle: Option<&AppHandle>, device_id: &str, activity_id: i64) {
+    let baseline = match db.get_device_baseline(device_id) {
+        Ok(Some(baseline)) if baseline != activity_id => baseline,
+        Ok(_) => return,
+        Err(e) => {
+            println!("[DB] Error loading baseline: {}", e);
+            return;
+        }
+    };
+    let diff = match (db.get_scan_info(baseline), db.get_scan_info(activity_id)) {
+        (Ok(Some(baseline)), Ok(Some(scan))) => diff_against(db, &baseline, &scan),
+        _ => Err(format!("Missing scan {} or {}", baseline, activity_id)),
+    };
+    let diff = match diff {
+        Ok(diff) => diff,
+        Err(e) => {
+            println!("[Baseline] Could not compare {}: {}", device_id, e);
+            return;
+        }
+    };
+    if !has_deviation(&diff) {
+        return;
+    }
+
+    raise_alert(
+        db,
+        app_handle,
+        Some(device_id),
+        Some(activity_id),
+        BASELINE_DEVIATION_ALERT,
+        AlertSeverity::High,
+        Message::new(
+            "alert.baseline_deviation",
+            serde_json::json!({
+                "device": device_id,
+                "added": diff.only_in_b.len(),
+                "removed": diff.only_in_a.len(),
+                "modified": diff.modified.len(),
+            }),
+        ),
+    );
+}
+
+/// Marcar un escaneo como referencia del dispositivo (sin `activity_id`, el último).
+/// Los escaneos posteriores se comparan con él al conectar.
+#[tauri::command]
+pub async fn set_device_baseline(state: tauri::State<'_, AppState>, device_id: String, activity_id: Option<i64>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+
+    let scan = match activity_id {
+        Some(activity_id) => db
+            .get_scan_info(activity_id)
+            .map_err(db_err)?
+            .filter(|scan| scan.device_id == device_id)
+            .ok_or_else(|| format!("Scan {} does not belong to device {}", activity_id, device_id))?,
+        None => db
+            .get_latest_scan(&device_id)
+            .map_err(db_err)?
+            .ok_or_else(|| format!("No scans found for device {}", device_id))?,
+    };
+    if db.get_file_snapshots(scan.activity_log_id).map_err(db_err)?.is_empty() {
+        return Err(format!("Scan {} has no files to use as baseline", scan.activity_log_id));
+    }
+
+    if !db.set_device_baseline(&device_id, Some(scan.activity_log_id)).map_err(db_err)? {
+        return Err(format!("Device not found: {}", device_id));
+    }
+    audit::record(&db, BASELINE_SET_AUDIT_ACTION, Some(&device_id), &serde_json::json!({ "activity_id": scan.activity_log_id }));
+    println!("[Baseline] Scan {} is now the baseline of {}", scan.activity_log_id, device_id);
+    Ok(serde_json::json!({ "success": true, "baseline": scan }))
+}
+
+#[tauri::command]
+pub async fn clear_device_baseline(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let cleared = db
+        .set_device_baseline(&device_id, None)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if cleared {
+        audit::record(&db, BASELINE_CLEARED_AUDIT_ACTION, Some(&device_id), &serde_json::json!({}));
+    }
+    Ok(serde_json::json!({ "success": cleared }))
+}
+
+/// Diferencias del último escaneo del dispositivo respecto a su referencia
+#[tauri::command]
+pub async fn compare_to_baseline(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let baseline = load_baseline(&db, &device_id)?;
+    let latest = db
+        .get_recent_scans(&device_id, 1)
+        .map_err(|e| format!("Database error: {}", e))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("No scans found for device {}", device_id))?;
+
+    let diff = diff_against(&db, &baseline, &latest)?;
+    println!(
+        "[Baseline] {} vs baseline: {} added, {} removed, {} modified",
+        device_id,
+        diff.only_in_b.len(),
+        diff.only_in_a.len(),
+        diff.modified.len()
+    );
+
+    Ok(serde_json::json!({
+        "success": true,
+        "device_id": device_id,
+        "baseline_scan": baseline,
+        "latest_scan": latest,
+        "matches": !has_deviation(&diff),
+        "added": diff.only_in_b,
+        "removed": diff.only_in_a,
+        "modified": diff.modified,
+        "unchanged": diff.in_both.len(),
+        "compared_by_hash": diff.compared_by_hash,
+    }))
+}
@@ -0,0 +1,129 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::approval::{self, Approval};
+use crate::audit;
+use crate::db::TrustLevel;
+use crate::kiosk;
+use crate::state::AppState;
+use crate::tray;
+
+// Borrar el historial exige dos llamadas: el token caduca pronto y sirve una sola vez
+const CLEAR_TOKEN_TTL: Duration = Duration::from_secs(120);
+const CLEAR_AUDIT_ACTION: &str = "HISTORY_CLEARED";
+
+static CLEAR_TOKEN: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+
+// Operaciones en lote para las acciones de multi-selección del frontend.
+// Cada comando se ejecuta en una única transacción.
+
+#[tauri::command]
+pub async fn delete_scans(state: tauri::State<'_, AppState>, ids: Vec<i64>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    if ids.is_empty() {
+        return Ok(serde_json::json!({ "success": true, "deleted": 0 }));
+    }
+
+    let db = &state.db;
+    match db.delete_scans(&ids) {
+        Ok(deleted) => Ok(serde_json::json!({ "success": true, "deleted": deleted })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn acknowledge_alerts(state: tauri::State<'_, AppState>, app_handle: tauri::AppHandle, ids: Vec<i64>) -> Result<serde_json::Value, String> {
+    if ids.is_empty() {
+        return Ok(serde_json::json!({ "success": true, "acknowledged": 0 }));
+    }
+
+    let db = &state.db;
+    match db.acknowledge_alerts(&ids) {
+        Ok(updated) => {
+            tray::refresh(&app_handle);
+            Ok(serde_json::json!({ "success": true, "acknowledged": updated }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn set_trust_for_devices(
+    state: tauri::State<'_, AppState>,
+    ids: Vec<String>,
+    level: TrustLevel,
+    approval: Option<Approval>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    if ids.is_empty() {
+        return Ok(serde_json::json!({ "success": true, "updated": 0 }));
+    }
+
+    let db = &state.db;
+    // En modo estricto, confiar o desbloquear exige el código de otra persona
+    let method = approval::authorize(db, &ids, level, approval.as_ref())?;
+    let previous: Vec<(String, TrustLevel)> = ids
+        .iter()
+        .map(|id| (id.clone(), db.get_device_trust(id).unwrap_or_default()))
+        .collect();
+    match db.set_trust_for_devices(&ids, level) {
+        Ok(updated) => {
+            if let Some(method) = method {
+                approval::record_approved(db, &previous, level, method, approval.as_ref());
+            }
+            Ok(serde_json::json!({ "success": true, "updated": updated }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Token para confirmar `clear_all_history`; caduca a los dos minutos
+#[tauri::command]
+pub async fn request_clear_token() -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let token = uuid::Uuid::new_v4().to_string();
+    *CLEAR_TOKEN.lock().unwrap() = Some((token.clone(), Instant::now()));
+    Ok(serde_json::json!({ "success": true, "token": token, "expires_in_secs": CLEAR_TOKEN_TTL.as_secs() }))
+}
+
+/// Borrar todo el historial (actividad, escaneos, eventos y alertas) con un token de `request_clear_token`.
+/// Con `keep_devices: false` se borran también los dispositivos y sus adjuntos. El registro de auditoría se conserva.
+#[tauri::command]
+pub async fn clear_all_history(
+    state: tauri::State<'_, AppState>,
+    confirm_token: String,
+    keep_devices: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    {
+        let mut issued = CLEAR_TOKEN.lock().unwrap();
+        match issued.take() {
+            Some((token, at)) if token == confirm_token && at.elapsed() < CLEAR_TOKEN_TTL => {}
+            _ => return Err("Invalid or expired confirmation token; call request_clear_token first".to_string()),
+        }
+    }
+
+    let db = state.db.clone();
+    let keep_devices = keep_devices.unwrap_or(true);
+    let (deleted, attachment_paths) = db
+        .clear_history(keep_devices)
+        .map_err(|e| format!("Database error: {}", e))?;
+    for path in &attachment_paths {
+        if let Err(e) = std::fs::remove_file(path) {
+            println!("[Bulk] Failed to remove attachment {}: {}", path, e);
+        }
+    }
+
+    // Las sesiones abiertas apuntaban a conexiones borradas: el próximo ciclo las vuelve a registrar
+    state.monitor.reset_session_state();
+    if let Some(ref app_handle) = state.monitor.app_handle {
+        tray::refresh(app_handle);
+    }
+    audit::record(
+        &db,
+        CLEAR_AUDIT_ACTION,
+        None,
+        &serde_json::json!({ "deleted": deleted, "keep_devices": keep_devices }),
+    );
+
+    Ok(serde_json::json!({ "success": true, "deleted": deleted, "keep_devices": keep_devices }))
+}
@@ -0,0 +1,64 @@
+use crate::audit;
+use crate::kiosk;
+use crate::state::AppState;
+
+const CASE_AUDIT_ACTION: &str = "CASE_REFERENCE_SET";
+const DEFAULT_CASE_HISTORY_LIMIT: i64 = 1000;
+
+/// Vincular dispositivos y eventos a un caso o ticket del sistema de incidencias (vacío o null lo quita)
+#[tauri::command]
+pub async fn set_case_reference(
+    state: tauri::State<'_, AppState>,
+    case_reference: Option<String>,
+    device_ids: Option<Vec<String>>,
+    activity_ids: Option<Vec<i64>>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let case_reference = case_reference.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+    let device_ids = device_ids.unwrap_or_default();
+    let activity_ids = activity_ids.unwrap_or_default();
+
+    let updated = db
+        .set_case_reference(&device_ids, &activity_ids, case_reference.as_deref())
+        .map_err(|e| format!("Database error: {}", e))?;
+    audit::record(
+        &db,
+        CASE_AUDIT_ACTION,
+        None,
+        &serde_json::json!({ "case_reference": case_reference, "devices": device_ids, "activity_ids": activity_ids }),
+    );
+
+    Ok(serde_json::json!({ "success": true, "case_reference": case_reference, "updated": updated }))
+}
+
+/// Casos en uso con su número de dispositivos y eventos vinculados
+#[tauri::command]
+pub async fn get_case_references(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let cases: Vec<serde_json::Value> = db
+        .get_case_references()
+        .map_err(|e| format!("Database error: {}", e))?
+        .into_iter()
+        .map(|(case_reference, devices, events)| {
+            serde_json::json!({ "case_reference": case_reference, "devices": devices, "events": events })
+        })
+        .collect();
+    Ok(serde_json::json!({ "success": true, "cases": cases }))
+}
+
+/// Dispositivos e historial de un caso: los eventos vinculados y todos los de sus dispositivos
+#[tauri::command]
+pub async fn get_case(state: tauri::State<'_, AppState>, case_reference: String, limit: Option<i64>) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let case_reference = case_reference.trim();
+
+    let devices = db
+        .get_case_devices(case_reference)
+        .map_err(|e| format!("Database error: {}", e))?;
+    let history = db
+        .get_activity_history(limit.unwrap_or(DEFAULT_CASE_HISTORY_LIMIT), None, Some(case_reference))
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    Ok(serde_json::json!({ "success": true, "case_reference": case_reference, "devices": devices, "history": history }))
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use crate::db::FileSnapshot;
+use crate::state::AppState;
+
+// Límites para no bloquear el portapapeles ni el cliente de correo con escaneos enormes
+const MAX_CLIPBOARD_ROWS: usize = 5000;
+const MAX_CLIPBOARD_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardFormat {
+    /// Separado por tabuladores: se pega como tabla en hojas de cálculo
+    #[default]
+    Tsv,
+    /// Tabla Markdown para correos, tickets y chats
+    Markdown,
+}
+
+fn clean_tsv(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+fn escape_markdown(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn row(format: ClipboardFormat, snapshot: &FileSnapshot) -> String {
+    let kind = if snapshot.is_folder { "folder" } else { "file" };
+    let size = if snapshot.is_folder { String::new() } else { snapshot.file_size.to_string() };
+    let sha256 = snapshot.sha256.as_deref().unwrap_or_default();
+    match format {
+        ClipboardFormat::Tsv => format!("{}\t{}\t{}\t{}\n", clean_tsv(&snapshot.file_path), kind, size, sha256),
+        ClipboardFormat::Markdown => {
+            format!("| {} | {} | {} | {} |\n", escape_markdown(&snapshot.file_path), kind, size, sha256)
+        }
+    }
+}
+
+/// Lista de archivos en el formato pedido. Devuelve el texto y las filas incluidas;
+/// si no caben todas, termina con una línea que indica cuántas faltan.
+pub fn render_file_list(snapshots: &[FileSnapshot], format: ClipboardFormat) -> (String, usize) {
+    let mut out = match format {
+        ClipboardFormat::Tsv => "Path\tType\tSize\tSHA-256\n".to_string(),
+        ClipboardFormat::Markdown => "| Path | Type | Size | SHA-256 |\n| --- | --- | ---: | --- |\n".to_string(),
+    };
+
+    let mut included = 0;
+    for snapshot in snapshots {
+        let line = row(format, snapshot);
+        if included == MAX_CLIPBOARD_ROWS || out.len() + line.len() > MAX_CLIPBOARD_BYTES {
+            break;
+        }
+        out.push_str(&line);
+        included += 1;
+    }
+
+    if included < snapshots.len() {
+        let note = format!("{} of {} entries shown; export the scan for the full list", included, snapshots.len());
+        match format {
+            ClipboardFormat::Tsv => out.push_str(&format!("# {}\n", note)),
+            ClipboardFormat::Markdown => out.push_str(&format!("\n_{}_\n", note)),
+        }
+    }
+    (out, included)
+}
+
+/// Copiar al portapapeles la lista de archivos de un escaneo, para pegarla en un correo o una hoja de cálculo.
+/// Las listas muy largas se recortan y `truncated` lo indica.
+#[tauri::command]
+pub async fn copy_snapshot_to_clipboard(
+    state: tauri::State<'_, AppState>,
+    app: AppHandle,
+    activity_id: i64,
+    format: Option<ClipboardFormat>,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let snapshots = db
+        .get_file_snapshots(activity_id)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if snapshots.is_empty() {
+        return Err(format!("Scan {} has no files", activity_id));
+    }
+
+    let format = format.unwrap_or_default();
+    let (text, copied) = render_file_list(&snapshots, format);
+    app.clipboard()
+        .write_text(text)
+        .map_err(|e| format!("Failed to write to the clipboard: {}", e))?;
+
+    let truncated = copied < snapshots.len();
+    if truncated {
+        println!("[Clipboard] Scan {}: copied {} of {} entries", activity_id, copied, snapshots.len());
+    }
+    Ok(serde_json::json!({
+        "success": true,
+        "format": format,
+        "copied": copied,
+        "total": snapshots.len(),
+        "truncated": truncated,
+    }))
+}
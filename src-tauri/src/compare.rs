@@ -0,0 +1,200 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use crate::db::{Database, FileSnapshot, HashMode, ScanInfo};
+use crate::state::AppState;
+
+/// Archivo presente en uno o ambos lados de una comparación
+#[derive(Debug, Serialize, Clone)]
+pub struct DiffEntry {
+    pub relative_path: String,
+    pub is_folder: bool,
+    pub size_a: Option<i64>,
+    pub size_b: Option<i64>,
+    pub sha256_a: Option<String>,
+    pub sha256_b: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SnapshotDiff {
+    pub only_in_a: Vec<DiffEntry>,
+    pub only_in_b: Vec<DiffEntry>,
+    pub modified: Vec<DiffEntry>,
+    pub in_both: Vec<DiffEntry>,
+    /// Si la comparación usó hashes (ambos escaneos los tenían) o solo tamaños
+    pub compared_by_hash: bool,
+}
+
+/// Ruta relativa a la raíz del montaje, con '/' como separador para comparar entre sistemas
+pub fn relative_path(file_path: &str, root: &str) -> String {
+    file_path
+        .strip_prefix(root)
+        .unwrap_or(file_path)
+        .trim_start_matches(['/', '\\'])
+        .replace('\\', "/")
+}
+
+/// Raíz de un escaneo: el punto de montaje guardado o, en escaneos antiguos, la carpeta más corta
+pub fn scan_root(scan: &ScanInfo, snapshots: &[FileSnapshot]) -> String {
+    scan.mount_point.clone().unwrap_or_else(|| {
+        snapshots
+            .iter()
+            .filter(|s| s.is_folder)
+            .map(|s| s.file_path.as_str())
+            .min_by_key(|p| p.len())
+            .unwrap_or("")
+            .to_string()
+    })
+}
+
+fn index_by_relative_path<'a>(snapshots: &'a [FileSnapshot], root: &str) -> BTreeMap<String, &'a FileSnapshot> {
+    snapshots
+        .iter()
+        .map(|s| (relative_path(&s.file_path, root), s))
+        .filter(|(rel, _)| !rel.is_empty())
+        .collect()
+}
+
+/// Comparar dos listas de snapshots por ruta relativa (y hash si ambos lados lo tienen)
+pub fn diff_snapshots(a: &[FileSnapshot], root_a: &str, b: &[FileSnapshot], root_b: &str) -> SnapshotDiff {
+    let index_a = index_by_relative_path(a, root_a);
+    let index_b = index_by_relative_path(b, root_b);
+
+    let has_hashes = |snaps: &[FileSnapshot]| snaps.iter().any(|s| s.sha256.is_some());
+    let mut diff = SnapshotDiff {
+        compared_by_hash: has_hashes(a) && has_hashes(b),
+        ..Default::default()
+    };
+
+    for (rel, sa) in &index_a {
+        let sb = index_b.get(rel);
+        let entry = DiffEntry {
+            relative_path: rel.clone(),
+            is_folder: sa.is_folder,
+            size_a: Some(sa.file_size),
+            size_b: sb.map(|s| s.file_size),
+            sha256_a: sa.sha256.clone(),
+            sha256_b: sb.and_then(|s| s.sha256.clone()),
+        };
+
+        match sb {
+            None => diff.only_in_a.push(entry),
+            Some(sb) => {
+                let same = if sa.is_folder || sb.is_folder {
+                    sa.is_folder == sb.is_folder
+                } else {
+                    // Un hash por muestra solo se compara con otro por muestra
+                    let mode = |s: &FileSnapshot| s.hash_mode.unwrap_or(HashMode::Full);
+                    match (&sa.sha256, &sb.sha256) {
+                        (Some(ha), Some(hb)) if mode(sa) == mode(sb) => ha == hb,
+                        _ => sa.file_size == sb.file_size,
+                    }
+                };
+
+                if same {
+                    diff.in_both.push(entry);
+                } else {
+                    diff.modified.push(entry);
+                }
+            }
+        }
+    }
+
+    for (rel, sb) in &index_b {
+        if !index_a.contains_key(rel) {
+            diff.only_in_b.push(DiffEntry {
+                relative_path: rel.clone(),
+                is_folder: sb.is_folder,
+                size_a: None,
+                size_b: Some(sb.file_size),
+                sha256_a: None,
+                sha256_b: sb.sha256.clone(),
+            });
+        }
+    }
+
+    diff
+}
+
+// Último escaneo de un dispositivo con sus snapshots y su raíz
+fn load_latest_scan(db: &Database, device_id: &str) -> Result<(ScanInfo, Vec<FileSnapshot>, String), String> {
+    let scan = db
+        .get_latest_scan(device_id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("No scans found for device {}", device_id))?;
+
+    let snapshots = db
+        .get_file_snapshots(scan.activity_log_id)
+        .map_err(|e| format!("Database error: {}", e))?;
+    let root = scan_root(&scan, &snapshots);
+
+    Ok((scan, snapshots, root))
+}
+
+#[tauri::command]
+pub async fn compare_devices(state: tauri::State<'_, AppState>, device_a: String, device_b: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    let (scan_a, snaps_a, root_a) = load_latest_scan(&db, &device_a)?;
+    let (scan_b, snaps_b, root_b) = load_latest_scan(&db, &device_b)?;
+
+    let diff = diff_snapshots(&snaps_a, &root_a, &snaps_b, &root_b);
+    println!(
+        "[Compare] {} vs {}: {} only A, {} only B, {} modified, {} in both",
+        device_a,
+        device_b,
+        diff.only_in_a.len(),
+        diff.only_in_b.len(),
+        diff.modified.len(),
+        diff.in_both.len()
+    );
+
+    Ok(serde_json::json!({
+        "success": true,
+        "scan_a": scan_a,
+        "scan_b": scan_b,
+        "diff": diff,
+    }))
+}
+
+#[tauri::command]
+pub async fn get_latest_changes(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    let scans = db
+        .get_recent_scans(&device_id, 2)
+        .map_err(|e| format!("Database error: {}", e))?;
+    let [latest, previous] = <[ScanInfo; 2]>::try_from(scans)
+        .map_err(|_| format!("At least two scans are needed to compare device {}", device_id))?;
+
+    let load = |scan: &ScanInfo| -> Result<(Vec<FileSnapshot>, String), String> {
+        let snapshots = db
+            .get_file_snapshots(scan.activity_log_id)
+            .map_err(|e| format!("Database error: {}", e))?;
+        let root = scan_root(scan, &snapshots);
+        Ok((snapshots, root))
+    };
+    let (previous_snaps, previous_root) = load(&previous)?;
+    let (latest_snaps, latest_root) = load(&latest)?;
+
+    // A = escaneo anterior, B = último: lo que solo está en B es nuevo
+    let diff = diff_snapshots(&previous_snaps, &previous_root, &latest_snaps, &latest_root);
+    println!(
+        "[Compare] Latest changes for {}: {} added, {} removed, {} modified",
+        device_id,
+        diff.only_in_b.len(),
+        diff.only_in_a.len(),
+        diff.modified.len()
+    );
+
+    Ok(serde_json::json!({
+        "success": true,
+        "device_id": device_id,
+        "previous_scan": previous,
+        "latest_scan": latest,
+        "added": diff.only_in_b,
+        "removed": diff.only_in_a,
+        "modified": diff.modified,
+        "unchanged": diff.in_both.len(),
+        "compared_by_hash": diff.compared_by_hash,
+    }))
+}
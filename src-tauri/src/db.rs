@@ -1,8 +1,17 @@
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, DatabaseName, OptionalExtension, Result};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use crate::file_scanner::ScanResult;
+use crate::usn::JournalPosition;
+use crate::i18n::{Message, DEFAULT_LANG};
+use crate::importer::{ImportSummary, ImportedDevice, WhitelistChange, WhitelistEntry, WhitelistSummary};
+use crate::os_session::OsSession;
+use crate::search::FileQuery;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Device {
@@ -12,6 +21,120 @@ pub struct Device {
     pub name: Option<String>,
     pub manufacturer: Option<String>,
     pub total_capacity: Option<i64>,
+    #[serde(default)]
+    pub trust_level: TrustLevel,
+    /// Equipo donde se registró el dispositivo por primera vez
+    #[serde(default)]
+    pub machine_id: Option<String>,
+    /// Nombre asignado por el usuario (p.ej. desde la lista blanca)
+    #[serde(default)]
+    pub nickname: Option<String>,
+    /// Número de serie del volumen (sistema de archivos), p.ej. "1A2B-3C4D", en el formato de Windows en todos los sistemas
+    #[serde(default)]
+    pub volume_serial: Option<String>,
+    /// Etiqueta del volumen ("KINGSTON", "BACKUP"...)
+    #[serde(default)]
+    pub volume_label: Option<String>,
+    /// Memoria USB, tarjeta SD u otro volumen extraíble
+    #[serde(default)]
+    pub media_type: MediaType,
+    /// Primera y última vez que se vio conectado
+    #[serde(default)]
+    pub first_seen: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Caso o ticket del sistema de incidencias al que está vinculado
+    #[serde(default)]
+    pub case_reference: Option<String>,
+    /// Número de serie USB tal como lo informa el dispositivo; según la estrategia de identificación
+    /// el registro puede llevar otro identificador (serie de volumen o huella)
+    #[serde(default)]
+    pub usb_serial: Option<String>,
+    /// No escanear sus archivos al conectar (p.ej. una unidad cifrada personal); la conexión se registra igual
+    #[serde(default)]
+    pub skip_scan: bool,
+    /// No vigilar cambios de archivos mientras está conectado
+    #[serde(default)]
+    pub skip_watch: bool,
+    /// Guardar las rutas de sus archivos anonimizadas; None sigue el ajuste global `privacy`
+    #[serde(default)]
+    pub redact_paths: Option<bool>,
+}
+
+/// Orden de la lista de dispositivos registrados
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceSort {
+    #[default]
+    Updated,
+    LastSeen,
+    FirstSeen,
+}
+
+impl DeviceSort {
+    fn order_by(&self) -> &'static str {
+        match self {
+            DeviceSort::Updated => "updated_at DESC",
+            DeviceSort::LastSeen => "last_seen IS NULL, last_seen DESC",
+            DeviceSort::FirstSeen => "first_seen IS NULL, first_seen ASC",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TrustLevel {
+    #[default]
+    Unknown,
+    Trusted,
+    Blocked,
+}
+
+impl TrustLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrustLevel::Unknown => "UNKNOWN",
+            TrustLevel::Trusted => "TRUSTED",
+            TrustLevel::Blocked => "BLOCKED",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "TRUSTED" => TrustLevel::Trusted,
+            "BLOCKED" => TrustLevel::Blocked,
+            _ => TrustLevel::Unknown,
+        }
+    }
+}
+
+/// Tipo de soporte: las tarjetas de un lector integrado no aparecen como dispositivos USB
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum MediaType {
+    #[default]
+    Usb,
+    SdCard,
+    /// Volumen extraíble que el sistema no asocia a un dispositivo USB ni a un lector de tarjetas
+    Removable,
+}
+
+impl MediaType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::Usb => "USB",
+            MediaType::SdCard => "SDCARD",
+            MediaType::Removable => "REMOVABLE",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "SDCARD" => MediaType::SdCard,
+            "REMOVABLE" => MediaType::Removable,
+            _ => MediaType::Usb,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,13 +143,35 @@ pub struct ActivityLog {
     pub device_id: String,
     pub event_type: EventType,
     pub timestamp: DateTime<Utc>,
+    /// Usuario con sesión interactiva cuando ocurrió el evento
+    #[serde(default)]
+    pub os_user: Option<String>,
+    /// ID de sesión de Windows
+    #[serde(default)]
+    pub session_id: Option<u32>,
+    /// Equipo donde ocurrió el evento
+    #[serde(default)]
+    pub machine_id: Option<String>,
+    /// Bus y cadena de puertos USB (p.ej. "1-3.2"): panel frontal, dock, hub interno...
+    #[serde(default)]
+    pub port_path: Option<String>,
+    /// Número de serie del volumen montado en ese momento (cambia al formatear)
+    #[serde(default)]
+    pub volume_serial: Option<String>,
+    /// Caso o ticket del sistema de incidencias al que está vinculado el evento
+    #[serde(default)]
+    pub case_reference: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum EventType {
     Connect,
+    /// Desconexión tras expulsar o sin escrituras pendientes
     Disconnect,
+    /// Retirada sin expulsar con escrituras recientes: los datos pueden no haberse volcado
+    #[serde(rename = "UNSAFE_REMOVAL")]
+    UnsafeRemoval,
 }
 
 impl EventType {
@@ -34,10 +179,64 @@ impl EventType {
         match self {
             EventType::Connect => "CONNECT",
             EventType::Disconnect => "DISCONNECT",
+            EventType::UnsafeRemoval => "UNSAFE_REMOVAL",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "DISCONNECT" => EventType::Disconnect,
+            "UNSAFE_REMOVAL" => EventType::UnsafeRemoval,
+            _ => EventType::Connect,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum AlertSeverity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl AlertSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertSeverity::Low => "LOW",
+            AlertSeverity::Medium => "MEDIUM",
+            AlertSeverity::High => "HIGH",
+            AlertSeverity::Critical => "CRITICAL",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "LOW" => AlertSeverity::Low,
+            "HIGH" => AlertSeverity::High,
+            "CRITICAL" => AlertSeverity::Critical,
+            _ => AlertSeverity::Medium,
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Alert {
+    pub id: i64,
+    pub device_id: Option<String>,
+    pub activity_log_id: Option<i64>,
+    pub alert_type: String,
+    pub severity: AlertSeverity,
+    /// Texto en inglés, guardado para informes y versiones anteriores de la interfaz
+    pub message: String,
+    /// Clave y parámetros para localizar el mensaje (ausentes en alertas antiguas)
+    pub message_key: Option<String>,
+    pub message_params: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+    pub acknowledged: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileSnapshot {
     pub id: Option<i64>,
@@ -47,431 +246,5797 @@ pub struct FileSnapshot {
     pub file_extension: Option<String>,
     pub file_size: i64,
     pub is_folder: bool,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Cómo se calculó `sha256` (ausente si no se calculó ni se intentó)
+    #[serde(default)]
+    pub hash_mode: Option<HashMode>,
 }
 
-pub struct Database {
-    conn: Arc<Mutex<Connection>>,
+/// Hash de un snapshot: del archivo entero, de una muestra (archivos muy grandes) o no calculado por los límites del escaneo
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HashMode {
+    Full,
+    /// SHA-256 de los primeros y los últimos MB y del tamaño; solo se compara con otros hashes por muestra
+    Sampled,
+    /// Archivo demasiado grande o tiempo de hash del escaneo agotado
+    Skipped,
 }
 
-impl Database {
-    pub fn new(app_data_dir: PathBuf) -> Result<Self> {
-        let db_path = app_data_dir.join("usb_manager.db");
-        println!("[DB] Initializing database at: {:?}", db_path);
+impl HashMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashMode::Full => "FULL",
+            HashMode::Sampled => "SAMPLED",
+            HashMode::Skipped => "SKIPPED",
+        }
+    }
 
-        let conn = Connection::open(&db_path)?;
-        let db = Self {
-            conn: Arc::new(Mutex::new(conn)),
-        };
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "SAMPLED" => HashMode::Sampled,
+            "SKIPPED" => HashMode::Skipped,
+            _ => HashMode::Full,
+        }
+    }
+}
 
-        db.init_tables()?;
-        println!("[DB] Database initialized successfully");
+/// Escaneo asociado a un evento CONNECT
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScanInfo {
+    pub activity_log_id: i64,
+    pub device_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub mount_point: Option<String>,
+}
 
-        Ok(db)
+/// Último escaneo de un dispositivo con la posición del diario USN, base de un escaneo incremental
+#[derive(Debug, Clone)]
+pub struct JournalScan {
+    pub activity_log_id: i64,
+    pub mount_point: String,
+    pub journal: JournalPosition,
+}
+
+/// Último escaneo terminado de un dispositivo con lo necesario para decidir si reutilizarlo al reconectar
+#[derive(Debug, Clone)]
+pub struct ReusableScan {
+    pub activity_log_id: i64,
+    pub volume_serial: Option<String>,
+    pub used_bytes: Option<i64>,
+    pub total_capacity: Option<i64>,
+    /// Segundos desde la desconexión posterior al escaneo; None si no consta
+    pub secs_since_disconnect: Option<i64>,
+}
+
+/// Registros anonimizados por `redact_history` en cada tabla
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RedactionSummary {
+    pub activities: usize,
+    pub snapshots: usize,
+    pub file_events: usize,
+    pub evidence: usize,
+    pub documents: usize,
+    pub alerts: usize,
+    pub sync_conflicts: usize,
+    /// Sesiones afectadas, para renombrar lo que se guarda fuera de la base de datos (cuarentena)
+    #[serde(skip)]
+    pub activity_ids: Vec<i64>,
+}
+
+/// Estado de un escaneo en la tabla scans
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ScanStatus {
+    /// Esperando turno en la cola de escaneos
+    Queued,
+    Running,
+    Done,
+    Failed,
+    /// Desconectado antes de empezar o interrumpido sin poder reanudarse
+    Canceled,
+}
+
+impl ScanStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScanStatus::Queued => "QUEUED",
+            ScanStatus::Running => "RUNNING",
+            ScanStatus::Done => "DONE",
+            ScanStatus::Failed => "FAILED",
+            ScanStatus::Canceled => "CANCELED",
+        }
     }
 
-    fn init_tables(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "QUEUED" => ScanStatus::Queued,
+            "RUNNING" => ScanStatus::Running,
+            "FAILED" => ScanStatus::Failed,
+            "CANCELED" => ScanStatus::Canceled,
+            _ => ScanStatus::Done,
+        }
+    }
+}
 
-        // Tabla devices
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS devices (
-                serial_number TEXT PRIMARY KEY,
-                vendor_id INTEGER NOT NULL,
-                product_id INTEGER NOT NULL,
-                name TEXT,
-                manufacturer TEXT,
-                total_capacity INTEGER,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            )",
-            [],
-        )?;
+/// Fila de la tabla scans con su estado
+#[derive(Debug, Serialize, Clone)]
+pub struct ScanState {
+    pub activity_log_id: i64,
+    pub device_id: String,
+    pub status: ScanStatus,
+    pub error: Option<String>,
+    pub total_files: i64,
+    pub total_bytes: i64,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
 
-        // Tabla activity_log
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS activity_log (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                device_id TEXT NOT NULL,
-                event_type TEXT NOT NULL CHECK(event_type IN ('CONNECT', 'DISCONNECT')),
-                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (device_id) REFERENCES devices(serial_number)
-            )",
-            [],
-        )?;
+/// Progreso de un escaneo completo sin terminar, guardado tras cada lote para poder reanudarlo
+#[derive(Debug, Serialize, Clone)]
+pub struct ScanCheckpoint {
+    pub activity_log_id: i64,
+    pub device_id: String,
+    pub mount_point: String,
+    /// Última ruta guardada, en el orden del recorrido (None si aún no se ha guardado ningún lote)
+    pub last_path: Option<String>,
+    pub total_files: i64,
+    pub total_folders: i64,
+    pub total_bytes: i64,
+    pub batches: i64,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Se empezó otro escaneo del dispositivo sin reanudar este: su snapshot quedará incompleto
+    pub abandoned: bool,
+}
 
-        // Tabla file_snapshots
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS file_snapshots (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                activity_log_id INTEGER NOT NULL,
-                file_path TEXT NOT NULL,
-                file_name TEXT NOT NULL,
-                file_extension TEXT,
-                file_size INTEGER NOT NULL,
-                is_folder BOOLEAN NOT NULL DEFAULT 0,
-                scanned_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-                FOREIGN KEY (activity_log_id) REFERENCES activity_log(id) ON DELETE CASCADE
-            )",
-            [],
-        )?;
+/// Volumen visto en las conexiones de un dispositivo (ver `get_device_volumes`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceVolume {
+    pub volume_serial: Option<String>,
+    pub connections: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub activity_ids: Vec<i64>,
+}
 
-        // Índices para búsquedas más rápidas
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_activity_log_device_id ON activity_log(device_id)",
-            [],
-        )?;
+/// Textos de los descriptores USB de un dispositivo en uno de los idiomas que declara
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StringDescriptors {
+    /// LANGID de USB (el de Windows): 0x0409 inglés de EE. UU., 0x0C0A español, 0x0411 japonés...
+    pub language_id: u16,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_activity_log_timestamp ON activity_log(timestamp)",
-            [],
-        )?;
+/// Coincidencia de `locate_file` o `search_files`: dónde y cuándo apareció un archivo
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileLocation {
+    pub device_id: String,
+    pub device_name: Option<String>,
+    pub activity_log_id: i64,
+    pub scan_timestamp: DateTime<Utc>,
+    pub file_path: String,
+    pub file_size: i64,
+    pub sha256: Option<String>,
+    /// El hash está en alguna lista de archivos conocidos (NSRL u otra importada)
+    #[serde(default)]
+    pub known: bool,
+}
 
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_file_snapshots_activity_id ON file_snapshots(activity_log_id)",
-            [],
-        )?;
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActivityBucket {
+    pub bucket: String,
+    pub connections: i64,
+    pub scans: i64,
+    pub alerts: i64,
+}
 
-        Ok(())
-    }
+/// Punto de la serie de crecimiento de un dispositivo
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GrowthPoint {
+    pub activity_log_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub total_bytes: i64,
+    pub total_files: i64,
+    pub used_bytes: Option<i64>,
+    pub total_capacity: Option<i64>,
+}
 
-    // Upsert device (insertar o actualizar)
-    pub fn upsert_device(&self, device: &Device) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceConnectionCount {
+    pub device_id: String,
+    pub device_name: Option<String>,
+    pub connections: i64,
+}
 
-        conn.execute(
-            "INSERT INTO devices (serial_number, vendor_id, product_id, name, manufacturer, total_capacity, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP)
-             ON CONFLICT(serial_number) DO UPDATE SET
-                vendor_id = excluded.vendor_id,
-                product_id = excluded.product_id,
-                name = excluded.name,
-                manufacturer = excluded.manufacturer,
-                total_capacity = excluded.total_capacity,
-                updated_at = CURRENT_TIMESTAMP",
-            params![
-                device.serial_number,
-                device.vendor_id,
-                device.product_id,
-                device.name,
-                device.manufacturer,
-                device.total_capacity,
-            ],
-        )?;
+/// Escaneo con más datos en un periodo (usado como "mayores transferencias" en informes)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransferSummary {
+    pub activity_log_id: i64,
+    pub device_id: String,
+    pub device_name: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    pub total_bytes: i64,
+    pub total_files: i64,
+}
 
-        println!("[DB] Device upserted: {}", device.serial_number);
-        Ok(())
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoredReport {
+    pub id: i64,
+    pub kind: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub format: String,
+    pub file_path: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum FileEventKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+impl FileEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileEventKind::Created => "CREATED",
+            FileEventKind::Modified => "MODIFIED",
+            FileEventKind::Removed => "REMOVED",
+        }
     }
 
-    // Crear registro de actividad
-    pub fn create_activity_log(&self, device_id: &str, event_type: EventType) -> Result<i64> {
-        let conn = self.conn.lock().unwrap();
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "CREATED" => FileEventKind::Created,
+            "REMOVED" => FileEventKind::Removed,
+            _ => FileEventKind::Modified,
+        }
+    }
+}
 
-        conn.execute(
-            "INSERT INTO activity_log (device_id, event_type, timestamp)
-             VALUES (?1, ?2, CURRENT_TIMESTAMP)",
-            params![device_id, event_type.as_str()],
-        )?;
+/// Regla con umbral configurable que se puede sobrescribir por dispositivo
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    /// Bytes escritos en una sesión
+    SessionWriteBytes,
+    /// Desviaciones estándar del volumen escaneado (ver AnomalySettings::volume_z_score)
+    ScanVolumeZScore,
+    /// Fracción mínima de conexiones en la franja horaria (ver AnomalySettings::min_hour_share)
+    UnusualHourShare,
+    /// Porcentaje de capacidad ocupada a partir del cual se alerta al escanear
+    StorageUsedPercent,
+}
 
-        let id = conn.last_insert_rowid();
-        println!(
-            "[DB] Activity log created: id={}, device={}, type={}",
-            id,
-            device_id,
-            event_type.as_str()
-        );
+impl RuleKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleKind::SessionWriteBytes => "session_write_bytes",
+            RuleKind::ScanVolumeZScore => "scan_volume_z_score",
+            RuleKind::UnusualHourShare => "unusual_hour_share",
+            RuleKind::StorageUsedPercent => "storage_used_percent",
+        }
+    }
 
-        Ok(id)
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "session_write_bytes" => Some(RuleKind::SessionWriteBytes),
+            "scan_volume_z_score" => Some(RuleKind::ScanVolumeZScore),
+            "unusual_hour_share" => Some(RuleKind::UnusualHourShare),
+            "storage_used_percent" => Some(RuleKind::StorageUsedPercent),
+            _ => None,
+        }
     }
+}
 
-    // Insertar snapshot de archivo en batch (más eficiente)
-    pub fn insert_file_snapshots_batch(&self, snapshots: &[FileSnapshot]) -> Result<()> {
-        let mut conn = self.conn.lock().unwrap();
-        let tx = conn.transaction()?;
+/// Umbral de una regla para un dispositivo concreto; sin umbral la regla queda desactivada para él
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RuleOverride {
+    pub device_id: String,
+    pub rule: RuleKind,
+    pub threshold: Option<f64>,
+    pub updated_at: DateTime<Utc>,
+}
 
-        {
-            let mut stmt = tx.prepare(
-                "INSERT INTO file_snapshots (activity_log_id, file_path, file_name, file_extension, file_size, is_folder)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
-            )?;
+/// Entrada del registro de auditoría: acciones de la app o de sus scripts que no son eventos USB
+#[derive(Debug, Serialize, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub action: String,
+    pub device_id: Option<String>,
+    pub details: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
 
-            for snapshot in snapshots {
-                stmt.execute(params![
-                    snapshot.activity_log_id,
-                    snapshot.file_path,
-                    snapshot.file_name,
-                    snapshot.file_extension,
-                    snapshot.file_size,
-                    snapshot.is_folder,
-                ])?;
-            }
-        }
+/// Metadatos internos de un documento Office, OpenDocument o PDF de un escaneo (quién lo hizo y con qué)
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DocumentMetadata {
+    pub snapshot_id: i64,
+    pub activity_log_id: i64,
+    pub file_path: String,
+    pub author: Option<String>,
+    pub title: Option<String>,
+    pub company: Option<String>,
+    pub last_saved_by: Option<String>,
+    /// Programa que creó o generó el documento
+    pub application: Option<String>,
+    /// Fechas guardadas en el propio documento (pueden no coincidir con las del sistema de archivos)
+    pub created: Option<String>,
+    pub modified: Option<String>,
+}
 
-        tx.commit()?;
-        println!("[DB] Inserted {} file snapshots", snapshots.len());
+/// Archivo copiado de un dispositivo al equipo: dónde estaba, dónde apareció y, si se guardó, su copia
+#[derive(Debug, Serialize, Clone)]
+pub struct EvidenceRecord {
+    pub id: Option<i64>,
+    pub device_id: String,
+    pub activity_log_id: i64,
+    pub source_path: String,
+    pub host_path: String,
+    pub file_name: String,
+    pub file_size: i64,
+    pub sha256: Option<String>,
+    pub evidence_path: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Archivo adjunto a la ficha de un dispositivo (foto de la memoria, hoja de cadena de custodia...)
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceAttachment {
+    pub id: Option<i64>,
+    pub device_id: String,
+    pub file_name: String,
+    /// Copia guardada en la carpeta de datos de la aplicación
+    pub stored_path: String,
+    pub mime_type: Option<String>,
+    pub file_size: i64,
+    pub sha256: Option<String>,
+    pub description: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    Pending,
+    /// Agotados los reintentos; solo se vuelve a enviar a mano
+    Dead,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Dead => "dead",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "dead" => OutboxStatus::Dead,
+            _ => OutboxStatus::Pending,
+        }
+    }
+}
+
+/// Notificación saliente aún no entregada (plugins, webhooks...)
+#[derive(Debug, Serialize, Clone)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub channel: String,
+    pub payload: serde_json::Value,
+    pub status: OutboxStatus,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GrantStatus {
+    Active,
+    /// Venció y se restauró la confianza anterior
+    Expired,
+    /// Retirado a mano antes de vencer
+    Revoked,
+}
+
+impl GrantStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GrantStatus::Active => "active",
+            GrantStatus::Expired => "expired",
+            GrantStatus::Revoked => "revoked",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "expired" => GrantStatus::Expired,
+            "revoked" => GrantStatus::Revoked,
+            _ => GrantStatus::Active,
+        }
+    }
+}
+
+/// Acceso temporal: la confianza `granted_trust` dura hasta `expires_at` y después vuelve a `previous_trust`
+#[derive(Debug, Serialize, Clone)]
+pub struct AccessGrant {
+    pub id: i64,
+    pub device_id: String,
+    pub previous_trust: TrustLevel,
+    pub granted_trust: TrustLevel,
+    pub status: GrantStatus,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    /// Solo añade y actualiza archivos en el dispositivo
+    #[default]
+    Additive,
+    /// Además borra del dispositivo lo que ya no existe en el origen
+    Mirror,
+}
+
+impl BackupMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackupMode::Additive => "additive",
+            BackupMode::Mirror => "mirror",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "mirror" => BackupMode::Mirror,
+            _ => BackupMode::Additive,
+        }
+    }
+}
+
+/// Qué hacer cuando el archivo ya existe en el dispositivo y es distinto
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    Overwrite,
+    Skip,
+    /// Sobrescribir solo si el origen es más reciente
+    #[default]
+    Newer,
+    /// Guardar la nueva versión con otro nombre
+    KeepBoth,
+}
+
+impl ConflictPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Newer => "newer",
+            ConflictPolicy::KeepBoth => "keep_both",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "overwrite" => ConflictPolicy::Overwrite,
+            "skip" => ConflictPolicy::Skip,
+            "keep_both" => ConflictPolicy::KeepBoth,
+            _ => ConflictPolicy::Newer,
+        }
+    }
+}
+
+/// Copia de seguridad automática de carpetas del equipo a un dispositivo de confianza al conectarlo
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupProfile {
+    pub device_id: String,
+    pub folders: Vec<String>,
+    /// Carpeta de destino, relativa a la raíz del dispositivo
+    #[serde(default = "default_backup_target")]
+    pub target_dir: String,
+    #[serde(default)]
+    pub mode: BackupMode,
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Para carpetas que van y vienen entre dos equipos: no sobrescribir lo cambiado en el dispositivo
+    /// desde la última copia y avisar si cambió en ambos lados
+    #[serde(default)]
+    pub detect_conflicts: bool,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+fn default_backup_target() -> String {
+    "USB Manager Backup".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+impl BackupStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackupStatus::Running => "running",
+            BackupStatus::Completed => "completed",
+            BackupStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "completed" => BackupStatus::Completed,
+            "failed" => BackupStatus::Failed,
+            _ => BackupStatus::Running,
+        }
+    }
+}
+
+/// Ejecución de una copia de seguridad (historial)
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct BackupRun {
+    pub id: i64,
+    pub device_id: String,
+    pub activity_log_id: Option<i64>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: Option<BackupStatus>,
+    pub files_copied: i64,
+    pub files_skipped: i64,
+    pub files_deleted: i64,
+    pub files_conflicted: i64,
+    pub bytes_copied: i64,
+    pub error: Option<String>,
+}
+
+/// Estado de un archivo tras la última copia, común al equipo y al dispositivo
+#[derive(Debug, Serialize, Clone)]
+pub struct SyncEntry {
+    /// Ruta relativa al destino de la copia, con '/' como separador
+    pub relative_path: String,
+    pub size: i64,
+    pub modified: i64,
+    pub sha256: Option<String>,
+}
+
+/// Archivo cambiado en el equipo y en el dispositivo desde la última copia
+#[derive(Debug, Serialize, Clone)]
+pub struct SyncConflict {
+    pub id: i64,
+    pub device_id: String,
+    pub run_id: i64,
+    pub relative_path: String,
+    pub host_path: String,
+    pub device_path: String,
+    /// Borrado en el equipo pero cambiado en el dispositivo (modo espejo)
+    pub host_deleted: bool,
+    pub resolved: bool,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Cambio de archivo detectado por el watcher durante una sesión
+#[derive(Debug, Serialize, Clone)]
+pub struct FileEvent {
+    pub id: i64,
+    pub activity_log_id: i64,
+    pub device_id: String,
+    pub file_path: String,
+    pub kind: FileEventKind,
+    pub file_size: Option<i64>,
+    /// Punto de montaje del volumen en el que ocurrió (dispositivos con varias particiones)
+    pub volume: Option<String>,
+    /// Proceso que escribió el archivo, si se pudo saber (solo Windows)
+    pub process_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Resumen de una sesión (de la conexión a la desconexión de un dispositivo)
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SessionSummary {
+    pub connect_activity_id: i64,
+    pub disconnect_activity_id: Option<i64>,
+    pub device_id: String,
+    pub connected_at: DateTime<Utc>,
+    pub disconnected_at: DateTime<Utc>,
+    pub duration_secs: i64,
+    pub files_added: i64,
+    pub files_removed: i64,
+    pub files_modified: i64,
+    pub bytes_written: i64,
+    pub alerts_raised: i64,
+}
+
+/// Acumulado de escrituras en un dispositivo: vistas por el watcher durante las sesiones
+/// y deducidas comparando cada escaneo con el anterior (escrituras hechas en otros equipos)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DeviceTransferStats {
+    pub device_id: String,
+    pub files_written: i64,
+    pub bytes_written: i64,
+    pub session_files: i64,
+    pub session_bytes: i64,
+    pub scan_files: i64,
+    pub scan_bytes: i64,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Lista importada de hashes de archivos conocidos (NSRL, instaladores del sistema, listas propias)
+#[derive(Debug, Serialize, Clone)]
+pub struct KnownHashSet {
+    pub id: i64,
+    pub name: String,
+    /// Archivo del que se importó
+    pub source: Option<String>,
+    pub hash_count: i64,
+    pub imported_at: DateTime<Utc>,
+}
+
+/// Archivos de un escaneo según su hash: en alguna lista de conocidos, fuera de ellas o sin hash completo
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct KnownHashSummary {
+    pub known: i64,
+    pub unknown: i64,
+    pub unhashed: i64,
+}
+
+/// Búsqueda guardada; con `alert_enabled` actúa como regla de alerta que se evalúa tras cada escaneo
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+    /// Estado de filtros propio de la interfaz (orden, columnas...), se guarda tal cual
+    pub filters: Option<serde_json::Value>,
+    pub alert_enabled: bool,
+    pub alert_severity: AlertSeverity,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Machine {
+    pub machine_id: String,
+    pub hostname: Option<String>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub events: i64,
+}
+
+/// Operación del registro de cambios (oplog): cada cambio replicable queda anotado con un UUID propio
+/// y el equipo que lo hizo, para fusionar las bases de datos de varios equipos sin duplicados
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OplogEntry {
+    /// Orden local; no se conserva al fusionar en otra base de datos
+    #[serde(default)]
+    pub seq: i64,
+    pub op_id: String,
+    pub machine_id: String,
+    /// `device`, `activity` o `alert`
+    pub entity: String,
+    /// Serie del dispositivo, o UUID del evento o de la alerta
+    pub entity_id: String,
+    /// `UPSERT`, `TRUST`, `DELETE`, `INSERT` o `ACK`
+    pub op: String,
+    pub payload: serde_json::Value,
+    /// Con milisegundos, para ordenar los cambios de confianza (gana el último)
+    pub created_at: String,
+}
+
+impl OplogEntry {
+    /// Nivel al que pasa el dispositivo si es un cambio de confianza
+    pub fn trust_change(&self) -> Option<TrustLevel> {
+        (self.entity == OP_ENTITY_DEVICE && self.op == OP_TRUST)
+            .then(|| TrustLevel::parse(self.payload.get("trust_level").and_then(|v| v.as_str()).unwrap_or_default()))
+    }
+}
+
+/// Resultado de fusionar un oplog exportado de otro equipo
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct OplogMergeSummary {
+    pub applied: usize,
+    /// Ya estaban en esta base de datos (se puede fusionar el mismo archivo varias veces)
+    pub skipped: usize,
+    /// Cambios de confianza anotados pero no aplicados porque hay otro más reciente
+    pub superseded: usize,
+    /// Dispositivos cuyos cambios de confianza no se han fusionado porque necesitan aprobación
+    pub held: Vec<String>,
+}
+
+const OP_ENTITY_DEVICE: &str = "device";
+const OP_ENTITY_ACTIVITY: &str = "activity";
+const OP_ENTITY_ALERT: &str = "alert";
+const OP_UPSERT: &str = "UPSERT";
+const OP_TRUST: &str = "TRUST";
+const OP_DELETE: &str = "DELETE";
+const OP_INSERT: &str = "INSERT";
+const OP_ACK: &str = "ACK";
+
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+    path: Mutex<PathBuf>,
+    // En memoria porque el archivo no se pudo abrir; `path` es el archivo que se debería usar
+    in_memory: AtomicBool,
+}
+
+impl Database {
+    pub fn open(db_path: PathBuf) -> Result<Self> {
+        println!("[DB] Initializing database at: {:?}", db_path);
+
+        let conn = Connection::open(&db_path)?;
+        let db = Self {
+            conn: Arc::new(Mutex::new(conn)),
+            path: Mutex::new(db_path),
+            in_memory: AtomicBool::new(false),
+        };
+
+        db.init_tables()?;
+        println!("[DB] Database initialized successfully");
+
+        Ok(db)
+    }
+
+    // Base de datos en memoria mientras `db_path` no se puede abrir: guarda dispositivos y actividad
+    // hasta que `flush_to_file` los pase al archivo
+    pub fn open_in_memory(db_path: PathBuf) -> Result<Self> {
+        println!("[DB] Initializing in-memory database (pending: {:?})", db_path);
+
+        let conn = Connection::open_in_memory()?;
+        let db = Self {
+            conn: Arc::new(Mutex::new(conn)),
+            path: Mutex::new(db_path),
+            in_memory: AtomicBool::new(true),
+        };
+
+        db.init_tables()?;
+        Ok(db)
+    }
+
+    pub fn is_in_memory(&self) -> bool {
+        self.in_memory.load(Ordering::Relaxed)
+    }
+
+    // Volcar lo guardado en memoria al archivo pendiente y seguir trabajando sobre él. Se pasa el oplog
+    // (dispositivos, confianza, actividad y alertas); escaneos, ajustes y el resto se quedan en memoria y se pierden.
+    // Las operaciones para las que `hold` devuelve true (con la base de datos del archivo) tampoco se pasan.
+    pub fn flush_to_file(&self, hold: impl Fn(&Database, &OplogEntry) -> bool) -> Result<OplogMergeSummary> {
+        let db_path = self.path();
+        println!("[DB] Flushing in-memory database to: {:?}", db_path);
+
+        let file_db = Database::open(db_path.clone())?;
+        // Sin soltar la conexión en memoria: lo que se anote mientras tanto espera al cambio de archivo
+        let mut conn = self.conn.lock().unwrap();
+        let (held, ops): (Vec<OplogEntry>, Vec<OplogEntry>) =
+            query_oplog(&conn, 0)?.into_iter().partition(|op| hold(&file_db, op));
+        let mut summary = file_db.merge_oplog(&ops)?;
+        summary.held = held.into_iter().map(|op| op.entity_id).collect();
+        drop(file_db);
+
+        *conn = Connection::open(&db_path)?;
+        self.in_memory.store(false, Ordering::Relaxed);
+        Ok(summary)
+    }
+
+    // Reemplazar la conexión por otro archivo (cambio de perfil).
+    // Todos los que comparten este Arc<Database> pasan a escribir en el nuevo archivo.
+    pub fn reopen(&self, db_path: PathBuf) -> Result<()> {
+        println!("[DB] Switching database to: {:?}", db_path);
+
+        // Se abre y se crean las tablas aparte: si falla, se sigue con la base de datos actual
+        let prepared = Database::open(db_path.clone())?;
+        let new_conn = std::mem::replace(&mut *prepared.conn.lock().unwrap(), Connection::open_in_memory()?);
+        *self.conn.lock().unwrap() = new_conn;
+        *self.path.lock().unwrap() = db_path;
+        self.in_memory.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.path.lock().unwrap().clone()
+    }
+
+    // Copia compacta y coherente de la base de datos en otro archivo (copias de seguridad)
+    pub fn export_copy(&self, dest: &Path) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute("VACUUM INTO ?1", params![dest.to_string_lossy()])?;
+        Ok(())
+    }
+
+    // Sustituir todo el contenido por el de otro archivo (restaurar una copia), sin cambiar de ruta
+    pub fn restore_from(&self, source: &Path) -> Result<()> {
+        {
+            let mut conn = self.conn.lock().unwrap();
+            conn.restore(DatabaseName::Main, source, None::<fn(rusqlite::backup::Progress)>)?;
+        }
+        println!("[DB] Database restored from {:?}", source);
+
+        // La copia puede venir de una versión anterior
+        self.init_tables()
+    }
+
+    // Escribir una fila de prueba dentro de una transacción que se descarta (autodiagnóstico)
+    pub fn check_writable(&self) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT OR REPLACE INTO settings (key, value, updated_at) VALUES ('__diagnostics', 'null', CURRENT_TIMESTAMP)",
+            [],
+        )?;
+        tx.rollback()
+    }
+
+    // Resultado de PRAGMA quick_check ("ok" si la base de datos está íntegra)
+    pub fn quick_check(&self) -> Result<String> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row("PRAGMA quick_check", [], |row| row.get(0))
+    }
+
+    fn init_tables(&self) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+
+        // Tabla devices
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS devices (
+                serial_number TEXT PRIMARY KEY,
+                vendor_id INTEGER NOT NULL,
+                product_id INTEGER NOT NULL,
+                name TEXT,
+                manufacturer TEXT,
+                total_capacity INTEGER,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Tabla activity_log
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS activity_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT NOT NULL,
+                event_type TEXT NOT NULL CHECK(event_type IN ('CONNECT', 'DISCONNECT', 'UNSAFE_REMOVAL')),
+                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (device_id) REFERENCES devices(serial_number)
+            )",
+            [],
+        )?;
+
+        // Tabla directories: cada prefijo de ruta se guarda una sola vez
+        // y los snapshots lo referencian por ID en lugar de repetir la ruta completa
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS directories (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                path TEXT NOT NULL UNIQUE
+            )",
+            [],
+        )?;
+
+        // Tabla file_snapshots (file_path = directories.path || file_name)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS file_snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                activity_log_id INTEGER NOT NULL,
+                directory_id INTEGER NOT NULL,
+                file_name TEXT NOT NULL,
+                file_extension TEXT,
+                file_size INTEGER NOT NULL,
+                is_folder BOOLEAN NOT NULL DEFAULT 0,
+                sha256 TEXT,
+                hash_mode TEXT,
+                scanned_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (activity_log_id) REFERENCES activity_log(id) ON DELETE CASCADE,
+                FOREIGN KEY (directory_id) REFERENCES directories(id)
+            )",
+            [],
+        )?;
+
+        // Tabla scans: totales de cada escaneo completado
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS scans (
+                activity_log_id INTEGER PRIMARY KEY,
+                device_id TEXT NOT NULL,
+                total_files INTEGER NOT NULL DEFAULT 0,
+                total_folders INTEGER NOT NULL DEFAULT 0,
+                total_bytes INTEGER NOT NULL DEFAULT 0,
+                used_bytes INTEGER,
+                total_capacity INTEGER,
+                completed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (activity_log_id) REFERENCES activity_log(id) ON DELETE CASCADE,
+                FOREIGN KEY (device_id) REFERENCES devices(serial_number)
+            )",
+            [],
+        )?;
+
+        // Tabla alerts
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS alerts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT,
+                activity_log_id INTEGER,
+                alert_type TEXT NOT NULL,
+                severity TEXT NOT NULL,
+                message TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                acknowledged BOOLEAN NOT NULL DEFAULT 0,
+                acknowledged_at DATETIME,
+                FOREIGN KEY (device_id) REFERENCES devices(serial_number),
+                FOREIGN KEY (activity_log_id) REFERENCES activity_log(id)
+            )",
+            [],
+        )?;
+
+        // Tabla reports: informes generados (contenido guardado o ruta del archivo exportado)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS reports (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                period_start DATETIME NOT NULL,
+                period_end DATETIME NOT NULL,
+                format TEXT NOT NULL,
+                content BLOB,
+                file_path TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Tabla file_events: cambios detectados por el watcher en cada sesión
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS file_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                activity_log_id INTEGER NOT NULL,
+                file_path TEXT NOT NULL,
+                event_type TEXT NOT NULL CHECK(event_type IN ('CREATED', 'MODIFIED', 'REMOVED')),
+                file_size INTEGER,
+                volume TEXT,
+                process_name TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (activity_log_id) REFERENCES activity_log(id)
+            )",
+            [],
+        )?;
+
+        // Tabla sessions: resumen de cada sesión, indexado por la conexión que la abrió
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sessions (
+                connect_activity_id INTEGER PRIMARY KEY,
+                disconnect_activity_id INTEGER,
+                device_id TEXT NOT NULL,
+                connected_at DATETIME NOT NULL,
+                disconnected_at DATETIME NOT NULL,
+                duration_secs INTEGER NOT NULL,
+                files_added INTEGER NOT NULL DEFAULT 0,
+                files_removed INTEGER NOT NULL DEFAULT 0,
+                files_modified INTEGER NOT NULL DEFAULT 0,
+                bytes_written INTEGER NOT NULL DEFAULT 0,
+                alerts_raised INTEGER NOT NULL DEFAULT 0,
+                FOREIGN KEY (connect_activity_id) REFERENCES activity_log(id),
+                FOREIGN KEY (device_id) REFERENCES devices(serial_number)
+            )",
+            [],
+        )?;
+
+        // Tabla saved_searches: consultas de search_files con nombre, opcionalmente usadas como regla de alerta
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS saved_searches (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                query TEXT NOT NULL,
+                filters TEXT,
+                alert_enabled BOOLEAN NOT NULL DEFAULT 0,
+                alert_severity TEXT NOT NULL DEFAULT 'MEDIUM',
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Tabla device_rule_overrides: umbrales de reglas propios de un dispositivo
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS device_rule_overrides (
+                device_id TEXT NOT NULL,
+                rule TEXT NOT NULL,
+                threshold REAL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (device_id, rule),
+                FOREIGN KEY (device_id) REFERENCES devices(serial_number)
+            )",
+            [],
+        )?;
+
+        // Tablas hash_sets y known_hashes: listas de SHA-256 de archivos conocidos para separarlos
+        // de los no identificados en los escaneos
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS hash_sets (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                source TEXT,
+                hash_count INTEGER NOT NULL DEFAULT 0,
+                imported_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS known_hashes (
+                sha256 TEXT NOT NULL,
+                hash_set_id INTEGER NOT NULL,
+                PRIMARY KEY (sha256, hash_set_id),
+                FOREIGN KEY (hash_set_id) REFERENCES hash_sets(id)
+            ) WITHOUT ROWID",
+            [],
+        )?;
+
+        // Tabla audit_log: ejecuciones de scripts y otras acciones auditables, con detalle JSON
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                action TEXT NOT NULL,
+                device_id TEXT,
+                details TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Tabla outbox: cola persistente de notificaciones salientes pendientes de entrega
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                channel TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                last_error TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Tabla device_descriptors: fabricante y producto en cada idioma que declara el dispositivo
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS device_descriptors (
+                device_id TEXT NOT NULL,
+                language_id INTEGER NOT NULL,
+                manufacturer TEXT,
+                product TEXT,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (device_id, language_id),
+                FOREIGN KEY (device_id) REFERENCES devices(serial_number)
+            )",
+            [],
+        )?;
+
+        // Tabla device_transfers: contadores de archivos y bytes escritos en cada dispositivo
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS device_transfers (
+                device_id TEXT PRIMARY KEY,
+                session_files INTEGER NOT NULL DEFAULT 0,
+                session_bytes INTEGER NOT NULL DEFAULT 0,
+                scan_files INTEGER NOT NULL DEFAULT 0,
+                scan_bytes INTEGER NOT NULL DEFAULT 0,
+                last_scan_activity_id INTEGER,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (device_id) REFERENCES devices(serial_number)
+            )",
+            [],
+        )?;
+
+        // Tabla device_attachments: archivos adjuntos a un dispositivo, copiados a la carpeta de datos
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS device_attachments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                stored_path TEXT NOT NULL,
+                mime_type TEXT,
+                file_size INTEGER NOT NULL,
+                sha256 TEXT,
+                description TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Tabla evidence: archivos llevados de un dispositivo al equipo (hash y copia opcional)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS evidence (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT NOT NULL,
+                activity_log_id INTEGER NOT NULL,
+                source_path TEXT NOT NULL,
+                host_path TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                file_size INTEGER NOT NULL,
+                sha256 TEXT,
+                evidence_path TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (activity_log_id) REFERENCES activity_log(id)
+            )",
+            [],
+        )?;
+
+        // Tabla document_metadata: autor, título, empresa... de los documentos de un escaneo
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS document_metadata (
+                snapshot_id INTEGER PRIMARY KEY,
+                activity_log_id INTEGER NOT NULL,
+                author TEXT,
+                title TEXT,
+                company TEXT,
+                last_saved_by TEXT,
+                application TEXT,
+                created TEXT,
+                modified TEXT,
+                FOREIGN KEY (snapshot_id) REFERENCES file_snapshots(id),
+                FOREIGN KEY (activity_log_id) REFERENCES activity_log(id)
+            )",
+            [],
+        )?;
+
+        // Tabla backup_profiles: carpetas a copiar a cada dispositivo de confianza al conectarlo
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS backup_profiles (
+                device_id TEXT PRIMARY KEY,
+                folders TEXT NOT NULL,
+                target_dir TEXT NOT NULL,
+                mode TEXT NOT NULL DEFAULT 'additive' CHECK(mode IN ('additive', 'mirror')),
+                conflict TEXT NOT NULL DEFAULT 'newer',
+                enabled BOOLEAN NOT NULL DEFAULT 1,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (device_id) REFERENCES devices(serial_number)
+            )",
+            [],
+        )?;
+
+        // Tabla backup_runs: historial de copias de seguridad
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS backup_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT NOT NULL,
+                activity_log_id INTEGER,
+                started_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                finished_at DATETIME,
+                status TEXT NOT NULL DEFAULT 'running',
+                files_copied INTEGER NOT NULL DEFAULT 0,
+                files_skipped INTEGER NOT NULL DEFAULT 0,
+                files_deleted INTEGER NOT NULL DEFAULT 0,
+                bytes_copied INTEGER NOT NULL DEFAULT 0,
+                error TEXT
+            )",
+            [],
+        )?;
+
+        // Tabla sync_state: estado de cada archivo tras la última copia de seguridad, para detectar conflictos
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_state (
+                device_id TEXT NOT NULL,
+                relative_path TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                modified INTEGER NOT NULL,
+                sha256 TEXT,
+                synced_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (device_id, relative_path)
+            )",
+            [],
+        )?;
+
+        // Tabla sync_conflicts: archivos cambiados en ambos lados que la copia no ha sobrescrito
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_conflicts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT NOT NULL,
+                run_id INTEGER NOT NULL,
+                relative_path TEXT NOT NULL,
+                host_path TEXT NOT NULL,
+                device_path TEXT NOT NULL,
+                host_deleted BOOLEAN NOT NULL DEFAULT 0,
+                resolved BOOLEAN NOT NULL DEFAULT 0,
+                detected_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (run_id) REFERENCES backup_runs(id)
+            )",
+            [],
+        )?;
+
+        // Tabla scan_checkpoints: escaneos completos en curso o interrumpidos (se borran al terminar)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS scan_checkpoints (
+                activity_log_id INTEGER PRIMARY KEY,
+                device_id TEXT NOT NULL,
+                mount_point TEXT NOT NULL,
+                last_path TEXT,
+                total_files INTEGER NOT NULL DEFAULT 0,
+                total_folders INTEGER NOT NULL DEFAULT 0,
+                total_bytes INTEGER NOT NULL DEFAULT 0,
+                batches INTEGER NOT NULL DEFAULT 0,
+                started_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                abandoned BOOLEAN NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // Tabla access_grants: accesos temporales que restauran la confianza anterior al vencer
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS access_grants (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT NOT NULL,
+                previous_trust TEXT NOT NULL,
+                granted_trust TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'active',
+                expires_at DATETIME NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                ended_at DATETIME
+            )",
+            [],
+        )?;
+
+        // Tabla oplog: registro de cambios solo de anexar, para fusionar bases de datos de varios equipos
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS oplog (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                op_id TEXT NOT NULL UNIQUE,
+                machine_id TEXT NOT NULL,
+                entity TEXT NOT NULL,
+                entity_id TEXT NOT NULL,
+                op TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Tabla settings (clave -> valor JSON), propia de cada perfil
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Tabla machines: equipos que han escrito en esta base de datos
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS machines (
+                machine_id TEXT PRIMARY KEY,
+                hostname TEXT,
+                first_seen DATETIME DEFAULT CURRENT_TIMESTAMP,
+                last_seen DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+
+        // Migraciones de columnas añadidas después de la primera versión
+        add_column_if_missing(&conn, "activity_log", "source", "TEXT NOT NULL DEFAULT 'monitor'")?;
+        add_column_if_missing(&conn, "devices", "trust_level", "TEXT NOT NULL DEFAULT 'UNKNOWN'")?;
+        add_column_if_missing(&conn, "activity_log", "mount_point", "TEXT")?;
+        add_column_if_missing(&conn, "file_snapshots", "sha256", "TEXT")?;
+        add_column_if_missing(&conn, "activity_log", "os_user", "TEXT")?;
+        add_column_if_missing(&conn, "activity_log", "session_id", "INTEGER")?;
+        add_column_if_missing(&conn, "activity_log", "machine_id", "TEXT")?;
+        add_column_if_missing(&conn, "devices", "machine_id", "TEXT")?;
+        add_column_if_missing(&conn, "devices", "nickname", "TEXT")?;
+        add_column_if_missing(&conn, "alerts", "message_key", "TEXT")?;
+        add_column_if_missing(&conn, "alerts", "message_params", "TEXT")?;
+        add_column_if_missing(&conn, "activity_log", "port_path", "TEXT")?;
+        add_column_if_missing(&conn, "devices", "volume_serial", "TEXT")?;
+        add_column_if_missing(&conn, "devices", "first_seen", "DATETIME")?;
+        add_column_if_missing(&conn, "devices", "last_seen", "DATETIME")?;
+        add_column_if_missing(&conn, "backup_profiles", "detect_conflicts", "BOOLEAN NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "backup_runs", "files_conflicted", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "scans", "usn_journal_id", "INTEGER")?;
+        add_column_if_missing(&conn, "scans", "usn_next", "INTEGER")?;
+        add_column_if_missing(&conn, "activity_log", "volume_serial", "TEXT")?;
+        add_column_if_missing(&conn, "devices", "volume_label", "TEXT")?;
+        add_column_if_missing(&conn, "devices", "media_type", "TEXT NOT NULL DEFAULT 'USB'")?;
+        add_column_if_missing(&conn, "scans", "status", "TEXT NOT NULL DEFAULT 'DONE'")?;
+        add_column_if_missing(&conn, "scans", "error", "TEXT")?;
+        add_column_if_missing(&conn, "scans", "started_at", "DATETIME")?;
+        add_column_if_missing(&conn, "activity_log", "uuid", "TEXT")?;
+        add_column_if_missing(&conn, "alerts", "uuid", "TEXT")?;
+        add_column_if_missing(&conn, "file_snapshots", "hash_mode", "TEXT")?;
+        add_column_if_missing(&conn, "devices", "case_reference", "TEXT")?;
+        add_column_if_missing(&conn, "activity_log", "case_reference", "TEXT")?;
+        add_column_if_missing(&conn, "file_events", "volume", "TEXT")?;
+        add_column_if_missing(&conn, "file_events", "process_name", "TEXT")?;
+        add_column_if_missing(&conn, "devices", "baseline_activity_id", "INTEGER")?;
+        add_column_if_missing(&conn, "devices", "usb_serial", "TEXT")?;
+        add_column_if_missing(&conn, "scans", "reused_from", "INTEGER")?;
+        add_column_if_missing(&conn, "devices", "skip_scan", "BOOLEAN NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "devices", "skip_watch", "BOOLEAN NOT NULL DEFAULT 0")?;
+        add_column_if_missing(&conn, "devices", "redact_paths", "BOOLEAN")?;
+        add_column_if_missing(&conn, "scans", "redacted", "BOOLEAN NOT NULL DEFAULT 0")?;
+        backfill_device_seen(&conn)?;
+        normalize_volume_serials(&conn)?;
+        migrate_snapshot_paths(&mut conn)?;
+        migrate_activity_event_types(&mut conn)?;
+        backfill_oplog(&mut conn)?;
+
+        // Índices para búsquedas más rápidas
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_activity_log_device_id ON activity_log(device_id)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_activity_log_timestamp ON activity_log(timestamp)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_activity_log_case ON activity_log(case_reference)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_devices_case ON devices(case_reference)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_file_snapshots_activity_id ON file_snapshots(activity_log_id)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_alerts_created_at ON alerts(created_at)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_activity_log_uuid ON activity_log(uuid)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_alerts_uuid ON alerts(uuid)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_oplog_entity ON oplog(entity, entity_id)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_file_snapshots_name ON file_snapshots(file_name COLLATE NOCASE)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_file_snapshots_sha256 ON file_snapshots(sha256)",
+            [],
+        )?;
+
+        // Hijos de una carpeta dentro de un escaneo (vista de árbol)
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_file_snapshots_directory ON file_snapshots(activity_log_id, directory_id)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_file_events_activity_id ON file_events(activity_log_id)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_outbox_due ON outbox(status, next_attempt_at)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_evidence_device_id ON evidence(device_id)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_device_attachments_device_id ON device_attachments(device_id)",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_backup_runs_device_id ON backup_runs(device_id)",
+            [],
+        )?;
+
+        // Registrar este equipo
+        let machine = crate::machine::current();
+        conn.execute(
+            "INSERT INTO machines (machine_id, hostname) VALUES (?1, ?2)
+             ON CONFLICT(machine_id) DO UPDATE SET
+                hostname = excluded.hostname,
+                last_seen = CURRENT_TIMESTAMP",
+            params![machine.machine_id, machine.hostname],
+        )?;
+
+        Ok(())
+    }
+
+    // Upsert device (insertar o actualizar)
+    pub fn upsert_device(&self, device: &Device) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO devices (serial_number, vendor_id, product_id, name, manufacturer, total_capacity, machine_id, volume_serial, volume_label, media_type, usb_serial, first_seen, last_seen, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+             ON CONFLICT(serial_number) DO UPDATE SET
+                vendor_id = excluded.vendor_id,
+                product_id = excluded.product_id,
+                name = excluded.name,
+                manufacturer = excluded.manufacturer,
+                total_capacity = excluded.total_capacity,
+                volume_serial = COALESCE(excluded.volume_serial, devices.volume_serial),
+                volume_label = COALESCE(excluded.volume_label, devices.volume_label),
+                media_type = excluded.media_type,
+                usb_serial = COALESCE(excluded.usb_serial, devices.usb_serial),
+                first_seen = COALESCE(devices.first_seen, excluded.first_seen),
+                last_seen = CURRENT_TIMESTAMP,
+                updated_at = CURRENT_TIMESTAMP",
+            params![
+                device.serial_number,
+                device.vendor_id,
+                device.product_id,
+                device.name,
+                device.manufacturer,
+                device.total_capacity,
+                device.machine_id,
+                device.volume_serial,
+                device.volume_label,
+                device.media_type.as_str(),
+                device.usb_serial,
+            ],
+        )?;
+        append_device_op(&tx, &device.serial_number)?;
+        tx.commit()?;
+
+        println!("[DB] Device upserted: {}", device.serial_number);
+        Ok(())
+    }
+
+    // Crear registro de actividad
+    pub fn create_activity_log(
+        &self,
+        device_id: &str,
+        event_type: EventType,
+        mount_point: Option<&str>,
+        port_path: Option<&str>,
+        volume_serial: Option<&str>,
+        session: Option<&OsSession>,
+    ) -> Result<i64> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO activity_log (device_id, event_type, timestamp, mount_point, port_path, volume_serial, os_user, session_id, machine_id)
+             VALUES (?1, ?2, CURRENT_TIMESTAMP, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                device_id,
+                event_type.as_str(),
+                mount_point,
+                port_path,
+                volume_serial,
+                session.and_then(|s| s.user.as_deref()),
+                session.and_then(|s| s.session_id),
+                crate::machine::current().machine_id,
+            ],
+        )?;
+
+        let id = tx.last_insert_rowid();
+        append_activity_op(&tx, id)?;
+        tx.commit()?;
+        println!(
+            "[DB] Activity log created: id={}, device={}, type={}",
+            id,
+            device_id,
+            event_type.as_str()
+        );
+
+        Ok(id)
+    }
+
+    // Insertar snapshot de archivo en batch (más eficiente)
+    pub fn insert_file_snapshots_batch(&self, snapshots: &[FileSnapshot]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        insert_snapshots(&tx, snapshots)?;
+
+        tx.commit()?;
+        println!("[DB] Inserted {} file snapshots", snapshots.len());
+
+        Ok(())
+    }
+
+    // Empezar (o retomar) el registro de progreso de un escaneo completo
+    pub fn start_scan_checkpoint(&self, activity_log_id: i64, device_id: &str, mount_point: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR IGNORE INTO scan_checkpoints (activity_log_id, device_id, mount_point, started_at, updated_at)
+             VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)",
+            params![activity_log_id, device_id, mount_point],
+        )?;
+
+        Ok(())
+    }
+
+    // Guardar un lote del escaneo y su progreso en la misma transacción: tras un corte,
+    // lo guardado coincide siempre con el punto de reanudación
+    pub fn save_scan_batch(&self, snapshots: &[FileSnapshot], checkpoint: &ScanCheckpoint) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        insert_snapshots(&tx, snapshots)?;
+        tx.execute(
+            "UPDATE scan_checkpoints
+             SET last_path = ?2, total_files = ?3, total_folders = ?4, total_bytes = ?5, batches = ?6, updated_at = CURRENT_TIMESTAMP
+             WHERE activity_log_id = ?1",
+            params![
+                checkpoint.activity_log_id,
+                checkpoint.last_path,
+                checkpoint.total_files,
+                checkpoint.total_folders,
+                checkpoint.total_bytes,
+                checkpoint.batches,
+            ],
+        )?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    // Escaneos completos sin terminar, de un dispositivo o de todos
+    pub fn get_scan_checkpoints(&self, device_id: Option<&str>) -> Result<Vec<ScanCheckpoint>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT activity_log_id, device_id, mount_point, last_path, total_files, total_folders, total_bytes,
+                    batches, started_at, updated_at, abandoned
+             FROM scan_checkpoints
+             WHERE ?1 IS NULL OR device_id = ?1
+             ORDER BY activity_log_id DESC",
+        )?;
+
+        let checkpoint_iter = stmt.query_map(params![device_id], |row| {
+            Ok(ScanCheckpoint {
+                activity_log_id: row.get(0)?,
+                device_id: row.get(1)?,
+                mount_point: row.get(2)?,
+                last_path: row.get(3)?,
+                total_files: row.get(4)?,
+                total_folders: row.get(5)?,
+                total_bytes: row.get(6)?,
+                batches: row.get(7)?,
+                started_at: row.get(8)?,
+                updated_at: row.get(9)?,
+                abandoned: row.get(10)?,
+            })
+        })?;
+
+        let mut checkpoints = Vec::new();
+        for checkpoint in checkpoint_iter {
+            checkpoints.push(checkpoint?);
+        }
+
+        Ok(checkpoints)
+    }
+
+    // Dar por perdidos los escaneos sin terminar de un dispositivo (salvo el de `activity_log_id`)
+    pub fn abandon_scan_checkpoints(&self, device_id: &str, activity_log_id: i64) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE scan_checkpoints SET abandoned = 1
+             WHERE device_id = ?1 AND activity_log_id != ?2 AND abandoned = 0",
+            params![device_id, activity_log_id],
+        )
+    }
+
+    // Insertar snapshot individual
+    pub fn insert_file_snapshot(&self, snapshot: &FileSnapshot) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let (prefix, file_name) = split_snapshot_path(&snapshot.file_path, &snapshot.file_name);
+        let directory_id = resolve_directory_id(&conn, prefix)?;
+
+        conn.execute(
+            "INSERT INTO file_snapshots (activity_log_id, directory_id, file_name, file_extension, file_size, is_folder, sha256, hash_mode)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                snapshot.activity_log_id,
+                directory_id,
+                file_name,
+                snapshot.file_extension,
+                snapshot.file_size,
+                snapshot.is_folder,
+                snapshot.sha256,
+                snapshot.hash_mode.map(|m| m.as_str()),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    // Obtener historial de actividad (opcionalmente de un solo dispositivo o de un caso).
+    // Un caso incluye los eventos vinculados a él y todos los de los dispositivos vinculados.
+    pub fn get_activity_history(&self, limit: i64, device_id: Option<&str>, case_reference: Option<&str>) -> Result<Vec<ActivityLog>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE (?2 IS NULL OR device_id = ?2)
+               AND (?3 IS NULL OR case_reference = ?3
+                    OR device_id IN (SELECT serial_number FROM devices WHERE case_reference = ?3))
+             ORDER BY timestamp DESC LIMIT ?1",
+            ACTIVITY_SELECT
+        ))?;
+
+        let activity_iter = stmt.query_map(params![limit, device_id, case_reference], row_to_activity)?;
+
+        let mut activities = Vec::new();
+        for activity in activity_iter {
+            activities.push(activity?);
+        }
+
+        Ok(activities)
+    }
+
+    // Una página de los archivos de un escaneo, por id, para recorrer escaneos enormes sin cargarlos enteros
+    pub fn get_file_snapshots_page(&self, activity_log_id: i64, after_id: i64, limit: i64) -> Result<Vec<FileSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE f.activity_log_id = ?1 AND f.id > ?2 ORDER BY f.id LIMIT ?3",
+            SNAPSHOT_SELECT
+        ))?;
+        let snapshots = stmt.query_map(params![activity_log_id, after_id, limit], row_to_snapshot)?;
+        snapshots.collect()
+    }
+
+    // Entradas de un escaneo cuyo directorio es uno de los dados (los hijos directos de una carpeta);
+    // carpetas primero y después por nombre
+    pub fn get_directory_children(&self, activity_log_id: i64, directory_paths: &[String]) -> Result<Vec<FileSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE f.activity_log_id = ? AND d.path IN ({})
+             ORDER BY f.is_folder DESC, f.file_name COLLATE NOCASE",
+            SNAPSHOT_SELECT,
+            id_placeholders(directory_paths.len())
+        ))?;
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&activity_log_id];
+        values.extend(directory_paths.iter().map(|p| p as &dyn rusqlite::ToSql));
+        let snapshots = stmt.query_map(values.as_slice(), row_to_snapshot)?;
+        snapshots.collect()
+    }
+
+    // Número de entradas de un escaneo en cada uno de los directorios dados (los que no tienen ninguna no aparecen)
+    pub fn count_directory_children(&self, activity_log_id: i64, directory_paths: &[String]) -> Result<HashMap<String, i64>> {
+        if directory_paths.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT d.path, COUNT(*) FROM file_snapshots f
+             JOIN directories d ON d.id = f.directory_id
+             WHERE f.activity_log_id = ? AND d.path IN ({})
+             GROUP BY d.path",
+            id_placeholders(directory_paths.len())
+        ))?;
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&activity_log_id];
+        values.extend(directory_paths.iter().map(|p| p as &dyn rusqlite::ToSql));
+        let counts = stmt.query_map(values.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?;
+        counts.collect()
+    }
+
+    // Obtener snapshots de un activity_log específico
+    pub fn get_file_snapshots(&self, activity_log_id: i64) -> Result<Vec<FileSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+
+        query_snapshots(&conn, activity_log_id)
+    }
+
+    // Vincular dispositivos y eventos a un caso (None lo quita). Devuelve cuántos se han cambiado.
+    pub fn set_case_reference(&self, device_ids: &[String], activity_ids: &[i64], case_reference: Option<&str>) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut updated = 0;
+
+        for device_id in device_ids {
+            updated += tx.execute(
+                "UPDATE devices SET case_reference = ?2, updated_at = CURRENT_TIMESTAMP WHERE serial_number = ?1",
+                params![device_id, case_reference],
+            )?;
+        }
+        for activity_id in activity_ids {
+            updated += tx.execute(
+                "UPDATE activity_log SET case_reference = ?2 WHERE id = ?1",
+                params![activity_id, case_reference],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(updated)
+    }
+
+    // Escaneo de referencia ("imagen maestra") de un dispositivo; None lo quita
+    pub fn set_device_baseline(&self, device_id: &str, activity_log_id: Option<i64>) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE devices SET baseline_activity_id = ?2, updated_at = CURRENT_TIMESTAMP WHERE serial_number = ?1",
+            params![device_id, activity_log_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // Excluir un dispositivo del escaneo y/o de la vigilancia de archivos
+    pub fn set_device_monitoring(&self, device_id: &str, skip_scan: bool, skip_watch: bool) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE devices SET skip_scan = ?2, skip_watch = ?3, updated_at = CURRENT_TIMESTAMP WHERE serial_number = ?1",
+            params![device_id, skip_scan, skip_watch],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn set_device_redact_paths(&self, device_id: &str, redact_paths: Option<bool>) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE devices SET redact_paths = ?2, updated_at = CURRENT_TIMESTAMP WHERE serial_number = ?1",
+            params![device_id, redact_paths],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // Anonimizar rutas (`path`) y nombres de usuario (`user`) de lo registrado de un dispositivo (o de todos)
+    // antes de `before`, sin tocar recuentos ni tamaños. Incluye las copias del oplog de actividad y alertas
+    // y el nombre de las copias de evidencias en disco; si alguna no se puede renombrar no se cambia nada.
+    // Después se hace VACUUM para que los valores antiguos no queden en páginas libres.
+    pub fn redact_history(
+        &self,
+        device_id: Option<&str>,
+        before: Option<DateTime<Utc>>,
+        path: Option<&dyn Fn(&str) -> String>,
+        user: Option<&dyn Fn(&str) -> String>,
+    ) -> Result<RedactionSummary> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let before = before.map(format_timestamp);
+        let apply = |redact: Option<&dyn Fn(&str) -> String>, value: Option<String>| match (redact, value) {
+            (Some(redact), Some(value)) => Some(redact(&value)),
+            (_, value) => value,
+        };
+        let mut summary = RedactionSummary::default();
+        let mut renames: Vec<(PathBuf, PathBuf)> = Vec::new();
+        const SCOPE: &str = "SELECT id FROM activity_log WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR timestamp < ?2)";
+
+        // Actividad: punto de montaje y usuario del sistema
+        let activities = tx
+            .prepare("SELECT id, mount_point, os_user, uuid FROM activity_log WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR timestamp < ?2)")?
+            .query_map(params![device_id, before], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        for (id, mount_point, os_user, uuid) in activities {
+            let mount_point = apply(path, mount_point);
+            let os_user = apply(user, os_user);
+            tx.execute("UPDATE activity_log SET mount_point = ?2, os_user = ?3 WHERE id = ?1", params![id, mount_point, os_user])?;
+            if let Some(uuid) = uuid {
+                tx.execute(
+                    "UPDATE oplog SET payload = json_set(payload, '$.mount_point', ?2, '$.os_user', ?3) WHERE entity = ?4 AND entity_id = ?1",
+                    params![uuid, mount_point, os_user, OP_ENTITY_ACTIVITY],
+                )?;
+            }
+            summary.activities += 1;
+            summary.activity_ids.push(id);
+        }
+
+        if let Some(path) = path {
+            // Snapshots: carpeta y nombre (con su extensión) por separado; las carpetas que quedan sin uso se borran
+            let snapshots = tx
+                .prepare(&format!(
+                    "SELECT s.id, d.path, s.file_name, s.file_extension, s.is_folder
+                     FROM file_snapshots s JOIN directories d ON d.id = s.directory_id
+                     WHERE s.activity_log_id IN ({})",
+                    SCOPE
+                ))?
+                .query_map(params![device_id, before], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, Option<String>>(3)?,
+                        row.get::<_, bool>(4)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            let mut directory_cache: HashMap<String, i64> = HashMap::new();
+            let mut update = tx.prepare("UPDATE file_snapshots SET directory_id = ?2, file_name = ?3 WHERE id = ?1")?;
+            for (id, directory, file_name, extension, is_folder) in snapshots {
+                let redacted_directory = format!("{}/", path(&directory));
+                let directory_id = match directory_cache.get(&redacted_directory) {
+                    Some(id) => *id,
+                    None => {
+                        let id = resolve_directory_id(&tx, &redacted_directory)?;
+                        directory_cache.insert(redacted_directory, id);
+                        id
+                    }
+                };
+                let mut name = path(&format!("{}{}", directory, file_name));
+                if let Some(extension) = extension.filter(|_| !is_folder) {
+                    name = format!("{}.{}", name, extension);
+                }
+                update.execute(params![id, directory_id, name])?;
+                summary.snapshots += 1;
+            }
+            drop(update);
+            tx.execute("DELETE FROM directories WHERE id NOT IN (SELECT directory_id FROM file_snapshots)", [])?;
+
+            let events = tx
+                .prepare(&format!("SELECT id, file_path, volume FROM file_events WHERE activity_log_id IN ({})", SCOPE))?
+                .query_map(params![device_id, before], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            for (id, file_path, volume) in events {
+                tx.execute(
+                    "UPDATE file_events SET file_path = ?2, volume = ?3 WHERE id = ?1",
+                    params![id, path(&file_path), apply(Some(path), volume)],
+                )?;
+                summary.file_events += 1;
+            }
+
+            let evidence = tx
+                .prepare(&format!(
+                    "SELECT id, source_path, host_path, file_name, evidence_path FROM evidence WHERE activity_log_id IN ({})",
+                    SCOPE
+                ))?
+                .query_map(params![device_id, before], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            // La copia guardada lleva el nombre original: se renombra con el id (el nombre anonimizado puede repetirse)
+            for (id, source_path, host_path, file_name, evidence_path) in evidence {
+                let file_name = path(&file_name);
+                let evidence_path = match evidence_path.map(PathBuf::from).filter(|copy| copy.exists()) {
+                    Some(copy) => {
+                        let target = copy.with_file_name(format!("{}_{}", id, file_name));
+                        let stored = target.to_string_lossy().to_string();
+                        renames.push((copy, target));
+                        Some(stored)
+                    }
+                    None => None,
+                };
+                tx.execute(
+                    "UPDATE evidence SET source_path = ?2, host_path = ?3, file_name = ?4, evidence_path = ?5 WHERE id = ?1",
+                    params![id, path(&source_path), path(&host_path), file_name, evidence_path],
+                )?;
+                summary.evidence += 1;
+            }
+
+            // Copias de seguridad: el estado de la última copia son solo rutas y se borra (la siguiente lo
+            // vuelve a crear); los conflictos se conservan con sus rutas anonimizadas
+            tx.execute(
+                "DELETE FROM sync_state WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR synced_at < ?2)",
+                params![device_id, before],
+            )?;
+            let conflicts = tx
+                .prepare(
+                    "SELECT id, relative_path, host_path, device_path FROM sync_conflicts
+                     WHERE (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR detected_at < ?2)",
+                )?
+                .query_map(params![device_id, before], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            for (id, relative_path, host_path, device_path) in conflicts {
+                tx.execute(
+                    "UPDATE sync_conflicts SET relative_path = ?2, host_path = ?3, device_path = ?4 WHERE id = ?1",
+                    params![id, path(&relative_path), path(&host_path), path(&device_path)],
+                )?;
+                summary.sync_conflicts += 1;
+            }
+
+            // Un inventario anonimizado no se reutiliza: la siguiente conexión copiaría las rutas anonimizadas
+            tx.execute(&format!("UPDATE scans SET redacted = 1 WHERE activity_log_id IN ({})", SCOPE), params![device_id, before])?;
+
+            // Los escaneos interrumpidos ya no se pueden reanudar sin la última ruta
+            tx.execute(
+                &format!(
+                    "UPDATE scan_checkpoints SET mount_point = '', last_path = NULL, abandoned = 1 WHERE activity_log_id IN ({})",
+                    SCOPE
+                ),
+                params![device_id, before],
+            )?;
+        }
+
+        if let Some(user) = user {
+            let documents = tx
+                .prepare(&format!("SELECT snapshot_id, author, last_saved_by FROM document_metadata WHERE activity_log_id IN ({})", SCOPE))?
+                .query_map(params![device_id, before], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?))
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            for (snapshot_id, author, last_saved_by) in documents {
+                tx.execute(
+                    "UPDATE document_metadata SET author = ?2, last_saved_by = ?3 WHERE snapshot_id = ?1",
+                    params![snapshot_id, apply(Some(user), author), apply(Some(user), last_saved_by)],
+                )?;
+                summary.documents += 1;
+            }
+        }
+
+        // Alertas: los parámetros con rutas o usuarios, y el texto vuelto a generar con ellos
+        let alerts = tx
+            .prepare(
+                "SELECT id, message_key, message_params, uuid FROM alerts
+                 WHERE message_key IS NOT NULL AND message_params IS NOT NULL
+                   AND (?1 IS NULL OR device_id = ?1) AND (?2 IS NULL OR created_at < ?2)",
+            )?
+            .query_map(params![device_id, before], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<String>>(3)?))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        for (id, key, params_json, uuid) in alerts {
+            let Ok(serde_json::Value::Object(mut values)) = serde_json::from_str::<serde_json::Value>(&params_json) else {
+                continue;
+            };
+            let mut changed = false;
+            for (name, value) in values.iter_mut() {
+                let redact = match name.as_str() {
+                    "path" | "paths" | "file" => path,
+                    "user" | "os_user" => user,
+                    _ => None,
+                };
+                if let (Some(redact), serde_json::Value::String(text)) = (redact, &value) {
+                    // "paths" es una lista separada por comas
+                    let redacted: Vec<String> = text.split(", ").map(redact).collect();
+                    *value = serde_json::Value::String(redacted.join(", "));
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+            let message = Message::new(&key, serde_json::Value::Object(values));
+            let text = message.render(DEFAULT_LANG);
+            let params_json = message.params.to_string();
+            tx.execute("UPDATE alerts SET message = ?2, message_params = ?3 WHERE id = ?1", params![id, text, params_json])?;
+            if let Some(uuid) = uuid {
+                tx.execute(
+                    "UPDATE oplog SET payload = json_set(payload, '$.message', ?2, '$.message_params', ?3) WHERE entity = ?4 AND entity_id = ?1",
+                    params![uuid, text, params_json, OP_ENTITY_ALERT],
+                )?;
+            }
+            summary.alerts += 1;
+        }
+
+        // Los archivos se renombran al final, y se deshace si falla alguno o no se puede confirmar
+        let done = rename_files(&renames).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        if let Err(e) = tx.commit() {
+            undo_renames(&renames[..done]);
+            return Err(e);
+        }
+        conn.execute("VACUUM", [])?;
+        println!(
+            "[DB] History redacted: {} activities, {} snapshots, {} file events, {} evidence, {} documents, {} alerts, {} sync conflicts",
+            summary.activities,
+            summary.snapshots,
+            summary.file_events,
+            summary.evidence,
+            summary.documents,
+            summary.alerts,
+            summary.sync_conflicts
+        );
+        Ok(summary)
+    }
+
+    pub fn get_device_baseline(&self, device_id: &str) -> Result<Option<i64>> {
+        let conn = self.conn.lock().unwrap();
+        let baseline = conn
+            .query_row(
+                "SELECT baseline_activity_id FROM devices WHERE serial_number = ?1",
+                params![device_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(baseline.flatten())
+    }
+
+    // Dispositivos vinculados a un caso
+    pub fn get_case_devices(&self, case_reference: &str) -> Result<Vec<Device>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!("{} WHERE case_reference = ?1 ORDER BY serial_number", DEVICE_SELECT))?;
+        let devices = stmt.query_map(params![case_reference], row_to_device)?;
+        devices.collect()
+    }
+
+    // Casos en uso, con cuántos dispositivos y eventos tiene cada uno
+    pub fn get_case_references(&self) -> Result<Vec<(String, i64, i64)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT case_reference, SUM(is_device), SUM(1 - is_device) FROM (
+                 SELECT case_reference, 1 AS is_device FROM devices WHERE case_reference IS NOT NULL
+                 UNION ALL
+                 SELECT case_reference, 0 FROM activity_log WHERE case_reference IS NOT NULL
+             )
+             GROUP BY case_reference ORDER BY case_reference",
+        )?;
+        let cases = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        cases.collect()
+    }
+
+    // Obtener un dispositivo registrado por su número de serie
+    pub fn get_device(&self, device_id: &str) -> Result<Option<Device>> {
+        let conn = self.conn.lock().unwrap();
+
+        let device = conn
+            .query_row(
+                &format!("{} WHERE serial_number = ?1", DEVICE_SELECT),
+                params![device_id],
+                row_to_device,
+            )
+            .ok();
+
+        Ok(device)
+    }
+
+    // El registro base y sus variantes ("<serie>~<volumen>"): memorias distintas que dan el mismo número de serie USB
+    pub fn get_device_variants(&self, base_serial: &str) -> Result<Vec<Device>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE serial_number = ?1 OR substr(serial_number, 1, length(?1) + 1) = ?1 || '{}'
+             ORDER BY serial_number = ?1 DESC, first_seen",
+            DEVICE_SELECT, DEVICE_VARIANT_SEPARATOR
+        ))?;
+
+        let device_iter = stmt.query_map(params![base_serial], row_to_device)?;
+
+        let mut devices = Vec::new();
+        for device in device_iter {
+            devices.push(device?);
+        }
+
+        Ok(devices)
+    }
+
+    // Completar los datos de un dispositivo cuyos descriptores no se pudieron leer al conectar
+    pub fn backfill_device_names(
+        &self,
+        device_id: &str,
+        vendor_id: u16,
+        product_id: u16,
+        name: Option<&str>,
+        manufacturer: Option<&str>,
+        usb_serial: Option<&str>,
+    ) -> Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let updated = tx.execute(
+            "UPDATE devices SET
+                vendor_id = ?2,
+                product_id = ?3,
+                name = COALESCE(?4, name),
+                manufacturer = COALESCE(?5, manufacturer),
+                usb_serial = COALESCE(?6, usb_serial),
+                updated_at = CURRENT_TIMESTAMP
+             WHERE serial_number = ?1",
+            params![device_id, vendor_id, product_id, name, manufacturer, usb_serial],
+        )?;
+        if updated > 0 {
+            append_device_op(&tx, device_id)?;
+        }
+        tx.commit()?;
+        Ok(updated > 0)
+    }
+
+    // Guardar los descriptores leídos al conectar; los idiomas ya guardados se actualizan
+    pub fn save_device_descriptors(&self, device_id: &str, descriptors: &[StringDescriptors]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for descriptor in descriptors {
+            tx.execute(
+                "INSERT INTO device_descriptors (device_id, language_id, manufacturer, product, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, CURRENT_TIMESTAMP)
+                 ON CONFLICT(device_id, language_id) DO UPDATE SET
+                    manufacturer = excluded.manufacturer,
+                    product = excluded.product,
+                    updated_at = CURRENT_TIMESTAMP",
+                params![device_id, descriptor.language_id, descriptor.manufacturer, descriptor.product],
+            )?;
+        }
+        tx.commit()
+    }
+
+    pub fn get_device_descriptors(&self, device_id: &str) -> Result<Vec<StringDescriptors>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT language_id, manufacturer, product FROM device_descriptors WHERE device_id = ?1 ORDER BY language_id",
+        )?;
+        let descriptors = stmt.query_map(params![device_id], |row| {
+            Ok(StringDescriptors { language_id: row.get(0)?, manufacturer: row.get(1)?, product: row.get(2)? })
+        })?;
+        descriptors.collect()
+    }
+
+    // Volúmenes vistos en las conexiones de un dispositivo; más de uno suele indicar memorias distintas mezcladas
+    pub fn get_device_volumes(&self, device_id: &str) -> Result<Vec<DeviceVolume>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT volume_serial, COUNT(*), MIN(timestamp), MAX(timestamp), GROUP_CONCAT(id)
+             FROM activity_log
+             WHERE device_id = ?1 AND event_type = 'CONNECT'
+             GROUP BY volume_serial
+             ORDER BY MIN(timestamp)",
+        )?;
+
+        let volume_iter = stmt.query_map(params![device_id], |row| {
+            let ids: String = row.get(4)?;
+            Ok(DeviceVolume {
+                volume_serial: row.get(0)?,
+                connections: row.get(1)?,
+                first_seen: row.get(2)?,
+                last_seen: row.get(3)?,
+                activity_ids: ids.split(',').filter_map(|id| id.parse().ok()).collect(),
+            })
+        })?;
+
+        let mut volumes = Vec::new();
+        for volume in volume_iter {
+            volumes.push(volume?);
+        }
+
+        Ok(volumes)
+    }
+
+    // Unir el historial de `source` al de `target` y borrar `source`. Devuelve las filas movidas.
+    pub fn merge_devices(&self, source: &str, target: &str) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let before = device_trust(&tx, target)?;
+        let moved = merge_device_rows(&tx, source, target)?;
+        append_merge_ops(&tx, source, target, before)?;
+        tx.commit()?;
+        println!("[DB] Merged device {} into {} ({} rows)", source, target, moved);
+        Ok(moved)
+    }
+
+    // Cambiar el identificador de un dispositivo con todo su historial. Si `new_id` ya existe se une a él
+    // (dos registros que con la nueva estrategia son el mismo). Devuelve las filas movidas.
+    pub fn rekey_device(&self, old_id: &str, new_id: &str) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM devices WHERE serial_number = ?1)",
+            params![new_id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            // Copia del registro con el nuevo identificador; el historial pasa a ella al unir
+            let columns: Vec<String> = {
+                let mut stmt = tx.prepare("SELECT name FROM pragma_table_info('devices') WHERE name != 'serial_number'")?;
+                let names = stmt.query_map([], |row| row.get(0))?;
+                names.collect::<Result<_>>()?
+            };
+            let columns = columns.join(", ");
+            tx.execute(
+                &format!(
+                    "INSERT INTO devices (serial_number, {0}) SELECT ?2, {0} FROM devices WHERE serial_number = ?1",
+                    columns
+                ),
+                params![old_id, new_id],
+            )?;
+        }
+        // La copia nueva no tiene aún confianza en los demás equipos
+        let before = if exists { device_trust(&tx, new_id)? } else { TrustLevel::Unknown };
+        let moved = merge_device_rows(&tx, old_id, new_id)?;
+        append_device_op(&tx, new_id)?;
+        append_merge_ops(&tx, old_id, new_id, before)?;
+        tx.commit()?;
+
+        println!("[DB] Re-keyed device {} as {} ({} rows)", old_id, new_id, moved);
+        Ok(moved)
+    }
+
+    // Separar en un registro nuevo las conexiones indicadas (con sus desconexiones y todo lo asociado a ellas).
+    // Devuelve el identificador del nuevo registro, o None si ninguna es una conexión del dispositivo.
+    pub fn split_device(&self, device_id: &str, connect_ids: &[i64]) -> Result<Option<String>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        // Solo conexiones de este dispositivo, cada una con la desconexión que la cierra
+        let mut activity_ids = Vec::new();
+        for &connect_id in connect_ids {
+            let is_connect: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM activity_log WHERE id = ?1 AND device_id = ?2 AND event_type = 'CONNECT')",
+                params![connect_id, device_id],
+                |row| row.get(0),
+            )?;
+            if !is_connect {
+                continue;
+            }
+            activity_ids.push(connect_id);
+
+            let disconnect_id: Option<i64> = tx.query_row(
+                "SELECT MIN(id) FROM activity_log
+                 WHERE device_id = ?1 AND event_type IN ('DISCONNECT', 'UNSAFE_REMOVAL') AND id > ?2
+                   AND id < COALESCE((SELECT MIN(id) FROM activity_log WHERE device_id = ?1 AND event_type = 'CONNECT' AND id > ?2), ?3)",
+                params![device_id, connect_id, i64::MAX],
+                |row| row.get(0),
+            )?;
+            activity_ids.extend(disconnect_id);
+        }
+        if activity_ids.is_empty() {
+            return Ok(None);
+        }
+
+        let mut suffix = 2;
+        let new_id = loop {
+            let candidate = format!("{}{}{}", device_id, DEVICE_VARIANT_SEPARATOR, suffix);
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM devices WHERE serial_number = ?1)",
+                params![candidate],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                break candidate;
+            }
+            suffix += 1;
+        };
+
+        // Mismos descriptores; el volumen es el de la última conexión separada y la confianza vuelve a desconocida
+        let ids = activity_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
+        tx.execute(
+            &format!(
+                "INSERT INTO devices (serial_number, vendor_id, product_id, name, manufacturer, total_capacity, machine_id, volume_serial, volume_label, media_type, first_seen, last_seen)
+                 SELECT ?2, vendor_id, product_id, name, manufacturer, total_capacity, machine_id,
+                        (SELECT volume_serial FROM activity_log WHERE id IN ({ids}) AND volume_serial IS NOT NULL ORDER BY id DESC LIMIT 1),
+                        NULL,
+                        media_type,
+                        (SELECT MIN(timestamp) FROM activity_log WHERE id IN ({ids})),
+                        (SELECT MAX(timestamp) FROM activity_log WHERE id IN ({ids}))
+                 FROM devices WHERE serial_number = ?1",
+                ids = ids
+            ),
+            params![device_id, new_id],
+        )?;
+
+        for (table, column) in SPLIT_ACTIVITY_COLUMNS {
+            tx.execute(
+                &format!("UPDATE {} SET device_id = ?2 WHERE device_id = ?1 AND {} IN ({})", table, column, ids),
+                params![device_id, new_id],
+            )?;
+        }
+
+        tx.commit()?;
+        println!("[DB] Split {} connections of {} into {}", activity_ids.len(), device_id, new_id);
+        Ok(Some(new_id))
+    }
+
+    // Dispositivos con la misma huella (descriptores, capacidad y serie de volumen) y otro número de serie USB
+    pub fn find_devices_by_fingerprint(
+        &self,
+        vendor_id: u16,
+        product_id: u16,
+        total_capacity: i64,
+        volume_serial: &str,
+        exclude_serial: &str,
+    ) -> Result<Vec<Device>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE vendor_id = ?1 AND product_id = ?2 AND total_capacity = ?3 AND volume_serial = ?4 AND serial_number != ?5",
+            DEVICE_SELECT
+        ))?;
+
+        let device_iter = stmt.query_map(
+            params![vendor_id, product_id, total_capacity, volume_serial, exclude_serial],
+            row_to_device,
+        )?;
+
+        let mut devices = Vec::new();
+        for device in device_iter {
+            devices.push(device?);
+        }
+
+        Ok(devices)
+    }
+
+    // Marcar el dispositivo como visto ahora (p.ej. al desconectarse)
+    pub fn touch_device_last_seen(&self, device_id: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE devices SET last_seen = CURRENT_TIMESTAMP WHERE serial_number = ?1",
+            params![device_id],
+        )?;
+
+        Ok(())
+    }
+
+    // Obtener dispositivos registrados
+    pub fn get_devices(&self, sort: DeviceSort) -> Result<Vec<Device>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!("{} ORDER BY {}", DEVICE_SELECT, sort.order_by()))?;
+
+        let device_iter = stmt.query_map([], row_to_device)?;
+
+        let mut devices = Vec::new();
+        for device in device_iter {
+            devices.push(device?);
+        }
+
+        Ok(devices)
+    }
+
+    // Obtener estadísticas de un escaneo
+    pub fn get_scan_stats(&self, activity_log_id: i64) -> Result<(i64, i64)> {
+        let conn = self.conn.lock().unwrap();
+
+        let total_files: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM file_snapshots WHERE activity_log_id = ?1 AND is_folder = 0",
+            params![activity_log_id],
+            |row| row.get(0),
+        )?;
+
+        let total_folders: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM file_snapshots WHERE activity_log_id = ?1 AND is_folder = 1",
+            params![activity_log_id],
+            |row| row.get(0),
+        )?;
+
+        Ok((total_files, total_folders))
+    }
+
+    // Obtener snapshots del último CONNECT de un dispositivo específico
+    pub fn get_latest_device_snapshots(&self, device_id: &str) -> Result<(i64, Vec<FileSnapshot>)> {
+        let conn = self.conn.lock().unwrap();
+
+        // Obtener el último activity_log CONNECT para este dispositivo
+        let activity_id: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM activity_log 
+             WHERE device_id = ?1 AND event_type = 'CONNECT'
+             ORDER BY timestamp DESC
+             LIMIT 1",
+                params![device_id],
+                |row| row.get(0),
+            )
+            .ok();
+
+        match activity_id {
+            Some(id) => {
+                let snapshots = query_snapshots(&conn, id)?;
+
+                println!(
+                    "[DB] Found {} snapshots for device {} (activity_id: {})",
+                    snapshots.len(),
+                    device_id,
+                    id
+                );
+                Ok((id, snapshots))
+            }
+            None => {
+                println!("[DB] No CONNECT activity found for device {}", device_id);
+                Ok((0, Vec::new()))
+            }
+        }
+    }
+
+    // Obtener todos los snapshots de un dispositivo (de todos sus connections)
+    pub fn get_all_device_snapshots(
+        &self,
+        device_id: &str,
+    ) -> Result<Vec<(i64, String, Vec<FileSnapshot>)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT al.id, al.timestamp 
+             FROM activity_log al
+             WHERE al.device_id = ?1 AND al.event_type = 'CONNECT'
+             ORDER BY al.timestamp DESC",
+        )?;
+
+        let activity_iter = stmt.query_map(params![device_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut results = Vec::new();
+        for activity_result in activity_iter {
+            let (activity_id, timestamp) = activity_result?;
+            let snapshots = query_snapshots(&conn, activity_id)?;
+
+            results.push((activity_id, timestamp, snapshots));
+        }
+
+        println!(
+            "[DB] Found {} connection events for device {}",
+            results.len(),
+            device_id
+        );
+        Ok(results)
+    }
+
+    // Leer un setting y deserializarlo al tipo pedido
+    pub fn get_setting<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let conn = self.conn.lock().unwrap();
+
+        let raw: Option<String> = conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .ok();
+
+        match raw {
+            Some(value) => serde_json::from_str(&value)
+                .map(Some)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))),
+            None => Ok(None),
+        }
+    }
+
+    // Guardar un setting serializado como JSON
+    pub fn set_setting<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let json = serde_json::to_string(value)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        conn.execute(
+            "INSERT INTO settings (key, value, updated_at)
+             VALUES (?1, ?2, CURRENT_TIMESTAMP)
+             ON CONFLICT(key) DO UPDATE SET
+                value = excluded.value,
+                updated_at = CURRENT_TIMESTAMP",
+            params![key, json],
+        )?;
+
+        println!("[DB] Setting updated: {}", key);
+        Ok(())
+    }
+
+    // Obtener todos los settings del perfil activo
+    pub fn get_all_settings(&self) -> Result<serde_json::Map<String, serde_json::Value>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare("SELECT key, value FROM settings ORDER BY key")?;
+        let setting_iter = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut settings = serde_json::Map::new();
+        for setting in setting_iter {
+            let (key, value) = setting?;
+            let value = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+            settings.insert(key, value);
+        }
+
+        Ok(settings)
+    }
+
+    // Importar historial externo: crea dispositivos desconocidos y sus eventos sin duplicar
+    pub fn import_device_history(&self, devices: &[ImportedDevice], source: &str) -> Result<ImportSummary> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut summary = ImportSummary::default();
+
+        for device in devices {
+            let inserted = tx.execute(
+                "INSERT INTO devices (serial_number, vendor_id, product_id, name, manufacturer, created_at, updated_at, first_seen, last_seen)
+                 VALUES (?1, ?2, ?3, ?4, ?5, COALESCE(?6, CURRENT_TIMESTAMP), COALESCE(?7, CURRENT_TIMESTAMP), COALESCE(?6, ?7), COALESCE(?8, ?7))
+                 ON CONFLICT(serial_number) DO NOTHING",
+                params![
+                    device.serial_number,
+                    device.vendor_id,
+                    device.product_id,
+                    device.name,
+                    device.manufacturer,
+                    device.first_seen.map(format_timestamp),
+                    device.last_connect.map(format_timestamp),
+                    device.last_disconnect.map(format_timestamp),
+                ],
+            )?;
+
+            if inserted > 0 {
+                append_device_op(&tx, &device.serial_number)?;
+                summary.devices_added += 1;
+            } else {
+                summary.devices_existing += 1;
+            }
+
+            let events = [
+                (EventType::Connect, device.last_connect),
+                (EventType::Disconnect, device.last_disconnect),
+            ];
+
+            for (event_type, timestamp) in events {
+                let Some(timestamp) = timestamp else { continue };
+                let timestamp = format_timestamp(timestamp);
+
+                let exists: bool = tx.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM activity_log WHERE device_id = ?1 AND event_type = ?2 AND timestamp = ?3)",
+                    params![device.serial_number, event_type.as_str(), timestamp],
+                    |row| row.get(0),
+                )?;
+
+                if !exists {
+                    tx.execute(
+                        "INSERT INTO activity_log (device_id, event_type, timestamp, source)
+                         VALUES (?1, ?2, ?3, ?4)",
+                        params![device.serial_number, event_type.as_str(), timestamp, source],
+                    )?;
+                    append_activity_op(&tx, tx.last_insert_rowid())?;
+                    summary.events_added += 1;
+                }
+            }
+        }
+
+        tx.commit()?;
+        println!(
+            "[DB] Imported history from {}: {} new devices, {} events",
+            source, summary.devices_added, summary.events_added
+        );
+
+        Ok(summary)
+    }
+
+    // Marcar como confiables los dispositivos de una lista blanca, creando los que no existan.
+    // En modo simulación se calculan los cambios y se deshace la transacción.
+    pub fn import_whitelist(&self, entries: &[WhitelistEntry], dry_run: bool) -> Result<WhitelistSummary> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut summary = WhitelistSummary { dry_run, ..Default::default() };
+
+        for entry in entries {
+            let existing: Option<(u16, u16, Option<String>, String)> = tx
+                .query_row(
+                    "SELECT vendor_id, product_id, nickname, trust_level FROM devices WHERE serial_number = ?1",
+                    params![entry.serial_number],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .ok();
+
+            let action = match existing {
+                None => {
+                    tx.execute(
+                        "INSERT INTO devices (serial_number, vendor_id, product_id, nickname, trust_level)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![
+                            entry.serial_number,
+                            entry.vendor_id.unwrap_or(0),
+                            entry.product_id.unwrap_or(0),
+                            entry.nickname,
+                            TrustLevel::Trusted.as_str(),
+                        ],
+                    )?;
+                    append_device_op(&tx, &entry.serial_number)?;
+                    append_trust_op(&tx, &entry.serial_number, TrustLevel::Trusted)?;
+                    summary.added += 1;
+                    "added"
+                }
+                Some((old_vendor_id, old_product_id, old_nickname, trust_level)) => {
+                    // VID/PID solo se completan si no se conocían
+                    let vendor_id = if old_vendor_id == 0 { entry.vendor_id.unwrap_or(0) } else { old_vendor_id };
+                    let product_id = if old_product_id == 0 { entry.product_id.unwrap_or(0) } else { old_product_id };
+                    let nickname = entry.nickname.clone().or_else(|| old_nickname.clone());
+
+                    let changed = TrustLevel::parse(&trust_level) != TrustLevel::Trusted
+                        || nickname != old_nickname
+                        || vendor_id != old_vendor_id
+                        || product_id != old_product_id;
+
+                    if changed {
+                        tx.execute(
+                            "UPDATE devices
+                             SET vendor_id = ?2, product_id = ?3, nickname = ?4, trust_level = ?5, updated_at = CURRENT_TIMESTAMP
+                             WHERE serial_number = ?1",
+                            params![
+                                entry.serial_number,
+                                vendor_id,
+                                product_id,
+                                nickname,
+                                TrustLevel::Trusted.as_str(),
+                            ],
+                        )?;
+                        append_device_op(&tx, &entry.serial_number)?;
+                        if TrustLevel::parse(&trust_level) != TrustLevel::Trusted {
+                            append_trust_op(&tx, &entry.serial_number, TrustLevel::Trusted)?;
+                        }
+                        summary.updated += 1;
+                        "updated"
+                    } else {
+                        summary.unchanged += 1;
+                        "unchanged"
+                    }
+                }
+            };
+
+            summary.changes.push(WhitelistChange {
+                serial_number: entry.serial_number.clone(),
+                nickname: entry.nickname.clone(),
+                action: action.to_string(),
+            });
+        }
+
+        if !dry_run {
+            tx.commit()?;
+        }
+
+        println!(
+            "[DB] Whitelist import{}: {} added, {} updated, {} unchanged",
+            if dry_run { " (dry run)" } else { "" },
+            summary.added,
+            summary.updated,
+            summary.unchanged
+        );
+        Ok(summary)
+    }
+
+    // Nivel de confianza de un dispositivo (UNKNOWN si no está registrado)
+    pub fn get_device_trust(&self, device_id: &str) -> Result<TrustLevel> {
+        let conn = self.conn.lock().unwrap();
+
+        let trust: Option<String> = conn
+            .query_row(
+                "SELECT trust_level FROM devices WHERE serial_number = ?1",
+                params![device_id],
+                |row| row.get(0),
+            )
+            .ok();
+
+        Ok(trust.map(|t| TrustLevel::parse(&t)).unwrap_or_default())
+    }
+
+    // Cambiar la confianza de varios dispositivos en una sola transacción
+    pub fn set_trust_for_devices(&self, device_ids: &[String], trust_level: TrustLevel) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let sql = format!(
+            "UPDATE devices SET trust_level = ?, updated_at = CURRENT_TIMESTAMP WHERE serial_number IN ({})",
+            id_placeholders(device_ids.len())
+        );
+        let trust = trust_level.as_str();
+        let mut values: Vec<&dyn rusqlite::ToSql> = vec![&trust];
+        values.extend(device_ids.iter().map(|id| id as &dyn rusqlite::ToSql));
+
+        let updated = tx.execute(&sql, values.as_slice())?;
+
+        let sql = format!("SELECT serial_number FROM devices WHERE serial_number IN ({})", id_placeholders(device_ids.len()));
+        let existing = tx
+            .prepare(&sql)?
+            .query_map(rusqlite::params_from_iter(device_ids), |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>>>()?;
+        for device_id in &existing {
+            append_trust_op(&tx, device_id, trust_level)?;
+        }
+        tx.commit()?;
+
+        println!("[DB] Trust set to {} for {} devices", trust_level.as_str(), updated);
+        Ok(updated)
+    }
+
+    // Crear alerta
+    pub fn create_alert(
+        &self,
+        device_id: Option<&str>,
+        activity_log_id: Option<i64>,
+        alert_type: &str,
+        severity: AlertSeverity,
+        message: &Message,
+    ) -> Result<i64> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO alerts (device_id, activity_log_id, alert_type, severity, message, message_key, message_params, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, CURRENT_TIMESTAMP)",
+            params![
+                device_id,
+                activity_log_id,
+                alert_type,
+                severity.as_str(),
+                message.render(DEFAULT_LANG),
+                message.key,
+                message.params.to_string(),
+            ],
+        )?;
+
+        let id = tx.last_insert_rowid();
+        append_alert_op(&tx, id)?;
+        tx.commit()?;
+        println!("[DB] Alert created: id={}, type={}, severity={}", id, alert_type, severity.as_str());
+
+        Ok(id)
+    }
+
+    // Sustituir el mensaje de una alerta (p. ej. con el resultado final de lo que avisaba), también en su copia del oplog
+    pub fn update_alert_message(&self, alert_id: i64, message: &Message) -> Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let text = message.render(DEFAULT_LANG);
+        let params_json = message.params.to_string();
+
+        let updated = tx.execute(
+            "UPDATE alerts SET message = ?2, message_key = ?3, message_params = ?4 WHERE id = ?1",
+            params![alert_id, text, message.key, params_json],
+        )?;
+        let uuid: Option<String> = tx
+            .query_row("SELECT uuid FROM alerts WHERE id = ?1", params![alert_id], |row| row.get(0))
+            .optional()?
+            .flatten();
+        if let Some(uuid) = uuid {
+            tx.execute(
+                "UPDATE oplog SET payload = json_set(payload, '$.message', ?2, '$.message_key', ?3, '$.message_params', ?4)
+                 WHERE entity = ?5 AND entity_id = ?1",
+                params![uuid, text, message.key, params_json, OP_ENTITY_ALERT],
+            )?;
+        }
+        tx.commit()?;
+        Ok(updated > 0)
+    }
+
+    pub fn get_alert(&self, alert_id: i64) -> Result<Alert> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            &format!("{} WHERE id = ?1", ALERT_SELECT),
+            params![alert_id],
+            row_to_alert,
+        )
+    }
+
+    // Obtener alertas recientes
+    pub fn get_alerts(&self, limit: i64, include_acknowledged: bool) -> Result<Vec<Alert>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE (?1 OR acknowledged = 0) ORDER BY created_at DESC, id DESC LIMIT ?2",
+            ALERT_SELECT
+        ))?;
+
+        let alert_iter = stmt.query_map(params![include_acknowledged, limit], row_to_alert)?;
+
+        let mut alerts = Vec::new();
+        for alert in alert_iter {
+            alerts.push(alert?);
+        }
+
+        Ok(alerts)
+    }
+
+    // Marcar varias alertas como revisadas en una sola transacción
+    pub fn acknowledge_alerts(&self, alert_ids: &[i64]) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let sql = format!(
+            "SELECT uuid FROM alerts WHERE acknowledged = 0 AND uuid IS NOT NULL AND id IN ({})",
+            id_placeholders(alert_ids.len())
+        );
+        let uuids = tx
+            .prepare(&sql)?
+            .query_map(rusqlite::params_from_iter(alert_ids), |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>>>()?;
+
+        let sql = format!(
+            "UPDATE alerts SET acknowledged = 1, acknowledged_at = CURRENT_TIMESTAMP
+             WHERE acknowledged = 0 AND id IN ({})",
+            id_placeholders(alert_ids.len())
+        );
+        let updated = tx.execute(&sql, rusqlite::params_from_iter(alert_ids))?;
+
+        let payload = serde_json::json!({ "acknowledged_at": format_timestamp(Utc::now()) }).to_string();
+        for uuid in &uuids {
+            append_op(&tx, OP_ENTITY_ALERT, uuid, OP_ACK, &payload)?;
+        }
+        tx.commit()?;
+
+        println!("[DB] Acknowledged {} alerts", updated);
+        Ok(updated)
+    }
+
+    // Borrar los snapshots de varios escaneos; el evento de conexión se conserva en el historial
+    pub fn delete_scans(&self, activity_log_ids: &[i64]) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let sql = format!(
+            "DELETE FROM document_metadata WHERE activity_log_id IN ({})",
+            id_placeholders(activity_log_ids.len())
+        );
+        tx.execute(&sql, rusqlite::params_from_iter(activity_log_ids))?;
+        let sql = format!(
+            "DELETE FROM file_snapshots WHERE activity_log_id IN ({})",
+            id_placeholders(activity_log_ids.len())
+        );
+        let deleted = tx.execute(&sql, rusqlite::params_from_iter(activity_log_ids))?;
+        let sql = format!(
+            "DELETE FROM scan_checkpoints WHERE activity_log_id IN ({})",
+            id_placeholders(activity_log_ids.len())
+        );
+        tx.execute(&sql, rusqlite::params_from_iter(activity_log_ids))?;
+        let sql = format!(
+            "UPDATE devices SET baseline_activity_id = NULL WHERE baseline_activity_id IN ({})",
+            id_placeholders(activity_log_ids.len())
+        );
+        tx.execute(&sql, rusqlite::params_from_iter(activity_log_ids))?;
+
+        tx.execute(
+            "DELETE FROM directories WHERE id NOT IN (SELECT DISTINCT directory_id FROM file_snapshots)",
+            [],
+        )?;
+        tx.commit()?;
+
+        println!("[DB] Deleted {} snapshots from {} scans", deleted, activity_log_ids.len());
+        Ok(deleted)
+    }
+
+    // Borrar todo el historial (actividad, escaneos, eventos de archivo, sesiones, alertas y evidencias) en una transacción.
+    // Sin `keep_devices` se borran también los dispositivos con todo lo que cuelga de ellos.
+    // El registro de auditoría y el oplog se conservan. Devuelve las filas borradas y las rutas de los adjuntos eliminados.
+    pub fn clear_history(&self, keep_devices: bool) -> Result<(usize, Vec<String>)> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let mut deleted = 0;
+        for table in [
+            "document_metadata",
+            "file_snapshots",
+            "directories",
+            "scans",
+            "scan_checkpoints",
+            "file_events",
+            "sessions",
+            "alerts",
+            "evidence",
+            "device_transfers",
+            "activity_log",
+        ] {
+            deleted += tx.execute(&format!("DELETE FROM {}", table), [])?;
+        }
+
+        let mut attachment_paths = Vec::new();
+        if !keep_devices {
+            attachment_paths = {
+                let mut stmt = tx.prepare("SELECT stored_path FROM device_attachments")?;
+                let paths = stmt.query_map([], |row| row.get(0))?;
+                paths.collect::<Result<_>>()?
+            };
+            let tables: Vec<String> = {
+                let mut stmt = tx.prepare(
+                    "SELECT m.name FROM sqlite_master m, pragma_table_info(m.name) p
+                     WHERE m.type = 'table' AND p.name = 'device_id' AND m.name NOT IN ('audit_log', 'oplog')",
+                )?;
+                let names = stmt.query_map([], |row| row.get(0))?;
+                names.collect::<Result<_>>()?
+            };
+            for table in &tables {
+                deleted += tx.execute(&format!("DELETE FROM {}", table), [])?;
+            }
+            deleted += tx.execute("DELETE FROM devices", [])?;
+        } else {
+            tx.execute("UPDATE devices SET baseline_activity_id = NULL", [])?;
+        }
+
+        tx.commit()?;
+        println!("[DB] History cleared: {} rows (devices {})", deleted, if keep_devices { "kept" } else { "removed" });
+        Ok((deleted, attachment_paths))
+    }
+
+    // Último escaneo (CONNECT) de un dispositivo con su punto de montaje
+    pub fn get_latest_scan(&self, device_id: &str) -> Result<Option<ScanInfo>> {
+        let conn = self.conn.lock().unwrap();
+
+        let scan = conn
+            .query_row(
+                "SELECT id, device_id, timestamp, mount_point FROM activity_log
+                 WHERE device_id = ?1 AND event_type = 'CONNECT'
+                 ORDER BY timestamp DESC, id DESC
+                 LIMIT 1",
+                params![device_id],
+                row_to_scan_info,
+            )
+            .ok();
+
+        Ok(scan)
+    }
+
+    pub fn get_scan_info(&self, activity_log_id: i64) -> Result<Option<ScanInfo>> {
+        let conn = self.conn.lock().unwrap();
+
+        let scan = conn
+            .query_row(
+                "SELECT id, device_id, timestamp, mount_point FROM activity_log
+                 WHERE id = ?1 AND event_type = 'CONNECT'",
+                params![activity_log_id],
+                row_to_scan_info,
+            )
+            .ok();
+
+        Ok(scan)
+    }
+
+    // Últimos escaneos con snapshots de un dispositivo, del más reciente al más antiguo
+    pub fn get_recent_scans(&self, device_id: &str, limit: i64) -> Result<Vec<ScanInfo>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT al.id, al.device_id, al.timestamp, al.mount_point FROM activity_log al
+             WHERE al.device_id = ?1 AND al.event_type = 'CONNECT'
+               AND EXISTS(SELECT 1 FROM file_snapshots f WHERE f.activity_log_id = al.id)
+             ORDER BY al.timestamp DESC, al.id DESC
+             LIMIT ?2",
+        )?;
+
+        let scan_iter = stmt.query_map(params![device_id, limit], row_to_scan_info)?;
+
+        let mut scans = Vec::new();
+        for scan in scan_iter {
+            scans.push(scan?);
+        }
+
+        Ok(scans)
+    }
+
+    // Buscar en todos los escaneos un archivo por nombre (admite '*') o por SHA-256
+    pub fn locate_file(&self, query: &str, by_hash: bool, limit: i64) -> Result<Vec<FileLocation>> {
+        let conn = self.conn.lock().unwrap();
+
+        let (condition, value) = if by_hash {
+            ("f.sha256 = ?1", query.to_lowercase())
+        } else if query.contains('*') {
+            ("f.file_name LIKE ?1", query.replace('*', "%"))
+        } else {
+            ("f.file_name = ?1 COLLATE NOCASE", query.to_string())
+        };
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT al.device_id, dev.name, al.id, al.timestamp, d.path || f.file_name, f.file_size, f.sha256, {}
+             FROM file_snapshots f
+             JOIN directories d ON d.id = f.directory_id
+             JOIN activity_log al ON al.id = f.activity_log_id
+             LEFT JOIN devices dev ON dev.serial_number = al.device_id
+             WHERE {} AND f.is_folder = 0
+             ORDER BY al.timestamp DESC
+             LIMIT ?2",
+            KNOWN_HASH_CONDITION, condition
+        ))?;
+
+        let location_iter = stmt.query_map(params![value, limit], |row| {
+            Ok(FileLocation {
+                device_id: row.get(0)?,
+                device_name: row.get(1)?,
+                activity_log_id: row.get(2)?,
+                scan_timestamp: row.get(3)?,
+                file_path: row.get(4)?,
+                file_size: row.get(5)?,
+                sha256: row.get(6)?,
+                known: row.get(7)?,
+            })
+        })?;
+
+        let mut locations = Vec::new();
+        for location in location_iter {
+            locations.push(location?);
+        }
+
+        Ok(locations)
+    }
+
+    // Archivos que cumplen los filtros de una consulta de búsqueda, en todos los escaneos o en uno concreto
+    pub fn search_files(&self, query: &FileQuery, activity_log_id: Option<i64>, limit: i64) -> Result<Vec<FileLocation>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut conditions = vec!["f.is_folder = 0".to_string()];
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+
+        if let Some(id) = activity_log_id {
+            conditions.push("f.activity_log_id = ?".to_string());
+            values.push(id.into());
+        }
+
+        for term in &query.terms {
+            conditions.push("f.file_name LIKE ?".to_string());
+            values.push(if term.contains('*') { term.replace('*', "%") } else { format!("%{}%", term) }.into());
+        }
+        if !query.extensions.is_empty() {
+            conditions.push(format!("lower(f.file_extension) IN ({})", id_placeholders(query.extensions.len())));
+            values.extend(query.extensions.iter().map(|e| e.clone().into()));
+        }
+        if let Some(min) = query.min_size {
+            conditions.push("f.file_size >= ?".to_string());
+            values.push(min.into());
+        }
+        if let Some(max) = query.max_size {
+            conditions.push("f.file_size <= ?".to_string());
+            values.push(max.into());
+        }
+        if let Some(ref device) = query.device {
+            conditions.push("(al.device_id = ? OR dev.name LIKE ? OR dev.nickname LIKE ?)".to_string());
+            let pattern = format!("%{}%", device);
+            values.extend([device.clone().into(), pattern.clone().into(), pattern.into()]);
+        }
+        if let Some(ref path) = query.path {
+            conditions.push("d.path || f.file_name LIKE ?".to_string());
+            values.push(format!("%{}%", path.replace('*', "%")).into());
+        }
+        if let Some(ref sha256) = query.sha256 {
+            conditions.push("f.sha256 = ?".to_string());
+            values.push(sha256.clone().into());
+        }
+        if let Some(known) = query.known {
+            conditions.push(format!("{}{}", if known { "" } else { "NOT " }, KNOWN_HASH_CONDITION));
+        }
+        if let Some(after) = query.after {
+            conditions.push("al.timestamp >= ?".to_string());
+            values.push(format_timestamp(after).into());
+        }
+        if let Some(before) = query.before {
+            conditions.push("al.timestamp < ?".to_string());
+            values.push(format_timestamp(before).into());
+        }
+        values.push(limit.into());
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT al.device_id, dev.name, al.id, al.timestamp, d.path || f.file_name, f.file_size, f.sha256, {}
+             FROM file_snapshots f
+             JOIN directories d ON d.id = f.directory_id
+             JOIN activity_log al ON al.id = f.activity_log_id
+             LEFT JOIN devices dev ON dev.serial_number = al.device_id
+             WHERE {}
+             ORDER BY al.timestamp DESC
+             LIMIT ?",
+            KNOWN_HASH_CONDITION,
+            conditions.join(" AND ")
+        ))?;
+
+        let location_iter = stmt.query_map(rusqlite::params_from_iter(values), |row| {
+            Ok(FileLocation {
+                device_id: row.get(0)?,
+                device_name: row.get(1)?,
+                activity_log_id: row.get(2)?,
+                scan_timestamp: row.get(3)?,
+                file_path: row.get(4)?,
+                file_size: row.get(5)?,
+                sha256: row.get(6)?,
+                known: row.get(7)?,
+            })
+        })?;
+
+        let mut locations = Vec::new();
+        for location in location_iter {
+            locations.push(location?);
+        }
+
+        Ok(locations)
+    }
+
+    // Crear una búsqueda guardada
+    pub fn create_saved_search(
+        &self,
+        name: &str,
+        query: &str,
+        filters: Option<&serde_json::Value>,
+        alert_enabled: bool,
+        alert_severity: AlertSeverity,
+    ) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO saved_searches (name, query, filters, alert_enabled, alert_severity, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)",
+            params![name, query, filters.map(|f| f.to_string()), alert_enabled, alert_severity.as_str()],
+        )?;
+
+        let id = conn.last_insert_rowid();
+        println!("[DB] Saved search created: id={}, name={}", id, name);
+
+        Ok(id)
+    }
+
+    // Actualizar una búsqueda guardada; devuelve false si no existe
+    pub fn update_saved_search(
+        &self,
+        id: i64,
+        name: &str,
+        query: &str,
+        filters: Option<&serde_json::Value>,
+        alert_enabled: bool,
+        alert_severity: AlertSeverity,
+    ) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let updated = conn.execute(
+            "UPDATE saved_searches
+             SET name = ?2, query = ?3, filters = ?4, alert_enabled = ?5, alert_severity = ?6, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?1",
+            params![id, name, query, filters.map(|f| f.to_string()), alert_enabled, alert_severity.as_str()],
+        )?;
+
+        Ok(updated > 0)
+    }
+
+    // Activar o desactivar la alerta de una búsqueda guardada
+    pub fn set_saved_search_alert(&self, id: i64, enabled: bool, severity: Option<AlertSeverity>) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let updated = conn.execute(
+            "UPDATE saved_searches
+             SET alert_enabled = ?2, alert_severity = COALESCE(?3, alert_severity), updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?1",
+            params![id, enabled, severity.map(|s| s.as_str())],
+        )?;
+
+        Ok(updated > 0)
+    }
+
+    pub fn delete_saved_search(&self, id: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let deleted = conn.execute("DELETE FROM saved_searches WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    // Crear una lista de hashes vacía; los hashes se añaden por lotes con add_known_hashes
+    pub fn create_hash_set(&self, name: &str, source: Option<&str>) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO hash_sets (name, source, imported_at) VALUES (?1, ?2, CURRENT_TIMESTAMP)",
+            params![name, source],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    // Añadir un lote de SHA-256 (en minúsculas) a una lista; los repetidos se ignoran
+    pub fn add_known_hashes(&self, hash_set_id: i64, hashes: &[String]) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut added = 0;
+        {
+            let mut insert = tx.prepare("INSERT OR IGNORE INTO known_hashes (sha256, hash_set_id) VALUES (?1, ?2)")?;
+            for hash in hashes {
+                added += insert.execute(params![hash, hash_set_id])?;
+            }
+        }
+        tx.execute(
+            "UPDATE hash_sets SET hash_count = hash_count + ?2 WHERE id = ?1",
+            params![hash_set_id, added as i64],
+        )?;
+        tx.commit()?;
+        Ok(added)
+    }
+
+    pub fn get_hash_sets(&self) -> Result<Vec<KnownHashSet>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, name, source, hash_count, imported_at FROM hash_sets ORDER BY name COLLATE NOCASE",
+        )?;
+        let sets = stmt.query_map([], |row| {
+            Ok(KnownHashSet {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                source: row.get(2)?,
+                hash_count: row.get(3)?,
+                imported_at: row.get(4)?,
+            })
+        })?;
+        sets.collect()
+    }
+
+    pub fn delete_hash_set(&self, id: i64) -> Result<bool> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM known_hashes WHERE hash_set_id = ?1", params![id])?;
+        let deleted = tx.execute("DELETE FROM hash_sets WHERE id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    // Archivos de un escaneo conocidos, desconocidos y sin hash completo
+    pub fn get_known_hash_summary(&self, activity_log_id: i64) -> Result<KnownHashSummary> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(known), 0), COALESCE(SUM(NOT known AND hashed), 0), COALESCE(SUM(NOT hashed), 0)
+                 FROM (SELECT {} AS known, f.sha256 IS NOT NULL AND COALESCE(f.hash_mode, 'FULL') = 'FULL' AS hashed
+                       FROM file_snapshots f WHERE f.activity_log_id = ?1 AND f.is_folder = 0)",
+                KNOWN_HASH_CONDITION
+            ),
+            params![activity_log_id],
+            |row| Ok(KnownHashSummary { known: row.get(0)?, unknown: row.get(1)?, unhashed: row.get(2)? }),
+        )
+    }
+
+    // Listar búsquedas guardadas (solo las que actúan como alerta si alerts_only)
+    pub fn get_saved_searches(&self, alerts_only: bool) -> Result<Vec<SavedSearch>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, name, query, filters, alert_enabled, alert_severity, created_at, updated_at
+             FROM saved_searches
+             WHERE (?1 = 0 OR alert_enabled = 1)
+             ORDER BY name COLLATE NOCASE",
+        )?;
+
+        let search_iter = stmt.query_map(params![alerts_only], |row| {
+            let filters: Option<String> = row.get(3)?;
+            let severity: String = row.get(5)?;
+            Ok(SavedSearch {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                query: row.get(2)?,
+                filters: filters.and_then(|f| serde_json::from_str(&f).ok()),
+                alert_enabled: row.get(4)?,
+                alert_severity: AlertSeverity::parse(&severity),
+                created_at: row.get(6)?,
+                updated_at: row.get(7)?,
+            })
+        })?;
+
+        let mut searches = Vec::new();
+        for search in search_iter {
+            searches.push(search?);
+        }
+
+        Ok(searches)
+    }
+
+    // Fijar el umbral de una regla para un dispositivo (None desactiva la regla para él)
+    pub fn set_rule_override(&self, device_id: &str, rule: RuleKind, threshold: Option<f64>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO device_rule_overrides (device_id, rule, threshold, updated_at)
+             VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)
+             ON CONFLICT(device_id, rule) DO UPDATE SET
+                threshold = excluded.threshold,
+                updated_at = CURRENT_TIMESTAMP",
+            params![device_id, rule.as_str(), threshold],
+        )?;
+
+        println!("[DB] Rule override set: device={}, rule={}, threshold={:?}", device_id, rule.as_str(), threshold);
+        Ok(())
+    }
+
+    // Quitar el umbral propio para volver al global; devuelve false si no había
+    pub fn delete_rule_override(&self, device_id: &str, rule: RuleKind) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let deleted = conn.execute(
+            "DELETE FROM device_rule_overrides WHERE device_id = ?1 AND rule = ?2",
+            params![device_id, rule.as_str()],
+        )?;
+
+        Ok(deleted > 0)
+    }
+
+    pub fn get_rule_override(&self, device_id: &str, rule: RuleKind) -> Result<Option<RuleOverride>> {
+        Ok(self
+            .get_rule_overrides(Some(device_id))?
+            .into_iter()
+            .find(|o| o.rule == rule))
+    }
+
+    // Umbrales propios de un dispositivo (o de todos)
+    pub fn get_rule_overrides(&self, device_id: Option<&str>) -> Result<Vec<RuleOverride>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT device_id, rule, threshold, updated_at
+             FROM device_rule_overrides
+             WHERE (?1 IS NULL OR device_id = ?1)
+             ORDER BY device_id, rule",
+        )?;
+
+        let override_iter = stmt.query_map(params![device_id], |row| {
+            let rule: String = row.get(1)?;
+            Ok((rule, row.get(0)?, row.get(2)?, row.get(3)?))
+        })?;
+
+        let mut overrides = Vec::new();
+        for row in override_iter {
+            let (rule, device_id, threshold, updated_at) = row?;
+            // Reglas de versiones posteriores que esta no conoce
+            if let Some(rule) = RuleKind::parse(&rule) {
+                overrides.push(RuleOverride { device_id, rule, threshold, updated_at });
+            }
+        }
+
+        Ok(overrides)
+    }
+
+    // Añadir una entrada al registro de auditoría
+    pub fn insert_audit_entry(&self, action: &str, device_id: Option<&str>, details: &serde_json::Value) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO audit_log (action, device_id, details, created_at) VALUES (?1, ?2, ?3, CURRENT_TIMESTAMP)",
+            params![action, device_id, details.to_string()],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    // Archivo de un escaneo con ese nombre y tamaño (candidato a origen de una copia al equipo)
+    pub fn find_snapshot_by_name_size(&self, activity_log_id: i64, file_name: &str, file_size: i64) -> Result<Option<FileSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+
+        let snapshot = conn
+            .query_row(
+                &format!(
+                    "{} WHERE f.activity_log_id = ?1 AND f.file_name = ?2 AND f.file_size = ?3 AND f.is_folder = 0 LIMIT 1",
+                    SNAPSHOT_SELECT
+                ),
+                params![activity_log_id, file_name, file_size],
+                row_to_snapshot,
+            )
+            .ok();
+
+        Ok(snapshot)
+    }
+
+    pub fn insert_evidence(&self, record: &EvidenceRecord) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO evidence (device_id, activity_log_id, source_path, host_path, file_name, file_size, sha256, evidence_path, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, CURRENT_TIMESTAMP)",
+            params![
+                record.device_id,
+                record.activity_log_id,
+                record.source_path,
+                record.host_path,
+                record.file_name,
+                record.file_size,
+                record.sha256,
+                record.evidence_path,
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    // Guardar los metadatos extraídos de los documentos de un escaneo (se reemplazan si ya estaban)
+    pub fn insert_document_metadata(&self, documents: &[DocumentMetadata]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR REPLACE INTO document_metadata
+                 (snapshot_id, activity_log_id, author, title, company, last_saved_by, application, created, modified)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            for document in documents {
+                insert.execute(params![
+                    document.snapshot_id,
+                    document.activity_log_id,
+                    document.author,
+                    document.title,
+                    document.company,
+                    document.last_saved_by,
+                    document.application,
+                    document.created,
+                    document.modified,
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    // Metadatos de documentos, de un escaneo o de todos; `person` busca en el autor y en quién lo guardó
+    pub fn get_document_metadata(
+        &self,
+        activity_log_id: Option<i64>,
+        person: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<DocumentMetadata>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT m.snapshot_id, m.activity_log_id, d.path || f.file_name, m.author, m.title, m.company,
+                    m.last_saved_by, m.application, m.created, m.modified
+             FROM document_metadata m
+             JOIN file_snapshots f ON f.id = m.snapshot_id
+             JOIN directories d ON d.id = f.directory_id
+             WHERE (?1 IS NULL OR m.activity_log_id = ?1)
+               AND (?2 IS NULL OR m.author LIKE ?2 OR m.last_saved_by LIKE ?2)
+             ORDER BY m.activity_log_id DESC, d.path, f.file_name
+             LIMIT ?3",
+        )?;
+
+        let person = person.map(|p| format!("%{}%", p));
+        let documents = stmt.query_map(params![activity_log_id, person, limit], |row| {
+            Ok(DocumentMetadata {
+                snapshot_id: row.get(0)?,
+                activity_log_id: row.get(1)?,
+                file_path: row.get(2)?,
+                author: row.get(3)?,
+                title: row.get(4)?,
+                company: row.get(5)?,
+                last_saved_by: row.get(6)?,
+                application: row.get(7)?,
+                created: row.get(8)?,
+                modified: row.get(9)?,
+            })
+        })?;
+        documents.collect()
+    }
+
+    // Archivos llevados al equipo, lo más reciente primero (opcionalmente de un dispositivo)
+    pub fn get_evidence(&self, device_id: Option<&str>, limit: i64) -> Result<Vec<EvidenceRecord>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, device_id, activity_log_id, source_path, host_path, file_name, file_size, sha256, evidence_path, created_at
+             FROM evidence
+             WHERE (?1 IS NULL OR device_id = ?1)
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?2",
+        )?;
+
+        let record_iter = stmt.query_map(params![device_id, limit], |row| {
+            Ok(EvidenceRecord {
+                id: row.get(0)?,
+                device_id: row.get(1)?,
+                activity_log_id: row.get(2)?,
+                source_path: row.get(3)?,
+                host_path: row.get(4)?,
+                file_name: row.get(5)?,
+                file_size: row.get(6)?,
+                sha256: row.get(7)?,
+                evidence_path: row.get(8)?,
+                created_at: row.get(9)?,
+            })
+        })?;
+
+        let mut records = Vec::new();
+        for record in record_iter {
+            records.push(record?);
+        }
+
+        Ok(records)
+    }
+
+    pub fn insert_device_attachment(&self, attachment: &DeviceAttachment) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO device_attachments (device_id, file_name, stored_path, mime_type, file_size, sha256, description, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, CURRENT_TIMESTAMP)",
+            params![
+                attachment.device_id,
+                attachment.file_name,
+                attachment.stored_path,
+                attachment.mime_type,
+                attachment.file_size,
+                attachment.sha256,
+                attachment.description,
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    // Adjuntos de un dispositivo, del más antiguo al más reciente
+    pub fn get_device_attachments(&self, device_id: &str) -> Result<Vec<DeviceAttachment>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!("{} WHERE device_id = ?1 ORDER BY created_at, id", ATTACHMENT_SELECT))?;
+        let attachments = stmt.query_map(params![device_id], row_to_attachment)?;
+        attachments.collect()
+    }
+
+    pub fn get_device_attachment(&self, id: i64) -> Result<Option<DeviceAttachment>> {
+        let conn = self.conn.lock().unwrap();
+
+        let attachment = conn
+            .query_row(&format!("{} WHERE id = ?1", ATTACHMENT_SELECT), params![id], row_to_attachment)
+            .ok();
+
+        Ok(attachment)
+    }
+
+    pub fn delete_device_attachment(&self, id: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let deleted = conn.execute("DELETE FROM device_attachments WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    pub fn set_backup_profile(&self, profile: &BackupProfile) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO backup_profiles (device_id, folders, target_dir, mode, conflict, enabled, detect_conflicts, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, CURRENT_TIMESTAMP)
+             ON CONFLICT(device_id) DO UPDATE SET
+                folders = excluded.folders,
+                target_dir = excluded.target_dir,
+                mode = excluded.mode,
+                conflict = excluded.conflict,
+                enabled = excluded.enabled,
+                detect_conflicts = excluded.detect_conflicts,
+                updated_at = CURRENT_TIMESTAMP",
+            params![
+                profile.device_id,
+                serde_json::to_string(&profile.folders).unwrap_or_default(),
+                profile.target_dir,
+                profile.mode.as_str(),
+                profile.conflict.as_str(),
+                profile.enabled,
+                profile.detect_conflicts,
+            ],
+        )?;
+
+        println!("[DB] Backup profile set: device={}", profile.device_id);
+        Ok(())
+    }
+
+    pub fn get_backup_profile(&self, device_id: &str) -> Result<Option<BackupProfile>> {
+        let conn = self.conn.lock().unwrap();
+
+        let profile = conn
+            .query_row(
+                "SELECT device_id, folders, target_dir, mode, conflict, enabled, detect_conflicts, updated_at
+                 FROM backup_profiles WHERE device_id = ?1",
+                params![device_id],
+                |row| {
+                    let folders: String = row.get(1)?;
+                    let mode: String = row.get(3)?;
+                    let conflict: String = row.get(4)?;
+                    Ok(BackupProfile {
+                        device_id: row.get(0)?,
+                        folders: serde_json::from_str(&folders).unwrap_or_default(),
+                        target_dir: row.get(2)?,
+                        mode: BackupMode::parse(&mode),
+                        conflict: ConflictPolicy::parse(&conflict),
+                        enabled: row.get(5)?,
+                        detect_conflicts: row.get(6)?,
+                        updated_at: row.get(7)?,
+                    })
+                },
+            )
+            .ok();
+
+        Ok(profile)
+    }
+
+    // Devuelve false si el dispositivo no tenía perfil
+    pub fn delete_backup_profile(&self, device_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let deleted = conn.execute("DELETE FROM backup_profiles WHERE device_id = ?1", params![device_id])?;
+
+        Ok(deleted > 0)
+    }
+
+    pub fn start_backup_run(&self, device_id: &str, activity_log_id: Option<i64>) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO backup_runs (device_id, activity_log_id, started_at, status) VALUES (?1, ?2, CURRENT_TIMESTAMP, ?3)",
+            params![device_id, activity_log_id, BackupStatus::Running.as_str()],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn finish_backup_run(&self, run: &BackupRun) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE backup_runs SET
+                finished_at = CURRENT_TIMESTAMP,
+                status = ?2,
+                files_copied = ?3,
+                files_skipped = ?4,
+                files_deleted = ?5,
+                bytes_copied = ?6,
+                error = ?7,
+                files_conflicted = ?8
+             WHERE id = ?1",
+            params![
+                run.id,
+                run.status.unwrap_or(BackupStatus::Completed).as_str(),
+                run.files_copied,
+                run.files_skipped,
+                run.files_deleted,
+                run.bytes_copied,
+                run.error,
+                run.files_conflicted,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    // Historial de copias de seguridad, lo más reciente primero
+    pub fn get_backup_runs(&self, device_id: Option<&str>, limit: i64) -> Result<Vec<BackupRun>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, device_id, activity_log_id, started_at, finished_at, status,
+                    files_copied, files_skipped, files_deleted, bytes_copied, error, files_conflicted
+             FROM backup_runs
+             WHERE (?1 IS NULL OR device_id = ?1)
+             ORDER BY started_at DESC, id DESC
+             LIMIT ?2",
+        )?;
+
+        let run_iter = stmt.query_map(params![device_id, limit], |row| {
+            let status: String = row.get(5)?;
+            Ok(BackupRun {
+                id: row.get(0)?,
+                device_id: row.get(1)?,
+                activity_log_id: row.get(2)?,
+                started_at: row.get(3)?,
+                finished_at: row.get(4)?,
+                status: Some(BackupStatus::parse(&status)),
+                files_copied: row.get(6)?,
+                files_skipped: row.get(7)?,
+                files_deleted: row.get(8)?,
+                bytes_copied: row.get(9)?,
+                error: row.get(10)?,
+                files_conflicted: row.get(11)?,
+            })
+        })?;
+
+        let mut runs = Vec::new();
+        for run in run_iter {
+            runs.push(run?);
+        }
+
+        Ok(runs)
+    }
+
+    // Estado de la última copia de un dispositivo, por ruta relativa
+    pub fn get_sync_state(&self, device_id: &str) -> Result<HashMap<String, SyncEntry>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT relative_path, size, modified, sha256 FROM sync_state WHERE device_id = ?1",
+        )?;
+
+        let entry_iter = stmt.query_map(params![device_id], |row| {
+            Ok(SyncEntry {
+                relative_path: row.get(0)?,
+                size: row.get(1)?,
+                modified: row.get(2)?,
+                sha256: row.get(3)?,
+            })
+        })?;
+
+        let mut entries = HashMap::new();
+        for entry in entry_iter {
+            let entry = entry?;
+            entries.insert(entry.relative_path.clone(), entry);
+        }
+
+        Ok(entries)
+    }
+
+    // Sustituir el estado de la última copia de un dispositivo
+    pub fn replace_sync_state(&self, device_id: &str, entries: &[SyncEntry]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM sync_state WHERE device_id = ?1", params![device_id])?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO sync_state (device_id, relative_path, size, modified, sha256, synced_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP)",
+            )?;
+            for entry in entries {
+                stmt.execute(params![device_id, entry.relative_path, entry.size, entry.modified, entry.sha256])?;
+            }
+        }
+
+        tx.commit()
+    }
+
+    // Guardar un conflicto si no hay ya uno pendiente para esa ruta; devuelve true si es nuevo
+    pub fn insert_sync_conflict(
+        &self,
+        device_id: &str,
+        run_id: i64,
+        relative_path: &str,
+        host_path: &str,
+        device_path: &str,
+        host_deleted: bool,
+    ) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let inserted = conn.execute(
+            "INSERT INTO sync_conflicts (device_id, run_id, relative_path, host_path, device_path, host_deleted, detected_at)
+             SELECT ?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP
+             WHERE NOT EXISTS(
+                SELECT 1 FROM sync_conflicts WHERE device_id = ?1 AND relative_path = ?3 AND resolved = 0
+             )",
+            params![device_id, run_id, relative_path, host_path, device_path, host_deleted],
+        )?;
+
+        Ok(inserted > 0)
+    }
+
+    // Conflictos detectados, lo más reciente primero
+    pub fn get_sync_conflicts(&self, device_id: Option<&str>, include_resolved: bool) -> Result<Vec<SyncConflict>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, device_id, run_id, relative_path, host_path, device_path, host_deleted, resolved, detected_at
+             FROM sync_conflicts
+             WHERE (?1 IS NULL OR device_id = ?1) AND (?2 OR resolved = 0)
+             ORDER BY detected_at DESC, id DESC",
+        )?;
+
+        let conflict_iter = stmt.query_map(params![device_id, include_resolved], |row| {
+            Ok(SyncConflict {
+                id: row.get(0)?,
+                device_id: row.get(1)?,
+                run_id: row.get(2)?,
+                relative_path: row.get(3)?,
+                host_path: row.get(4)?,
+                device_path: row.get(5)?,
+                host_deleted: row.get(6)?,
+                resolved: row.get(7)?,
+                detected_at: row.get(8)?,
+            })
+        })?;
+
+        let mut conflicts = Vec::new();
+        for conflict in conflict_iter {
+            conflicts.push(conflict?);
+        }
+
+        Ok(conflicts)
+    }
+
+    // Marcar un conflicto como resuelto; devuelve false si no existía
+    pub fn resolve_sync_conflict(&self, id: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let updated = conn.execute("UPDATE sync_conflicts SET resolved = 1 WHERE id = ?1", params![id])?;
+
+        Ok(updated > 0)
+    }
+
+    // Registro de auditoría, lo más reciente primero (opcionalmente de una sola acción)
+    pub fn get_audit_log(&self, limit: i64, action: Option<&str>) -> Result<Vec<AuditEntry>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, action, device_id, details, created_at
+             FROM audit_log
+             WHERE (?2 IS NULL OR action = ?2)
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?1",
+        )?;
+
+        let entry_iter = stmt.query_map(params![limit, action], |row| {
+            let details: Option<String> = row.get(3)?;
+            Ok(AuditEntry {
+                id: row.get(0)?,
+                action: row.get(1)?,
+                device_id: row.get(2)?,
+                details: details.and_then(|d| serde_json::from_str(&d).ok()),
+                created_at: row.get(4)?,
+            })
+        })?;
+
+        let mut entries = Vec::new();
+        for entry in entry_iter {
+            entries.push(entry?);
+        }
+
+        Ok(entries)
+    }
+
+    // Encolar una notificación saliente; se intenta entregar en cuanto la procese el worker
+    pub fn enqueue_outbox(&self, channel: &str, payload: &serde_json::Value) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO outbox (channel, payload, status, attempts, next_attempt_at, created_at)
+             VALUES (?1, ?2, 'pending', 0, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)",
+            params![channel, payload.to_string()],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    // Mensajes pendientes cuyo próximo intento ya ha llegado, los más antiguos primero
+    pub fn get_due_outbox(&self, now: DateTime<Utc>, limit: i64) -> Result<Vec<OutboxMessage>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE status = 'pending' AND next_attempt_at <= ?1 ORDER BY next_attempt_at, id LIMIT ?2",
+            OUTBOX_SELECT
+        ))?;
+
+        let message_iter = stmt.query_map(params![format_timestamp(now), limit], row_to_outbox)?;
+
+        let mut messages = Vec::new();
+        for message in message_iter {
+            messages.push(message?);
+        }
+
+        Ok(messages)
+    }
+
+    // Cola completa o de un solo estado, lo más reciente primero
+    pub fn get_outbox(&self, status: Option<OutboxStatus>, limit: i64) -> Result<Vec<OutboxMessage>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE (?1 IS NULL OR status = ?1) ORDER BY created_at DESC, id DESC LIMIT ?2",
+            OUTBOX_SELECT
+        ))?;
+
+        let message_iter = stmt.query_map(params![status.map(|s| s.as_str()), limit], row_to_outbox)?;
+
+        let mut messages = Vec::new();
+        for message in message_iter {
+            messages.push(message?);
+        }
+
+        Ok(messages)
+    }
+
+    // Registrar un intento fallido: se reprograma, o queda como `dead` si no hay próximo intento
+    pub fn record_outbox_failure(&self, id: i64, error: &str, next_attempt_at: Option<DateTime<Utc>>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE outbox
+             SET attempts = attempts + 1,
+                 last_error = ?2,
+                 status = CASE WHEN ?3 IS NULL THEN 'dead' ELSE 'pending' END,
+                 next_attempt_at = COALESCE(?3, next_attempt_at)
+             WHERE id = ?1",
+            params![id, error, next_attempt_at.map(format_timestamp)],
+        )?;
+
+        Ok(())
+    }
+
+    // Volver a poner un mensaje en cola para entregarlo ya; devuelve false si no existe
+    pub fn retry_outbox(&self, id: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let updated = conn.execute(
+            "UPDATE outbox SET status = 'pending', next_attempt_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            params![id],
+        )?;
+
+        Ok(updated > 0)
+    }
+
+    // Quitar un mensaje de la cola (entregado o descartado)
+    pub fn delete_outbox(&self, id: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let deleted = conn.execute("DELETE FROM outbox WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    // Conceder acceso temporal. Si el dispositivo ya tiene uno activo se amplía, conservando la confianza
+    // que había antes del primero para restaurarla al vencer.
+    pub fn create_access_grant(
+        &self,
+        device_id: &str,
+        previous_trust: TrustLevel,
+        granted_trust: TrustLevel,
+        expires_at: DateTime<Utc>,
+    ) -> Result<i64> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let existing: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM access_grants WHERE device_id = ?1 AND status = 'active' ORDER BY id DESC LIMIT 1",
+                params![device_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let id = match existing {
+            Some(id) => {
+                tx.execute(
+                    "UPDATE access_grants SET granted_trust = ?2, expires_at = ?3 WHERE id = ?1",
+                    params![id, granted_trust.as_str(), format_timestamp(expires_at)],
+                )?;
+                id
+            }
+            None => {
+                tx.execute(
+                    "INSERT INTO access_grants (device_id, previous_trust, granted_trust, status, expires_at, created_at)
+                     VALUES (?1, ?2, ?3, 'active', ?4, CURRENT_TIMESTAMP)",
+                    params![device_id, previous_trust.as_str(), granted_trust.as_str(), format_timestamp(expires_at)],
+                )?;
+                tx.last_insert_rowid()
+            }
+        };
+
+        tx.execute(
+            "UPDATE devices SET trust_level = ?2, updated_at = CURRENT_TIMESTAMP WHERE serial_number = ?1",
+            params![device_id, granted_trust.as_str()],
+        )?;
+        append_trust_op(&tx, device_id, granted_trust)?;
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    pub fn get_access_grant(&self, id: i64) -> Result<Option<AccessGrant>> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(&format!("{} WHERE id = ?1", GRANT_SELECT), params![id], row_to_grant)
+            .optional()
+    }
+
+    // Accesos (solo los activos, o todos) de un dispositivo o de todos, lo más reciente primero
+    pub fn get_access_grants(&self, device_id: Option<&str>, active_only: bool, limit: i64) -> Result<Vec<AccessGrant>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE (?1 IS NULL OR device_id = ?1) AND (?2 = 0 OR status = 'active')
+             ORDER BY created_at DESC, id DESC LIMIT ?3",
+            GRANT_SELECT
+        ))?;
+
+        let grant_iter = stmt.query_map(params![device_id, active_only, limit], row_to_grant)?;
+
+        let mut grants = Vec::new();
+        for grant in grant_iter {
+            grants.push(grant?);
+        }
+
+        Ok(grants)
+    }
+
+    // Accesos activos que ya han vencido
+    pub fn get_expired_access_grants(&self, now: DateTime<Utc>) -> Result<Vec<AccessGrant>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE status = 'active' AND expires_at <= ?1 ORDER BY expires_at, id",
+            GRANT_SELECT
+        ))?;
+
+        let grant_iter = stmt.query_map(params![format_timestamp(now)], row_to_grant)?;
+
+        let mut grants = Vec::new();
+        for grant in grant_iter {
+            grants.push(grant?);
+        }
+
+        Ok(grants)
+    }
+
+    // Cerrar un acceso activo y restaurar la confianza anterior, salvo que alguien la haya cambiado entretanto.
+    // Devuelve si se ha restaurado; None si el acceso ya no estaba activo.
+    pub fn end_access_grant(&self, id: i64, status: GrantStatus) -> Result<Option<bool>> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        let grant = match tx
+            .query_row(&format!("{} WHERE id = ?1 AND status = 'active'", GRANT_SELECT), params![id], row_to_grant)
+            .optional()?
+        {
+            Some(grant) => grant,
+            None => return Ok(None),
+        };
+
+        tx.execute(
+            "UPDATE access_grants SET status = ?2, ended_at = CURRENT_TIMESTAMP WHERE id = ?1",
+            params![id, status.as_str()],
+        )?;
+        let restored = tx.execute(
+            "UPDATE devices SET trust_level = ?2, updated_at = CURRENT_TIMESTAMP
+             WHERE serial_number = ?1 AND trust_level = ?3",
+            params![grant.device_id, grant.previous_trust.as_str(), grant.granted_trust.as_str()],
+        )?;
+        if restored > 0 {
+            append_trust_op(&tx, &grant.device_id, grant.previous_trust)?;
+        }
+        tx.commit()?;
+
+        Ok(Some(restored > 0))
+    }
+
+    // Conectado ahora: su último evento es una conexión
+    pub fn is_device_connected(&self, device_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+
+        let last_event: Option<String> = conn
+            .query_row(
+                "SELECT event_type FROM activity_log WHERE device_id = ?1 ORDER BY id DESC LIMIT 1",
+                params![device_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(last_event.as_deref() == Some("CONNECT"))
+    }
+
+    // Operaciones del oplog posteriores a `since_seq`, en el orden en que se anotaron
+    pub fn get_oplog(&self, since_seq: i64) -> Result<Vec<OplogEntry>> {
+        let conn = self.conn.lock().unwrap();
+        query_oplog(&conn, since_seq)
+    }
+
+    // Fusionar el oplog exportado de otro equipo en una sola transacción. Las operaciones se anotan con su
+    // equipo de origen, así que una exportación posterior de esta base de datos las incluye también.
+    pub fn merge_oplog(&self, ops: &[OplogEntry]) -> Result<OplogMergeSummary> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut summary = OplogMergeSummary::default();
+
+        // En el orden del archivo: cada evento llega después de su dispositivo y cada alerta después de su evento
+        for op in ops {
+            let inserted = tx.execute(
+                "INSERT OR IGNORE INTO oplog (op_id, machine_id, entity, entity_id, op, payload, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![op.op_id, op.machine_id, op.entity, op.entity_id, op.op, op.payload.to_string(), op.created_at],
+            )?;
+            if inserted == 0 {
+                summary.skipped += 1;
+            } else if apply_op(&tx, op)? {
+                summary.applied += 1;
+            } else {
+                summary.superseded += 1;
+            }
+        }
+        tx.commit()?;
+
+        println!(
+            "[DB] Merged oplog: {} applied, {} already present, {} superseded",
+            summary.applied, summary.skipped, summary.superseded
+        );
+        Ok(summary)
+    }
+
+    // Conexiones, escaneos y alertas agrupados por periodo (formato strftime del bucket)
+    pub fn get_activity_buckets(
+        &self,
+        bucket_format: &str,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<ActivityBucket>> {
+        let conn = self.conn.lock().unwrap();
+
+        let from = from.map(format_timestamp).unwrap_or_else(|| "0000-01-01 00:00:00".to_string());
+        let to = to.map(format_timestamp).unwrap_or_else(|| "9999-12-31 23:59:59".to_string());
+
+        let mut stmt = conn.prepare(
+            "SELECT bucket, SUM(connections), SUM(scans), SUM(alerts) FROM (
+                SELECT strftime(?1, timestamp) AS bucket, 1 AS connections, 0 AS scans, 0 AS alerts
+                FROM activity_log
+                WHERE event_type = 'CONNECT' AND timestamp BETWEEN ?2 AND ?3
+                UNION ALL
+                SELECT strftime(?1, al.timestamp), 0, 1, 0
+                FROM activity_log al
+                WHERE al.event_type = 'CONNECT' AND al.timestamp BETWEEN ?2 AND ?3
+                  AND EXISTS(SELECT 1 FROM file_snapshots f WHERE f.activity_log_id = al.id)
+                UNION ALL
+                SELECT strftime(?1, created_at), 0, 0, 1
+                FROM alerts
+                WHERE created_at BETWEEN ?2 AND ?3
+             )
+             GROUP BY bucket
+             ORDER BY bucket",
+        )?;
+
+        let bucket_iter = stmt.query_map(params![bucket_format, from, to], |row| {
+            Ok(ActivityBucket {
+                bucket: row.get(0)?,
+                connections: row.get(1)?,
+                scans: row.get(2)?,
+                alerts: row.get(3)?,
+            })
+        })?;
+
+        let mut buckets = Vec::new();
+        for bucket in bucket_iter {
+            buckets.push(bucket?);
+        }
+
+        Ok(buckets)
+    }
+
+    // Conexiones por (día de la semana, hora) en hora local; 0 = domingo
+    pub fn get_connection_hour_counts(&self, device_id: Option<&str>) -> Result<Vec<(usize, usize, i64)>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT CAST(strftime('%w', timestamp, 'localtime') AS INTEGER),
+                    CAST(strftime('%H', timestamp, 'localtime') AS INTEGER),
+                    COUNT(*)
+             FROM activity_log
+             WHERE event_type = 'CONNECT' AND (?1 IS NULL OR device_id = ?1)
+             GROUP BY 1, 2",
+        )?;
+
+        let count_iter = stmt.query_map(params![device_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+
+        let mut counts = Vec::new();
+        for count in count_iter {
+            counts.push(count?);
+        }
+
+        Ok(counts)
+    }
+
+    // Guardar los totales de un escaneo terminado
+    pub fn record_scan_result(
+        &self,
+        activity_log_id: i64,
+        device_id: &str,
+        result: &ScanResult,
+        used_bytes: Option<i64>,
+        total_capacity: Option<i64>,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO scans (activity_log_id, device_id, total_files, total_folders, total_bytes, used_bytes, total_capacity, usn_journal_id, usn_next, status, completed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 'DONE', CURRENT_TIMESTAMP)
+             ON CONFLICT(activity_log_id) DO UPDATE SET
+                status = 'DONE',
+                error = NULL,
+                total_files = excluded.total_files,
+                total_folders = excluded.total_folders,
+                total_bytes = excluded.total_bytes,
+                used_bytes = excluded.used_bytes,
+                total_capacity = excluded.total_capacity,
+                usn_journal_id = excluded.usn_journal_id,
+                usn_next = excluded.usn_next,
+                completed_at = CURRENT_TIMESTAMP",
+            params![
+                activity_log_id,
+                device_id,
+                result.total_files as i64,
+                result.total_folders as i64,
+                result.total_size_bytes,
+                used_bytes,
+                total_capacity,
+                result.journal.map(|j| j.journal_id),
+                result.journal.map(|j| j.next_usn),
+            ],
+        )?;
+        conn.execute("DELETE FROM scan_checkpoints WHERE activity_log_id = ?1", params![activity_log_id])?;
+
+        Ok(())
+    }
+
+    // Escaneo de la última conexión del dispositivo, si terminó y no se ha anonimizado, con la desconexión que la cerró.
+    // Llamar antes de registrar la conexión nueva.
+    pub fn get_reusable_scan(&self, device_id: &str) -> Result<Option<ReusableScan>> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT s.activity_log_id, c.volume_serial, s.used_bytes, s.total_capacity,
+                    (SELECT CAST(strftime('%s', 'now') - strftime('%s', MAX(d.timestamp)) AS INTEGER)
+                     FROM activity_log d
+                     WHERE d.device_id = c.device_id AND d.event_type IN ('DISCONNECT', 'UNSAFE_REMOVAL') AND d.id > c.id)
+             FROM scans s
+             JOIN activity_log c ON c.id = s.activity_log_id
+             WHERE s.status = 'DONE' AND NOT s.redacted
+               AND c.id = (SELECT MAX(id) FROM activity_log WHERE device_id = ?1 AND event_type = 'CONNECT')",
+            params![device_id],
+            |row| {
+                Ok(ReusableScan {
+                    activity_log_id: row.get(0)?,
+                    volume_serial: row.get(1)?,
+                    used_bytes: row.get(2)?,
+                    total_capacity: row.get(3)?,
+                    secs_since_disconnect: row.get(4)?,
+                })
+            },
+        )
+        .optional()
+    }
+
+    // Dar por escaneada una conexión con el inventario de un escaneo anterior: se copian sus archivos y totales.
+    // Devuelve los archivos y bytes del escaneo.
+    pub fn reuse_scan(&self, source_activity_id: i64, activity_log_id: i64) -> Result<(i64, i64)> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO file_snapshots (activity_log_id, directory_id, file_name, file_extension, file_size, is_folder, sha256, hash_mode, scanned_at)
+             SELECT ?2, directory_id, file_name, file_extension, file_size, is_folder, sha256, hash_mode, scanned_at
+             FROM file_snapshots WHERE activity_log_id = ?1
+             ORDER BY id",
+            params![source_activity_id, activity_log_id],
+        )?;
+        tx.execute(
+            "INSERT INTO scans (activity_log_id, device_id, total_files, total_folders, total_bytes, used_bytes, total_capacity,
+                                usn_journal_id, usn_next, reused_from, status, started_at, completed_at)
+             SELECT ?2, device_id, total_files, total_folders, total_bytes, used_bytes, total_capacity,
+                    usn_journal_id, usn_next, ?1, 'DONE', CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+             FROM scans WHERE activity_log_id = ?1
+             ON CONFLICT(activity_log_id) DO UPDATE SET
+                status = 'DONE',
+                error = NULL,
+                total_files = excluded.total_files,
+                total_folders = excluded.total_folders,
+                total_bytes = excluded.total_bytes,
+                used_bytes = excluded.used_bytes,
+                total_capacity = excluded.total_capacity,
+                usn_journal_id = excluded.usn_journal_id,
+                usn_next = excluded.usn_next,
+                reused_from = excluded.reused_from,
+                completed_at = CURRENT_TIMESTAMP",
+            params![source_activity_id, activity_log_id],
+        )?;
+        let totals = tx.query_row(
+            "SELECT total_files, total_bytes FROM scans WHERE activity_log_id = ?1",
+            params![activity_log_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        tx.commit()?;
+        Ok(totals)
+    }
+
+    // Cambiar el estado de un escaneo; la fila se crea al encolarlo. Los totales solo los escribe `record_scan_result`.
+    pub fn set_scan_status(&self, activity_log_id: i64, device_id: &str, status: ScanStatus, error: Option<&str>) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO scans (activity_log_id, device_id, status, error, started_at, completed_at)
+             VALUES (?1, ?2, ?3, ?4,
+                     CASE WHEN ?3 = 'RUNNING' THEN CURRENT_TIMESTAMP END,
+                     CASE WHEN ?3 IN ('FAILED', 'CANCELED') THEN CURRENT_TIMESTAMP END)
+             ON CONFLICT(activity_log_id) DO UPDATE SET
+                status = excluded.status,
+                error = excluded.error,
+                started_at = COALESCE(excluded.started_at, scans.started_at),
+                completed_at = excluded.completed_at",
+            params![activity_log_id, device_id, status.as_str(), error],
+        )?;
+
+        Ok(())
+    }
+
+    // Los escaneos en cola o en curso de una ejecución anterior ya no van a terminar
+    pub fn cancel_unfinished_scans(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "UPDATE scans SET status = 'CANCELED', error = 'Interrupted', completed_at = CURRENT_TIMESTAMP
+             WHERE status IN ('QUEUED', 'RUNNING')",
+            [],
+        )
+    }
+
+    // Estado de los escaneos más recientes, de un dispositivo o de todos
+    pub fn get_scan_states(&self, device_id: Option<&str>, limit: i64) -> Result<Vec<ScanState>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT activity_log_id, device_id, status, error, total_files, total_bytes, started_at, completed_at
+             FROM scans
+             WHERE ?1 IS NULL OR device_id = ?1
+             ORDER BY activity_log_id DESC
+             LIMIT ?2",
+        )?;
+
+        let state_iter = stmt.query_map(params![device_id, limit], |row| {
+            let status: String = row.get(2)?;
+            Ok(ScanState {
+                activity_log_id: row.get(0)?,
+                device_id: row.get(1)?,
+                status: ScanStatus::parse(&status),
+                error: row.get(3)?,
+                total_files: row.get(4)?,
+                total_bytes: row.get(5)?,
+                started_at: row.get(6)?,
+                completed_at: row.get(7)?,
+            })
+        })?;
+
+        let mut states = Vec::new();
+        for state in state_iter {
+            states.push(state?);
+        }
+
+        Ok(states)
+    }
+
+    // Último escaneo terminado de un dispositivo, si guardó la posición del diario USN
+    pub fn get_last_journal_scan(&self, device_id: &str) -> Result<Option<JournalScan>> {
+        let conn = self.conn.lock().unwrap();
+
+        let scan = conn
+            .query_row(
+                "SELECT s.activity_log_id, al.mount_point, s.usn_journal_id, s.usn_next
+                 FROM scans s
+                 JOIN activity_log al ON al.id = s.activity_log_id
+                 WHERE s.device_id = ?1 AND s.status = 'DONE'
+                 ORDER BY s.completed_at DESC, s.activity_log_id DESC
+                 LIMIT 1",
+                params![device_id],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                        row.get::<_, Option<i64>>(3)?,
+                    ))
+                },
+            )
+            .ok();
+
+        // Solo sirve el más reciente: si no tiene diario, lo que cambió después de él no se puede saber
+        Ok(scan.and_then(|(activity_log_id, mount_point, journal_id, next_usn)| {
+            Some(JournalScan {
+                activity_log_id,
+                mount_point: mount_point?,
+                journal: JournalPosition {
+                    journal_id: journal_id?,
+                    next_usn: next_usn?,
+                },
+            })
+        }))
+    }
+
+    // Serie de bytes usados por escaneo; los escaneos antiguos sin fila en scans se calculan desde los snapshots
+    pub fn get_device_growth(&self, device_id: &str) -> Result<Vec<GrowthPoint>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT al.id, al.timestamp,
+                    COALESCE(s.total_bytes, (SELECT COALESCE(SUM(file_size), 0) FROM file_snapshots WHERE activity_log_id = al.id)),
+                    COALESCE(s.total_files, (SELECT COUNT(*) FROM file_snapshots WHERE activity_log_id = al.id AND is_folder = 0)),
+                    s.used_bytes,
+                    s.total_capacity
+             FROM activity_log al
+             LEFT JOIN scans s ON s.activity_log_id = al.id AND s.status = 'DONE'
+             WHERE al.device_id = ?1 AND al.event_type = 'CONNECT'
+               AND (s.activity_log_id IS NOT NULL
+                    OR (EXISTS(SELECT 1 FROM file_snapshots f WHERE f.activity_log_id = al.id)
+                        AND NOT EXISTS(SELECT 1 FROM scans x WHERE x.activity_log_id = al.id)))
+             ORDER BY al.timestamp, al.id",
+        )?;
+
+        let point_iter = stmt.query_map(params![device_id], |row| {
+            Ok(GrowthPoint {
+                activity_log_id: row.get(0)?,
+                timestamp: row.get(1)?,
+                total_bytes: row.get(2)?,
+                total_files: row.get(3)?,
+                used_bytes: row.get(4)?,
+                total_capacity: row.get(5)?,
+            })
+        })?;
+
+        let mut points = Vec::new();
+        for point in point_iter {
+            points.push(point?);
+        }
+
+        Ok(points)
+    }
+
+    // Número de escaneos, media y desviación estándar de bytes de un dispositivo (excluyendo un escaneo)
+    pub fn get_scan_volume_stats(&self, device_id: &str, exclude_activity_id: i64) -> Result<(i64, f64, f64)> {
+        let conn = self.conn.lock().unwrap();
+
+        let (count, mean, mean_sq): (i64, Option<f64>, Option<f64>) = conn.query_row(
+            "SELECT COUNT(*), AVG(total_bytes), AVG(CAST(total_bytes AS REAL) * total_bytes)
+             FROM scans
+             WHERE device_id = ?1 AND activity_log_id != ?2 AND status = 'DONE'",
+            params![device_id, exclude_activity_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+        let mean = mean.unwrap_or(0.0);
+        let variance = (mean_sq.unwrap_or(0.0) - mean * mean).max(0.0);
+
+        Ok((count, mean, variance.sqrt()))
+    }
+
+    // Conexiones previas de un dispositivo con equipo conocido: totales y en un equipo concreto
+    pub fn count_machine_connections(&self, device_id: &str, machine_id: &str, exclude_activity_id: i64) -> Result<(i64, i64)> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT COUNT(machine_id), COALESCE(SUM(machine_id = ?2), 0)
+             FROM activity_log
+             WHERE device_id = ?1 AND event_type = 'CONNECT' AND id != ?3",
+            params![device_id, machine_id, exclude_activity_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+    }
+
+    // Equipos registrados y número de eventos de cada uno
+    pub fn get_machines(&self) -> Result<Vec<Machine>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT m.machine_id, m.hostname, m.first_seen, m.last_seen,
+                    (SELECT COUNT(*) FROM activity_log al WHERE al.machine_id = m.machine_id)
+             FROM machines m
+             ORDER BY m.last_seen DESC",
+        )?;
+
+        let machine_iter = stmt.query_map([], |row| {
+            Ok(Machine {
+                machine_id: row.get(0)?,
+                hostname: row.get(1)?,
+                first_seen: row.get(2)?,
+                last_seen: row.get(3)?,
+                events: row.get(4)?,
+            })
+        })?;
+
+        let mut machines = Vec::new();
+        for machine in machine_iter {
+            machines.push(machine?);
+        }
+
+        Ok(machines)
+    }
+
+    // Dispositivos vistos por primera vez en el periodo
+    pub fn get_new_devices(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        device_id: Option<&str>,
+    ) -> Result<Vec<Device>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE created_at BETWEEN ?1 AND ?2 AND (?3 IS NULL OR serial_number = ?3) ORDER BY created_at",
+            DEVICE_SELECT
+        ))?;
+
+        let device_iter = stmt.query_map(
+            params![format_timestamp(from), format_timestamp(to), device_id],
+            row_to_device,
+        )?;
+
+        let mut devices = Vec::new();
+        for device in device_iter {
+            devices.push(device?);
+        }
+
+        Ok(devices)
+    }
+
+    // Número de conexiones por dispositivo en el periodo
+    pub fn get_connection_counts(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        device_id: Option<&str>,
+    ) -> Result<Vec<DeviceConnectionCount>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT al.device_id, d.name, COUNT(*)
+             FROM activity_log al
+             LEFT JOIN devices d ON d.serial_number = al.device_id
+             WHERE al.event_type = 'CONNECT' AND al.timestamp BETWEEN ?1 AND ?2
+               AND (?3 IS NULL OR al.device_id = ?3)
+             GROUP BY al.device_id
+             ORDER BY COUNT(*) DESC",
+        )?;
+
+        let count_iter = stmt.query_map(
+            params![format_timestamp(from), format_timestamp(to), device_id],
+            |row| {
+                Ok(DeviceConnectionCount {
+                    device_id: row.get(0)?,
+                    device_name: row.get(1)?,
+                    connections: row.get(2)?,
+                })
+            },
+        )?;
+
+        let mut counts = Vec::new();
+        for count in count_iter {
+            counts.push(count?);
+        }
+
+        Ok(counts)
+    }
+
+    // Eventos de conexión/desconexión del periodo, en orden cronológico
+    pub fn get_activity_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        device_id: Option<&str>,
+    ) -> Result<Vec<ActivityLog>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE timestamp BETWEEN ?1 AND ?2 AND (?3 IS NULL OR device_id = ?3) ORDER BY timestamp, id",
+            ACTIVITY_SELECT
+        ))?;
+
+        let activity_iter = stmt.query_map(
+            params![format_timestamp(from), format_timestamp(to), device_id],
+            row_to_activity,
+        )?;
+
+        let mut activities = Vec::new();
+        for activity in activity_iter {
+            activities.push(activity?);
+        }
+
+        Ok(activities)
+    }
+
+    // Alertas del periodo
+    pub fn get_alerts_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        device_id: Option<&str>,
+    ) -> Result<Vec<Alert>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE created_at BETWEEN ?1 AND ?2 AND (?3 IS NULL OR device_id = ?3) ORDER BY created_at",
+            ALERT_SELECT
+        ))?;
+
+        let alert_iter = stmt.query_map(
+            params![format_timestamp(from), format_timestamp(to), device_id],
+            row_to_alert,
+        )?;
+
+        let mut alerts = Vec::new();
+        for alert in alert_iter {
+            alerts.push(alert?);
+        }
+
+        Ok(alerts)
+    }
+
+    // Escaneos completados en el periodo, con la hora de finalización como timestamp
+    pub fn get_scans_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        device_id: Option<&str>,
+    ) -> Result<Vec<TransferSummary>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT s.activity_log_id, s.device_id, d.name, s.completed_at, s.total_bytes, s.total_files
+             FROM scans s
+             LEFT JOIN devices d ON d.serial_number = s.device_id
+             WHERE s.status = 'DONE' AND s.completed_at BETWEEN ?1 AND ?2 AND (?3 IS NULL OR s.device_id = ?3)
+             ORDER BY s.completed_at",
+        )?;
+
+        let scan_iter = stmt.query_map(
+            params![format_timestamp(from), format_timestamp(to), device_id],
+            |row| {
+                Ok(TransferSummary {
+                    activity_log_id: row.get(0)?,
+                    device_id: row.get(1)?,
+                    device_name: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    total_bytes: row.get(4)?,
+                    total_files: row.get(5)?,
+                })
+            },
+        )?;
+
+        let mut scans = Vec::new();
+        for scan in scan_iter {
+            scans.push(scan?);
+        }
+
+        Ok(scans)
+    }
+
+    // Escaneos con más bytes del periodo
+    pub fn get_largest_scans(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        device_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<TransferSummary>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT s.activity_log_id, s.device_id, d.name, al.timestamp, s.total_bytes, s.total_files
+             FROM scans s
+             JOIN activity_log al ON al.id = s.activity_log_id
+             LEFT JOIN devices d ON d.serial_number = s.device_id
+             WHERE s.status = 'DONE' AND al.timestamp BETWEEN ?1 AND ?2 AND (?3 IS NULL OR s.device_id = ?3)
+             ORDER BY s.total_bytes DESC
+             LIMIT ?4",
+        )?;
+
+        let transfer_iter = stmt.query_map(
+            params![format_timestamp(from), format_timestamp(to), device_id, limit],
+            |row| {
+                Ok(TransferSummary {
+                    activity_log_id: row.get(0)?,
+                    device_id: row.get(1)?,
+                    device_name: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    total_bytes: row.get(4)?,
+                    total_files: row.get(5)?,
+                })
+            },
+        )?;
+
+        let mut transfers = Vec::new();
+        for transfer in transfer_iter {
+            transfers.push(transfer?);
+        }
+
+        Ok(transfers)
+    }
+
+    // Guardar un informe generado
+    pub fn save_report(
+        &self,
+        kind: &str,
+        period_start: DateTime<Utc>,
+        period_end: DateTime<Utc>,
+        format: &str,
+        content: Option<&[u8]>,
+        file_path: Option<&str>,
+    ) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO reports (kind, period_start, period_end, format, content, file_path, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, CURRENT_TIMESTAMP)",
+            params![
+                kind,
+                format_timestamp(period_start),
+                format_timestamp(period_end),
+                format,
+                content,
+                file_path,
+            ],
+        )?;
+
+        let id = conn.last_insert_rowid();
+        println!("[DB] Report saved: id={}, kind={}, format={}", id, kind, format);
+
+        Ok(id)
+    }
+
+    // Listar informes guardados (sin el contenido)
+    pub fn get_reports(&self, limit: i64) -> Result<Vec<StoredReport>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, period_start, period_end, format, file_path, created_at
+             FROM reports
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?1",
+        )?;
+
+        let report_iter = stmt.query_map(params![limit], |row| {
+            Ok(StoredReport {
+                id: row.get(0)?,
+                kind: row.get(1)?,
+                period_start: row.get(2)?,
+                period_end: row.get(3)?,
+                format: row.get(4)?,
+                file_path: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?;
+
+        let mut reports = Vec::new();
+        for report in report_iter {
+            reports.push(report?);
+        }
+
+        Ok(reports)
+    }
+
+    // Contenido de un informe guardado
+    pub fn get_report_content(&self, report_id: i64) -> Result<Option<Vec<u8>>> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT content FROM reports WHERE id = ?1",
+            params![report_id],
+            |row| row.get(0),
+        )
+    }
+
+    // Registrar un cambio de archivo detectado por el watcher
+    pub fn insert_file_event(
+        &self,
+        activity_log_id: i64,
+        file_path: &str,
+        kind: FileEventKind,
+        file_size: Option<i64>,
+        volume: Option<&str>,
+        process_name: Option<&str>,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO file_events (activity_log_id, file_path, event_type, file_size, volume, process_name)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![activity_log_id, file_path, kind.as_str(), file_size, volume, process_name],
+        )?;
+
+        Ok(())
+    }
+
+    // Cambios de archivo de una sesión, en orden de llegada
+    pub fn get_file_events(&self, activity_log_id: i64) -> Result<Vec<FileEvent>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!("{} WHERE fe.activity_log_id = ?1 ORDER BY fe.id", FILE_EVENT_SELECT))?;
+
+        let event_iter = stmt.query_map(params![activity_log_id], row_to_file_event)?;
+
+        let mut events = Vec::new();
+        for event in event_iter {
+            events.push(event?);
+        }
+
+        Ok(events)
+    }
+
+    // Cambios de archivo del periodo, en orden cronológico
+    pub fn get_file_events_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        device_id: Option<&str>,
+    ) -> Result<Vec<FileEvent>> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE fe.created_at BETWEEN ?1 AND ?2 AND (?3 IS NULL OR al.device_id = ?3) ORDER BY fe.created_at, fe.id",
+            FILE_EVENT_SELECT
+        ))?;
+
+        let event_iter = stmt.query_map(
+            params![format_timestamp(from), format_timestamp(to), device_id],
+            row_to_file_event,
+        )?;
+
+        let mut events = Vec::new();
+        for event in event_iter {
+            events.push(event?);
+        }
+
+        Ok(events)
+    }
+
+    pub fn get_activity_timestamp(&self, activity_log_id: i64) -> Result<DateTime<Utc>> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.query_row(
+            "SELECT timestamp FROM activity_log WHERE id = ?1",
+            params![activity_log_id],
+            |row| row.get(0),
+        )
+    }
+
+    // Alertas de un dispositivo durante una sesión (ligadas a la conexión o creadas mientras duró)
+    pub fn count_session_alerts(
+        &self,
+        device_id: &str,
+        connect_activity_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
 
-        Ok(())
+        conn.query_row(
+            "SELECT COUNT(*) FROM alerts
+             WHERE activity_log_id = ?1 OR (device_id = ?2 AND created_at BETWEEN ?3 AND ?4)",
+            params![connect_activity_id, device_id, format_timestamp(from), format_timestamp(to)],
+            |row| row.get(0),
+        )
     }
 
-    // Insertar snapshot individual
-    pub fn insert_file_snapshot(&self, snapshot: &FileSnapshot) -> Result<()> {
+    pub fn save_session_summary(&self, summary: &SessionSummary) -> Result<()> {
         let conn = self.conn.lock().unwrap();
 
         conn.execute(
-            "INSERT INTO file_snapshots (activity_log_id, file_path, file_name, file_extension, file_size, is_folder)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT OR REPLACE INTO sessions (
+                connect_activity_id, disconnect_activity_id, device_id, connected_at, disconnected_at,
+                duration_secs, files_added, files_removed, files_modified, bytes_written, alerts_raised
+             ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
-                snapshot.activity_log_id,
-                snapshot.file_path,
-                snapshot.file_name,
-                snapshot.file_extension,
-                snapshot.file_size,
-                snapshot.is_folder,
+                summary.connect_activity_id,
+                summary.disconnect_activity_id,
+                summary.device_id,
+                format_timestamp(summary.connected_at),
+                format_timestamp(summary.disconnected_at),
+                summary.duration_secs,
+                summary.files_added,
+                summary.files_removed,
+                summary.files_modified,
+                summary.bytes_written,
+                summary.alerts_raised,
             ],
         )?;
 
+        println!(
+            "[DB] Session summary saved: device={}, connect_id={}",
+            summary.device_id, summary.connect_activity_id
+        );
         Ok(())
     }
 
-    // Obtener historial de actividad
-    pub fn get_activity_history(&self, limit: i64) -> Result<Vec<ActivityLog>> {
+    // Sumar lo escrito durante una sesión según el watcher
+    pub fn add_session_transfer(&self, device_id: &str, files: i64, bytes: i64) -> Result<()> {
         let conn = self.conn.lock().unwrap();
 
-        let mut stmt = conn.prepare(
-            "SELECT id, device_id, event_type, timestamp 
-             FROM activity_log 
-             ORDER BY timestamp DESC 
-             LIMIT ?1",
+        conn.execute(
+            "INSERT INTO device_transfers (device_id, session_files, session_bytes) VALUES (?1, ?2, ?3)
+             ON CONFLICT(device_id) DO UPDATE SET
+                session_files = session_files + excluded.session_files,
+                session_bytes = session_bytes + excluded.session_bytes,
+                updated_at = CURRENT_TIMESTAMP",
+            params![device_id, files, bytes],
         )?;
 
-        let activity_iter = stmt.query_map(params![limit], |row| {
-            let event_type_str: String = row.get(2)?;
-            let event_type = match event_type_str.as_str() {
-                "CONNECT" => EventType::Connect,
-                "DISCONNECT" => EventType::Disconnect,
-                _ => EventType::Connect, // default
-            };
+        Ok(())
+    }
 
-            Ok(ActivityLog {
-                id: row.get(0)?,
-                device_id: row.get(1)?,
-                event_type,
-                timestamp: row.get(3)?,
-            })
-        })?;
+    // Sumar lo escrito entre dos escaneos; cada escaneo cuenta una sola vez (false si ya estaba contado)
+    pub fn add_scan_transfer(&self, device_id: &str, activity_log_id: i64, files: i64, bytes: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
 
-        let mut activities = Vec::new();
-        for activity in activity_iter {
-            activities.push(activity?);
-        }
+        let updated = conn.execute(
+            "INSERT INTO device_transfers (device_id, scan_files, scan_bytes, last_scan_activity_id) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(device_id) DO UPDATE SET
+                scan_files = scan_files + excluded.scan_files,
+                scan_bytes = scan_bytes + excluded.scan_bytes,
+                last_scan_activity_id = excluded.last_scan_activity_id,
+                updated_at = CURRENT_TIMESTAMP
+             WHERE last_scan_activity_id IS NULL OR last_scan_activity_id < excluded.last_scan_activity_id",
+            params![device_id, files, bytes, activity_log_id],
+        )?;
 
-        Ok(activities)
+        Ok(updated > 0)
     }
 
-    // Obtener snapshots de un activity_log específico
-    pub fn get_file_snapshots(&self, activity_log_id: i64) -> Result<Vec<FileSnapshot>> {
+    pub fn get_device_transfer_stats(&self, device_id: &str) -> Result<DeviceTransferStats> {
         let conn = self.conn.lock().unwrap();
 
-        let mut stmt = conn.prepare(
-            "SELECT id, activity_log_id, file_path, file_name, file_extension, file_size, is_folder
-             FROM file_snapshots 
-             WHERE activity_log_id = ?1
-             ORDER BY file_path",
-        )?;
-
-        let snapshot_iter = stmt.query_map(params![activity_log_id], |row| {
-            Ok(FileSnapshot {
-                id: row.get(0)?,
-                activity_log_id: row.get(1)?,
-                file_path: row.get(2)?,
-                file_name: row.get(3)?,
-                file_extension: row.get(4)?,
-                file_size: row.get(5)?,
-                is_folder: row.get(6)?,
-            })
-        })?;
+        let stats = conn
+            .query_row(
+                "SELECT device_id, session_files, session_bytes, scan_files, scan_bytes, updated_at
+                 FROM device_transfers WHERE device_id = ?1",
+                params![device_id],
+                |row| {
+                    let (session_files, session_bytes, scan_files, scan_bytes) =
+                        (row.get::<_, i64>(1)?, row.get::<_, i64>(2)?, row.get::<_, i64>(3)?, row.get::<_, i64>(4)?);
+                    Ok(DeviceTransferStats {
+                        device_id: row.get(0)?,
+                        files_written: session_files + scan_files,
+                        bytes_written: session_bytes + scan_bytes,
+                        session_files,
+                        session_bytes,
+                        scan_files,
+                        scan_bytes,
+                        updated_at: row.get(5)?,
+                    })
+                },
+            )
+            .ok();
 
-        let mut snapshots = Vec::new();
-        for snapshot in snapshot_iter {
-            snapshots.push(snapshot?);
-        }
+        // Sin fila todavía: todo a cero
+        Ok(stats.unwrap_or_else(|| DeviceTransferStats { device_id: device_id.to_string(), ..Default::default() }))
+    }
 
-        Ok(snapshots)
+    /// Retiradas del dispositivo: (desconexiones limpias, retiradas sin expulsar)
+    pub fn get_removal_counts(&self, device_id: &str) -> Result<(i64, i64)> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COALESCE(SUM(event_type = 'DISCONNECT'), 0), COALESCE(SUM(event_type = 'UNSAFE_REMOVAL'), 0)
+             FROM activity_log WHERE device_id = ?1",
+            params![device_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
     }
 
-    // Obtener dispositivos registrados
-    pub fn get_devices(&self) -> Result<Vec<Device>> {
+    pub fn get_session_summaries(&self, device_id: Option<&str>, limit: i64) -> Result<Vec<SessionSummary>> {
         let conn = self.conn.lock().unwrap();
 
         let mut stmt = conn.prepare(
-            "SELECT serial_number, vendor_id, product_id, name, manufacturer, total_capacity
-             FROM devices
-             ORDER BY updated_at DESC",
-        )?;
-
-        let device_iter = stmt.query_map([], |row| {
-            Ok(Device {
-                serial_number: row.get(0)?,
-                vendor_id: row.get(1)?,
-                product_id: row.get(2)?,
-                name: row.get(3)?,
-                manufacturer: row.get(4)?,
-                total_capacity: row.get(5)?,
+            "SELECT connect_activity_id, disconnect_activity_id, device_id, connected_at, disconnected_at,
+                    duration_secs, files_added, files_removed, files_modified, bytes_written, alerts_raised
+             FROM sessions
+             WHERE ?1 IS NULL OR device_id = ?1
+             ORDER BY connected_at DESC
+             LIMIT ?2",
+        )?;
+
+        let summary_iter = stmt.query_map(params![device_id, limit], |row| {
+            Ok(SessionSummary {
+                connect_activity_id: row.get(0)?,
+                disconnect_activity_id: row.get(1)?,
+                device_id: row.get(2)?,
+                connected_at: row.get(3)?,
+                disconnected_at: row.get(4)?,
+                duration_secs: row.get(5)?,
+                files_added: row.get(6)?,
+                files_removed: row.get(7)?,
+                files_modified: row.get(8)?,
+                bytes_written: row.get(9)?,
+                alerts_raised: row.get(10)?,
             })
         })?;
 
-        let mut devices = Vec::new();
-        for device in device_iter {
-            devices.push(device?);
+        let mut summaries = Vec::new();
+        for summary in summary_iter {
+            summaries.push(summary?);
         }
 
-        Ok(devices)
+        Ok(summaries)
     }
+}
 
-    // Obtener estadísticas de un escaneo
-    pub fn get_scan_stats(&self, activity_log_id: i64) -> Result<(i64, i64)> {
-        let conn = self.conn.lock().unwrap();
+fn row_to_scan_info(row: &rusqlite::Row) -> Result<ScanInfo> {
+    Ok(ScanInfo {
+        activity_log_id: row.get(0)?,
+        device_id: row.get(1)?,
+        timestamp: row.get(2)?,
+        mount_point: row.get(3)?,
+    })
+}
 
-        let total_files: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM file_snapshots WHERE activity_log_id = ?1 AND is_folder = 0",
-            params![activity_log_id],
-            |row| row.get(0),
-        )?;
+/// Separador entre el número de serie USB y el volumen en los registros de memorias que comparten serie
+pub const DEVICE_VARIANT_SEPARATOR: char = '~';
 
-        let total_folders: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM file_snapshots WHERE activity_log_id = ?1 AND is_folder = 1",
-            params![activity_log_id],
-            |row| row.get(0),
-        )?;
+// Tablas cuyo historial va con cada conexión al separar un dispositivo
+const SPLIT_ACTIVITY_COLUMNS: &[(&str, &str)] = &[
+    ("activity_log", "id"),
+    ("scans", "activity_log_id"),
+    ("scan_checkpoints", "activity_log_id"),
+    ("alerts", "activity_log_id"),
+    ("sessions", "connect_activity_id"),
+    ("evidence", "activity_log_id"),
+    ("backup_runs", "activity_log_id"),
+];
 
-        Ok((total_files, total_folders))
+const DEVICE_SELECT: &str =
+    "SELECT serial_number, vendor_id, product_id, name, manufacturer, total_capacity, trust_level, machine_id, nickname, volume_serial,
+            first_seen, last_seen, volume_label, media_type, case_reference, usb_serial, skip_scan, skip_watch, redact_paths
+     FROM devices";
+
+fn row_to_device(row: &rusqlite::Row) -> Result<Device> {
+    Ok(Device {
+        serial_number: row.get(0)?,
+        vendor_id: row.get(1)?,
+        product_id: row.get(2)?,
+        name: row.get(3)?,
+        manufacturer: row.get(4)?,
+        total_capacity: row.get(5)?,
+        trust_level: TrustLevel::parse(&row.get::<_, String>(6)?),
+        machine_id: row.get(7)?,
+        nickname: row.get(8)?,
+        volume_serial: row.get(9)?,
+        first_seen: row.get(10)?,
+        last_seen: row.get(11)?,
+        volume_label: row.get(12)?,
+        media_type: MediaType::parse(&row.get::<_, String>(13)?),
+        case_reference: row.get(14)?,
+        usb_serial: row.get(15)?,
+        skip_scan: row.get(16)?,
+        skip_watch: row.get(17)?,
+        redact_paths: row.get(18)?,
+    })
+}
+
+const FILE_EVENT_SELECT: &str =
+    "SELECT fe.id, fe.activity_log_id, al.device_id, fe.file_path, fe.event_type, fe.file_size, fe.created_at, fe.volume, fe.process_name
+     FROM file_events fe
+     JOIN activity_log al ON al.id = fe.activity_log_id";
+
+fn row_to_file_event(row: &rusqlite::Row) -> Result<FileEvent> {
+    Ok(FileEvent {
+        id: row.get(0)?,
+        activity_log_id: row.get(1)?,
+        device_id: row.get(2)?,
+        file_path: row.get(3)?,
+        kind: FileEventKind::parse(&row.get::<_, String>(4)?),
+        file_size: row.get(5)?,
+        volume: row.get(7)?,
+        process_name: row.get(8)?,
+        created_at: row.get(6)?,
+    })
+}
+
+const ACTIVITY_SELECT: &str =
+    "SELECT id, device_id, event_type, timestamp, os_user, session_id, machine_id, port_path, volume_serial, case_reference
+     FROM activity_log";
+
+fn row_to_activity(row: &rusqlite::Row) -> Result<ActivityLog> {
+    Ok(ActivityLog {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        event_type: EventType::parse(&row.get::<_, String>(2)?),
+        timestamp: row.get(3)?,
+        os_user: row.get(4)?,
+        session_id: row.get(5)?,
+        machine_id: row.get(6)?,
+        port_path: row.get(7)?,
+        volume_serial: row.get(8)?,
+        case_reference: row.get(9)?,
+    })
+}
+
+const ALERT_SELECT: &str =
+    "SELECT id, device_id, activity_log_id, alert_type, severity, message, created_at, acknowledged, message_key, message_params
+     FROM alerts";
+
+fn row_to_alert(row: &rusqlite::Row) -> Result<Alert> {
+    Ok(Alert {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        activity_log_id: row.get(2)?,
+        alert_type: row.get(3)?,
+        severity: AlertSeverity::parse(&row.get::<_, String>(4)?),
+        message: row.get(5)?,
+        message_key: row.get(8)?,
+        message_params: row
+            .get::<_, Option<String>>(9)?
+            .and_then(|p| serde_json::from_str(&p).ok()),
+        created_at: row.get(6)?,
+        acknowledged: row.get(7)?,
+    })
+}
+
+// Snapshot `f` cuyo hash completo está en alguna lista de archivos conocidos
+// (un hash por muestra no es el del archivo y no puede coincidir)
+const KNOWN_HASH_CONDITION: &str = "(f.sha256 IS NOT NULL AND COALESCE(f.hash_mode, 'FULL') = 'FULL'
+     AND EXISTS(SELECT 1 FROM known_hashes k WHERE k.sha256 = f.sha256))";
+
+// "?, ?, ?" para cláusulas IN con n valores
+fn id_placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+// Columnas de un FileSnapshot reconstruyendo la ruta completa desde su directorio
+const SNAPSHOT_SELECT: &str =
+    "SELECT f.id, f.activity_log_id, d.path || f.file_name AS file_path, f.file_name, f.file_extension, f.file_size, f.is_folder, f.sha256, f.hash_mode
+     FROM file_snapshots f
+     JOIN directories d ON d.id = f.directory_id";
+
+const ATTACHMENT_SELECT: &str =
+    "SELECT id, device_id, file_name, stored_path, mime_type, file_size, sha256, description, created_at
+     FROM device_attachments";
+
+fn row_to_attachment(row: &rusqlite::Row) -> Result<DeviceAttachment> {
+    Ok(DeviceAttachment {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        file_name: row.get(2)?,
+        stored_path: row.get(3)?,
+        mime_type: row.get(4)?,
+        file_size: row.get(5)?,
+        sha256: row.get(6)?,
+        description: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+fn row_to_snapshot(row: &rusqlite::Row) -> Result<FileSnapshot> {
+    Ok(FileSnapshot {
+        id: row.get(0)?,
+        activity_log_id: row.get(1)?,
+        file_path: row.get(2)?,
+        file_name: row.get(3)?,
+        file_extension: row.get(4)?,
+        file_size: row.get(5)?,
+        is_folder: row.get(6)?,
+        sha256: row.get(7)?,
+        hash_mode: row.get::<_, Option<String>>(8)?.map(|m| HashMode::parse(&m)),
+    })
+}
+
+fn query_snapshots(conn: &Connection, activity_log_id: i64) -> Result<Vec<FileSnapshot>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE f.activity_log_id = ?1 ORDER BY file_path",
+        SNAPSHOT_SELECT
+    ))?;
+
+    let snapshot_iter = stmt.query_map(params![activity_log_id], row_to_snapshot)?;
+
+    let mut snapshots = Vec::new();
+    for snapshot in snapshot_iter {
+        snapshots.push(snapshot?);
     }
 
-    // Obtener snapshots del último CONNECT de un dispositivo específico
-    pub fn get_latest_device_snapshots(&self, device_id: &str) -> Result<(i64, Vec<FileSnapshot>)> {
-        let conn = self.conn.lock().unwrap();
+    Ok(snapshots)
+}
 
-        // Obtener el último activity_log CONNECT para este dispositivo
-        let activity_id: Option<i64> = conn
-            .query_row(
-                "SELECT id FROM activity_log 
-             WHERE device_id = ?1 AND event_type = 'CONNECT'
-             ORDER BY timestamp DESC
-             LIMIT 1",
-                params![device_id],
-                |row| row.get(0),
-            )
-            .ok();
+const OUTBOX_SELECT: &str =
+    "SELECT id, channel, payload, status, attempts, next_attempt_at, last_error, created_at FROM outbox";
 
-        match activity_id {
-            Some(id) => {
-                let mut stmt = conn.prepare(
-                    "SELECT id, activity_log_id, file_path, file_name, file_extension, file_size, is_folder
-                     FROM file_snapshots 
-                     WHERE activity_log_id = ?1
-                     ORDER BY file_path"
-                )?;
+fn row_to_outbox(row: &rusqlite::Row) -> Result<OutboxMessage> {
+    let payload: String = row.get(2)?;
+    let status: String = row.get(3)?;
+    Ok(OutboxMessage {
+        id: row.get(0)?,
+        channel: row.get(1)?,
+        payload: serde_json::from_str(&payload).unwrap_or_default(),
+        status: OutboxStatus::parse(&status),
+        attempts: row.get(4)?,
+        next_attempt_at: row.get(5)?,
+        last_error: row.get(6)?,
+        created_at: row.get(7)?,
+    })
+}
 
-                let snapshot_iter = stmt.query_map(params![id], |row| {
-                    Ok(FileSnapshot {
-                        id: row.get(0)?,
-                        activity_log_id: row.get(1)?,
-                        file_path: row.get(2)?,
-                        file_name: row.get(3)?,
-                        file_extension: row.get(4)?,
-                        file_size: row.get(5)?,
-                        is_folder: row.get(6)?,
-                    })
-                })?;
+const GRANT_SELECT: &str =
+    "SELECT id, device_id, previous_trust, granted_trust, status, expires_at, created_at, ended_at FROM access_grants";
 
-                let mut snapshots = Vec::new();
-                for snapshot in snapshot_iter {
-                    snapshots.push(snapshot?);
-                }
+fn row_to_grant(row: &rusqlite::Row) -> Result<AccessGrant> {
+    let previous_trust: String = row.get(2)?;
+    let granted_trust: String = row.get(3)?;
+    let status: String = row.get(4)?;
+    Ok(AccessGrant {
+        id: row.get(0)?,
+        device_id: row.get(1)?,
+        previous_trust: TrustLevel::parse(&previous_trust),
+        granted_trust: TrustLevel::parse(&granted_trust),
+        status: GrantStatus::parse(&status),
+        expires_at: row.get(5)?,
+        created_at: row.get(6)?,
+        ended_at: row.get(7)?,
+    })
+}
 
-                println!(
-                    "[DB] Found {} snapshots for device {} (activity_id: {})",
-                    snapshots.len(),
-                    device_id,
-                    id
-                );
-                Ok((id, snapshots))
-            }
+// Separar la ruta en prefijo de directorio + nombre, de forma que prefijo || nombre == ruta.
+// Si la ruta no termina en el nombre (p.ej. la raíz "E:\") se guarda entera como nombre.
+fn split_snapshot_path<'a>(file_path: &'a str, file_name: &'a str) -> (&'a str, &'a str) {
+    if !file_name.is_empty() && file_path.len() > file_name.len() && file_path.ends_with(file_name) {
+        (&file_path[..file_path.len() - file_name.len()], file_name)
+    } else {
+        ("", file_path)
+    }
+}
+
+fn insert_snapshots(conn: &Connection, snapshots: &[FileSnapshot]) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO file_snapshots (activity_log_id, directory_id, file_name, file_extension, file_size, is_folder, sha256, hash_mode)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+    )?;
+    let mut directory_cache: HashMap<String, i64> = HashMap::new();
+
+    for snapshot in snapshots {
+        let (prefix, file_name) = split_snapshot_path(&snapshot.file_path, &snapshot.file_name);
+        let directory_id = match directory_cache.get(prefix) {
+            Some(id) => *id,
             None => {
-                println!("[DB] No CONNECT activity found for device {}", device_id);
-                Ok((0, Vec::new()))
+                let id = resolve_directory_id(conn, prefix)?;
+                directory_cache.insert(prefix.to_string(), id);
+                id
             }
+        };
+
+        stmt.execute(params![
+            snapshot.activity_log_id,
+            directory_id,
+            file_name,
+            snapshot.file_extension,
+            snapshot.file_size,
+            snapshot.is_folder,
+            snapshot.sha256,
+            snapshot.hash_mode.map(|m| m.as_str()),
+        ])?;
+    }
+
+    Ok(())
+}
+
+fn resolve_directory_id(conn: &Connection, path: &str) -> Result<i64> {
+    conn.execute(
+        "INSERT OR IGNORE INTO directories (path) VALUES (?1)",
+        params![path],
+    )?;
+
+    conn.query_row(
+        "SELECT id FROM directories WHERE path = ?1",
+        params![path],
+        |row| row.get(0),
+    )
+}
+
+// Bases de datos anteriores a UNSAFE_REMOVAL: la restricción CHECK de activity_log solo admite
+// CONNECT/DISCONNECT y SQLite no permite cambiarla, así que se rehace la tabla con los mismos datos
+fn migrate_activity_event_types(conn: &mut Connection) -> Result<()> {
+    const LEGACY_CHECK: &str = "CHECK(event_type IN ('CONNECT', 'DISCONNECT'))";
+    let sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'activity_log'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !sql.contains(LEGACY_CHECK) {
+        return Ok(());
+    }
+
+    println!("[DB] Migrating activity_log to allow UNSAFE_REMOVAL events...");
+    let create = sql
+        .replacen(LEGACY_CHECK, "CHECK(event_type IN ('CONNECT', 'DISCONNECT', 'UNSAFE_REMOVAL'))", 1)
+        .replacen("activity_log", "activity_log_new", 1);
+
+    // Con las claves foráneas activas, DROP TABLE comprobaría las referencias de los snapshots y eventos
+    let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+    conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+    let result = rebuild_activity_log(conn, &create);
+    if foreign_keys {
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+    }
+    result
+}
+
+fn rebuild_activity_log(conn: &mut Connection, create: &str) -> Result<()> {
+    let tx = conn.transaction()?;
+    // Conservar el contador de AUTOINCREMENT para no reutilizar IDs de eventos borrados
+    let sequence: Option<i64> = tx
+        .query_row("SELECT seq FROM sqlite_sequence WHERE name = 'activity_log'", [], |row| row.get(0))
+        .optional()?;
+    tx.execute(create, [])?;
+    tx.execute("INSERT INTO activity_log_new SELECT * FROM activity_log", [])?;
+    tx.execute("DROP TABLE activity_log", [])?;
+    tx.execute("ALTER TABLE activity_log_new RENAME TO activity_log", [])?;
+    if let Some(sequence) = sequence {
+        tx.execute("UPDATE sqlite_sequence SET seq = MAX(seq, ?1) WHERE name = 'activity_log'", params![sequence])?;
+    }
+    tx.commit()
+}
+
+// Migrar bases de datos antiguas que guardaban file_path completo en cada fila
+fn migrate_snapshot_paths(conn: &mut Connection) -> Result<()> {
+    let has_file_path: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info('file_snapshots') WHERE name = 'file_path')",
+        [],
+        |row| row.get(0),
+    )?;
+
+    if !has_file_path {
+        return Ok(());
+    }
+
+    println!("[DB] Migrating file_snapshots to normalized directories...");
+    add_column_if_missing(conn, "file_snapshots", "directory_id", "INTEGER REFERENCES directories(id)")?;
+
+    let tx = conn.transaction()?;
+    let mut migrated = 0;
+    {
+        let mut select = tx.prepare("SELECT id, file_path, file_name FROM file_snapshots WHERE directory_id IS NULL")?;
+        let rows = select
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut update = tx.prepare("UPDATE file_snapshots SET directory_id = ?1, file_name = ?2 WHERE id = ?3")?;
+        let mut directory_cache: HashMap<String, i64> = HashMap::new();
+
+        for (id, file_path, file_name) in &rows {
+            let (prefix, name) = split_snapshot_path(file_path, file_name);
+            let directory_id = match directory_cache.get(prefix) {
+                Some(id) => *id,
+                None => {
+                    let dir_id = resolve_directory_id(&tx, prefix)?;
+                    directory_cache.insert(prefix.to_string(), dir_id);
+                    dir_id
+                }
+            };
+            update.execute(params![directory_id, name, id])?;
+            migrated += 1;
         }
     }
 
-    // Obtener todos los snapshots de un dispositivo (de todos sus connections)
-    pub fn get_all_device_snapshots(
-        &self,
-        device_id: &str,
-    ) -> Result<Vec<(i64, String, Vec<FileSnapshot>)>> {
-        let conn = self.conn.lock().unwrap();
+    tx.execute("ALTER TABLE file_snapshots DROP COLUMN file_path", [])?;
+    tx.commit()?;
 
-        let mut stmt = conn.prepare(
-            "SELECT al.id, al.timestamp 
-             FROM activity_log al
-             WHERE al.device_id = ?1 AND al.event_type = 'CONNECT'
-             ORDER BY al.timestamp DESC",
+    // Recuperar el espacio liberado por las rutas duplicadas
+    conn.execute("VACUUM", [])?;
+    println!("[DB] Migrated {} snapshots to normalized paths", migrated);
+
+    Ok(())
+}
+
+// Mismo formato que CURRENT_TIMESTAMP de SQLite, para que el orden por texto siga siendo válido
+pub fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// Hora de una operación del oplog: formato de CURRENT_TIMESTAMP con milisegundos
+fn oplog_timestamp() -> String {
+    Utc::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+}
+
+// UUID v4 generado por SQLite, para rellenar muchas filas de una vez en las migraciones
+const SQL_UUID: &str = "lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' || substr(hex(randomblob(2)), 2) || '-'
+    || substr('89ab', 1 + abs(random()) % 4, 1) || substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6)))";
+
+// Contenido de las operaciones del oplog, calculado a partir de la fila ya guardada
+const DEVICE_OP_PAYLOAD: &str = "json_object('vendor_id', vendor_id, 'product_id', product_id, 'name', name,
+    'manufacturer', manufacturer, 'total_capacity', total_capacity, 'volume_serial', volume_serial,
+    'volume_label', volume_label, 'media_type', media_type, 'nickname', nickname)";
+const ACTIVITY_OP_PAYLOAD: &str = "json_object('device_id', device_id, 'event_type', event_type, 'timestamp', timestamp,
+    'mount_point', mount_point, 'port_path', port_path, 'volume_serial', volume_serial, 'os_user', os_user,
+    'session_id', session_id, 'machine_id', machine_id, 'source', source)";
+const ALERT_OP_PAYLOAD: &str = "json_object('device_id', device_id,
+    'activity_uuid', (SELECT l.uuid FROM activity_log l WHERE l.id = alerts.activity_log_id),
+    'alert_type', alert_type, 'severity', severity, 'message', message, 'message_key', message_key,
+    'message_params', message_params, 'created_at', created_at)";
+
+// Anotar una operación en el oplog, con la misma conexión o transacción que hace el cambio
+fn append_op(conn: &Connection, entity: &str, entity_id: &str, op: &str, payload: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO oplog (op_id, machine_id, entity, entity_id, op, payload, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            uuid::Uuid::new_v4().to_string(),
+            crate::machine::current().machine_id,
+            entity,
+            entity_id,
+            op,
+            payload,
+            oplog_timestamp(),
+        ],
+    )?;
+    Ok(())
+}
+
+// Pasar todo el historial de `source` a `target` (que debe existir) y borrar `source`
+fn merge_device_rows(tx: &Connection, source: &str, target: &str) -> Result<usize> {
+    // Contadores acumulados: se suman en lugar de perder los de `source`
+    tx.execute(
+        "INSERT INTO device_transfers (device_id, session_files, session_bytes, scan_files, scan_bytes, last_scan_activity_id)
+         SELECT ?2, session_files, session_bytes, scan_files, scan_bytes, last_scan_activity_id
+         FROM device_transfers WHERE device_id = ?1
+         ON CONFLICT(device_id) DO UPDATE SET
+            session_files = session_files + excluded.session_files,
+            session_bytes = session_bytes + excluded.session_bytes,
+            scan_files = scan_files + excluded.scan_files,
+            scan_bytes = scan_bytes + excluded.scan_bytes,
+            last_scan_activity_id = MAX(COALESCE(last_scan_activity_id, 0), COALESCE(excluded.last_scan_activity_id, 0))",
+        params![source, target],
+    )?;
+
+    // Todas las tablas con device_id; en las que es clave única se queda la fila de `target`.
+    // El registro de auditoría no se reescribe: quien une los dispositivos anota la unión.
+    let tables: Vec<String> = {
+        let mut stmt = tx.prepare(
+            "SELECT m.name FROM sqlite_master m, pragma_table_info(m.name) p
+             WHERE m.type = 'table' AND p.name = 'device_id' AND m.name != 'audit_log'",
         )?;
+        let names = stmt.query_map([], |row| row.get(0))?;
+        names.collect::<Result<_>>()?
+    };
 
-        let activity_iter = stmt.query_map(params![device_id], |row| {
-            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
-        })?;
+    let mut moved = 0;
+    for table in &tables {
+        moved += tx.execute(
+            &format!("UPDATE OR IGNORE {} SET device_id = ?2 WHERE device_id = ?1", table),
+            params![source, target],
+        )?;
+        tx.execute(&format!("DELETE FROM {} WHERE device_id = ?1", table), params![source])?;
+    }
 
-        let mut results = Vec::new();
-        for activity_result in activity_iter {
-            let (activity_id, timestamp) = activity_result?;
+    // Se queda la confianza más restrictiva: unir una memoria bloqueada a otra no la desbloquea
+    tx.execute(
+        "UPDATE devices SET
+            first_seen = (SELECT MIN(first_seen) FROM devices WHERE serial_number IN (?1, ?2)),
+            last_seen = (SELECT MAX(last_seen) FROM devices WHERE serial_number IN (?1, ?2)),
+            trust_level = (SELECT trust_level FROM devices WHERE serial_number IN (?1, ?2)
+                ORDER BY CASE trust_level WHEN ?3 THEN 0 WHEN ?4 THEN 2 ELSE 1 END LIMIT 1),
+            updated_at = CURRENT_TIMESTAMP
+         WHERE serial_number = ?2",
+        params![source, target, TrustLevel::Blocked.as_str(), TrustLevel::Trusted.as_str()],
+    )?;
+    tx.execute("DELETE FROM devices WHERE serial_number = ?1", params![source])?;
+    Ok(moved)
+}
 
-            let mut snapshot_stmt = conn.prepare(
-                "SELECT id, activity_log_id, file_path, file_name, file_extension, file_size, is_folder
-                 FROM file_snapshots 
-                 WHERE activity_log_id = ?1
-                 ORDER BY file_path"
-            )?;
+fn append_device_op(conn: &Connection, serial_number: &str) -> Result<()> {
+    let payload: String = conn.query_row(
+        &format!("SELECT {} FROM devices WHERE serial_number = ?1", DEVICE_OP_PAYLOAD),
+        params![serial_number],
+        |row| row.get(0),
+    )?;
+    append_op(conn, OP_ENTITY_DEVICE, serial_number, OP_UPSERT, &payload)
+}
 
-            let snapshot_iter = snapshot_stmt.query_map(params![activity_id], |row| {
-                Ok(FileSnapshot {
-                    id: row.get(0)?,
-                    activity_log_id: row.get(1)?,
-                    file_path: row.get(2)?,
-                    file_name: row.get(3)?,
-                    file_extension: row.get(4)?,
-                    file_size: row.get(5)?,
-                    is_folder: row.get(6)?,
-                })
-            })?;
+fn append_trust_op(conn: &Connection, serial_number: &str, trust_level: TrustLevel) -> Result<()> {
+    let payload = serde_json::json!({ "trust_level": trust_level.as_str() });
+    append_op(conn, OP_ENTITY_DEVICE, serial_number, OP_TRUST, &payload.to_string())
+}
+
+// Anotar una unión: la confianza de `target` si ha cambiado (`before`) y el borrado de `source`,
+// para que los demás equipos unan también su historial en lugar de conservar un registro que ya no existe
+fn append_merge_ops(conn: &Connection, source: &str, target: &str, before: TrustLevel) -> Result<()> {
+    let after = device_trust(conn, target)?;
+    if after != before {
+        append_trust_op(conn, target, after)?;
+    }
+    let payload = serde_json::json!({ "merged_into": target });
+    append_op(conn, OP_ENTITY_DEVICE, source, OP_DELETE, &payload.to_string())
+}
+
+fn device_trust(conn: &Connection, serial_number: &str) -> Result<TrustLevel> {
+    let trust: Option<String> = conn
+        .query_row("SELECT trust_level FROM devices WHERE serial_number = ?1", params![serial_number], |row| row.get(0))
+        .optional()?;
+    Ok(trust.map(|t| TrustLevel::parse(&t)).unwrap_or_default())
+}
+
+// Dar UUID a un evento recién insertado y anotarlo en el oplog
+fn append_activity_op(conn: &Connection, activity_log_id: i64) -> Result<()> {
+    let uuid = uuid::Uuid::new_v4().to_string();
+    conn.execute("UPDATE activity_log SET uuid = ?2 WHERE id = ?1", params![activity_log_id, uuid])?;
+    let payload: String = conn.query_row(
+        &format!("SELECT {} FROM activity_log WHERE id = ?1", ACTIVITY_OP_PAYLOAD),
+        params![activity_log_id],
+        |row| row.get(0),
+    )?;
+    append_op(conn, OP_ENTITY_ACTIVITY, &uuid, OP_INSERT, &payload)
+}
+
+fn append_alert_op(conn: &Connection, alert_id: i64) -> Result<()> {
+    let uuid = uuid::Uuid::new_v4().to_string();
+    conn.execute("UPDATE alerts SET uuid = ?2 WHERE id = ?1", params![alert_id, uuid])?;
+    let payload: String = conn.query_row(
+        &format!("SELECT {} FROM alerts WHERE id = ?1", ALERT_OP_PAYLOAD),
+        params![alert_id],
+        |row| row.get(0),
+    )?;
+    append_op(conn, OP_ENTITY_ALERT, &uuid, OP_INSERT, &payload)
+}
+
+// Crear un dispositivo mínimo para eventos de otro equipo que llegan sin sus datos
+fn ensure_device(conn: &Connection, serial_number: &str, seen: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO devices (serial_number, vendor_id, product_id, first_seen, last_seen)
+         VALUES (?1, 0, 0, ?2, ?2)",
+        params![serial_number, seen],
+    )?;
+    Ok(())
+}
+
+// Renombrar (origen, destino) en orden; si uno falla se deshacen los anteriores. Devuelve cuántos se han hecho.
+fn rename_files(renames: &[(PathBuf, PathBuf)]) -> std::io::Result<usize> {
+    for (done, (from, to)) in renames.iter().enumerate() {
+        if let Err(e) = std::fs::rename(from, to) {
+            undo_renames(&renames[..done]);
+            return Err(e);
+        }
+    }
+    Ok(renames.len())
+}
+
+fn undo_renames(renames: &[(PathBuf, PathBuf)]) {
+    for (from, to) in renames.iter().rev() {
+        let _ = std::fs::rename(to, from);
+    }
+}
+
+fn query_oplog(conn: &Connection, since_seq: i64) -> Result<Vec<OplogEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT seq, op_id, machine_id, entity, entity_id, op, payload, created_at
+         FROM oplog WHERE seq > ?1 ORDER BY seq",
+    )?;
+
+    let op_iter = stmt.query_map(params![since_seq], |row| {
+        let payload: String = row.get(6)?;
+        Ok(OplogEntry {
+            seq: row.get(0)?,
+            op_id: row.get(1)?,
+            machine_id: row.get(2)?,
+            entity: row.get(3)?,
+            entity_id: row.get(4)?,
+            op: row.get(5)?,
+            payload: serde_json::from_str(&payload).unwrap_or(serde_json::Value::Null),
+            created_at: row.get(7)?,
+        })
+    })?;
+
+    let mut ops = Vec::new();
+    for op in op_iter {
+        ops.push(op?);
+    }
 
-            let mut snapshots = Vec::new();
-            for snapshot in snapshot_iter {
-                snapshots.push(snapshot?);
+    Ok(ops)
+}
+
+// Aplicar una operación de otro equipo. Los datos del dispositivo solo completan lo que falta, eventos y alertas
+// se identifican por UUID y en la confianza gana el cambio más reciente, así que el resultado no depende
+// del orden en que se fusionen los equipos. Devuelve false si es un cambio de confianza ya superado.
+fn apply_op(conn: &Connection, op: &OplogEntry) -> Result<bool> {
+    let payload = &op.payload;
+    let text = |key: &str| payload.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let number = |key: &str| payload.get(key).and_then(|v| v.as_i64());
+    // Las columnas de fechas no llevan milisegundos
+    let seen = op.created_at.get(..19).unwrap_or(&op.created_at);
+
+    match (op.entity.as_str(), op.op.as_str()) {
+        (OP_ENTITY_DEVICE, OP_UPSERT) => {
+            conn.execute(
+                "INSERT INTO devices (serial_number, vendor_id, product_id, name, manufacturer, total_capacity, volume_serial, volume_label, media_type, nickname, first_seen, last_seen)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, COALESCE(?9, 'USB'), ?10, ?11, ?11)
+                 ON CONFLICT(serial_number) DO UPDATE SET
+                    vendor_id = CASE WHEN devices.vendor_id = 0 THEN excluded.vendor_id ELSE devices.vendor_id END,
+                    product_id = CASE WHEN devices.product_id = 0 THEN excluded.product_id ELSE devices.product_id END,
+                    name = COALESCE(devices.name, excluded.name),
+                    manufacturer = COALESCE(devices.manufacturer, excluded.manufacturer),
+                    total_capacity = COALESCE(devices.total_capacity, excluded.total_capacity),
+                    volume_serial = COALESCE(devices.volume_serial, excluded.volume_serial),
+                    volume_label = COALESCE(devices.volume_label, excluded.volume_label),
+                    nickname = COALESCE(devices.nickname, excluded.nickname),
+                    first_seen = MIN(COALESCE(devices.first_seen, excluded.first_seen), excluded.first_seen),
+                    last_seen = MAX(COALESCE(devices.last_seen, excluded.last_seen), excluded.last_seen)",
+                params![
+                    op.entity_id,
+                    number("vendor_id").unwrap_or(0),
+                    number("product_id").unwrap_or(0),
+                    text("name"),
+                    text("manufacturer"),
+                    number("total_capacity"),
+                    text("volume_serial"),
+                    text("volume_label"),
+                    text("media_type"),
+                    text("nickname"),
+                    seen,
+                ],
+            )?;
+        }
+        (OP_ENTITY_DEVICE, OP_TRUST) => {
+            let newer: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM oplog WHERE entity = ?1 AND entity_id = ?2 AND op = ?3
+                    AND (created_at > ?4 OR (created_at = ?4 AND op_id > ?5)))",
+                params![OP_ENTITY_DEVICE, op.entity_id, OP_TRUST, op.created_at, op.op_id],
+                |row| row.get(0),
+            )?;
+            if newer {
+                return Ok(false);
             }
 
-            results.push((activity_id, timestamp, snapshots));
+            ensure_device(conn, &op.entity_id, seen)?;
+            let trust_level = TrustLevel::parse(&text("trust_level").unwrap_or_default());
+            conn.execute(
+                "UPDATE devices SET trust_level = ?2, updated_at = CURRENT_TIMESTAMP WHERE serial_number = ?1",
+                params![op.entity_id, trust_level.as_str()],
+            )?;
+        }
+        (OP_ENTITY_DEVICE, OP_DELETE) => {
+            // Registro unido a otro en el equipo de origen: aquí también pasa su historial al otro
+            if let Some(target) = text("merged_into") {
+                ensure_device(conn, &target, seen)?;
+                merge_device_rows(conn, &op.entity_id, &target)?;
+            }
+        }
+        (OP_ENTITY_ACTIVITY, OP_INSERT) => {
+            let device_id = text("device_id").unwrap_or_default();
+            ensure_device(conn, &device_id, seen)?;
+            conn.execute(
+                "INSERT OR IGNORE INTO activity_log (uuid, device_id, event_type, timestamp, mount_point, port_path, volume_serial, os_user, session_id, machine_id, source)
+                 VALUES (?1, ?2, ?3, COALESCE(?4, ?5), ?6, ?7, ?8, ?9, ?10, COALESCE(?11, ?12), COALESCE(?13, 'monitor'))",
+                params![
+                    op.entity_id,
+                    device_id,
+                    text("event_type"),
+                    text("timestamp"),
+                    seen,
+                    text("mount_point"),
+                    text("port_path"),
+                    text("volume_serial"),
+                    text("os_user"),
+                    number("session_id"),
+                    text("machine_id"),
+                    op.machine_id,
+                    text("source"),
+                ],
+            )?;
+        }
+        (OP_ENTITY_ALERT, OP_INSERT) => {
+            let device_id = text("device_id");
+            if let Some(ref device_id) = device_id {
+                ensure_device(conn, device_id, seen)?;
+            }
+            conn.execute(
+                "INSERT OR IGNORE INTO alerts (uuid, device_id, activity_log_id, alert_type, severity, message, message_key, message_params, created_at)
+                 VALUES (?1, ?2, (SELECT id FROM activity_log WHERE uuid = ?3), ?4, ?5, ?6, ?7, ?8, COALESCE(?9, ?10))",
+                params![
+                    op.entity_id,
+                    device_id,
+                    text("activity_uuid"),
+                    text("alert_type").unwrap_or_default(),
+                    text("severity").unwrap_or_default(),
+                    text("message").unwrap_or_default(),
+                    text("message_key"),
+                    text("message_params"),
+                    text("created_at"),
+                    seen,
+                ],
+            )?;
+        }
+        (OP_ENTITY_ALERT, OP_ACK) => {
+            conn.execute(
+                "UPDATE alerts SET acknowledged = 1, acknowledged_at = COALESCE(?2, ?3) WHERE uuid = ?1 AND acknowledged = 0",
+                params![op.entity_id, text("acknowledged_at"), seen],
+            )?;
         }
+        // Operaciones de versiones posteriores: se guardan igualmente para volver a exportarlas
+        _ => {}
+    }
 
-        println!(
-            "[DB] Found {} connection events for device {}",
-            results.len(),
-            device_id
-        );
-        Ok(results)
+    Ok(true)
+}
+
+// Primera vez con oplog: dar UUID a los eventos y alertas existentes y anotarlos como operaciones,
+// para que la primera exportación incluya todo el historial
+fn backfill_oplog(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(&format!("UPDATE activity_log SET uuid = {} WHERE uuid IS NULL", SQL_UUID), [])?;
+    tx.execute(&format!("UPDATE alerts SET uuid = {} WHERE uuid IS NULL", SQL_UUID), [])?;
+
+    let empty: bool = tx.query_row("SELECT NOT EXISTS(SELECT 1 FROM oplog)", [], |row| row.get(0))?;
+    if !empty {
+        return tx.commit();
+    }
+
+    let machine_id = &crate::machine::current().machine_id;
+    let mut added = tx.execute(
+        &format!(
+            "INSERT INTO oplog (op_id, machine_id, entity, entity_id, op, payload, created_at)
+             SELECT {}, COALESCE(machine_id, ?1), ?2, serial_number, ?3, {}, COALESCE(first_seen, created_at, CURRENT_TIMESTAMP)
+             FROM devices ORDER BY first_seen",
+            SQL_UUID, DEVICE_OP_PAYLOAD
+        ),
+        params![machine_id, OP_ENTITY_DEVICE, OP_UPSERT],
+    )?;
+    added += tx.execute(
+        &format!(
+            "INSERT INTO oplog (op_id, machine_id, entity, entity_id, op, payload, created_at)
+             SELECT {}, COALESCE(machine_id, ?1), ?2, serial_number, ?3, json_object('trust_level', trust_level), COALESCE(updated_at, CURRENT_TIMESTAMP)
+             FROM devices WHERE trust_level <> 'UNKNOWN'",
+            SQL_UUID
+        ),
+        params![machine_id, OP_ENTITY_DEVICE, OP_TRUST],
+    )?;
+    added += tx.execute(
+        &format!(
+            "INSERT INTO oplog (op_id, machine_id, entity, entity_id, op, payload, created_at)
+             SELECT {}, COALESCE(machine_id, ?1), ?2, uuid, ?3, {}, COALESCE(timestamp, CURRENT_TIMESTAMP)
+             FROM activity_log ORDER BY id",
+            SQL_UUID, ACTIVITY_OP_PAYLOAD
+        ),
+        params![machine_id, OP_ENTITY_ACTIVITY, OP_INSERT],
+    )?;
+    added += tx.execute(
+        &format!(
+            "INSERT INTO oplog (op_id, machine_id, entity, entity_id, op, payload, created_at)
+             SELECT {}, ?1, ?2, uuid, ?3, {}, COALESCE(created_at, CURRENT_TIMESTAMP)
+             FROM alerts ORDER BY id",
+            SQL_UUID, ALERT_OP_PAYLOAD
+        ),
+        params![machine_id, OP_ENTITY_ALERT, OP_INSERT],
+    )?;
+    added += tx.execute(
+        &format!(
+            "INSERT INTO oplog (op_id, machine_id, entity, entity_id, op, payload, created_at)
+             SELECT {}, ?1, ?2, uuid, ?3, json_object('acknowledged_at', acknowledged_at), COALESCE(acknowledged_at, created_at, CURRENT_TIMESTAMP)
+             FROM alerts WHERE acknowledged = 1 ORDER BY id",
+            SQL_UUID
+        ),
+        params![machine_id, OP_ENTITY_ALERT, OP_ACK],
+    )?;
+    tx.commit()?;
+
+    if added > 0 {
+        println!("[DB] Backfilled oplog with {} operations", added);
     }
+    Ok(())
 }
 
-// Singleton para acceso global
-use std::sync::OnceLock;
+// Completar first_seen/last_seen de dispositivos anteriores a esas columnas con su historial
+fn backfill_device_seen(conn: &Connection) -> Result<()> {
+    let updated = conn.execute(
+        "UPDATE devices SET
+            first_seen = COALESCE(first_seen, (SELECT MIN(timestamp) FROM activity_log WHERE device_id = devices.serial_number), created_at),
+            last_seen = COALESCE(last_seen, (SELECT MAX(timestamp) FROM activity_log WHERE device_id = devices.serial_number), updated_at)
+         WHERE first_seen IS NULL OR last_seen IS NULL",
+        [],
+    )?;
+
+    if updated > 0 {
+        println!("[DB] Backfilled first/last seen for {} devices", updated);
+    }
+    Ok(())
+}
 
-static DB_INSTANCE: OnceLock<Arc<Database>> = OnceLock::new();
+// Series de volumen NTFS guardadas en Linux con los 64 bits: pasar al formato de Windows ("5E6F-7A8B")
+fn normalize_volume_serials(conn: &Connection) -> Result<()> {
+    let updated = conn.execute(
+        "UPDATE devices SET volume_serial = substr(volume_serial, 9, 4) || '-' || substr(volume_serial, 13, 4)
+         WHERE length(volume_serial) = 16 AND volume_serial NOT GLOB '*[^0-9A-F]*'",
+        [],
+    )?;
 
-pub fn init_database(app_data_dir: PathBuf) -> Result<Arc<Database>> {
-    let db = Arc::new(Database::new(app_data_dir)?);
-    let _ = DB_INSTANCE.set(db.clone());
-    Ok(db)
+    if updated > 0 {
+        println!("[DB] Normalized volume serial for {} devices", updated);
+    }
+    Ok(())
 }
 
-pub fn get_database() -> Option<Arc<Database>> {
-    DB_INSTANCE.get().cloned()
+// Añadir una columna si la tabla todavía no la tiene (bases de datos creadas con versiones anteriores)
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1)", table),
+        params![column],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+        println!("[DB] Migrated {}: added column {}", table, column);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,164 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::compare::{relative_path, scan_root};
+use crate::db::Database;
+use crate::state::AppState;
+
+// Niveles que se pueden cargar de una vez; el resto se pide al expandir
+const MAX_DEPTH: u32 = 5;
+// Entradas de los niveles inferiores por llamada (los hijos directos del prefijo van siempre completos)
+const MAX_NESTED_NODES: usize = 5000;
+// Directorios por consulta, por debajo del límite de parámetros de SQLite
+const PATHS_PER_QUERY: usize = 400;
+
+/// Entrada del árbol de contenido de una unidad
+#[derive(Debug, Serialize, Clone)]
+pub struct TreeNode {
+    pub name: String,
+    /// Ruta relativa a la raíz de la unidad, con '/'; es el `path_prefix` para expandir la carpeta
+    pub path: String,
+    pub full_path: String,
+    pub is_folder: bool,
+    pub size: i64,
+    pub sha256: Option<String>,
+    /// Entradas directas dentro de la carpeta (0 en archivos)
+    pub child_count: i64,
+    /// Hijos ya cargados; None si hay que pedirlos con otra llamada
+    pub children: Option<Vec<TreeNode>>,
+}
+
+// Directorio tal como se guarda (con separador final), en las dos variantes de separador
+fn directory_keys(folder_path: &str) -> [String; 2] {
+    let base = folder_path.trim_end_matches(['/', '\\']);
+    [format!("{}/", base), format!("{}\\", base)]
+}
+
+// Ruta completa de una carpeta a partir de su ruta relativa a la raíz
+fn folder_path(root: &str, prefix: &str) -> String {
+    let separator = if root.contains('\\') { "\\" } else { "/" };
+    let base = root.trim_end_matches(['/', '\\']);
+    if prefix.is_empty() {
+        format!("{}{}", base, separator)
+    } else {
+        format!("{}{}{}", base, separator, prefix.replace('/', separator))
+    }
+}
+
+struct TreeBuilder<'a> {
+    db: &'a Database,
+    activity_id: i64,
+    root: String,
+    budget: usize,
+    truncated: bool,
+}
+
+impl TreeBuilder<'_> {
+    fn children(&mut self, folder: &str, depth: u32) -> Result<Vec<TreeNode>, String> {
+        let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+        let snapshots = self
+            .db
+            .get_directory_children(self.activity_id, &directory_keys(folder))
+            .map_err(db_err)?;
+
+        let keys: Vec<String> = snapshots
+            .iter()
+            .filter(|s| s.is_folder)
+            .flat_map(|s| directory_keys(&s.file_path))
+            .collect();
+        let mut counts = HashMap::new();
+        for chunk in keys.chunks(PATHS_PER_QUERY) {
+            counts.extend(self.db.count_directory_children(self.activity_id, chunk).map_err(db_err)?);
+        }
+
+        let mut nodes: Vec<TreeNode> = snapshots
+            .into_iter()
+            .map(|s| {
+                let child_count = if s.is_folder {
+                    directory_keys(&s.file_path).iter().filter_map(|k| counts.get(k)).sum()
+                } else {
+                    0
+                };
+                TreeNode {
+                    name: s.file_name,
+                    path: relative_path(&s.file_path, &self.root),
+                    full_path: s.file_path,
+                    is_folder: s.is_folder,
+                    size: s.file_size,
+                    sha256: s.sha256,
+                    child_count,
+                    children: None,
+                }
+            })
+            .collect();
+
+        if depth > 1 {
+            for node in nodes.iter_mut().filter(|n| n.is_folder) {
+                let count = node.child_count as usize;
+                if count == 0 {
+                    node.children = Some(Vec::new());
+                    continue;
+                }
+                // Sin sitio para la carpeta entera: se queda sin cargar en lugar de a medias
+                if count > self.budget {
+                    self.truncated = true;
+                    continue;
+                }
+                self.budget -= count;
+                node.children = Some(self.children(&node.full_path, depth - 1)?);
+            }
+        }
+        Ok(nodes)
+    }
+}
+
+/// Contenido conocido de una unidad (su último escaneo) como árbol de carpetas que se expande por partes:
+/// devuelve los hijos de `path_prefix` (relativo a la raíz, vacío para la raíz) hasta `depth` niveles.
+/// Las carpetas sin cargar llevan `children: null` y se piden con su `path`.
+#[tauri::command]
+pub async fn get_device_tree(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+    path_prefix: Option<String>,
+    depth: Option<u32>,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let scan = db
+        .get_latest_scan(&device_id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("No scans found for device {}", device_id))?;
+
+    // Escaneos antiguos sin punto de montaje: la raíz se deduce de la lista completa
+    let root = match scan.mount_point {
+        Some(ref mount_point) => mount_point.clone(),
+        None => {
+            let snapshots = db
+                .get_file_snapshots(scan.activity_log_id)
+                .map_err(|e| format!("Database error: {}", e))?;
+            scan_root(&scan, &snapshots)
+        }
+    };
+
+    let prefix = path_prefix.unwrap_or_default().replace('\\', "/").trim_matches('/').to_string();
+    let depth = depth.unwrap_or(1).clamp(1, MAX_DEPTH);
+    let folder = folder_path(&root, &prefix);
+
+    let mut builder = TreeBuilder {
+        db: &db,
+        activity_id: scan.activity_log_id,
+        root,
+        budget: MAX_NESTED_NODES,
+        truncated: false,
+    };
+    let children = builder.children(&folder, depth)?;
+
+    Ok(serde_json::json!({
+        "success": true,
+        "device_id": device_id,
+        "activity_id": scan.activity_log_id,
+        "timestamp": scan.timestamp,
+        "path_prefix": prefix,
+        "depth": depth,
+        "children": children,
+        "truncated": builder.truncated,
+    }))
+}
@@ -0,0 +1,228 @@
+use notify::{RecursiveMode, Watcher};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use sysinfo::Disks;
+use tauri::{AppHandle, Manager};
+use crate::db::Database;
+use crate::state::AppState;
+use crate::usb_backend::is_removable_drive;
+
+// Espacio libre por debajo del cual se avisa o se da por fallida la comprobación
+const DISK_WARN_BYTES: u64 = 500 * 1024 * 1024;
+const DISK_FAIL_BYTES: u64 = 50 * 1024 * 1024;
+const WATCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Resultado de una comprobación del autodiagnóstico
+#[derive(Debug, Serialize, Clone)]
+pub struct DiagnosticCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl DiagnosticCheck {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self { name, status, detail: detail.into() }
+    }
+}
+
+// libusb cargado y capaz de enumerar dispositivos
+fn check_libusb() -> DiagnosticCheck {
+    let version = rusb::version();
+    let version = format!("{}.{}.{}", version.major(), version.minor(), version.micro());
+
+    match rusb::Context::new().and_then(rusb::DeviceList::new_with_context) {
+        Ok(list) => DiagnosticCheck::new(
+            "libusb",
+            CheckStatus::Pass,
+            format!("libusb {} enumerated {} USB devices", version, list.len()),
+        ),
+        Err(e) => DiagnosticCheck::new("libusb", CheckStatus::Fail, format!("libusb {} unavailable: {}", version, e)),
+    }
+}
+
+// Sin permiso para abrir los dispositivos no se leen los números de serie
+fn check_usb_permissions() -> DiagnosticCheck {
+    let list = match rusb::Context::new().and_then(rusb::DeviceList::new_with_context) {
+        Ok(list) => list,
+        Err(e) => return DiagnosticCheck::new("usb_permissions", CheckStatus::Fail, format!("Cannot list USB devices: {}", e)),
+    };
+
+    let total = list.len();
+    let denied = list
+        .iter()
+        .filter(|device| matches!(device.open(), Err(rusb::Error::Access)))
+        .count();
+
+    if denied == 0 {
+        DiagnosticCheck::new("usb_permissions", CheckStatus::Pass, format!("All {} USB devices can be opened", total))
+    } else {
+        DiagnosticCheck::new(
+            "usb_permissions",
+            CheckStatus::Warn,
+            format!("Access denied to {} of {} USB devices; names and serial numbers may be missing", denied, total),
+        )
+    }
+}
+
+fn check_database(db: &Database) -> Vec<DiagnosticCheck> {
+    // Sin archivo no hay nada que comprobar: lo guardado se pierde al cerrar hasta `retry_db_init`
+    if db.is_in_memory() {
+        return vec![DiagnosticCheck::new("database", CheckStatus::Fail, "Database could not be opened, running in memory")];
+    }
+    let path = db.path().to_string_lossy().to_string();
+
+    let writable = match db.check_writable() {
+        Ok(_) => DiagnosticCheck::new("database_writable", CheckStatus::Pass, format!("{} is writable", path)),
+        Err(e) => DiagnosticCheck::new("database_writable", CheckStatus::Fail, format!("Cannot write to {}: {}", path, e)),
+    };
+    let integrity = match db.quick_check() {
+        Ok(result) if result == "ok" => DiagnosticCheck::new("database_integrity", CheckStatus::Pass, "quick_check ok"),
+        Ok(result) => DiagnosticCheck::new("database_integrity", CheckStatus::Fail, result),
+        Err(e) => DiagnosticCheck::new("database_integrity", CheckStatus::Fail, e.to_string()),
+    };
+
+    vec![writable, integrity]
+}
+
+// Crear un archivo en una carpeta temporal vigilada y esperar el evento del watcher
+fn check_file_watch() -> DiagnosticCheck {
+    let dir = std::env::temp_dir().join(format!("usb-manager-diagnostics-{}", std::process::id()));
+    let result = watch_test(&dir);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    match result {
+        Ok(true) => DiagnosticCheck::new("file_watch", CheckStatus::Pass, "File system events are delivered"),
+        Ok(false) => DiagnosticCheck::new(
+            "file_watch",
+            CheckStatus::Fail,
+            format!("No file system event received within {}s", WATCH_TIMEOUT.as_secs()),
+        ),
+        Err(e) => DiagnosticCheck::new("file_watch", CheckStatus::Fail, e),
+    }
+}
+
+fn watch_test(dir: &Path) -> Result<bool, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create temp dir: {}", e))?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if event.kind.is_create() || event.kind.is_modify() {
+                let _ = tx.send(());
+            }
+        }
+    })
+    .map_err(|e| format!("Cannot create watcher: {}", e))?;
+    watcher
+        .watch(dir, RecursiveMode::Recursive)
+        .map_err(|e| format!("Cannot watch temp dir: {}", e))?;
+
+    std::fs::write(dir.join("probe.txt"), b"usb-manager").map_err(|e| format!("Cannot write temp file: {}", e))?;
+    Ok(rx.recv_timeout(WATCH_TIMEOUT).is_ok())
+}
+
+// Espacio libre en el disco que contiene los datos de la app (el punto de montaje más largo que la contiene)
+fn check_disk_space(data_dir: &Path) -> DiagnosticCheck {
+    let disks = Disks::new_with_refreshed_list();
+    let disk = disks
+        .iter()
+        .filter(|disk| data_dir.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len());
+
+    let disk = match disk {
+        Some(disk) => disk,
+        None => return DiagnosticCheck::new("disk_space", CheckStatus::Warn, format!("No disk found for {}", data_dir.display())),
+    };
+
+    let available = disk.available_space();
+    let detail = format!(
+        "{} MB free on {}",
+        available / (1024 * 1024),
+        disk.mount_point().display()
+    );
+    let status = if available < DISK_FAIL_BYTES {
+        CheckStatus::Fail
+    } else if available < DISK_WARN_BYTES {
+        CheckStatus::Warn
+    } else {
+        CheckStatus::Pass
+    };
+    DiagnosticCheck::new("disk_space", status, detail)
+}
+
+// La carpeta de datos debe admitir escritura (perfiles, exportaciones programadas...)
+fn check_data_dir(data_dir: &Path) -> DiagnosticCheck {
+    let probe = data_dir.join(".diagnostics");
+    match std::fs::write(&probe, b"ok").and_then(|_| std::fs::remove_file(&probe)) {
+        Ok(_) => DiagnosticCheck::new("data_dir_permissions", CheckStatus::Pass, format!("{} is writable", data_dir.display())),
+        Err(e) => DiagnosticCheck::new(
+            "data_dir_permissions",
+            CheckStatus::Fail,
+            format!("Cannot write to {}: {}", data_dir.display(), e),
+        ),
+    }
+}
+
+// Las unidades extraíbles montadas deben poder leerse para escanearlas
+fn check_removable_mounts() -> DiagnosticCheck {
+    let disks = Disks::new_with_refreshed_list();
+    let mounts: Vec<PathBuf> = disks
+        .iter()
+        .filter(|disk| is_removable_drive(disk))
+        .map(|disk| disk.mount_point().to_path_buf())
+        .collect();
+
+    if mounts.is_empty() {
+        return DiagnosticCheck::new("removable_mounts", CheckStatus::Warn, "No removable drives are mounted");
+    }
+
+    let unreadable: Vec<String> = mounts
+        .iter()
+        .filter(|mount| std::fs::read_dir(mount).is_err())
+        .map(|mount| mount.display().to_string())
+        .collect();
+
+    if unreadable.is_empty() {
+        DiagnosticCheck::new("removable_mounts", CheckStatus::Pass, format!("{} removable drives readable", mounts.len()))
+    } else {
+        DiagnosticCheck::new("removable_mounts", CheckStatus::Fail, format!("Cannot read: {}", unreadable.join(", ")))
+    }
+}
+
+/// Autodiagnóstico para soporte ("no aparece nada"): cada comprobación devuelve pass/warn/fail con detalle
+#[tauri::command]
+pub async fn run_diagnostics(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    let db = state.db.clone();
+    let checks = tauri::async_runtime::spawn_blocking(move || {
+        let mut checks = vec![check_libusb(), check_usb_permissions()];
+        checks.extend(check_database(&db));
+        checks.push(check_file_watch());
+        checks.push(check_disk_space(&data_dir));
+        checks.push(check_data_dir(&data_dir));
+        checks.push(check_removable_mounts());
+        checks
+    })
+    .await
+    .map_err(|e| format!("Diagnostics failed: {}", e))?;
+
+    let failed = checks.iter().filter(|c| c.status == CheckStatus::Fail).count();
+    println!("[Diagnostics] {} checks, {} failed", checks.len(), failed);
+
+    Ok(serde_json::json!({ "success": true, "passed": failed == 0, "checks": checks }))
+}
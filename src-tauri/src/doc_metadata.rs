@@ -0,0 +1,209 @@
+use lopdf::{Document, Object};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use crate::db::{Database, DocumentMetadata, FileSnapshot};
+use crate::state::AppState;
+
+// Documentos más grandes se saltan: el PDF se carga entero para leer su diccionario Info
+const MAX_DOCUMENT_BYTES: i64 = 64 * 1024 * 1024;
+// Partes XML de metadatos dentro de un documento comprimido
+const MAX_XML_BYTES: u64 = 1024 * 1024;
+// Documentos por transacción al guardar
+const SAVE_BATCH: usize = 500;
+const DEFAULT_LIMIT: i64 = 500;
+
+const OFFICE_EXTENSIONS: &[&str] = &["docx", "docm", "xlsx", "xlsm", "pptx", "pptm"];
+const OPENDOCUMENT_EXTENSIONS: &[&str] = &["odt", "ods", "odp"];
+
+// Texto del primer elemento `tag` (con prefijo, p.ej. "dc:creator"), sin entidades XML
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}", tag);
+    let mut rest = xml;
+    loop {
+        let start = rest.find(&open)? + open.len();
+        rest = &rest[start..];
+        // "<dc:creator>" o "<dc:creator xml:lang=...>", no "<dc:creatorX>"
+        if rest.starts_with(['>', ' ', '\t', '\r', '\n']) {
+            break;
+        }
+    }
+    let content_start = rest.find('>')? + 1;
+    if rest[..content_start].ends_with("/>") {
+        return None;
+    }
+    let content = &rest[content_start..];
+    let end = content.find(&format!("</{}>", tag))?;
+    let text = content[..end]
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn read_zip_entry(archive: &mut zip::ZipArchive<File>, name: &str) -> Option<String> {
+    let entry = archive.by_name(name).ok()?;
+    let mut xml = String::new();
+    entry.take(MAX_XML_BYTES).read_to_string(&mut xml).ok()?;
+    Some(xml)
+}
+
+// Office Open XML: docProps/core.xml (autor, título, fechas) y docProps/app.xml (empresa, programa)
+fn extract_office(path: &Path, document: &mut DocumentMetadata) -> Option<()> {
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    if let Some(core) = read_zip_entry(&mut archive, "docProps/core.xml") {
+        document.author = xml_text(&core, "dc:creator");
+        document.title = xml_text(&core, "dc:title");
+        document.last_saved_by = xml_text(&core, "cp:lastModifiedBy");
+        document.created = xml_text(&core, "dcterms:created");
+        document.modified = xml_text(&core, "dcterms:modified");
+    }
+    if let Some(app) = read_zip_entry(&mut archive, "docProps/app.xml") {
+        document.company = xml_text(&app, "Company");
+        document.application = xml_text(&app, "Application");
+    }
+    Some(())
+}
+
+// OpenDocument: meta.xml (el autor original es initial-creator; dc:creator es quien lo guardó por última vez)
+fn extract_opendocument(path: &Path, document: &mut DocumentMetadata) -> Option<()> {
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    let meta = read_zip_entry(&mut archive, "meta.xml")?;
+    document.author = xml_text(&meta, "meta:initial-creator");
+    document.title = xml_text(&meta, "dc:title");
+    document.last_saved_by = xml_text(&meta, "dc:creator");
+    document.application = xml_text(&meta, "meta:generator");
+    document.created = xml_text(&meta, "meta:creation-date");
+    document.modified = xml_text(&meta, "dc:date");
+    Some(())
+}
+
+// Cadena de texto PDF: UTF-16BE con BOM o PDFDocEncoding (compatible con Latin-1 en lo habitual)
+fn pdf_text(object: &Object) -> Option<String> {
+    let Object::String(bytes, _) = object else { return None };
+    let text = match bytes.strip_prefix(&[0xFE, 0xFF]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|&b| b as char).collect(),
+    };
+    let text = text.trim_matches(char::from(0)).trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+// "D:20240131123000+01'00'" -> "2024-01-31T12:30:00"
+fn pdf_date(value: String) -> String {
+    let digits: String = value.trim_start_matches("D:").chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 14 {
+        return value;
+    }
+    format!(
+        "{}-{}-{}T{}:{}:{}",
+        &digits[0..4],
+        &digits[4..6],
+        &digits[6..8],
+        &digits[8..10],
+        &digits[10..12],
+        &digits[12..14]
+    )
+}
+
+// PDF: diccionario Info del trailer
+fn extract_pdf(path: &Path, document: &mut DocumentMetadata) -> Option<()> {
+    let pdf = Document::load(path).ok()?;
+    let info = match pdf.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => pdf.get_dictionary(*id).ok()?,
+        Object::Dictionary(info) => info,
+        _ => return None,
+    };
+    let field = |key: &[u8]| info.get(key).ok().and_then(pdf_text);
+    document.author = field(b"Author");
+    document.title = field(b"Title");
+    document.application = field(b"Creator").or_else(|| field(b"Producer"));
+    document.created = field(b"CreationDate").map(pdf_date);
+    document.modified = field(b"ModDate").map(pdf_date);
+    Some(())
+}
+
+/// Metadatos internos de un documento del escaneo; None si no es un tipo compatible,
+/// no se puede leer o no tiene ninguno
+pub fn extract(snapshot: &FileSnapshot) -> Option<DocumentMetadata> {
+    if snapshot.is_folder || snapshot.file_size > MAX_DOCUMENT_BYTES {
+        return None;
+    }
+    let extension = snapshot.file_extension.as_deref()?.to_lowercase();
+    let path = Path::new(&snapshot.file_path);
+    let mut document = DocumentMetadata {
+        snapshot_id: snapshot.id?,
+        activity_log_id: snapshot.activity_log_id,
+        file_path: snapshot.file_path.clone(),
+        ..Default::default()
+    };
+
+    if OFFICE_EXTENSIONS.contains(&extension.as_str()) {
+        extract_office(path, &mut document)?;
+    } else if OPENDOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+        extract_opendocument(path, &mut document)?;
+    } else if extension == "pdf" {
+        extract_pdf(path, &mut document)?;
+    } else {
+        return None;
+    }
+
+    let empty = document.author.is_none()
+        && document.title.is_none()
+        && document.company.is_none()
+        && document.last_saved_by.is_none()
+        && document.application.is_none();
+    (!empty).then_some(document)
+}
+
+/// Extraer y guardar los metadatos de los documentos de un escaneo recién terminado (con la unidad montada)
+pub fn extract_scan(db: &Arc<Database>, activity_id: i64) {
+    let snapshots = match db.get_file_snapshots(activity_id) {
+        Ok(snapshots) => snapshots,
+        Err(e) => {
+            println!("[DB] Error loading snapshots for document metadata: {}", e);
+            return;
+        }
+    };
+
+    let mut batch = Vec::new();
+    let mut extracted = 0;
+    for document in snapshots.iter().filter_map(extract) {
+        batch.push(document);
+        if batch.len() == SAVE_BATCH {
+            extracted += batch.len();
+            if let Err(e) = db.insert_document_metadata(&std::mem::take(&mut batch)) {
+                println!("[DB] Error saving document metadata: {}", e);
+                return;
+            }
+        }
+    }
+    extracted += batch.len();
+    if let Err(e) = db.insert_document_metadata(&batch) {
+        println!("[DB] Error saving document metadata: {}", e);
+        return;
+    }
+    println!("[DocMetadata] Extracted metadata from {} documents of scan {}", extracted, activity_id);
+}
+
+/// Metadatos de documentos de un escaneo (o de todos), opcionalmente de una persona (autor o último en guardar)
+#[tauri::command]
+pub async fn get_document_metadata(
+    state: tauri::State<'_, AppState>,
+    activity_log_id: Option<i64>,
+    person: Option<String>,
+    limit: Option<i64>,
+) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_document_metadata(activity_log_id, person.as_deref(), limit.unwrap_or(DEFAULT_LIMIT)) {
+        Ok(documents) => Ok(serde_json::json!({ "success": true, "documents": documents })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
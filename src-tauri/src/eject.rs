@@ -0,0 +1,175 @@
+use crate::audit;
+use crate::removal;
+use crate::state::AppState;
+use crate::usb_monitor::UsbMonitor;
+
+const EJECTED_AUDIT_ACTION: &str = "DEVICE_EJECTED";
+
+/// Expulsar de forma segura un dispositivo conectado: desmontar su volumen para poder retirarlo.
+/// La desconexión la registra el monitor en el siguiente ciclo, como desconexión limpia.
+pub fn eject(monitor: &UsbMonitor, device_id: &str) -> Result<(), String> {
+    let mount_point = monitor
+        .mount_point_for(device_id)
+        .ok_or_else(|| format!("Device is not connected: {}", device_id))?;
+
+    // El watcher tiene abierto el volumen y lo bloquearía
+    monitor.active_watchers.lock().unwrap().remove(device_id);
+
+    eject_volume(&mount_point)?;
+    removal::note_eject(device_id);
+    println!("[Eject] Ejected {} ({})", device_id, mount_point);
+
+    audit::record(&monitor.db, EJECTED_AUDIT_ACTION, Some(device_id), &serde_json::json!({ "mount_point": mount_point }));
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn eject_device(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let monitor = state.monitor.clone();
+    tauri::async_runtime::spawn_blocking(move || eject(&monitor, &device_id))
+        .await
+        .map_err(|e| format!("Eject task failed: {}", e))??;
+    Ok(serde_json::json!({ "success": true }))
+}
+
+#[cfg(windows)]
+fn eject_volume(mount_point: &str) -> Result<(), String> {
+    windows::Volume::open(mount_point)
+        .ok_or_else(|| format!("Failed to open volume {}", mount_point))?
+        .eject()
+}
+
+// diskutil desmonta todos los volúmenes del disco y lo expulsa
+#[cfg(target_os = "macos")]
+fn eject_volume(mount_point: &str) -> Result<(), String> {
+    run("diskutil", &["eject", mount_point])
+}
+
+// Con udisks (sin root) se desmonta y se apaga el dispositivo; si no está, umount
+#[cfg(all(unix, not(target_os = "macos")))]
+fn eject_volume(mount_point: &str) -> Result<(), String> {
+    match block_device(mount_point) {
+        Some(device) if run("udisksctl", &["unmount", "-b", &device]).is_ok() => {
+            if let Err(e) = run("udisksctl", &["power-off", "-b", &device]) {
+                println!("[Eject] {} unmounted but not powered off: {}", device, e);
+            }
+            Ok(())
+        }
+        _ => run("umount", &[mount_point]),
+    }
+}
+
+// Dispositivo de bloque montado en `mount_point` según /proc/mounts (los espacios vienen como \040)
+#[cfg(all(unix, not(target_os = "macos")))]
+fn block_device(mount_point: &str) -> Option<String> {
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    let target = mount_point.trim_end_matches('/');
+    mounts.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let device = fields.next()?;
+        let mounted_on = fields.next()?.replace("\\040", " ");
+        (mounted_on == target && device.starts_with("/dev/")).then(|| device.to_string())
+    })
+}
+
+#[cfg(unix)]
+fn run(program: &str, args: &[&str]) -> Result<(), String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use windows_sys::Win32::Foundation::{CloseHandle, GENERIC_READ, GENERIC_WRITE, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{CreateFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING};
+    use windows_sys::Win32::System::Ioctl::{
+        FSCTL_DISMOUNT_VOLUME, FSCTL_LOCK_VOLUME, IOCTL_STORAGE_EJECT_MEDIA, IOCTL_STORAGE_MEDIA_REMOVAL,
+    };
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    const LOCK_ATTEMPTS: u32 = 10;
+    const LOCK_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+    pub struct Volume(HANDLE);
+
+    impl Drop for Volume {
+        fn drop(&mut self) {
+            // SAFETY: el handle se abrió en `open` y solo se cierra aquí
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    impl Volume {
+        // "E:\" -> \\.\E:
+        pub fn open(mount_point: &str) -> Option<Self> {
+            let letter = mount_point.chars().next().filter(|c| c.is_ascii_alphabetic())?;
+            if mount_point.chars().nth(1) != Some(':') {
+                return None;
+            }
+
+            let path: Vec<u16> = format!("\\\\.\\{}:", letter).encode_utf16().chain(std::iter::once(0)).collect();
+            // SAFETY: `path` termina en NUL; sin atributos de seguridad ni plantilla
+            let handle = unsafe {
+                CreateFileW(
+                    path.as_ptr(),
+                    GENERIC_READ | GENERIC_WRITE,
+                    FILE_SHARE_READ | FILE_SHARE_WRITE,
+                    std::ptr::null(),
+                    OPEN_EXISTING,
+                    0,
+                    std::ptr::null_mut(),
+                )
+            };
+            (handle != INVALID_HANDLE_VALUE).then_some(Volume(handle))
+        }
+
+        fn control(&self, code: u32, input: &[u8]) -> bool {
+            let mut returned = 0u32;
+            // SAFETY: sin búfer de salida; la entrada se pasa con su tamaño real
+            let ok = unsafe {
+                DeviceIoControl(
+                    self.0,
+                    code,
+                    if input.is_empty() { std::ptr::null() } else { input.as_ptr() as *const _ },
+                    input.len() as u32,
+                    std::ptr::null_mut(),
+                    0,
+                    &mut returned,
+                    std::ptr::null_mut(),
+                )
+            };
+            ok != 0
+        }
+
+        // Bloquear (reintentando mientras otro programa suelta sus archivos), desmontar y expulsar
+        pub fn eject(&self) -> Result<(), String> {
+            let mut locked = false;
+            for _ in 0..LOCK_ATTEMPTS {
+                if self.control(FSCTL_LOCK_VOLUME, &[]) {
+                    locked = true;
+                    break;
+                }
+                std::thread::sleep(LOCK_RETRY_DELAY);
+            }
+            if !locked {
+                return Err("The volume is in use by another program".to_string());
+            }
+            if !self.control(FSCTL_DISMOUNT_VOLUME, &[]) {
+                return Err("Failed to dismount the volume".to_string());
+            }
+            // PREVENT_MEDIA_REMOVAL { PreventMediaRemoval: FALSE }
+            self.control(IOCTL_STORAGE_MEDIA_REMOVAL, &[0u8]);
+            if !self.control(IOCTL_STORAGE_EJECT_MEDIA, &[]) {
+                return Err("The volume was dismounted but the device could not be ejected".to_string());
+            }
+            Ok(())
+        }
+    }
+}
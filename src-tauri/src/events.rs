@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use crate::overlay;
+
+// Ventana de limitación por tipo de evento
+const WINDOW: Duration = Duration::from_secs(1);
+/// Evento con el resumen de lo que no se ha enviado en una ventana
+pub const COALESCED_EVENT: &str = "events-coalesced";
+// Eventos recientes que se guardan para reenviarlos a una interfaz recargada
+const REPLAY_CAPACITY: usize = 500;
+
+// Eventos por ventana que llegan tal cual a la interfaz; el resto se agrupa
+fn limit_for(event: &str) -> usize {
+    match event {
+        "file-copy-detected" => 10,
+        "usb-connected" | "usb-disconnected" => 20,
+        _ => 30,
+    }
+}
+
+struct Bucket {
+    window_start: Instant,
+    sent: usize,
+    suppressed: usize,
+    // Último evento descartado, para que la interfaz pueda mostrar algo concreto
+    last: Option<serde_json::Value>,
+    flush_scheduled: bool,
+}
+
+impl Bucket {
+    fn new() -> Self {
+        Self { window_start: Instant::now(), sent: 0, suppressed: 0, last: None, flush_scheduled: false }
+    }
+}
+
+static BUCKETS: OnceLock<Mutex<HashMap<String, Bucket>>> = OnceLock::new();
+
+fn buckets() -> &'static Mutex<HashMap<String, Bucket>> {
+    BUCKETS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Evento ya emitido, con su número de secuencia
+#[derive(Debug, Serialize, Clone)]
+pub struct BufferedEvent {
+    pub seq: u64,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+struct Replay {
+    last_seq: u64,
+    events: VecDeque<BufferedEvent>,
+}
+
+static REPLAY: OnceLock<Mutex<Replay>> = OnceLock::new();
+
+fn replay() -> &'static Mutex<Replay> {
+    REPLAY.get_or_init(|| Mutex::new(Replay { last_seq: 0, events: VecDeque::with_capacity(REPLAY_CAPACITY) }))
+}
+
+// Guardar el evento en el búfer de reenvío y devolver su número de secuencia
+fn record(event: &str, payload: &serde_json::Value) -> u64 {
+    let mut replay = replay().lock().unwrap();
+    replay.last_seq += 1;
+    let seq = replay.last_seq;
+
+    if replay.events.len() == REPLAY_CAPACITY {
+        replay.events.pop_front();
+    }
+    replay.events.push_back(BufferedEvent {
+        seq,
+        event: event.to_string(),
+        payload: payload.clone(),
+        timestamp: Utc::now(),
+    });
+    seq
+}
+
+/// Emitir un evento a la interfaz. En ráfagas (tormentas de descriptores, copias enormes) los eventos
+/// que superan el límite de su tipo no se envían: al cerrar la ventana se emite un único `events-coalesced`.
+/// Todos quedan en el búfer de reenvío; los objetos llevan además un campo `seq`.
+pub fn emit<S: Serialize>(app_handle: &AppHandle, event: &str, payload: S) {
+    let mut payload = serde_json::to_value(&payload).unwrap_or_default();
+    let seq = record(event, &payload);
+    overlay::push(app_handle, event, &payload);
+    if let Some(fields) = payload.as_object_mut() {
+        fields.insert("seq".to_string(), serde_json::json!(seq));
+    }
+
+    let mut guard = buckets().lock().unwrap();
+    let bucket = guard.entry(event.to_string()).or_insert_with(Bucket::new);
+
+    // Con eventos pendientes, la ventana la reinicia el resumen
+    if bucket.suppressed == 0 && bucket.window_start.elapsed() >= WINDOW {
+        bucket.window_start = Instant::now();
+        bucket.sent = 0;
+    }
+
+    if bucket.sent < limit_for(event) {
+        bucket.sent += 1;
+        drop(guard);
+        let _ = app_handle.emit(event, payload);
+        return;
+    }
+
+    bucket.suppressed += 1;
+    bucket.last = Some(payload);
+    if !bucket.flush_scheduled {
+        bucket.flush_scheduled = true;
+        let delay = WINDOW.saturating_sub(bucket.window_start.elapsed());
+        tauri::async_runtime::spawn(flush(app_handle.clone(), event.to_string(), delay));
+    }
+}
+
+async fn flush(app_handle: AppHandle, event: String, delay: Duration) {
+    tokio::time::sleep(delay).await;
+
+    let (suppressed, last) = {
+        let mut guard = buckets().lock().unwrap();
+        let bucket = match guard.get_mut(&event) {
+            Some(bucket) => bucket,
+            None => return,
+        };
+        let pending = (bucket.suppressed, bucket.last.take());
+        *bucket = Bucket::new();
+        pending
+    };
+
+    println!("[Events] Coalesced {} '{}' events", suppressed, event);
+    let _ = app_handle.emit(
+        COALESCED_EVENT,
+        serde_json::json!({
+            "event": event,
+            "count": suppressed,
+            "window_ms": WINDOW.as_millis() as u64,
+            "last": last,
+        }),
+    );
+}
+
+/// Eventos emitidos después de `seq`, para que una interfaz recargada o que ha tardado en cargar se ponga al día.
+/// `truncated` indica que algunos ya no están en el búfer y conviene recargar la vista completa.
+#[tauri::command]
+pub async fn get_events_since(seq: u64, events: Option<Vec<String>>) -> Result<serde_json::Value, String> {
+    let replay = replay().lock().unwrap();
+
+    let truncated = replay.events.front().map(|first| first.seq > seq + 1).unwrap_or(false);
+    let missed: Vec<&BufferedEvent> = replay
+        .events
+        .iter()
+        .filter(|e| e.seq > seq)
+        .filter(|e| events.as_ref().map(|names| names.contains(&e.event)).unwrap_or(true))
+        .collect();
+
+    Ok(serde_json::json!({
+        "success": true,
+        "events": missed,
+        "latest_seq": replay.last_seq,
+        "truncated": truncated,
+    }))
+}
@@ -1,13 +1,127 @@
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
-use crate::db::{FileSnapshot, Database};
+use serde::{Deserialize, Serialize};
+use crate::db::{FileSnapshot, Database, HashMode, ScanCheckpoint};
+use crate::hashing::{sha256_file, sha256_sampled};
+use crate::privacy;
+use crate::usn::{self, JournalPosition};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const SCAN_SETTINGS_KEY: &str = "scan";
+pub const DEFAULT_MAX_CONCURRENT_SCANS: usize = 2;
+const DEFAULT_REUSE_WINDOW_SECS: u64 = 60;
+// Entradas por lote guardado en un escaneo completo; tras cada lote se guarda el punto de control
+const SCAN_BATCH_SIZE: usize = 1000;
+pub const DEFAULT_HASH_SAMPLE_MB: u64 = 16;
+const MB: u64 = 1024 * 1024;
+
+/// Opciones del escaneo guardadas en settings bajo la clave `scan`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanSettings {
+    /// Calcular SHA-256 de cada archivo (más lento, pero permite comparar contenidos)
+    pub hash_files: bool,
+    /// Escaneos a la vez; los demás esperan en cola (p.ej. varias memorias en un hub)
+    pub max_concurrent_scans: Option<usize>,
+    /// No calcular el hash de archivos de más de estos MB
+    pub hash_max_file_mb: Option<u64>,
+    /// Archivos de más de estos MB se hashean por muestra: los primeros y los últimos `hash_sample_mb`
+    pub hash_sample_above_mb: Option<u64>,
+    pub hash_sample_mb: Option<u64>,
+    /// Tiempo máximo de hash por escaneo; al agotarse, el resto de archivos queda sin hash
+    pub hash_time_limit_secs: Option<u64>,
+    /// Extraer tras el escaneo los metadatos de los documentos Office, OpenDocument y PDF (autor, título, empresa...)
+    pub extract_document_metadata: bool,
+    /// Enviar a la interfaz cada lote guardado como evento `usb-scan-batch` para ir llenando la lista durante el escaneo
+    pub stream_batches: bool,
+    /// Si el dispositivo se reconecta antes de estos segundos con el mismo volumen (serie, capacidad y espacio usado),
+    /// se reutiliza el escaneo anterior en lugar de escanear otra vez; 0 lo desactiva
+    pub reuse_window_secs: Option<u64>,
+}
+
+impl ScanSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(SCAN_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.max_concurrent_scans.unwrap_or(DEFAULT_MAX_CONCURRENT_SCANS).max(1)
+    }
+
+    pub fn reuse_window_secs(&self) -> u64 {
+        self.reuse_window_secs.unwrap_or(DEFAULT_REUSE_WINDOW_SECS)
+    }
+}
+
+/// Hash de los archivos de un escaneo con los límites de `ScanSettings`. Lleva la cuenta del tiempo gastado.
+pub struct ScanHasher {
+    enabled: bool,
+    max_file_bytes: Option<u64>,
+    sample_above_bytes: Option<u64>,
+    sample_bytes: u64,
+    time_limit: Option<Duration>,
+    spent_micros: AtomicU64,
+    limit_reached: AtomicBool,
+}
+
+impl ScanHasher {
+    pub fn new(settings: &ScanSettings) -> Self {
+        ScanHasher {
+            enabled: settings.hash_files,
+            max_file_bytes: settings.hash_max_file_mb.map(|mb| mb * MB),
+            sample_above_bytes: settings.hash_sample_above_mb.map(|mb| mb * MB),
+            sample_bytes: settings.hash_sample_mb.unwrap_or(DEFAULT_HASH_SAMPLE_MB).max(1) * MB,
+            time_limit: settings.hash_time_limit_secs.map(Duration::from_secs),
+            spent_micros: AtomicU64::new(0),
+            limit_reached: AtomicBool::new(false),
+        }
+    }
+
+    /// Hash de un archivo y cómo se calculó. Sin hash si está desactivado o no se puede leer el archivo.
+    pub fn hash(&self, path: &Path, size: u64) -> (Option<String>, Option<HashMode>) {
+        if !self.enabled {
+            return (None, None);
+        }
+        if self.max_file_bytes.is_some_and(|max| size > max) {
+            return (None, Some(HashMode::Skipped));
+        }
+        if let Some(limit) = self.time_limit {
+            if Duration::from_micros(self.spent_micros.load(Ordering::Relaxed)) >= limit {
+                if !self.limit_reached.swap(true, Ordering::Relaxed) {
+                    println!("[Scanner] Hashing time limit reached ({}s); remaining files are not hashed", limit.as_secs());
+                }
+                return (None, Some(HashMode::Skipped));
+            }
+        }
+
+        // Por muestra solo si el archivo es mayor que las dos muestras juntas
+        let sampled = self.sample_above_bytes.is_some_and(|above| size > above) && size > self.sample_bytes * 2;
+        let started = Instant::now();
+        let result = if sampled {
+            sha256_sampled(path, self.sample_bytes)
+        } else {
+            sha256_file(path)
+        };
+        self.spent_micros.fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+
+        match result {
+            Ok(hash) => (Some(hash), Some(if sampled { HashMode::Sampled } else { HashMode::Full })),
+            Err(e) => {
+                println!("[Scanner] Error hashing {:?}: {}", path, e);
+                (None, None)
+            }
+        }
+    }
+}
 
 pub struct FileScanner;
 
 impl FileScanner {
     /// Escanear un directorio recursivamente y devolver los snapshots
-    pub fn scan_directory(mount_point: &str, activity_log_id: i64) -> Vec<FileSnapshot> {
+    pub fn scan_directory(mount_point: &str, activity_log_id: i64, hasher: &ScanHasher) -> Vec<FileSnapshot> {
         let mut snapshots = Vec::new();
         let mount_path = Path::new(mount_point);
         
@@ -31,36 +145,14 @@ impl FileScanner {
         for entry in walker {
             match entry {
                 Ok(entry) => {
-                    let path = entry.path();
                     let metadata = match entry.metadata() {
                         Ok(m) => m,
                         Err(e) => {
-                            println!("[Scanner] Error reading metadata for {:?}: {}", path, e);
+                            println!("[Scanner] Error reading metadata for {:?}: {}", entry.path(), e);
                             continue;
                         }
                     };
-                    
-                    let file_name = path.file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("unknown")
-                        .to_string();
-                    
-                    let file_path = path.to_string_lossy().to_string();
-                    let is_folder = metadata.is_dir();
-                    let file_size = if is_folder { 0 } else { metadata.len() as i64 };
-                    let file_extension = path.extension()
-                        .and_then(|e| e.to_str())
-                        .map(|s| s.to_lowercase());
-                    
-                    snapshots.push(FileSnapshot {
-                        id: None,
-                        activity_log_id,
-                        file_path,
-                        file_name,
-                        file_extension,
-                        file_size,
-                        is_folder,
-                    });
+                    snapshots.push(Self::snapshot_entry(entry.path(), &metadata, activity_log_id, hasher));
                 }
                 Err(e) => {
                     println!("[Scanner] Error accessing entry: {}", e);
@@ -72,36 +164,279 @@ impl FileScanner {
         snapshots
     }
     
-    /// Escanear y guardar directamente en la base de datos en batch
+    // Snapshot de una entrada a partir de sus metadatos
+    fn snapshot_entry(path: &Path, metadata: &std::fs::Metadata, activity_log_id: i64, hasher: &ScanHasher) -> FileSnapshot {
+        let file_path = path.to_string_lossy().to_string();
+        // La raíz del montaje ("E:\") no tiene nombre: se usa la ruta completa
+        let file_name = path.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| file_path.clone());
+        
+        let is_folder = metadata.is_dir();
+        let file_size = if is_folder { 0 } else { metadata.len() as i64 };
+        let file_extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|s| s.to_lowercase());
+        let (sha256, hash_mode) = if is_folder {
+            (None, None)
+        } else {
+            hasher.hash(path, file_size as u64)
+        };
+        
+        FileSnapshot {
+            id: None,
+            activity_log_id,
+            file_path,
+            file_name,
+            file_extension,
+            file_size,
+            is_folder,
+            sha256,
+            hash_mode,
+        }
+    }
+
+    /// Escaneo incremental con el diario USN (NTFS): se parte de los snapshots del escaneo anterior
+    /// y solo se vuelven a leer las carpetas que han cambiado desde entonces.
+    /// `None` si no se puede (sin diario, otro punto de montaje, diario recreado...) y hay que escanear entero.
+    fn scan_incremental(
+        mount_point: &str,
+        device_id: &str,
+        activity_log_id: i64,
+        journal: JournalPosition,
+        hasher: &ScanHasher,
+        db: &Database,
+    ) -> Option<Vec<FileSnapshot>> {
+        let previous = db.get_last_journal_scan(device_id).ok().flatten()?;
+        if previous.mount_point != mount_point {
+            return None;
+        }
+        let dirty = usn::changed_dirs(mount_point, previous.journal, journal)?;
+        let previous_snapshots = db.get_file_snapshots(previous.activity_log_id).ok()?;
+        if previous_snapshots.is_empty() {
+            return None;
+        }
+
+        println!("[Scanner] Incremental scan of {}: {} changed folders", mount_point, dirty.len());
+        let mut entries: BTreeMap<PathBuf, FileSnapshot> = previous_snapshots
+            .into_iter()
+            .map(|snapshot| (PathBuf::from(&snapshot.file_path), FileSnapshot { id: None, activity_log_id, ..snapshot }))
+            .collect();
+
+        for dir in &dirty {
+            if !dir.starts_with(mount_point) {
+                continue;
+            }
+            // Hijos directos que había en la carpeta; las subcarpetas que desaparecen se quitan con todo su contenido
+            let children: Vec<PathBuf> = entries
+                .range(dir.clone()..)
+                .map(|(path, _)| path)
+                .take_while(|path| path.starts_with(dir))
+                .filter(|path| path.parent() == Some(dir.as_path()))
+                .cloned()
+                .collect();
+            let listing = match std::fs::read_dir(dir) {
+                Ok(listing) => listing,
+                Err(_) => {
+                    // La propia carpeta ya no existe: su padre también está en la lista y la quitará
+                    continue;
+                }
+            };
+
+            let mut present = Vec::new();
+            for entry in listing.flatten() {
+                let path = entry.path();
+                let metadata = match std::fs::symlink_metadata(&path) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        println!("[Scanner] Error reading metadata for {:?}: {}", path, e);
+                        continue;
+                    }
+                };
+                // Una carpeta nueva (o renombrada) se recorre entera
+                let known = entries.get(&path).map(|s| s.is_folder).unwrap_or(false);
+                if metadata.is_dir() && !known {
+                    remove_tree(&mut entries, &path);
+                    for snapshot in Self::scan_directory(&path.to_string_lossy(), activity_log_id, hasher) {
+                        entries.insert(PathBuf::from(&snapshot.file_path), snapshot);
+                    }
+                } else {
+                    if !metadata.is_dir() && entries.get(&path).map(|s| s.is_folder).unwrap_or(false) {
+                        remove_tree(&mut entries, &path);
+                    }
+                    entries.insert(path.clone(), Self::snapshot_entry(&path, &metadata, activity_log_id, hasher));
+                }
+                present.push(path);
+            }
+
+            for child in children.into_iter().filter(|child| !present.contains(child)) {
+                remove_tree(&mut entries, &child);
+            }
+        }
+
+        Some(entries.into_values().collect())
+    }
+    
+    /// Escaneo completo guardado por lotes, con un punto de control tras cada uno (y `progress` con el lote). Si `checkpoint`
+    /// viene de un escaneo interrumpido se salta lo que ya se guardó (todo lo anterior a `last_path` en el recorrido).
+    fn scan_full(
+        mount_point: &str,
+        hasher: &ScanHasher,
+        db: &Database,
+        mut checkpoint: ScanCheckpoint,
+        progress: &dyn Fn(&ScanCheckpoint, &[FileSnapshot]),
+    ) -> Result<ScanCheckpoint, String> {
+        if !Path::new(mount_point).is_dir() {
+            println!("[Scanner] Mount point is not a directory: {}", mount_point);
+            return Ok(checkpoint);
+        }
+
+        let resume_after = checkpoint.last_path.clone().map(PathBuf::from);
+        let redact_root = privacy::redacts_paths(db, &checkpoint.device_id).then_some(mount_point);
+        match resume_after {
+            Some(ref last) => println!("[Scanner] Resuming scan of {} after {:?} ({} items saved)", mount_point, last, checkpoint.total_files + checkpoint.total_folders),
+            None => println!("[Scanner] Starting scan of: {}", mount_point),
+        }
+
+        // Ordenado por nombre: es el mismo orden que el de las rutas, así la última guardada marca lo que falta
+        let mut walker = WalkDir::new(mount_point)
+            .follow_links(false)
+            .max_open(100)
+            .sort_by_file_name()
+            .into_iter();
+        let mut batch = Vec::with_capacity(SCAN_BATCH_SIZE);
+
+        while let Some(entry) = walker.next() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    println!("[Scanner] Error accessing entry: {}", e);
+                    continue;
+                }
+            };
+            if let Some(ref last) = resume_after {
+                if entry.path() <= last.as_path() {
+                    // Carpeta ya terminada antes del corte: no hace falta recorrerla
+                    if entry.file_type().is_dir() && !last.starts_with(entry.path()) {
+                        walker.skip_current_dir();
+                    }
+                    continue;
+                }
+            }
+
+            let metadata = match entry.metadata() {
+                Ok(m) => m,
+                Err(e) => {
+                    println!("[Scanner] Error reading metadata for {:?}: {}", entry.path(), e);
+                    continue;
+                }
+            };
+            let snapshot = Self::snapshot_entry(entry.path(), &metadata, checkpoint.activity_log_id, hasher);
+            if snapshot.is_folder {
+                checkpoint.total_folders += 1;
+            } else {
+                checkpoint.total_files += 1;
+            }
+            checkpoint.total_bytes += snapshot.file_size;
+            batch.push(snapshot);
+
+            if batch.len() >= SCAN_BATCH_SIZE {
+                Self::save_batch(db, &batch, &mut checkpoint, redact_root, progress)?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            Self::save_batch(db, &batch, &mut checkpoint, redact_root, progress)?;
+        }
+
+        println!("[Scanner] Scan complete. Saved {} items in {} batches", checkpoint.total_files + checkpoint.total_folders, checkpoint.batches);
+        Ok(checkpoint)
+    }
+
+    // El punto de control guarda la ruta real (para poder reanudar); los snapshots, anonimizados si toca
+    fn save_batch(
+        db: &Database,
+        batch: &[FileSnapshot],
+        checkpoint: &mut ScanCheckpoint,
+        redact_root: Option<&str>,
+        progress: &dyn Fn(&ScanCheckpoint, &[FileSnapshot]),
+    ) -> Result<(), String> {
+        checkpoint.last_path = batch.last().map(|s| s.file_path.clone());
+        checkpoint.batches += 1;
+        let redacted: Option<Vec<FileSnapshot>> = redact_root.map(|root| {
+            let key = privacy::path_key(db);
+            batch.iter().map(|s| privacy::redact_snapshot(&key, s, root)).collect()
+        });
+        let saved = redacted.as_deref().unwrap_or(batch);
+        db.save_scan_batch(saved, checkpoint).map_err(|e| {
+            let msg = format!("Failed to save snapshots: {}", e);
+            println!("[Scanner] {}", msg);
+            msg
+        })?;
+        progress(checkpoint, saved);
+        Ok(())
+    }
+
+    /// Escanear y guardar directamente en la base de datos en batch. `progress` se llama tras cada lote guardado
+    /// (el incremental se guarda de una vez y no lo llama).
     pub async fn scan_and_save(
         mount_point: &str, 
+        device_id: &str,
         activity_log_id: i64, 
-        db: Arc<Database>
+        db: Arc<Database>,
+        progress: impl Fn(&ScanCheckpoint, &[FileSnapshot]),
     ) -> Result<ScanResult, String> {
-        let snapshots = Self::scan_directory(mount_point, activity_log_id);
-        let total_items = snapshots.len();
-        
-        if total_items == 0 {
-            return Ok(ScanResult {
-                total_files: 0,
-                total_folders: 0,
-                total_size_bytes: 0,
-            });
+        let hasher = ScanHasher::new(&ScanSettings::load(&db));
+        // Un escaneo anterior sin terminar ya no se podrá reanudar: su snapshot queda incompleto
+        match db.abandon_scan_checkpoints(device_id, activity_log_id) {
+            Ok(0) => {}
+            Ok(abandoned) => println!("[Scanner] {} interrupted scans of {} left incomplete", abandoned, device_id),
+            Err(e) => println!("[DB] Error updating scan checkpoints: {}", e),
         }
-        
-        // Calcular estadísticas
+
+        // La posición se toma antes de leer nada: lo que cambie durante el escaneo entra en el siguiente.
+        // Con las rutas anonimizadas no hay incremental: parte de las rutas del escaneo anterior.
+        let journal = if privacy::redacts_paths(&db, device_id) { None } else { usn::query(mount_point) };
+        let incremental = journal
+            .and_then(|journal| Self::scan_incremental(mount_point, device_id, activity_log_id, journal, &hasher, &db));
+
+        let snapshots = match incremental {
+            Some(snapshots) => snapshots,
+            None => {
+                db.start_scan_checkpoint(activity_log_id, device_id, mount_point)
+                    .map_err(|e| format!("Failed to start scan: {}", e))?;
+                let checkpoint = ScanCheckpoint {
+                    activity_log_id,
+                    device_id: device_id.to_string(),
+                    mount_point: mount_point.to_string(),
+                    last_path: None,
+                    total_files: 0,
+                    total_folders: 0,
+                    total_bytes: 0,
+                    batches: 0,
+                    started_at: chrono::Utc::now(),
+                    updated_at: chrono::Utc::now(),
+                    abandoned: false,
+                };
+                let done = Self::scan_full(mount_point, &hasher, &db, checkpoint, &progress)?;
+                return Ok(ScanResult::from_checkpoint(&done, journal));
+            }
+        };
+
+        // Incremental: se guarda de una vez
         let total_files = snapshots.iter().filter(|s| !s.is_folder).count();
         let total_folders = snapshots.iter().filter(|s| s.is_folder).count();
         let total_size_bytes: i64 = snapshots.iter().map(|s| s.file_size).sum();
         
-        // Guardar en batch para mejor rendimiento
         match db.insert_file_snapshots_batch(&snapshots) {
             Ok(_) => {
-                println!("[Scanner] Saved {} items to database", total_items);
+                println!("[Scanner] Saved {} items to database", snapshots.len());
                 Ok(ScanResult {
                     total_files,
                     total_folders,
                     total_size_bytes,
+                    journal,
+                    incremental: true,
                 })
             }
             Err(e) => {
@@ -111,6 +446,19 @@ impl FileScanner {
             }
         }
     }
+
+    /// Reanudar un escaneo completo interrumpido (cierre de la app o reinicio) con el dispositivo aún conectado.
+    /// No guarda la posición del diario USN: lo cambiado durante el corte no se vería en un incremental.
+    pub async fn resume_scan(
+        checkpoint: ScanCheckpoint,
+        db: Arc<Database>,
+        progress: impl Fn(&ScanCheckpoint, &[FileSnapshot]),
+    ) -> Result<ScanResult, String> {
+        let hasher = ScanHasher::new(&ScanSettings::load(&db));
+        let mount_point = checkpoint.mount_point.clone();
+        let done = Self::scan_full(&mount_point, &hasher, &db, checkpoint, &progress)?;
+        Ok(ScanResult::from_checkpoint(&done, None))
+    }
     
     /// Obtener el tamaño total de un directorio sin guardar en DB
     pub fn get_directory_size(mount_point: &str) -> u64 {
@@ -139,4 +487,34 @@ pub struct ScanResult {
     pub total_files: usize,
     pub total_folders: usize,
     pub total_size_bytes: i64,
+    /// Posición del diario USN al empezar, para que el siguiente escaneo pueda ser incremental
+    pub journal: Option<JournalPosition>,
+    /// El escaneo partió del anterior y solo leyó las carpetas cambiadas
+    pub incremental: bool,
+}
+
+impl ScanResult {
+    fn from_checkpoint(checkpoint: &ScanCheckpoint, journal: Option<JournalPosition>) -> Self {
+        ScanResult {
+            total_files: checkpoint.total_files as usize,
+            total_folders: checkpoint.total_folders as usize,
+            total_size_bytes: checkpoint.total_bytes,
+            journal,
+            incremental: false,
+        }
+    }
+}
+
+// Quitar una ruta y todo lo que cuelga de ella
+fn remove_tree(entries: &mut BTreeMap<PathBuf, FileSnapshot>, root: &Path) {
+    // Los descendientes van seguidos en el orden por componentes de PathBuf
+    let nested: Vec<PathBuf> = entries
+        .range(root.to_path_buf()..)
+        .map(|(path, _)| path)
+        .take_while(|path| path.starts_with(root))
+        .cloned()
+        .collect();
+    for path in nested {
+        entries.remove(&path);
+    }
 }
@@ -1,41 +1,115 @@
-use crate::db::{Database, FileSnapshot};
+use crate::db::{Database, FileEventKind, FileSnapshot};
+use crate::events;
+use crate::i18n::Message;
+use crate::idle_eject;
+use crate::ipc;
+use crate::privacy;
+use crate::removal;
+use crate::rules;
+use crate::watchlists;
+use crate::write_block;
+use crate::writer_process;
 use notify::{Event, RecursiveMode, Watcher};
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter};
+use tauri::AppHandle;
 
 const DEBOUNCE_DURATION: Duration = Duration::from_secs(3);
 
+// Último tamaño conocido de cada archivo escrito en la sesión, para la regla de bytes escritos
+#[derive(Default)]
+struct SessionWrites {
+    sizes: HashMap<String, i64>,
+    alerted: bool,
+    // Ya se ha alertado de un ejecutable escrito en esta sesión
+    executable_alerted: bool,
+}
+
+// Volumen vigilado: sus eventos van a la sesión del dispositivo, marcados con el punto de montaje
+// (un dispositivo con varias particiones tiene un watcher por volumen)
+struct WatchedVolume {
+    device_id: String,
+    mount_point: String,
+    activity_id: i64,
+    // Clave para guardar las rutas anonimizadas (ajuste `privacy` o del dispositivo)
+    redact_key: Option<Vec<u8>>,
+}
+
+impl WatchedVolume {
+    // Ruta y nombre tal como se guardan
+    fn stored_path(&self, path: &Path, file_path: String, file_name: String, extension: Option<&str>) -> (String, String) {
+        match &self.redact_key {
+            Some(key) => privacy::redact_path(key, path, &self.mount_point, extension),
+            None => (file_path, file_name),
+        }
+    }
+
+    // Ruta tal como se guarda, para alertas y auditoría
+    fn display_path(&self, path: &Path) -> String {
+        let file_path = path.to_string_lossy().to_string();
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| file_path.clone());
+        let extension = path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase());
+        self.stored_path(path, file_path, file_name, extension.as_deref()).0
+    }
+}
+
 pub struct FileWatcher;
 
 impl FileWatcher {
     pub fn watch_mount(
         mount_point: String,
+        device_id: String,
         activity_id: i64,
         db: Arc<Database>,
-        app_handle: AppHandle,
+        app_handle: Option<AppHandle>,
     ) -> notify::Result<notify::RecommendedWatcher> {
-        let mount_path = mount_point.clone();
         let recent_files = Arc::new(Mutex::new(HashMap::new()));
+        let writes = Arc::new(Mutex::new(SessionWrites::default()));
+        let redact_key = privacy::redacts_paths(&db, &device_id).then(|| privacy::path_key(&db));
+        let volume = WatchedVolume { device_id, mount_point: mount_point.clone(), activity_id, redact_key };
 
         let mut watcher =
             notify::recommended_watcher(move |res: notify::Result<Event>| match res {
                 Ok(event) => {
+                    if event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove() {
+                        idle_eject::touch(&volume.device_id);
+                    }
                     if event.kind.is_create() || event.kind.is_modify() {
+                        removal::note_write(&volume.device_id);
+                        let kind = if event.kind.is_create() {
+                            FileEventKind::Created
+                        } else {
+                            FileEventKind::Modified
+                        };
                         for path in event.paths {
-                            if path.is_file() {
-                                Self::handle_copy_event(
-                                    &path,
-                                    &mount_path,
-                                    activity_id,
-                                    &db,
-                                    &app_handle,
-                                    Arc::clone(&recent_files),
-                                );
+                            if !path.is_file() {
+                                continue;
+                            }
+                            let stored_path = volume.display_path(&path);
+                            if write_block::enforce(&db, app_handle.as_ref(), &path, &stored_path, &volume.device_id, volume.activity_id) {
+                                continue;
                             }
+                            if let Some(size) = Self::handle_copy_event(
+                                &path,
+                                &volume,
+                                kind,
+                                &db,
+                                app_handle.as_ref(),
+                                Arc::clone(&recent_files),
+                            ) {
+                                Self::track_write(&writes, &path, size, &volume.device_id, volume.activity_id, &db, app_handle.as_ref());
+                                if kind == FileEventKind::Created {
+                                    Self::track_executable(&writes, &path, &volume, &db, app_handle.as_ref());
+                                }
+                            }
+                        }
+                    } else if event.kind.is_remove() {
+                        for path in event.paths {
+                            writes.lock().unwrap().sizes.remove(path.to_string_lossy().as_ref());
+                            Self::handle_remove_event(&path, &volume, &db);
                         }
                     }
                 }
@@ -48,30 +122,30 @@ impl FileWatcher {
         Ok(watcher)
     }
 
+    // Devuelve el tamaño del archivo si el evento se ha registrado
     fn handle_copy_event(
         path: &Path,
-        _mount_point: &str,
-        activity_id: i64,
+        volume: &WatchedVolume,
+        kind: FileEventKind,
         db: &Arc<Database>,
-        app_handle: &AppHandle,
+        app_handle: Option<&AppHandle>,
         recent_files: Arc<Mutex<HashMap<String, Instant>>>,
-    ) {
+    ) -> Option<i64> {
+        let file_path = path.to_string_lossy().to_string();
         let file_name = path
             .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown")
-            .to_string();
-        let file_path = path.to_string_lossy().to_string();
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| file_path.clone());
 
         if file_name.starts_with('~') || file_name.starts_with('.') {
-            return;
+            return None;
         }
 
         let mut recent = recent_files.lock().unwrap();
 
         if let Some(&last_seen) = recent.get(&file_path) {
             if last_seen.elapsed() < DEBOUNCE_DURATION {
-                return;
+                return None;
             }
         }
 
@@ -85,26 +159,110 @@ impl FileWatcher {
             .and_then(|e| e.to_str())
             .map(|s| s.to_lowercase());
 
+        let process = writer_process::writer_of(path);
+        // Las listas de vigilancia buscan el nombre real; la alerta lleva el guardado
+        let real_name = file_name.clone();
+        let (file_path, file_name) = volume.stored_path(path, file_path, file_name, extension.as_deref());
+        if let Err(e) = db.insert_file_event(
+            volume.activity_id,
+            &file_path,
+            kind,
+            Some(size),
+            Some(&volume.mount_point),
+            process.as_deref(),
+        ) {
+            println!("[Watcher] Error saving file event: {}", e);
+        }
+
         let snapshot = FileSnapshot {
             id: None,
-            activity_log_id: activity_id,
+            activity_log_id: volume.activity_id,
             file_path: file_path.clone(),
             file_name: file_name.clone(),
             file_extension: extension,
             file_size: size,
             is_folder: false,
+            sha256: None,
+            hash_mode: None,
         };
 
-        if let Ok(_) = db.insert_file_snapshot(&snapshot) {
-            let _ = app_handle.emit(
-                "file-copy-detected",
-                serde_json::json!({
-                    "activity_id": activity_id,
-                    "file_name": file_name,
-                    "file_size": size,
-                    "path": file_path
-                }),
-            );
+        if db.insert_file_snapshot(&snapshot).is_ok() {
+            let payload = serde_json::json!({
+                "activity_id": volume.activity_id,
+                "file_name": file_name,
+                "file_size": size,
+                "path": file_path,
+                "volume": volume.mount_point,
+                "process": process,
+                "message": Message::new("file.copied", serde_json::json!({
+                    "device": volume.device_id,
+                    "file": file_name,
+                    "size": size,
+                })),
+            });
+            ipc::publish("file-copy-detected", &payload);
+            if let Some(app_handle) = app_handle {
+                events::emit(app_handle, "file-copy-detected", payload);
+            }
+            if kind == FileEventKind::Created {
+                watchlists::check_file(db, app_handle, &volume.device_id, volume.activity_id, &file_path, &real_name);
+            }
+        }
+
+        Some(size)
+    }
+
+    // Acumular lo escrito en la sesión y alertar (una vez) si supera el umbral del dispositivo
+    fn track_write(
+        writes: &Mutex<SessionWrites>,
+        path: &Path,
+        size: i64,
+        device_id: &str,
+        activity_id: i64,
+        db: &Arc<Database>,
+        app_handle: Option<&AppHandle>,
+    ) {
+        let mut writes = writes.lock().unwrap();
+        writes.sizes.insert(path.to_string_lossy().to_string(), size);
+        if writes.alerted {
+            return;
+        }
+
+        let total: i64 = writes.sizes.values().sum();
+        writes.alerted = rules::check_session_writes(db, app_handle, device_id, activity_id, total);
+    }
+
+    // Alertar (una vez) del primer ejecutable creado en la sesión
+    fn track_executable(
+        writes: &Mutex<SessionWrites>,
+        path: &Path,
+        volume: &WatchedVolume,
+        db: &Arc<Database>,
+        app_handle: Option<&AppHandle>,
+    ) {
+        let mut writes = writes.lock().unwrap();
+        if writes.executable_alerted {
+            return;
+        }
+
+        let file_path = path.to_string_lossy().to_string();
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| file_path.clone());
+        let extension = path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase());
+        let (_, file_name) = volume.stored_path(path, file_path, file_name, extension.as_deref());
+        writes.executable_alerted =
+            rules::check_executable_written(db, app_handle, &volume.device_id, volume.activity_id, path, &file_name);
+    }
+
+    fn handle_remove_event(path: &Path, volume: &WatchedVolume, db: &Arc<Database>) {
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if file_name.starts_with('~') || file_name.starts_with('.') {
+            return;
+        }
+
+        let extension = path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase());
+        let (file_path, _) = volume.stored_path(path, path.to_string_lossy().to_string(), file_name, extension.as_deref());
+        if let Err(e) = db.insert_file_event(volume.activity_id, &file_path, FileEventKind::Removed, None, Some(&volume.mount_point), None) {
+            println!("[Watcher] Error saving file event: {}", e);
         }
     }
 }
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use crate::alerts::raise_alert;
+use crate::db::{AlertSeverity, Database};
+use crate::events;
+use crate::i18n::Message;
+use crate::ipc;
+use crate::usb_monitor::UsbMonitor;
+
+pub const FLAPPING_SETTINGS_KEY: &str = "flapping";
+/// Inicio y fin de una racha de conexiones y desconexiones de un dispositivo
+pub const FLAPPING_EVENT: &str = "usb-flapping";
+const FLAPPING_ALERT: &str = "DEVICE_FLAPPING";
+
+/// Detección de conexiones inestables (cable o puerto defectuoso), guardada en settings bajo `flapping`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlappingSettings {
+    pub enabled: bool,
+    /// Conexiones y desconexiones dentro de `window_secs` a partir de las que el dispositivo fluctúa
+    pub transitions: usize,
+    pub window_secs: u64,
+    /// Segundos sin cambios para darlo por estable y volver a registrarlo y escanearlo
+    pub stable_secs: u64,
+}
+
+impl Default for FlappingSettings {
+    fn default() -> Self {
+        Self { enabled: true, transitions: 6, window_secs: 60, stable_secs: 30 }
+    }
+}
+
+impl FlappingSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(FLAPPING_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+struct FlapState {
+    transitions: VecDeque<Instant>,
+    // Desde cuándo fluctúa y cuántos cambios se han ignorado
+    flapping_since: Option<Instant>,
+    suppressed: usize,
+    // Alerta del inicio de la racha, que se completa al estabilizarse
+    alert_id: Option<i64>,
+}
+
+// device_id -> cambios recientes
+static STATE: OnceLock<Mutex<HashMap<String, FlapState>>> = OnceLock::new();
+
+fn state() -> &'static Mutex<HashMap<String, FlapState>> {
+    STATE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Resultado de anotar una conexión o desconexión
+pub enum Transition {
+    Normal,
+    /// Este cambio es el que supera el umbral
+    Started { count: usize },
+    Flapping,
+}
+
+/// Anotar una conexión o desconexión de un dispositivo
+pub fn note_transition(device_id: &str, settings: &FlappingSettings) -> Transition {
+    if !settings.enabled || settings.transitions == 0 {
+        return Transition::Normal;
+    }
+    let now = Instant::now();
+    let window = Duration::from_secs(settings.window_secs);
+    let mut state = state().lock().unwrap();
+    let entry = state.entry(device_id.to_string()).or_default();
+
+    entry.transitions.push_back(now);
+    while entry.transitions.front().is_some_and(|at| now.duration_since(*at) > window) {
+        entry.transitions.pop_front();
+    }
+
+    if entry.flapping_since.is_some() {
+        entry.suppressed += 1;
+        return Transition::Flapping;
+    }
+    if entry.transitions.len() >= settings.transitions {
+        entry.flapping_since = Some(now);
+        entry.suppressed = 1;
+        return Transition::Started { count: entry.transitions.len() };
+    }
+    Transition::Normal
+}
+
+fn publish(monitor: &UsbMonitor, payload: serde_json::Value) {
+    ipc::publish(FLAPPING_EVENT, &payload);
+    if let Some(ref app_handle) = monitor.app_handle {
+        events::emit(app_handle, FLAPPING_EVENT, payload);
+    }
+}
+
+/// Avisar de que un dispositivo ha empezado a fluctuar: una sola alerta con los cambios vistos en la ventana.
+/// A partir de aquí sus conexiones y desconexiones no se registran ni lanzan escaneos hasta que se estabilice.
+pub fn announce(monitor: &UsbMonitor, db: &Arc<Database>, device_id: &str, count: usize, settings: &FlappingSettings) {
+    println!("[USB] {} is flapping ({} changes in {} s), ignoring it until stable", device_id, count, settings.window_secs);
+    let alert_id = raise_alert(
+        db,
+        monitor.app_handle.as_ref(),
+        Some(device_id),
+        None,
+        FLAPPING_ALERT,
+        AlertSeverity::Medium,
+        Message::new(
+            "alert.device_flapping",
+            serde_json::json!({ "device": device_id, "count": count, "window": settings.window_secs }),
+        ),
+    );
+    if let Some(entry) = state().lock().unwrap().get_mut(device_id) {
+        entry.alert_id = alert_id;
+    }
+    publish(monitor, serde_json::json!({ "device_id": device_id, "flapping": true, "transitions": count }));
+}
+
+/// Llamado en cada ciclo del monitor: los dispositivos sin cambios durante `stable_secs` dejan de fluctuar
+/// y, si siguen conectados, se registran y escanean como una conexión nueva
+pub fn check(monitor: &Arc<UsbMonitor>) {
+    let db = &monitor.db;
+    let settings = FlappingSettings::load(db);
+    let stable = Duration::from_secs(settings.stable_secs);
+
+    let settled: Vec<(String, usize, Duration, Option<i64>)> = {
+        let mut state = state().lock().unwrap();
+        let settled: Vec<(String, usize, Duration, Option<i64>)> = state
+            .iter()
+            .filter_map(|(device_id, s)| {
+                let since = s.flapping_since?;
+                let last = s.transitions.back().copied().unwrap_or(since);
+                (last.elapsed() >= stable).then(|| (device_id.clone(), s.suppressed, since.elapsed(), s.alert_id))
+            })
+            .collect();
+        for (device_id, _, _, _) in &settled {
+            state.remove(device_id);
+        }
+        // Sin racha en curso, los cambios fuera de la ventana ya no cuentan
+        let window = Duration::from_secs(settings.window_secs);
+        state.retain(|_, s| s.flapping_since.is_some() || s.transitions.back().is_some_and(|at| at.elapsed() <= window));
+        settled
+    };
+
+    for (device_id, suppressed, duration, alert_id) in settled {
+        println!("[USB] {} is stable again after {} s ({} changes ignored)", device_id, duration.as_secs(), suppressed);
+        // La alerta se queda con el total de cambios ignorados, que solo se sabe ahora
+        if let Some(alert_id) = alert_id {
+            let message = Message::new(
+                "alert.device_flapping_settled",
+                serde_json::json!({ "device": device_id, "ignored": suppressed, "duration": duration.as_secs() }),
+            );
+            if let Err(e) = db.update_alert_message(alert_id, &message) {
+                println!("[DB] Error updating flapping alert: {}", e);
+            }
+        }
+        publish(
+            monitor,
+            serde_json::json!({
+                "device_id": device_id,
+                "flapping": false,
+                "transitions": suppressed,
+                "duration_secs": duration.as_secs(),
+            }),
+        );
+        monitor.resume_after_flapping(&device_id);
+    }
+}
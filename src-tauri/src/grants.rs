@@ -0,0 +1,168 @@
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::approval::{self, Approval};
+use crate::audit;
+use crate::db::{AccessGrant, AlertSeverity, Database, GrantStatus, TrustLevel};
+use crate::i18n::Message;
+use crate::kiosk;
+use crate::state::AppState;
+
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(15);
+const GRANTED_AUDIT_ACTION: &str = "ACCESS_GRANTED";
+const EXPIRED_AUDIT_ACTION: &str = "ACCESS_GRANT_EXPIRED";
+const REVOKED_AUDIT_ACTION: &str = "ACCESS_GRANT_REVOKED";
+const EXPIRED_ALERT: &str = "ACCESS_GRANT_EXPIRED";
+// Un acceso temporal no puede durar más de una semana
+const MAX_DURATION_MINUTES: i64 = 7 * 24 * 60;
+
+// Cerrar un acceso y dejar constancia; al vencer, avisar si el dispositivo sigue conectado
+fn end_grant(db: &Arc<Database>, app_handle: Option<&AppHandle>, grant: &AccessGrant, status: GrantStatus) -> Result<bool, String> {
+    let restored = match db.end_access_grant(grant.id, status).map_err(|e| format!("Database error: {}", e))? {
+        Some(restored) => restored,
+        None => return Ok(false),
+    };
+
+    let action = if status == GrantStatus::Expired { EXPIRED_AUDIT_ACTION } else { REVOKED_AUDIT_ACTION };
+    audit::record(
+        db,
+        action,
+        Some(&grant.device_id),
+        &serde_json::json!({
+            "grant": grant.id,
+            "from": grant.granted_trust.as_str(),
+            "to": grant.previous_trust.as_str(),
+            // false: la confianza se cambió a mano durante el acceso y se ha respetado
+            "restored": restored,
+        }),
+    );
+    println!(
+        "[Grants] Access for {} {} ({} -> {})",
+        grant.device_id,
+        status.as_str(),
+        grant.granted_trust.as_str(),
+        if restored { grant.previous_trust.as_str() } else { "unchanged" }
+    );
+
+    if status == GrantStatus::Expired && restored && db.is_device_connected(&grant.device_id).unwrap_or(false) {
+        raise_alert(
+            db,
+            app_handle,
+            Some(&grant.device_id),
+            None,
+            EXPIRED_ALERT,
+            if grant.previous_trust == TrustLevel::Blocked { AlertSeverity::High } else { AlertSeverity::Medium },
+            Message::new(
+                "alert.access_grant_expired",
+                serde_json::json!({ "device": grant.device_id, "trust": grant.previous_trust.as_str() }),
+            ),
+        );
+    }
+
+    Ok(restored)
+}
+
+// Restaurar la confianza de los accesos vencidos
+fn expire_due(db: &Arc<Database>, app_handle: Option<&AppHandle>) {
+    let grants = match db.get_expired_access_grants(Utc::now()) {
+        Ok(grants) => grants,
+        Err(e) => {
+            println!("[DB] Error loading access grants: {}", e);
+            return;
+        }
+    };
+
+    for grant in &grants {
+        if let Err(e) = end_grant(db, app_handle, grant, GrantStatus::Expired) {
+            println!("[Grants] {}", e);
+        }
+    }
+}
+
+/// Bucle que revierte los accesos temporales al vencer; los vencidos con la app cerrada se revierten al arrancar
+pub async fn run_grant_scheduler(db: Arc<Database>, app_handle: Option<AppHandle>) {
+    println!("[Grants] Scheduler started.");
+    loop {
+        expire_due(&db, app_handle.as_ref());
+        tokio::time::sleep(SCHEDULER_INTERVAL).await;
+    }
+}
+
+/// Confiar en un dispositivo (o desbloquearlo con `level` UNKNOWN) durante `duration_minutes`.
+/// Al vencer vuelve a la confianza que tenía antes; en modo estricto exige aprobación como cualquier otro cambio.
+#[tauri::command]
+pub async fn grant_temporary_access(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+    duration_minutes: i64,
+    level: Option<TrustLevel>,
+    approval: Option<Approval>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let level = level.unwrap_or(TrustLevel::Trusted);
+    if level == TrustLevel::Blocked {
+        return Err("Temporary access cannot block a device".to_string());
+    }
+    if !(1..=MAX_DURATION_MINUTES).contains(&duration_minutes) {
+        return Err(format!("Duration must be between 1 and {} minutes", MAX_DURATION_MINUTES));
+    }
+
+    let ids = [device_id.clone()];
+    let method = approval::authorize(&db, &ids, level, approval.as_ref())?;
+    let previous = db.get_device_trust(&device_id).unwrap_or_default();
+    let expires_at = Utc::now() + chrono::Duration::minutes(duration_minutes);
+
+    let id = db
+        .create_access_grant(&device_id, previous, level, expires_at)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if let Some(method) = method {
+        approval::record_approved(&db, &[(device_id.clone(), previous)], level, method, approval.as_ref());
+    }
+    audit::record(
+        &db,
+        GRANTED_AUDIT_ACTION,
+        Some(&device_id),
+        &serde_json::json!({
+            "grant": id,
+            "from": previous.as_str(),
+            "to": level.as_str(),
+            "expires_at": expires_at,
+            "os_user": crate::os_session::OsSession::current().user,
+        }),
+    );
+
+    let grant = db.get_access_grant(id).map_err(|e| format!("Database error: {}", e))?;
+    Ok(serde_json::json!({ "success": true, "grant": grant }))
+}
+
+#[tauri::command]
+pub async fn get_access_grants(
+    state: tauri::State<'_, AppState>,
+    device_id: Option<String>,
+    active_only: Option<bool>,
+    limit: i64,
+) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_access_grants(device_id.as_deref(), active_only.unwrap_or(false), limit) {
+        Ok(grants) => Ok(serde_json::json!({ "success": true, "grants": grants })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Retirar un acceso antes de que venza, restaurando ya la confianza anterior
+#[tauri::command]
+pub async fn revoke_access_grant(state: tauri::State<'_, AppState>, id: i64) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let grant = db
+        .get_access_grant(id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .filter(|g| g.status == GrantStatus::Active)
+        .ok_or("Access grant not found or no longer active")?;
+
+    let restored = end_grant(&db, None, &grant, GrantStatus::Revoked)?;
+    Ok(serde_json::json!({ "success": true, "restored": restored }))
+}
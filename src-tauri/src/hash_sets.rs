@@ -0,0 +1,152 @@
+use rusqlite::{Connection, OpenFlags};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek};
+use std::path::Path;
+use crate::audit;
+use crate::db::Database;
+use crate::kiosk;
+use crate::search::{looks_like_sha256, FileQuery};
+use crate::state::AppState;
+
+const IMPORTED_AUDIT_ACTION: &str = "HASH_SET_IMPORTED";
+const DELETED_AUDIT_ACTION: &str = "HASH_SET_DELETED";
+// Hashes por transacción al importar (el RDS del NSRL tiene decenas de millones)
+const IMPORT_BATCH: usize = 20_000;
+const DEFAULT_FILE_LIMIT: i64 = 500;
+
+// Importar por lotes para no cargar la lista entera en memoria; devuelve cuántos se han añadido
+fn import_batches(db: &Database, hash_set_id: i64, hashes: impl Iterator<Item = String>) -> Result<usize, String> {
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+    let mut added = 0;
+    let mut batch = Vec::with_capacity(IMPORT_BATCH);
+    for hash in hashes {
+        batch.push(hash);
+        if batch.len() == IMPORT_BATCH {
+            added += db.add_known_hashes(hash_set_id, &batch).map_err(db_err)?;
+            batch.clear();
+        }
+    }
+    added += db.add_known_hashes(hash_set_id, &batch).map_err(db_err)?;
+    Ok(added)
+}
+
+// RDS v3 del NSRL: base de datos SQLite con los hashes en la tabla FILE
+fn import_nsrl_rds(db: &Database, hash_set_id: i64, path: &Path) -> Result<usize, String> {
+    let rds = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut stmt = rds
+        .prepare("SELECT DISTINCT lower(sha256) FROM FILE WHERE sha256 IS NOT NULL")
+        .map_err(|e| format!("Not an NSRL RDS database (no FILE table with sha256): {}", e))?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    import_batches(db, hash_set_id, rows.filter_map(|row| row.ok()).filter(|h| looks_like_sha256(h)))
+}
+
+// Listas de texto: de cada línea el primer SHA-256 que aparezca, así valen una lista de hashes,
+// la salida de sha256sum/hashdeep o un CSV con una columna SHA-256. Las demás líneas se ignoran.
+fn import_text(db: &Database, hash_set_id: i64, file: File) -> Result<usize, String> {
+    let hashes = BufReader::new(file).lines().map_while(|line| line.ok()).filter_map(|line| {
+        line.split(|c: char| c == ',' || c == '"' || c == ';' || c.is_whitespace())
+            .find(|token| looks_like_sha256(token))
+            .map(|token| token.to_lowercase())
+    });
+    import_batches(db, hash_set_id, hashes)
+}
+
+/// Importar una lista de archivos conocidos: el RDS v3 del NSRL (SQLite) o una lista de texto con SHA-256.
+/// Las listas antiguas del NSRL (NSRLFile.txt) solo traen SHA-1 y MD5 y no sirven.
+#[tauri::command]
+pub async fn import_hash_set(state: tauri::State<'_, AppState>, name: String, path: String) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Hash set name cannot be empty".to_string());
+    }
+
+    let hash_set_id = db
+        .create_hash_set(&name, Some(&path))
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    let import_db = db.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let path = Path::new(&path);
+        let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut header = [0u8; 16];
+        let is_sqlite = file.read_exact(&mut header).is_ok() && &header == b"SQLite format 3\0";
+        if is_sqlite {
+            import_nsrl_rds(&import_db, hash_set_id, path)
+        } else {
+            file.rewind().map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            import_text(&import_db, hash_set_id, file)
+        }
+    })
+    .await
+    .map_err(|e| format!("Import task failed: {}", e))?;
+
+    let added = match result {
+        Ok(added) if added > 0 => added,
+        Ok(_) => {
+            let _ = db.delete_hash_set(hash_set_id);
+            return Err("No SHA-256 hashes found in the file".to_string());
+        }
+        Err(e) => {
+            let _ = db.delete_hash_set(hash_set_id);
+            return Err(e);
+        }
+    };
+
+    println!("[HashSets] Imported {} hashes into '{}'", added, name);
+    audit::record(&db, IMPORTED_AUDIT_ACTION, None, &serde_json::json!({ "id": hash_set_id, "name": name, "hashes": added }));
+    Ok(serde_json::json!({ "success": true, "id": hash_set_id, "hashes": added }))
+}
+
+#[tauri::command]
+pub async fn get_hash_sets(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_hash_sets() {
+        Ok(sets) => Ok(serde_json::json!({ "success": true, "hash_sets": sets })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn delete_hash_set(state: tauri::State<'_, AppState>, id: i64) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let deleted = db.delete_hash_set(id).map_err(|e| format!("Database error: {}", e))?;
+    if deleted {
+        audit::record(&db, DELETED_AUDIT_ACTION, None, &serde_json::json!({ "id": id }));
+    }
+    Ok(serde_json::json!({ "success": deleted }))
+}
+
+/// Cuántos archivos de un escaneo son conocidos y cuáles no: con `known` se listan los de ese lado
+/// (`known: false` deja solo los no identificados para revisarlos)
+#[tauri::command]
+pub async fn get_scan_hash_matches(
+    state: tauri::State<'_, AppState>,
+    activity_log_id: i64,
+    known: Option<bool>,
+    limit: Option<i64>,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+
+    let summary = db.get_known_hash_summary(activity_log_id).map_err(db_err)?;
+    let files = match known {
+        Some(known) => {
+            let query = FileQuery { known: Some(known), ..Default::default() };
+            db.search_files(&query, Some(activity_log_id), limit.unwrap_or(DEFAULT_FILE_LIMIT)).map_err(db_err)?
+        }
+        None => Vec::new(),
+    };
+
+    Ok(serde_json::json!({
+        "success": true,
+        "activity_log_id": activity_log_id,
+        "summary": summary,
+        "files": files,
+    }))
+}
@@ -0,0 +1,43 @@
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Calcular el SHA-256 de un archivo leyendo por bloques
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// SHA-256 de una muestra de un archivo grande: los primeros y los últimos `sample_bytes` y después
+/// el tamaño como u64 little-endian. Pensado para archivos mayores que las dos muestras juntas.
+pub fn sha256_sampled(path: &Path, sample_bytes: u64) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+
+    let mut head = Vec::new();
+    (&mut file).take(sample_bytes).read_to_end(&mut head)?;
+    hasher.update(&head);
+    if size > sample_bytes {
+        // El final sin volver a leer lo que ya entró en la primera muestra
+        file.seek(SeekFrom::Start(size.saturating_sub(sample_bytes).max(sample_bytes)))?;
+        let mut tail = Vec::new();
+        file.take(sample_bytes).read_to_end(&mut tail)?;
+        hasher.update(&tail);
+    }
+    hasher.update(size.to_le_bytes());
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
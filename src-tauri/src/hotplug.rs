@@ -0,0 +1,100 @@
+use rusb::{Context, Device, Hotplug, HotplugBuilder, UsbContext};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use tokio::sync::Notify;
+use crate::db::Database;
+
+pub const HOTPLUG_SETTINGS_KEY: &str = "hotplug";
+
+/// Avisos de conexión de libusb, guardados en settings bajo la clave `hotplug`. Se leen al iniciar la monitorización.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotplugSettings {
+    /// Detectar los cambios con los avisos de libusb en lugar de recorrer el bus cada 2 s (si el sistema los admite)
+    pub enabled: bool,
+    /// Con avisos, cada cuántos segundos se recorre el bus de todas formas (volúmenes montados a mano, expulsión por inactividad...)
+    pub idle_poll_secs: u64,
+}
+
+impl Default for HotplugSettings {
+    fn default() -> Self {
+        Self { enabled: true, idle_poll_secs: 30 }
+    }
+}
+
+impl HotplugSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(HOTPLUG_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+// Despierta al monitor en cada conexión o desconexión; la lista se sigue obteniendo del backend
+struct Listener {
+    wake: Arc<Notify>,
+}
+
+impl Hotplug<Context> for Listener {
+    fn device_arrived(&mut self, device: Device<Context>) {
+        println!("[USB] Hotplug: device arrived (bus {}, address {})", device.bus_number(), device.address());
+        self.wake.notify_one();
+    }
+
+    fn device_left(&mut self, device: Device<Context>) {
+        println!("[USB] Hotplug: device left (bus {}, address {})", device.bus_number(), device.address());
+        self.wake.notify_one();
+    }
+}
+
+/// Registrar los avisos de libusb en un hilo propio. Devuelve false si el sistema no los admite (Windows)
+/// o no se pudieron registrar; `active` vuelve a false si el hilo falla después, y el monitor sigue sondeando.
+pub fn listen(wake: Arc<Notify>, active: Arc<AtomicBool>) -> bool {
+    if !rusb::has_hotplug() {
+        println!("[USB] libusb hotplug not supported, polling the bus");
+        return false;
+    }
+
+    let (registered_tx, registered_rx) = mpsc::channel();
+    let spawned = std::thread::Builder::new().name("usb-hotplug".to_string()).spawn(move || {
+        let registration = Context::new().and_then(|context| {
+            let registration = HotplugBuilder::new().register(&context, Box::new(Listener { wake: wake.clone() }))?;
+            Ok((context, registration))
+        });
+        let (context, _registration) = match registration {
+            Ok(registered) => {
+                active.store(true, Ordering::Relaxed);
+                let _ = registered_tx.send(Ok(()));
+                registered
+            }
+            Err(e) => {
+                let _ = registered_tx.send(Err(e));
+                return;
+            }
+        };
+
+        loop {
+            if let Err(e) = context.handle_events(None) {
+                println!("[USB] Hotplug event loop failed, falling back to polling: {}", e);
+                active.store(false, Ordering::Relaxed);
+                wake.notify_one();
+                return;
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        println!("[USB] Failed to start hotplug thread: {}", e);
+        return false;
+    }
+
+    match registered_rx.recv() {
+        Ok(Ok(())) => {
+            println!("[USB] Listening for libusb hotplug events");
+            true
+        }
+        Ok(Err(e)) => {
+            println!("[USB] Failed to register hotplug callback, polling the bus: {}", e);
+            false
+        }
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_LANG: &str = "en";
+
+/// Mensaje localizable: clave estable más parámetros que la interfaz
+/// sustituye en la plantilla de su idioma (ver `get_locale_bundle`)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Message {
+    pub key: String,
+    pub params: serde_json::Value,
+}
+
+impl Message {
+    pub fn new(key: &str, params: serde_json::Value) -> Self {
+        Self { key: key.to_string(), params }
+    }
+
+    /// Texto en el idioma indicado; si falta la plantilla se usa inglés y, en último caso, la clave
+    pub fn render(&self, lang: &str) -> String {
+        let template = match lookup(lang, &self.key).or_else(|| lookup(DEFAULT_LANG, &self.key)) {
+            Some(template) => template,
+            None => return self.key.clone(),
+        };
+
+        let mut text = template.to_string();
+        if let Some(params) = self.params.as_object() {
+            for (name, value) in params {
+                let value = match value {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                text = text.replace(&format!("{{{}}}", name), &value);
+            }
+        }
+        text
+    }
+}
+
+// Plantillas con parámetros entre llaves; las claves no deben cambiar una vez publicadas
+const EN: &[(&str, &str)] = &[
+    ("alert.blocked_device", "Blocked device connected: {device}"),
+    ("alert.device_connected", "Device connected: {device}"),
+    ("alert.untrusted_device_connected", "Untrusted device connected: {device}"),
+    ("alert.unusual_hour", "Device {device} connected at {hour}:00, outside its usual hours ({window} of {previous} previous connections around this time)"),
+    ("alert.new_machine", "Device {device} connected to {machine} for the first time after {previous} connections on other machines"),
+    ("alert.scan_volume", "Device {device} holds {bytes} bytes, far above its usual {mean} bytes ({z} standard deviations)"),
+    ("alert.saved_search", "Saved search '{search}' matched files on device {device}: {paths}"),
+    ("alert.watchlist_scan", "Watchlist '{watchlist}' matched {count} files on device {device}: {paths}"),
+    ("alert.watchlist_file", "Watchlist '{watchlist}' matched a new file on device {device}: {path}"),
+    ("alert.test_notification", "Test notification sent through {channel}"),
+    ("alert.write_threshold", "{bytes} bytes written to device {device} in this session (limit {limit} bytes)"),
+    ("alert.storage_quota", "Device {device} is {percent}% full ({free} bytes free, limit {limit}%)"),
+    ("alert.executable_written", "Executable written to device {device}: {file}"),
+    ("alert.baseline_deviation", "Device {device} differs from its baseline: {added} added, {removed} removed, {modified} modified"),
+    ("alert.device_flapping", "Device {device} is connecting and disconnecting repeatedly ({count} changes in {window} s); ignoring it until it is stable"),
+    ("alert.device_flapping_settled", "Device {device} was connecting and disconnecting repeatedly: {ignored} changes were ignored over {duration} s until it was stable"),
+    ("alert.plugin", "Plugin {plugin} reported on device {device}: {message}"),
+    ("alert.serial_changed", "Device {device} matches known device {known} (same model, capacity and volume serial) but reports a different USB serial: possible cloned or re-flashed device"),
+    ("alert.fingerprint_changed", "Device {device} reports the USB serial of a known device but its {changes} changed: possible cloned serial number"),
+    ("alert.write_blocked", "Blocked file written to device {device} ({reason}): {file} was moved to quarantine"),
+    ("alert.write_block_failed", "Blocked file written to device {device} ({reason}) but it could not be removed: {file}"),
+    ("alert.sync_conflict", "{count} files changed both on this computer and on device {device} since the last backup and were not overwritten: {paths}"),
+    ("alert.access_grant_expired", "Temporary access for device {device} expired while it was still connected; trust restored to {trust}"),
+    ("scan.completed", "Scan of {device} completed: {files} files, {bytes} bytes"),
+    ("file.copied", "File copied to {device}: {file} ({size} bytes)"),
+    ("session.ended", "{device} disconnected after {duration}s: {added} added, {removed} removed, {modified} modified, {bytes} bytes written"),
+    ("profile.switched", "Switched to profile {profile}"),
+    ("report.email", "USB Manager {kind} report from {from} to {to}"),
+    ("database.restored", "Database available again: {count} changes kept in memory were saved to {path}"),
+    ("tray.connected", "Connected devices: {count}"),
+    ("tray.eject", "Eject"),
+    ("tray.charge_only", "charging only"),
+    ("tray.recent_alerts", "Recent alerts ({count})"),
+    ("tray.pause", "Pause monitoring"),
+    ("tray.open", "Open USB Manager"),
+    ("tray.quit", "Quit"),
+    ("tray.tooltip", "USB Manager: {count} devices connected"),
+    ("tray.tooltip_paused", "USB Manager: monitoring paused"),
+    ("ticker.connected", "{device} connected"),
+    ("ticker.disconnected", "{device} disconnected"),
+    ("ticker.alert", "Alert: {message}"),
+    ("ticker.file_taken", "{file} copied from {device} to this computer"),
+    ("idle_eject.warning", "{device} has had no file activity and will be ejected in {seconds} s"),
+    ("idle_eject.ejected", "{device} was ejected after {minutes} minutes without file activity"),
+];
+
+const ES: &[(&str, &str)] = &[
+    ("alert.blocked_device", "Dispositivo bloqueado conectado: {device}"),
+    ("alert.device_connected", "Dispositivo conectado: {device}"),
+    ("alert.untrusted_device_connected", "Dispositivo sin confianza conectado: {device}"),
+    ("alert.unusual_hour", "El dispositivo {device} se conectó a las {hour}:00, fuera de su horario habitual ({window} de {previous} conexiones previas en esa franja)"),
+    ("alert.new_machine", "El dispositivo {device} se conectó a {machine} por primera vez tras {previous} conexiones en otros equipos"),
+    ("alert.scan_volume", "El dispositivo {device} contiene {bytes} bytes, muy por encima de sus {mean} bytes habituales ({z} desviaciones estándar)"),
+    ("alert.saved_search", "La búsqueda guardada '{search}' encontró archivos en el dispositivo {device}: {paths}"),
+    ("alert.watchlist_scan", "La lista de vigilancia '{watchlist}' coincide con {count} archivos en el dispositivo {device}: {paths}"),
+    ("alert.watchlist_file", "La lista de vigilancia '{watchlist}' coincide con un archivo nuevo en el dispositivo {device}: {path}"),
+    ("alert.test_notification", "Notificación de prueba enviada por {channel}"),
+    ("alert.write_threshold", "Se han escrito {bytes} bytes en el dispositivo {device} en esta sesión (límite {limit} bytes)"),
+    ("alert.storage_quota", "El dispositivo {device} está lleno al {percent}% ({free} bytes libres, límite {limit}%)"),
+    ("alert.executable_written", "Se ha escrito un ejecutable en el dispositivo {device}: {file}"),
+    ("alert.baseline_deviation", "El dispositivo {device} no coincide con su referencia: {added} añadidos, {removed} eliminados, {modified} modificados"),
+    ("alert.device_flapping", "El dispositivo {device} se conecta y desconecta repetidamente ({count} cambios en {window} s); se ignora hasta que se estabilice"),
+    ("alert.device_flapping_settled", "El dispositivo {device} se conectaba y desconectaba repetidamente: se ignoraron {ignored} cambios durante {duration} s hasta que se estabilizó"),
+    ("alert.plugin", "El plugin {plugin} informó sobre el dispositivo {device}: {message}"),
+    ("alert.serial_changed", "El dispositivo {device} coincide con el dispositivo conocido {known} (mismo modelo, capacidad y serie de volumen) pero tiene otro número de serie USB: posible dispositivo clonado o reprogramado"),
+    ("alert.fingerprint_changed", "El dispositivo {device} tiene el número de serie USB de un dispositivo conocido pero ha cambiado: {changes}. Posible número de serie clonado"),
+    ("alert.write_blocked", "Archivo bloqueado escrito en el dispositivo {device} ({reason}): {file} se ha movido a cuarentena"),
+    ("alert.write_block_failed", "Archivo bloqueado escrito en el dispositivo {device} ({reason}) pero no se ha podido eliminar: {file}"),
+    ("alert.sync_conflict", "{count} archivos han cambiado en este equipo y en el dispositivo {device} desde la última copia y no se han sobrescrito: {paths}"),
+    ("alert.access_grant_expired", "El acceso temporal del dispositivo {device} ha vencido mientras seguía conectado; confianza restaurada a {trust}"),
+    ("scan.completed", "Escaneo de {device} completado: {files} archivos, {bytes} bytes"),
+    ("file.copied", "Archivo copiado a {device}: {file} ({size} bytes)"),
+    ("session.ended", "{device} desconectado tras {duration}s: {added} añadidos, {removed} eliminados, {modified} modificados, {bytes} bytes escritos"),
+    ("profile.switched", "Perfil cambiado a {profile}"),
+    ("report.email", "Informe {kind} de USB Manager del {from} al {to}"),
+    ("database.restored", "Base de datos disponible de nuevo: {count} cambios guardados en memoria se han pasado a {path}"),
+    ("tray.connected", "Dispositivos conectados: {count}"),
+    ("tray.eject", "Expulsar"),
+    ("tray.charge_only", "solo carga"),
+    ("tray.recent_alerts", "Alertas recientes ({count})"),
+    ("tray.pause", "Pausar monitorización"),
+    ("tray.open", "Abrir USB Manager"),
+    ("tray.quit", "Salir"),
+    ("tray.tooltip", "USB Manager: {count} dispositivos conectados"),
+    ("tray.tooltip_paused", "USB Manager: monitorización en pausa"),
+    ("ticker.connected", "{device} conectado"),
+    ("ticker.disconnected", "{device} desconectado"),
+    ("ticker.alert", "Alerta: {message}"),
+    ("ticker.file_taken", "{file} copiado de {device} a este equipo"),
+    ("idle_eject.warning", "{device} no tiene actividad de archivos y se expulsará en {seconds} s"),
+    ("idle_eject.ejected", "{device} se ha expulsado tras {minutes} minutos sin actividad de archivos"),
+];
+
+fn bundle(lang: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    // "es-ES" -> "es"
+    match lang.split(['-', '_']).next().unwrap_or_default().to_lowercase().as_str() {
+        "en" => Some(EN),
+        "es" => Some(ES),
+        _ => None,
+    }
+}
+
+fn lookup(lang: &str, key: &str) -> Option<&'static str> {
+    bundle(lang)?.iter().find(|(k, _)| *k == key).map(|(_, template)| *template)
+}
+
+/// Plantillas de todas las claves en el idioma pedido, completadas con inglés donde falten
+#[tauri::command]
+pub async fn get_locale_bundle(lang: String) -> Result<serde_json::Value, String> {
+    let supported = bundle(&lang).is_some();
+
+    let mut messages: BTreeMap<&str, &str> = EN.iter().copied().collect();
+    if let Some(localized) = bundle(&lang) {
+        messages.extend(localized.iter().copied());
+    }
+
+    Ok(serde_json::json!({
+        "success": true,
+        "lang": if supported { lang.as_str() } else { DEFAULT_LANG },
+        "fallback": DEFAULT_LANG,
+        "messages": messages,
+    }))
+}
@@ -0,0 +1,333 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::audit;
+use crate::db::{AlertSeverity, Database, Device, DeviceSort, DEVICE_VARIANT_SEPARATOR};
+use crate::i18n::Message;
+use crate::kiosk;
+use crate::state::AppState;
+use crate::usb_monitor::UsbDevice;
+
+const IDENTITY_ALERT: &str = "DEVICE_IDENTITY_MISMATCH";
+const MERGE_AUDIT_ACTION: &str = "DEVICES_MERGED";
+const SPLIT_AUDIT_ACTION: &str = "DEVICE_SPLIT";
+const REKEY_AUDIT_ACTION: &str = "DEVICES_REKEYED";
+// Identificadores generados cuando libusb no puede leer el número de serie (ver usb_backend.rs)
+const SYNTHETIC_SERIAL_PREFIX: &str = "DISK_";
+pub const IDENTITY_SETTINGS_KEY: &str = "identity";
+const VOLUME_ID_PREFIX: &str = "VOL_";
+const FINGERPRINT_ID_PREFIX: &str = "FP_";
+
+/// Cómo se identifica un dispositivo en el historial. Según el entorno es más fiable uno u otro:
+/// hay memorias clonadas con el mismo número de serie USB y lectores o carcasas que lo cambian.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityStrategy {
+    /// Número de serie USB; memorias distintas con el mismo número se separan por volumen ("<serie>~<volumen>")
+    #[default]
+    UsbSerial,
+    /// Número de serie del volumen ("VOL_<serie>"): no depende del lector, pero cambia al formatear
+    VolumeSerial,
+    /// Huella de descriptores, número de serie USB y capacidad ("FP_<hash>"): separa clones de otro tamaño
+    Fingerprint,
+}
+
+/// Estrategia de identificación, guardada en settings bajo la clave `identity`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdentitySettings {
+    pub strategy: IdentityStrategy,
+}
+
+impl IdentitySettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(IDENTITY_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+// Identificador según la estrategia; None si es la del número de serie USB o faltan datos para aplicarla
+fn strategy_key(
+    strategy: IdentityStrategy,
+    usb_serial: &str,
+    vendor_id: u16,
+    product_id: u16,
+    capacity: Option<i64>,
+    volume_serial: Option<&str>,
+) -> Option<String> {
+    match strategy {
+        IdentityStrategy::UsbSerial => None,
+        IdentityStrategy::VolumeSerial => volume_serial.map(|serial| format!("{}{}", VOLUME_ID_PREFIX, serial)),
+        IdentityStrategy::Fingerprint => {
+            let mut hasher = Sha256::new();
+            hasher.update(format!("{:04x}:{:04x}:{}:{}", vendor_id, product_id, usb_serial, capacity?));
+            let digest = format!("{:x}", hasher.finalize());
+            Some(format!("{}{}", FINGERPRINT_ID_PREFIX, &digest[..16]))
+        }
+    }
+}
+
+// Diferencias de huella entre el registro conocido y el dispositivo conectado (solo datos presentes en ambos)
+fn fingerprint_changes(known: &Device, current: &Device) -> Vec<&'static str> {
+    let mut changes = Vec::new();
+    if known.vendor_id != current.vendor_id || known.product_id != current.product_id {
+        changes.push("vendor/product ID");
+    }
+    if let (Some(a), Some(b)) = (known.total_capacity, current.total_capacity) {
+        if a != b {
+            changes.push("capacity");
+        }
+    }
+    if let (Some(a), Some(b)) = (&known.volume_serial, &current.volume_serial) {
+        if a != b {
+            changes.push("volume serial");
+        }
+    }
+    changes
+}
+
+/// Comparar el dispositivo conectado con los ya registrados:
+/// - número de serie nuevo con la huella (descriptores + capacidad + serie de volumen) de otro conocido
+/// - número de serie conocido con otra huella
+///
+/// Cualquiera de los dos casos apunta a un dispositivo clonado o reprogramado.
+pub fn check_connection(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    current: &Device,
+    previous: Option<&Device>,
+    activity_id: i64,
+) {
+    let device_id = current.serial_number.as_str();
+    if current.usb_serial.as_deref().unwrap_or(device_id).starts_with(SYNTHETIC_SERIAL_PREFIX) {
+        return;
+    }
+
+    let message = match previous {
+        Some(known) => {
+            let changes = fingerprint_changes(known, current);
+            if changes.is_empty() {
+                return;
+            }
+            Message::new(
+                "alert.fingerprint_changed",
+                serde_json::json!({ "device": device_id, "changes": changes.join(", ") }),
+            )
+        }
+        None => {
+            let (Some(capacity), Some(volume_serial)) = (current.total_capacity, current.volume_serial.as_deref()) else {
+                return;
+            };
+            let matches = match db.find_devices_by_fingerprint(
+                current.vendor_id,
+                current.product_id,
+                capacity,
+                volume_serial,
+                device_id,
+            ) {
+                Ok(matches) => matches,
+                Err(e) => {
+                    println!("[DB] Error looking up device fingerprint: {}", e);
+                    return;
+                }
+            };
+            let known = match matches.iter().find(|d| !d.serial_number.starts_with(SYNTHETIC_SERIAL_PREFIX)) {
+                Some(known) => known,
+                None => return,
+            };
+            Message::new(
+                "alert.serial_changed",
+                serde_json::json!({ "device": device_id, "known": known.serial_number }),
+            )
+        }
+    };
+
+    raise_alert(
+        db,
+        app_handle,
+        Some(device_id),
+        Some(activity_id),
+        IDENTITY_ALERT,
+        AlertSeverity::High,
+        message,
+    );
+}
+
+/// Registro al que pertenece una unidad conectada. Memorias distintas pueden dar el mismo número de serie USB
+/// (clones baratos, o ninguno y entonces sale de la letra y la capacidad): el volumen las separa.
+///
+/// - mismo serie de volumen que un registro conocido: ese registro
+/// - registro sin volumen guardado, o misma capacidad y etiqueta (la misma memoria formateada): ese registro
+/// - si no, otra memoria: registro nuevo "<serie>~<volumen>"
+///
+/// Con otra estrategia de identificación (ajuste `identity`) el registro es el que da la estrategia,
+/// o el de arriba si a la unidad le faltan los datos que usa.
+pub fn resolve_device_id(db: &Database, device: &UsbDevice) -> String {
+    let reported = device.serial_number.clone().unwrap_or_default();
+    let capacity = device.total_space.map(|s| s as i64);
+    let strategy = IdentitySettings::load(db).strategy;
+    if let Some(key) = strategy_key(strategy, &reported, device.vendor_id, device.product_id, capacity, device.volume_serial.as_deref()) {
+        return key;
+    }
+    let Some(volume_serial) = device.volume_serial.as_deref() else {
+        return reported;
+    };
+    let candidates = match db.get_device_variants(&reported) {
+        Ok(candidates) => candidates,
+        Err(e) => {
+            println!("[DB] Error looking up device variants: {}", e);
+            return reported;
+        }
+    };
+    if candidates.is_empty() {
+        return reported;
+    }
+
+    if let Some(known) = candidates.iter().find(|d| d.volume_serial.as_deref() == Some(volume_serial)) {
+        return known.serial_number.clone();
+    }
+
+    let same_stick = |d: &&Device| {
+        d.volume_serial.is_none()
+            || (d.total_capacity == capacity && device.volume_label.is_some() && d.volume_label == device.volume_label)
+    };
+    if let Some(known) = candidates.iter().find(same_stick) {
+        return known.serial_number.clone();
+    }
+
+    let variant = format!("{}{}{}", reported, DEVICE_VARIANT_SEPARATOR, volume_serial);
+    println!("[USB] {} reported by a different volume ({}), recorded as {}", reported, volume_serial, variant);
+    variant
+}
+
+#[tauri::command]
+pub async fn get_device_volumes(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_device_volumes(&device_id) {
+        Ok(volumes) => Ok(serde_json::json!({ "success": true, "volumes": volumes })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Unir dos registros que son la misma memoria: todo el historial de `source` pasa a `target`
+#[tauri::command]
+pub async fn merge_devices(state: tauri::State<'_, AppState>, source_id: String, target_id: String) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    if source_id == target_id {
+        return Err("Cannot merge a device into itself".to_string());
+    }
+
+    let db = &state.db;
+    for id in [&source_id, &target_id] {
+        if db.get_device(id).map_err(|e| format!("Database error: {}", e))?.is_none() {
+            return Err(format!("Device not found: {}", id));
+        }
+    }
+    match db.merge_devices(&source_id, &target_id) {
+        Ok(moved) => {
+            audit::record(db, MERGE_AUDIT_ACTION, Some(&target_id), &serde_json::json!({ "source": source_id, "rows": moved }));
+            Ok(serde_json::json!({ "success": true, "device_id": target_id, "moved": moved }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Separar en un registro nuevo las conexiones (CONNECT) de otra memoria que quedaron mezcladas en `device_id`
+#[tauri::command]
+pub async fn split_device(state: tauri::State<'_, AppState>, device_id: String, activity_ids: Vec<i64>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = &state.db;
+    match db.split_device(&device_id, &activity_ids) {
+        Ok(Some(new_id)) => {
+            audit::record(
+                db,
+                SPLIT_AUDIT_ACTION,
+                Some(&device_id),
+                &serde_json::json!({ "new_device": new_id, "activity_ids": activity_ids }),
+            );
+            Ok(serde_json::json!({ "success": true, "device_id": new_id }))
+        }
+        Ok(None) => Err(format!("No connections of {} to split", device_id)),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Cambio de identificador de un registro al aplicar otra estrategia
+#[derive(Debug, Serialize)]
+pub struct Rekey {
+    pub from: String,
+    pub to: String,
+    /// Otro registro ya tiene (o va a tener) ese identificador y se unen
+    pub merged: bool,
+}
+
+// Nuevos identificadores de los registros con la estrategia indicada (solo los que cambian)
+fn plan_rekey(strategy: IdentityStrategy, devices: &[Device]) -> Vec<Rekey> {
+    let existing: HashSet<&str> = devices.iter().map(|d| d.serial_number.as_str()).collect();
+    // identificador nuevo -> serie de volumen del primer registro que lo recibe
+    let mut assigned: HashMap<String, Option<&str>> = HashMap::new();
+    let mut plan = Vec::new();
+
+    for device in devices {
+        let current = device.serial_number.as_str();
+        // Los registros anteriores a guardar el serie USB usaban el número de serie USB (con la variante de volumen)
+        let usb_serial = device
+            .usb_serial
+            .as_deref()
+            .unwrap_or_else(|| current.split(DEVICE_VARIANT_SEPARATOR).next().unwrap_or(current));
+        let volume_serial = device.volume_serial.as_deref();
+
+        let target = match strategy_key(strategy, usb_serial, device.vendor_id, device.product_id, device.total_capacity, volume_serial) {
+            Some(key) => key,
+            // Las variantes ya separadas por volumen se conservan
+            None if current == usb_serial || current.strip_prefix(usb_serial).is_some_and(|rest| rest.starts_with(DEVICE_VARIANT_SEPARATOR)) => {
+                current.to_string()
+            }
+            None => match (assigned.get(usb_serial), volume_serial) {
+                // Otra memoria con el mismo serie USB: variante por volumen, como al conectar
+                (Some(&other), Some(volume)) if other != Some(volume) => {
+                    format!("{}{}{}", usb_serial, DEVICE_VARIANT_SEPARATOR, volume)
+                }
+                _ => usb_serial.to_string(),
+            },
+        };
+
+        let merged = assigned.contains_key(&target) || (target != current && existing.contains(target.as_str()));
+        assigned.entry(target.clone()).or_insert(volume_serial);
+        if target != current {
+            plan.push(Rekey { from: current.to_string(), to: target, merged });
+        }
+    }
+    plan
+}
+
+/// Volver a identificar los registros con la estrategia configurada en `identity`, moviendo todo su historial.
+/// Con `dry_run` solo devuelve los cambios. Las memorias conectadas conservan su registro hasta reconectarse.
+#[tauri::command]
+pub async fn rekey_devices(state: tauri::State<'_, AppState>, dry_run: Option<bool>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+
+    let strategy = IdentitySettings::load(&db).strategy;
+    let devices = db.get_devices(DeviceSort::default()).map_err(db_err)?;
+    let changes = plan_rekey(strategy, &devices);
+    if dry_run.unwrap_or(false) || changes.is_empty() {
+        return Ok(serde_json::json!({ "success": true, "strategy": strategy, "changes": changes, "moved": 0 }));
+    }
+
+    let mut moved = 0;
+    for change in &changes {
+        moved += db.rekey_device(&change.from, &change.to).map_err(db_err)?;
+    }
+    println!("[Identity] Re-keyed {} devices with strategy {:?} ({} rows)", changes.len(), strategy, moved);
+    audit::record(
+        &db,
+        REKEY_AUDIT_ACTION,
+        None,
+        &serde_json::json!({ "strategy": strategy, "devices": changes.len(), "rows": moved, "changes": changes }),
+    );
+    Ok(serde_json::json!({ "success": true, "strategy": strategy, "changes": changes, "moved": moved }))
+}
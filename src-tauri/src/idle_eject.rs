@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use crate::db::{Database, TrustLevel};
+use crate::eject;
+use crate::events;
+use crate::i18n::Message;
+use crate::ipc;
+use crate::usb_monitor::UsbMonitor;
+
+pub const IDLE_EJECT_SETTINGS_KEY: &str = "idle_eject";
+/// Aviso antes de expulsar, con los segundos que quedan; cualquier actividad de archivos lo cancela
+pub const WARNING_EVENT: &str = "idle-eject-warning";
+pub const EJECTED_EVENT: &str = "device-auto-ejected";
+
+/// Expulsión automática de dispositivos de confianza sin actividad, guardada en settings bajo `idle_eject`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleEjectSettings {
+    pub enabled: bool,
+    /// Minutos sin cambios de archivos (según el watcher) tras los que se expulsa
+    pub idle_minutes: u64,
+    /// Segundos de aviso antes de expulsar
+    pub warning_seconds: u64,
+}
+
+impl Default for IdleEjectSettings {
+    fn default() -> Self {
+        Self { enabled: false, idle_minutes: 30, warning_seconds: 60 }
+    }
+}
+
+impl IdleEjectSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(IDLE_EJECT_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+struct Activity {
+    last: Instant,
+    warned: bool,
+}
+
+// device_id -> última actividad de archivos (o la conexión)
+static ACTIVITY: OnceLock<Mutex<HashMap<String, Activity>>> = OnceLock::new();
+
+fn activity() -> &'static Mutex<HashMap<String, Activity>> {
+    ACTIVITY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Anotar actividad en el dispositivo (conexión o evento del watcher): vuelve a contar desde cero
+pub fn touch(device_id: &str) {
+    activity()
+        .lock()
+        .unwrap()
+        .insert(device_id.to_string(), Activity { last: Instant::now(), warned: false });
+}
+
+pub fn forget(device_id: &str) {
+    activity().lock().unwrap().remove(device_id);
+}
+
+fn publish(monitor: &UsbMonitor, event: &str, payload: serde_json::Value) {
+    ipc::publish(event, &payload);
+    if let Some(ref app_handle) = monitor.app_handle {
+        events::emit(app_handle, event, payload);
+    }
+}
+
+/// Llamado en cada ciclo del monitor: avisar y después expulsar los dispositivos de confianza inactivos.
+/// Los que se están escaneando no cuentan como inactivos.
+pub fn check(monitor: &Arc<UsbMonitor>) {
+    let db = &monitor.db;
+    let settings = IdleEjectSettings::load(db);
+    if !settings.enabled || settings.idle_minutes == 0 {
+        return;
+    }
+    let limit = Duration::from_secs(settings.idle_minutes * 60);
+    let warning = Duration::from_secs(settings.warning_seconds).min(limit);
+
+    let devices = monitor.devices.lock().unwrap().clone();
+    for device_id in devices.iter().filter_map(|d| d.serial_number.clone()) {
+        // Sin watcher no se sabe si se está usando
+        let Some(record) = db.get_device(&device_id).ok().flatten() else { continue };
+        if record.trust_level != TrustLevel::Trusted || record.skip_watch {
+            continue;
+        }
+        if monitor.scans.is_scanning(&device_id) {
+            touch(&device_id);
+            continue;
+        }
+
+        let idle = {
+            let mut activity = activity().lock().unwrap();
+            let entry = activity
+                .entry(device_id.clone())
+                .or_insert_with(|| Activity { last: Instant::now(), warned: false });
+            let idle = entry.last.elapsed();
+            if idle < limit && (idle < limit - warning || entry.warned) {
+                continue;
+            }
+            if idle < limit {
+                entry.warned = true;
+            } else {
+                activity.remove(&device_id);
+            }
+            idle
+        };
+
+        if idle < limit {
+            let seconds = (limit - idle).as_secs();
+            println!("[IdleEject] {} idle, ejecting in {}s", device_id, seconds);
+            publish(monitor, WARNING_EVENT, serde_json::json!({
+                "device_id": device_id,
+                "seconds": seconds,
+                "message": Message::new("idle_eject.warning", serde_json::json!({ "device": device_id, "seconds": seconds })),
+            }));
+            continue;
+        }
+
+        // La expulsión ejecuta herramientas del sistema: fuera del hilo del monitor
+        let monitor = monitor.clone();
+        tauri::async_runtime::spawn_blocking(move || match eject::eject(&monitor, &device_id) {
+            Ok(()) => {
+                println!("[IdleEject] Ejected {} after {} idle minutes", device_id, settings.idle_minutes);
+                publish(&monitor, EJECTED_EVENT, serde_json::json!({
+                    "device_id": device_id,
+                    "idle_minutes": settings.idle_minutes,
+                    "message": Message::new("idle_eject.ejected", serde_json::json!({ "device": device_id, "minutes": settings.idle_minutes })),
+                }));
+            }
+            Err(e) => println!("[IdleEject] Failed to eject {}: {}", device_id, e),
+        });
+    }
+}
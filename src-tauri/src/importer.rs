@@ -0,0 +1,385 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use crate::approval::{self, Approval};
+use crate::db::{Database, TrustLevel};
+use crate::kiosk;
+use crate::state::AppState;
+
+/// Dispositivo histórico leído de una fuente externa (USBDeview o registro de Windows)
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ImportedDevice {
+    pub serial_number: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub drive_letter: Option<String>,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_connect: Option<DateTime<Utc>>,
+    pub last_disconnect: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ImportSummary {
+    pub devices_added: usize,
+    pub devices_existing: usize,
+    pub events_added: usize,
+    pub rows_skipped: usize,
+}
+
+/// Fila de una lista blanca de dispositivos confiables
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WhitelistEntry {
+    pub serial_number: String,
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+    pub nickname: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WhitelistChange {
+    pub serial_number: String,
+    pub nickname: Option<String>,
+    /// "added", "updated" o "unchanged"
+    pub action: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct WhitelistSummary {
+    pub dry_run: bool,
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub rows_skipped: usize,
+    pub changes: Vec<WhitelistChange>,
+}
+
+// Formatos de fecha que USBDeview usa según la configuración regional
+const USBDEVIEW_DATE_FORMATS: &[&str] = &[
+    "%m/%d/%Y %I:%M:%S %p",
+    "%d/%m/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+];
+
+fn parse_local_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+
+    USBDEVIEW_DATE_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(value, fmt).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|local| local.with_timezone(&Utc))
+}
+
+fn parse_hex_id(value: &str) -> u16 {
+    let value = value.trim().trim_start_matches("0x");
+    u16::from_str_radix(value, 16).unwrap_or(0)
+}
+
+fn non_empty(value: Option<&String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Leer un CSV exportado por USBDeview ("Save All Items" en formato CSV con cabecera)
+pub fn parse_usbdeview_csv(path: &Path) -> Result<(Vec<ImportedDevice>, usize), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| format!("Failed to open CSV: {}", e))?;
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV header: {}", e))?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+
+    let mut devices = Vec::new();
+    let mut skipped = 0;
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                println!("[Importer] Skipping malformed row: {}", e);
+                skipped += 1;
+                continue;
+            }
+        };
+
+        let row: HashMap<&str, String> = headers
+            .iter()
+            .map(|h| h.as_str())
+            .zip(record.iter().map(|v| v.to_string()))
+            .collect();
+
+        // Solo interesan dispositivos de almacenamiento
+        if let Some(device_type) = row.get("Device Type") {
+            if !device_type.to_lowercase().contains("mass storage") {
+                skipped += 1;
+                continue;
+            }
+        }
+
+        let serial = match non_empty(row.get("Serial Number")) {
+            Some(s) => s,
+            None => {
+                skipped += 1;
+                continue;
+            }
+        };
+
+        let date = |keys: &[&str]| keys.iter().find_map(|k| row.get(k).and_then(|v| parse_local_date(v)));
+
+        devices.push(ImportedDevice {
+            serial_number: serial,
+            vendor_id: row.get("VendorID").map(|v| parse_hex_id(v)).unwrap_or(0),
+            product_id: row.get("ProductID").map(|v| parse_hex_id(v)).unwrap_or(0),
+            name: non_empty(row.get("Product Name")).or_else(|| non_empty(row.get("Description"))),
+            manufacturer: non_empty(row.get("Vendor Name")).or_else(|| non_empty(row.get("Device Mfg"))),
+            drive_letter: non_empty(row.get("Drive Letter")),
+            first_seen: date(&["First Install Time", "Install Time", "Created Date"]),
+            last_connect: date(&["Connect Time", "Last Plug/Unplug Date"]),
+            last_disconnect: date(&["Disconnect Time"]),
+        });
+    }
+
+    println!("[Importer] Parsed {} devices from USBDeview CSV ({} skipped)", devices.len(), skipped);
+    Ok((devices, skipped))
+}
+
+// Primer campo no vacío entre los nombres de columna aceptados
+fn first_field(row: &HashMap<String, String>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|k| non_empty(row.get(*k)))
+}
+
+/// Leer una lista blanca en CSV con cabecera: serial (obligatorio), vid, pid y nickname.
+/// Los nombres de columna no distinguen mayúsculas y admiten variantes habituales.
+pub fn parse_whitelist_csv(path: &Path) -> Result<(Vec<WhitelistEntry>, usize), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| format!("Failed to open CSV: {}", e))?;
+
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV header: {}", e))?
+        .iter()
+        .map(|h| h.to_lowercase().replace([' ', '-'], "_"))
+        .collect();
+
+    if !headers.iter().any(|h| h == "serial" || h == "serial_number") {
+        return Err("The CSV needs a 'serial' column".to_string());
+    }
+
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+
+    for record in reader.records() {
+        let Ok(record) = record else {
+            skipped += 1;
+            continue;
+        };
+
+        let row: HashMap<String, String> = headers
+            .iter()
+            .cloned()
+            .zip(record.iter().map(|v| v.to_string()))
+            .collect();
+
+        let Some(serial_number) = first_field(&row, &["serial", "serial_number"]) else {
+            skipped += 1;
+            continue;
+        };
+
+        let hex = |keys: &[&str]| first_field(&row, keys).map(|v| parse_hex_id(&v)).filter(|v| *v != 0);
+        entries.push(WhitelistEntry {
+            serial_number,
+            vendor_id: hex(&["vid", "vendor_id", "vendorid"]),
+            product_id: hex(&["pid", "product_id", "productid"]),
+            nickname: first_field(&row, &["nickname", "name", "label"]),
+        });
+    }
+
+    println!("[Importer] Parsed {} whitelist entries ({} skipped)", entries.len(), skipped);
+    Ok((entries, skipped))
+}
+
+/// Leer el historial de USBSTOR / USB / MountedDevices del registro de Windows
+#[cfg(windows)]
+pub fn read_registry_history() -> Result<Vec<ImportedDevice>, String> {
+    use winreg::enums::HKEY_LOCAL_MACHINE;
+    use winreg::RegKey;
+
+    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+    let usbstor = hklm
+        .open_subkey("SYSTEM\\CurrentControlSet\\Enum\\USBSTOR")
+        .map_err(|e| format!("Cannot open USBSTOR key: {}", e))?;
+
+    // Serial -> (VID, PID) a partir de Enum\USB\VID_xxxx&PID_yyyy\<serial>
+    let mut usb_ids: HashMap<String, (u16, u16)> = HashMap::new();
+    if let Ok(usb) = hklm.open_subkey("SYSTEM\\CurrentControlSet\\Enum\\USB") {
+        for vid_pid in usb.enum_keys().flatten() {
+            let upper = vid_pid.to_uppercase();
+            let vid = upper.split("VID_").nth(1).map(|v| parse_hex_id(&v[..v.len().min(4)]));
+            let pid = upper.split("PID_").nth(1).map(|p| parse_hex_id(&p[..p.len().min(4)]));
+            if let (Some(vid), Some(pid), Ok(key)) = (vid, pid, usb.open_subkey(&vid_pid)) {
+                for serial in key.enum_keys().flatten() {
+                    usb_ids.insert(serial.to_uppercase(), (vid, pid));
+                }
+            }
+        }
+    }
+
+    // Serial -> letra de unidad a partir de MountedDevices
+    let mut drive_letters: HashMap<String, String> = HashMap::new();
+    if let Ok(mounted) = hklm.open_subkey("SYSTEM\\MountedDevices") {
+        for (name, value) in mounted.enum_values().flatten() {
+            let Some(letter) = name.strip_prefix("\\DosDevices\\") else { continue };
+            let utf16: Vec<u16> = value
+                .bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            let target = String::from_utf16_lossy(&utf16).to_uppercase();
+            if let Some(rest) = target.split("USBSTOR#").nth(1) {
+                if let Some(instance) = rest.split('#').nth(1) {
+                    drive_letters.insert(registry_serial(instance), letter.to_string());
+                }
+            }
+        }
+    }
+
+    let mut devices = Vec::new();
+    for class in usbstor.enum_keys().flatten() {
+        let Ok(class_key) = usbstor.open_subkey(&class) else { continue };
+        let vendor = class_field(&class, "Ven_");
+        let product = class_field(&class, "Prod_");
+
+        for instance in class_key.enum_keys().flatten() {
+            let Ok(key) = class_key.open_subkey(&instance) else { continue };
+            let serial = registry_serial(&instance);
+            let friendly: Option<String> = key.get_value("FriendlyName").ok();
+            let last_write = key
+                .query_info()
+                .ok()
+                .map(|info| Utc.from_utc_datetime(&info.get_last_write_time_chrono()));
+            let (vendor_id, product_id) = usb_ids.get(&serial).copied().unwrap_or((0, 0));
+
+            devices.push(ImportedDevice {
+                serial_number: serial.clone(),
+                vendor_id,
+                product_id,
+                name: friendly.or_else(|| product.clone()),
+                manufacturer: vendor.clone(),
+                drive_letter: drive_letters.get(&serial).cloned(),
+                first_seen: None,
+                last_connect: last_write,
+                last_disconnect: None,
+            });
+        }
+    }
+
+    println!("[Importer] Found {} devices in registry", devices.len());
+    Ok(devices)
+}
+
+#[cfg(not(windows))]
+pub fn read_registry_history() -> Result<Vec<ImportedDevice>, String> {
+    Err("Registry import is only available on Windows".to_string())
+}
+
+// "4C530001230512116303&0" -> "4C530001230512116303"
+#[cfg(windows)]
+fn registry_serial(instance: &str) -> String {
+    instance
+        .rsplit_once('&')
+        .map(|(serial, _)| serial)
+        .unwrap_or(instance)
+        .to_uppercase()
+}
+
+// "Disk&Ven_SanDisk&Prod_Cruzer_Blade&Rev_1.00" -> campo pedido, con '_' como espacios
+#[cfg(windows)]
+fn class_field(class: &str, prefix: &str) -> Option<String> {
+    class
+        .split('&')
+        .find_map(|part| part.strip_prefix(prefix))
+        .map(|v| v.replace('_', " ").trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[tauri::command]
+pub async fn import_usb_history(state: tauri::State<'_, AppState>, source: String, path: Option<String>) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    let (devices, skipped) = match source.as_str() {
+        "usbdeview" => {
+            let path = path.ok_or("A CSV path is required for USBDeview imports")?;
+            parse_usbdeview_csv(Path::new(&path))?
+        }
+        "registry" => (read_registry_history()?, 0),
+        other => return Err(format!("Unknown import source: {}", other)),
+    };
+
+    let mut summary = db
+        .import_device_history(&devices, &source)
+        .map_err(|e| format!("Database error: {}", e))?;
+    summary.rows_skipped += skipped;
+
+    Ok(serde_json::json!({ "success": true, "summary": summary, "devices": devices }))
+}
+
+#[tauri::command]
+pub async fn import_whitelist_csv(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    dry_run: Option<bool>,
+    approval: Option<Approval>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let dry_run = dry_run.unwrap_or(false);
+
+    let (entries, skipped) = parse_whitelist_csv(Path::new(&path))?;
+    let mut summary = apply_whitelist(&db, &entries, dry_run, approval.as_ref())?;
+    summary.rows_skipped = skipped;
+
+    Ok(serde_json::json!({ "success": true, "summary": summary }))
+}
+
+/// Marcar como confiables los dispositivos de una lista blanca (CSV o lista firmada)
+pub fn apply_whitelist(
+    db: &Database,
+    entries: &[WhitelistEntry],
+    dry_run: bool,
+    approval: Option<&Approval>,
+) -> Result<WhitelistSummary, String> {
+    // Importar marca los dispositivos como de confianza: en modo estricto necesita aprobación
+    let ids: Vec<String> = entries.iter().map(|e| e.serial_number.clone()).collect();
+    let method = if dry_run {
+        None
+    } else {
+        approval::authorize(db, &ids, TrustLevel::Trusted, approval)?
+    };
+    let previous: Vec<(String, TrustLevel)> = ids
+        .iter()
+        .map(|id| (id.clone(), db.get_device_trust(id).unwrap_or_default()))
+        .collect();
+
+    let summary = db
+        .import_whitelist(entries, dry_run)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if let Some(method) = method {
+        approval::record_approved(db, &previous, TrustLevel::Trusted, method, approval);
+    }
+    Ok(summary)
+}
@@ -0,0 +1,91 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tauri::AppHandle;
+use crate::autostart;
+use crate::ipc;
+use crate::tray;
+
+// Una sola interfaz por usuario: dos monitores a la vez registrarían cada evento dos veces en la misma base de datos.
+// La instancia en marcha es la que tiene el bloqueo del archivo de instancia, que el sistema suelta al terminar
+// el proceso. Se toma antes de arrancar nada, así que dos lanzamientos a la vez no pueden pasar los dos.
+
+pub const SHOW_WINDOW_METHOD: &str = "show_window";
+// La otra instancia puede tener el bloqueo sin haber abierto aún su canal IPC
+const FORWARD_ATTEMPTS: u32 = 10;
+const FORWARD_RETRY_DELAY: Duration = Duration::from_millis(300);
+
+static APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
+static LOCK: OnceLock<File> = OnceLock::new();
+
+// Junto al canal IPC de usuario; en Windows en el directorio temporal, que también es de cada usuario
+fn lock_path() -> PathBuf {
+    if cfg!(windows) {
+        let user = std::env::var("USERNAME").unwrap_or_else(|_| "default".to_string());
+        std::env::temp_dir().join(format!("usb-manager-{}.lock", user))
+    } else {
+        PathBuf::from(format!("{}.lock", ipc::user_endpoint()))
+    }
+}
+
+/// Tomar el bloqueo de instancia para todo el proceso. Si ya lo tiene otra interfaz, pedirle que muestre
+/// su ventana y devolver true: esta debe terminar.
+pub fn forward_to_running() -> bool {
+    let path = lock_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let file = match OpenOptions::new().create(true).truncate(false).write(true).open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            // Sin archivo no se puede saber: mejor arrancar que no abrir nunca
+            println!("[Instance] Failed to open {:?}: {}", path, e);
+            return false;
+        }
+    };
+    match file.try_lock() {
+        Ok(()) => {
+            let _ = LOCK.set(file);
+            false
+        }
+        Err(TryLockError::WouldBlock) => {
+            println!("[Instance] USB Manager is already running ({:?}); exiting", path);
+            show_running();
+            true
+        }
+        Err(TryLockError::Error(e)) => {
+            println!("[Instance] Failed to lock {:?}: {}", path, e);
+            false
+        }
+    }
+}
+
+fn show_running() {
+    // Lanzada al iniciar sesión con otra ya abierta: salir sin abrir ninguna ventana
+    let show = !autostart::launched_at_login();
+    let endpoint = ipc::user_endpoint();
+    for _ in 0..FORWARD_ATTEMPTS {
+        let request = ipc::call(&endpoint, SHOW_WINDOW_METHOD, serde_json::json!({ "show": show }));
+        if tauri::async_runtime::block_on(request).is_ok() {
+            return;
+        }
+        std::thread::sleep(FORWARD_RETRY_DELAY);
+    }
+    println!("[Instance] The running instance did not answer on {}", endpoint);
+}
+
+/// Guardar el AppHandle para atender las peticiones de otras instancias
+pub fn register(app: &AppHandle) {
+    let _ = APP_HANDLE.set(app.clone());
+}
+
+/// Método IPC `show_window`: otra instancia se ha intentado abrir
+pub fn show_window(params: &serde_json::Value) -> Result<serde_json::Value, String> {
+    let app = APP_HANDLE.get().ok_or("No window in this process")?;
+    let show = params.get("show").and_then(|v| v.as_bool()).unwrap_or(true);
+    if show {
+        tray::show_window(app);
+    }
+    Ok(serde_json::json!({ "shown": show }))
+}
@@ -0,0 +1,311 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast;
+use crate::approval;
+use crate::cloud_backup;
+use crate::instance;
+use crate::kiosk;
+use crate::plugins;
+use crate::privacy;
+use crate::routing;
+use crate::trust_list;
+use crate::usb_monitor::UsbMonitor;
+
+// Protocolo: un objeto JSON por línea en cada sentido.
+//   -> {"id": 1, "method": "ping", "params": {}}
+//   <- {"id": 1, "result": {...}}  o  {"id": 1, "error": "..."}
+//
+// Métodos: ping, list_devices, get_history {limit?, device_id?}, get_settings, set_setting {key, value},
+// show_window {show?}, subscribe_events {events?}. Tras suscribirse, la conexión recibe además líneas
+//   <- {"event": "usb-connected", "payload": {...}}
+// con los mismos eventos que la interfaz (filtrados por nombre si se pasa `events`).
+
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+const EVENT_BUFFER: usize = 256;
+const DEFAULT_HISTORY_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: serde_json::Value,
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    id: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Evento enviado a los clientes suscritos
+#[derive(Debug, Serialize, Clone)]
+pub struct IpcEvent {
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+static EVENTS: OnceLock<broadcast::Sender<IpcEvent>> = OnceLock::new();
+
+fn events() -> &'static broadcast::Sender<IpcEvent> {
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_BUFFER).0)
+}
+
+/// Reenviar un evento a los clientes IPC suscritos (no hace nada si no hay ninguno)
+pub fn publish<S: Serialize>(event: &str, payload: &S) {
+    let sender = events();
+    if sender.receiver_count() == 0 {
+        return;
+    }
+    if let Ok(payload) = serde_json::to_value(payload) {
+        let _ = sender.send(IpcEvent { event: event.to_string(), payload });
+    }
+}
+
+/// Socket Unix o named pipe donde escucha el servicio
+pub fn service_endpoint() -> String {
+    if let Ok(endpoint) = std::env::var("USB_MANAGER_SOCKET") {
+        return endpoint;
+    }
+    if cfg!(windows) {
+        r"\\.\pipe\usb-manager".to_string()
+    } else if cfg!(target_os = "linux") {
+        "/run/usb-manager/usb-manager.sock".to_string()
+    } else {
+        "/var/run/usb-manager.sock".to_string()
+    }
+}
+
+/// Canal IPC de la interfaz cuando no hay servicio: uno por usuario, para scripts y otras herramientas
+pub fn user_endpoint() -> String {
+    if cfg!(windows) {
+        let user = std::env::var("USERNAME").unwrap_or_else(|_| "default".to_string());
+        format!(r"\\.\pipe\usb-manager-{}", user)
+    } else if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+        format!("{}/usb-manager.sock", runtime_dir)
+    } else {
+        let user = std::env::var("USER").unwrap_or_else(|_| "default".to_string());
+        std::env::temp_dir()
+            .join(format!("usb-manager-{}.sock", user))
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+fn param_str<'a>(params: &'a serde_json::Value, name: &str) -> Result<&'a str, String> {
+    params
+        .get(name)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| format!("Missing parameter '{}'", name))
+}
+
+fn dispatch(method: &str, params: &serde_json::Value, monitor: &UsbMonitor) -> Result<serde_json::Value, String> {
+    // Dispositivos conectados ahora mismo, según el monitor de este proceso
+    if method == "list_devices" {
+        let devices = monitor.devices.lock().unwrap().clone();
+        return Ok(serde_json::json!({ "devices": devices }));
+    }
+
+    if method == instance::SHOW_WINDOW_METHOD {
+        return instance::show_window(params);
+    }
+
+    let db = &monitor.db;
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+
+    match method {
+        "ping" => Ok(serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "machine": crate::machine::current(),
+            "database": db.path(),
+        })),
+        "get_history" => {
+            let limit = params.get("limit").and_then(|v| v.as_i64()).unwrap_or(DEFAULT_HISTORY_LIMIT);
+            let device_id = params.get("device_id").and_then(|v| v.as_str());
+            let case_reference = params.get("case_reference").and_then(|v| v.as_str());
+            let history = db.get_activity_history(limit, device_id, case_reference).map_err(db_err)?;
+            Ok(serde_json::json!({ "history": history }))
+        }
+        "get_settings" => {
+            let mut settings = db.get_all_settings().map_err(db_err)?;
+            approval::redact(&mut settings);
+            cloud_backup::redact(&mut settings);
+            privacy::redact(&mut settings);
+            routing::redact(&mut settings);
+            trust_list::redact(&mut settings);
+            Ok(serde_json::Value::Object(settings))
+        }
+        "set_setting" => {
+            let key = param_str(params, "key")?;
+            kiosk::check_token(params.get("unlock_token").and_then(|v| v.as_str()))?;
+            approval::check_writable(key)?;
+            cloud_backup::check_writable(key)?;
+            privacy::check_writable(key)?;
+            trust_list::check_writable(key)?;
+            let value = params.get("value").cloned().unwrap_or_default();
+            let stored = if key == routing::ROUTING_SETTINGS_KEY { routing::seal_setting(db, &value)? } else { value.clone() };
+            db.set_setting(key, &stored).map_err(db_err)?;
+            if key == plugins::PLUGIN_SETTINGS_KEY {
+                plugins::reload(db);
+            }
+            Ok(serde_json::json!({ "key": key, "value": value }))
+        }
+        _ => Err(format!("Unknown method '{}'", method)),
+    }
+}
+
+// Suscripción de una conexión: receptor y nombres de evento aceptados (vacío = todos)
+type Subscription = (broadcast::Receiver<IpcEvent>, Vec<String>);
+
+// Siguiente evento para la suscripción; sin suscripción no termina nunca
+async fn next_event(subscription: &mut Option<Subscription>) -> IpcEvent {
+    let (receiver, filter) = match subscription {
+        Some(subscription) => subscription,
+        None => return std::future::pending().await,
+    };
+
+    loop {
+        match receiver.recv().await {
+            Ok(event) if filter.is_empty() || filter.contains(&event.event) => return event,
+            Ok(_) => continue,
+            // Cliente demasiado lento: avisarle de cuántos eventos ha perdido
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                return IpcEvent { event: "lagged".to_string(), payload: serde_json::json!({ "missed": missed }) };
+            }
+            Err(broadcast::error::RecvError::Closed) => return std::future::pending().await,
+        }
+    }
+}
+
+fn subscribe(params: &serde_json::Value) -> Subscription {
+    let filter = params
+        .get("events")
+        .and_then(|v| v.as_array())
+        .map(|events| events.iter().filter_map(|e| e.as_str().map(String::from)).collect())
+        .unwrap_or_default();
+    (events().subscribe(), filter)
+}
+
+async fn handle_connection<S>(stream: S, monitor: Arc<UsbMonitor>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    let mut subscription: Option<Subscription> = None;
+
+    loop {
+        let out = tokio::select! {
+            line = lines.next_line() => {
+                let line = match line {
+                    Ok(Some(line)) => line,
+                    _ => break,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let response = match serde_json::from_str::<Request>(&line) {
+                    Ok(request) if request.method == "subscribe_events" => {
+                        let (receiver, filter) = subscribe(&request.params);
+                        let result = serde_json::json!({ "subscribed": true, "events": filter });
+                        subscription = Some((receiver, filter));
+                        Response { id: request.id, result: Some(result), error: None }
+                    }
+                    Ok(request) => match dispatch(&request.method, &request.params, &monitor) {
+                        Ok(result) => Response { id: request.id, result: Some(result), error: None },
+                        Err(e) => Response { id: request.id, result: None, error: Some(e) },
+                    },
+                    Err(e) => Response { id: serde_json::Value::Null, result: None, error: Some(format!("Invalid request: {}", e)) },
+                };
+                serde_json::to_string(&response)
+            }
+            event = next_event(&mut subscription) => serde_json::to_string(&event),
+        };
+
+        let mut out = out.unwrap_or_default();
+        out.push('\n');
+        if writer.write_all(out.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Atender clientes locales hasta que se cancele la tarea
+#[cfg(unix)]
+pub async fn serve(endpoint: String, monitor: Arc<UsbMonitor>) -> std::io::Result<()> {
+    use tokio::net::UnixListener;
+
+    // Un socket que quedó de una ejecución anterior impide hacer bind; si alguien contesta, sigue en uso
+    if tokio::net::UnixStream::connect(&endpoint).await.is_ok() {
+        return Err(std::io::Error::new(std::io::ErrorKind::AddrInUse, format!("{} is in use", endpoint)));
+    }
+    let _ = std::fs::remove_file(&endpoint);
+    if let Some(dir) = std::path::Path::new(&endpoint).parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    let listener = UnixListener::bind(&endpoint)?;
+    println!("[IPC] Listening on {}", endpoint);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(handle_connection(stream, monitor.clone()));
+    }
+}
+
+#[cfg(windows)]
+pub async fn serve(endpoint: String, monitor: Arc<UsbMonitor>) -> std::io::Result<()> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut server = ServerOptions::new().first_pipe_instance(true).create(&endpoint)?;
+    println!("[IPC] Listening on {}", endpoint);
+
+    loop {
+        server.connect().await?;
+        // Crear la siguiente instancia antes de pasar la conectada al manejador
+        let connected = std::mem::replace(&mut server, ServerOptions::new().create(&endpoint)?);
+        tokio::spawn(handle_connection(connected, monitor.clone()));
+    }
+}
+
+async fn call_inner(endpoint: &str, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+    #[cfg(unix)]
+    let stream = tokio::net::UnixStream::connect(endpoint).await.map_err(|e| e.to_string())?;
+    #[cfg(windows)]
+    let stream = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(endpoint)
+        .map_err(|e| e.to_string())?;
+
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut request = serde_json::to_string(&serde_json::json!({ "id": 1, "method": method, "params": params }))
+        .map_err(|e| e.to_string())?;
+    request.push('\n');
+    writer.write_all(request.as_bytes()).await.map_err(|e| e.to_string())?;
+
+    let line = BufReader::new(reader)
+        .lines()
+        .next_line()
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Connection closed by service")?;
+    let response: serde_json::Value = serde_json::from_str(&line).map_err(|e| e.to_string())?;
+
+    match response.get("error").and_then(|e| e.as_str()) {
+        Some(error) => Err(error.to_string()),
+        None => Ok(response.get("result").cloned().unwrap_or_default()),
+    }
+}
+
+/// Llamar a un método del servicio (usado por la interfaz cuando hay un servicio en marcha)
+pub async fn call(endpoint: &str, method: &str, params: serde_json::Value) -> Result<serde_json::Value, String> {
+    tokio::time::timeout(CALL_TIMEOUT, call_inner(endpoint, method, params))
+        .await
+        .map_err(|_| format!("IPC call '{}' timed out", method))?
+}
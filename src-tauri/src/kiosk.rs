@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use crate::audit;
+use crate::state::AppState;
+
+const KIOSK_FILE: &str = "kiosk.json";
+// Tras desbloquear, la configuración se puede cambiar durante este tiempo
+const UNLOCK_DURATION: Duration = Duration::from_secs(10 * 60);
+const UNLOCKED_AUDIT_ACTION: &str = "CONFIG_UNLOCKED";
+const UNLOCK_DENIED_AUDIT_ACTION: &str = "CONFIG_UNLOCK_DENIED";
+
+/// Modo bloqueado (kiosco) para equipos compartidos o públicos. Se activa al desplegar con
+/// `--kiosk-token`, que deja el hash del token en una ruta del sistema que los usuarios no pueden modificar.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+struct KioskConfig {
+    /// SHA-256 en hexadecimal del token de desbloqueo
+    token_sha256: Option<String>,
+}
+
+static CONFIG: OnceLock<KioskConfig> = OnceLock::new();
+// Token aceptado y hasta cuándo vale; se guarda para reenviarlo al servicio
+static UNLOCKED: Mutex<Option<(Instant, String)>> = Mutex::new(None);
+
+/// Configuración de despliegue, común a todos los usuarios (no depende de `USB_MANAGER_DATA_DIR`)
+pub fn config_path() -> PathBuf {
+    if cfg!(windows) {
+        std::env::var("ProgramData")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(r"C:\ProgramData"))
+            .join(crate::service::SERVICE_NAME)
+            .join(KIOSK_FILE)
+    } else {
+        PathBuf::from("/etc/usb-manager").join(KIOSK_FILE)
+    }
+}
+
+// Se lee una sola vez: borrar el archivo con la app abierta no la desbloquea
+fn config() -> &'static KioskConfig {
+    CONFIG.get_or_init(|| {
+        let config: KioskConfig = std::fs::read_to_string(config_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        if config.token_sha256.is_some() {
+            println!("[Kiosk] Configuration is locked");
+        }
+        config
+    })
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
+
+pub fn enabled() -> bool {
+    config().token_sha256.is_some()
+}
+
+fn token_matches(token: &str) -> bool {
+    config().token_sha256.as_deref().is_some_and(|hash| hash.eq_ignore_ascii_case(&hash_token(token)))
+}
+
+/// Comprobar un token recibido en la propia petición (IPC, sin sesión desbloqueada)
+pub fn check_token(token: Option<&str>) -> Result<(), String> {
+    if !enabled() || token.is_some_and(token_matches) {
+        return Ok(());
+    }
+    Err("Configuration is locked: an unlock token is required".to_string())
+}
+
+/// Rechazar los comandos que cambian la configuración salvo que se haya desbloqueado con el token
+pub fn ensure_unlocked() -> Result<(), String> {
+    check_token(session_token().as_deref())
+}
+
+/// Token de la sesión desbloqueada, si sigue vigente
+pub fn session_token() -> Option<String> {
+    let mut unlocked = UNLOCKED.lock().unwrap();
+    match unlocked.as_ref() {
+        Some((until, token)) if Instant::now() < *until => Some(token.clone()),
+        _ => {
+            *unlocked = None;
+            None
+        }
+    }
+}
+
+/// Escribir (o quitar con `None`) el token de desbloqueo al desplegar; requiere permisos de administrador
+pub fn write_token(token: Option<&str>) -> Result<PathBuf, String> {
+    let path = config_path();
+    match token.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(token) => {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+            }
+            let config = KioskConfig { token_sha256: Some(hash_token(token)) };
+            let content = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+            std::fs::write(&path, content).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        }
+        None => {
+            if path.exists() {
+                std::fs::remove_file(&path).map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
+            }
+        }
+    }
+    Ok(path)
+}
+
+#[tauri::command]
+pub async fn get_kiosk_status() -> Result<serde_json::Value, String> {
+    Ok(serde_json::json!({
+        "success": true,
+        "locked": enabled(),
+        "unlocked": enabled() && session_token().is_some(),
+    }))
+}
+
+#[tauri::command]
+pub async fn unlock_configuration(state: tauri::State<'_, AppState>, token: String) -> Result<serde_json::Value, String> {
+    if !enabled() {
+        return Ok(serde_json::json!({ "success": true, "locked": false }));
+    }
+
+    let accepted = token_matches(&token);
+    let db = &state.db;
+    let action = if accepted { UNLOCKED_AUDIT_ACTION } else { UNLOCK_DENIED_AUDIT_ACTION };
+    audit::record(db, action, None, &serde_json::json!({ "os_user": crate::os_session::OsSession::current().user }));
+    if !accepted {
+        return Err("Invalid unlock token".to_string());
+    }
+
+    *UNLOCKED.lock().unwrap() = Some((Instant::now() + UNLOCK_DURATION, token.trim().to_string()));
+    Ok(serde_json::json!({ "success": true, "locked": true, "expires_in": UNLOCK_DURATION.as_secs() }))
+}
+
+/// Volver a bloquear antes de que venza el desbloqueo
+#[tauri::command]
+pub async fn lock_configuration() -> Result<serde_json::Value, String> {
+    *UNLOCKED.lock().unwrap() = None;
+    Ok(serde_json::json!({ "success": true }))
+}
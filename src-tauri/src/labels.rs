@@ -0,0 +1,104 @@
+use qrcode::{Color, QrCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::db::Device;
+use crate::state::AppState;
+
+const QUIET_ZONE: usize = 4;
+const MODULE_PX: usize = 8;
+
+/// Contenido codificado en la etiqueta QR de un dispositivo
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceLabel {
+    pub v: u8,
+    pub fingerprint: String,
+    pub serial: String,
+    pub vid: u16,
+    pub pid: u16,
+    #[serde(default)]
+    pub nickname: Option<String>,
+}
+
+/// Huella corta del dispositivo: primeros 16 caracteres del SHA-256 de "vid:pid:serial"
+pub fn device_fingerprint(device: &Device) -> String {
+    let digest = Sha256::digest(
+        format!("{:04x}:{:04x}:{}", device.vendor_id, device.product_id, device.serial_number).as_bytes(),
+    );
+    digest.iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn device_label(device: &Device) -> DeviceLabel {
+    DeviceLabel {
+        v: 1,
+        fingerprint: device_fingerprint(device),
+        serial: device.serial_number.clone(),
+        vid: device.vendor_id,
+        pid: device.product_id,
+        nickname: device.nickname.clone(),
+    }
+}
+
+/// Generar un PNG en escala de grises con el código QR del texto dado
+pub fn render_qr_png(data: &str) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| format!("QR error: {}", e))?;
+    let modules = code.width();
+    let colors = code.to_colors();
+
+    let size = (modules + QUIET_ZONE * 2) * MODULE_PX;
+    let mut pixels = vec![255u8; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+        let x0 = (i % modules + QUIET_ZONE) * MODULE_PX;
+        let y0 = (i / modules + QUIET_ZONE) * MODULE_PX;
+        for y in y0..y0 + MODULE_PX {
+            pixels[y * size + x0..y * size + x0 + MODULE_PX].fill(0);
+        }
+    }
+
+    let mut png_bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_bytes, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| format!("PNG error: {}", e))?;
+        writer.write_image_data(&pixels).map_err(|e| format!("PNG error: {}", e))?;
+    }
+
+    Ok(png_bytes)
+}
+
+#[tauri::command]
+pub async fn get_device_qr(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    let device = db
+        .get_device(&device_id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Device not found: {}", device_id))?;
+
+    let payload = serde_json::to_string(&device_label(&device)).map_err(|e| e.to_string())?;
+    let png = render_qr_png(&payload)?;
+
+    Ok(serde_json::json!({ "success": true, "payload": payload, "png": png }))
+}
+
+/// Buscar el dispositivo de una etiqueta escaneada y comprobar que su huella coincide
+#[tauri::command]
+pub async fn match_device_qr(state: tauri::State<'_, AppState>, payload: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    let label: DeviceLabel = serde_json::from_str(&payload).map_err(|e| format!("Invalid label: {}", e))?;
+    let device = db
+        .get_device(&label.serial)
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    match device {
+        Some(device) => {
+            let matches = device_fingerprint(&device) == label.fingerprint;
+            Ok(serde_json::json!({ "success": true, "found": true, "matches": matches, "device": device }))
+        }
+        None => Ok(serde_json::json!({ "success": true, "found": false, "matches": false, "label": label })),
+    }
+}
@@ -1,7 +1,82 @@
 mod usb_monitor;
 mod db;
+mod alerts;
+mod analytics;
+mod anomaly;
+mod approval;
+mod attachments;
+mod audit;
+mod autostart;
+mod backup;
+mod baseline;
+mod bulk;
+mod cases;
+mod clipboard;
+mod cloud_backup;
+mod compare;
+mod device_tree;
+mod diagnostics;
+mod doc_metadata;
+mod eject;
+mod events;
 mod file_scanner;
 mod file_watcher;
+mod flapping;
+mod grants;
+mod hash_sets;
+mod hashing;
+mod hotplug;
+mod i18n;
+mod identity;
+mod idle_eject;
+mod importer;
+mod instance;
+mod ipc;
+mod kiosk;
+mod labels;
+mod machine;
+mod manifest;
+mod notifications;
+mod oplog;
+mod os_session;
+mod outbox;
+mod overlay;
+mod persistence;
+mod plugins;
+mod privacy;
+mod profiles;
+mod report_markdown;
+mod report_pdf;
+mod report_xlsx;
+mod removal;
+mod reports;
+mod routing;
+mod rules;
+mod saved_searches;
+mod scan_export;
+mod scan_queue;
+mod scripts;
+mod search;
+mod secrets;
+mod service;
+mod sessions;
+mod settings;
+mod shadow_copy;
+mod state;
+mod timeline_export;
+mod transfers;
+mod tray;
+mod trust_list;
+mod usn;
+mod usb_backend;
+#[cfg(target_os = "linux")]
+mod usb_linux;
+#[cfg(windows)]
+mod usb_windows;
+mod verify;
+mod watchlists;
+mod write_block;
+mod writer_process;
 
 use std::sync::Arc;
 use usb_monitor::{
@@ -9,11 +84,85 @@ use usb_monitor::{
     start_usb_monitoring,
     get_device_history,
     get_registered_devices,
+    get_device_descriptors,
+    set_device_monitoring,
     get_file_snapshots,
     get_device_files,
     get_device_all_scans,
+    get_interrupted_scans,
+    get_scan_states,
+    get_monitoring_status,
+    set_monitoring_paused,
+    simulate_device_event,
 };
-use db::init_database;
+use alerts::get_alerts;
+use analytics::{get_activity_analytics, get_activity_heatmap, get_device_growth};
+use anomaly::get_device_baseline;
+use approval::{configure_approval, get_approval_status};
+use attachments::{
+    add_device_attachment, delete_device_attachment, get_device_attachment_content, get_device_attachments,
+};
+use audit::get_audit_log;
+use autostart::{get_autostart_status, set_autostart};
+use backup::{
+    delete_backup_profile, get_backup_history, get_backup_profile, get_sync_conflicts, resolve_sync_conflict, run_backup_now,
+    set_backup_profile,
+};
+use baseline::{clear_device_baseline, compare_to_baseline, set_device_baseline};
+use bulk::{acknowledge_alerts, clear_all_history, delete_scans, request_clear_token, set_trust_for_devices};
+use cases::{get_case, get_case_references, set_case_reference};
+use clipboard::copy_snapshot_to_clipboard;
+use cloud_backup::{
+    configure_cloud_backup, get_cloud_backup_status, list_cloud_backups, restore_cloud_backup, run_cloud_backup_now,
+};
+use compare::{compare_devices, get_latest_changes};
+use db::Database;
+use device_tree::get_device_tree;
+use diagnostics::run_diagnostics;
+use doc_metadata::get_document_metadata;
+use eject::eject_device;
+use events::get_events_since;
+use grants::{get_access_grants, grant_temporary_access, revoke_access_grant};
+use hash_sets::{delete_hash_set, get_hash_sets, get_scan_hash_matches, import_hash_set};
+use i18n::get_locale_bundle;
+use identity::{get_device_volumes, merge_devices, rekey_devices, split_device};
+use importer::{import_usb_history, import_whitelist_csv};
+use kiosk::{get_kiosk_status, lock_configuration, unlock_configuration};
+use labels::{get_device_qr, match_device_qr};
+use machine::get_machines;
+use manifest::{export_device_manifest, verify_device_manifest};
+use notifications::get_notification_status;
+use oplog::{export_oplog, merge_oplog};
+use outbox::{delete_outbox_message, get_outbox, retry_outbox_message, test_notification_channel};
+use overlay::{get_window_mode, set_always_on_top, set_compact_mode};
+use persistence::retry_db_init;
+use plugins::{get_plugins, set_plugins};
+use privacy::{redact_history, set_device_privacy};
+use profiles::{create_profile, list_profiles, switch_profile};
+use report_xlsx::export_xlsx;
+use reports::{export_report, export_report_pdf, generate_report, get_report_content, get_reports};
+use rules::{clear_device_rule_override, get_device_rules, get_rule_overrides, set_device_rule_override};
+use saved_searches::{
+    create_saved_search, delete_saved_search, get_saved_searches, set_saved_search_alert, update_saved_search,
+};
+use scan_export::export_scan;
+use scan_queue::get_scan_queue;
+use scripts::{get_script_hooks, set_script_hooks, test_script_hook};
+use search::{locate_file, search_files};
+use secrets::get_secrets_status;
+use service::get_service_status;
+use sessions::get_session_summaries;
+use settings::{get_settings, set_setting};
+use shadow_copy::get_evidence;
+use state::AppState;
+use timeline_export::export_timeline;
+use transfers::get_device_stats;
+use trust_list::{
+    configure_trust_list_keys, export_signed_trust_list, generate_trust_list_key, get_trust_list_status,
+    import_signed_trust_list,
+};
+use verify::{verify_copy, verify_device};
+use watchlists::test_watchlists;
 use tauri::Manager;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -54,26 +203,177 @@ fn start_dragging(app: tauri::AppHandle) {
     }
 }
 
+/// Modos sin interfaz (servicio, demonio); devuelve el código de salida si se ha ejecutado alguno
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    service::run_cli(args)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    if instance::forward_to_running() {
+        return;
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_notification::init())
         .invoke_handler(tauri::generate_handler![
             greet, 
             get_connected_devices, 
             start_usb_monitoring,
             get_device_history,
             get_registered_devices,
+            get_device_descriptors,
+            set_device_monitoring,
             get_file_snapshots,
             get_device_files,
+            get_device_tree,
             get_device_all_scans,
+            get_scan_queue,
+            get_interrupted_scans,
+            get_scan_states,
+            simulate_device_event,
+            get_monitoring_status,
+            set_monitoring_paused,
+            eject_device,
+            set_autostart,
+            get_autostart_status,
+            list_profiles,
+            create_profile,
+            switch_profile,
+            import_usb_history,
+            import_whitelist_csv,
+            get_device_qr,
+            match_device_qr,
+            get_alerts,
+            get_notification_status,
+            delete_scans,
+            acknowledge_alerts,
+            request_clear_token,
+            clear_all_history,
+            set_trust_for_devices,
+            compare_devices,
+            get_latest_changes,
+            set_device_baseline,
+            clear_device_baseline,
+            compare_to_baseline,
+            locate_file,
+            search_files,
+            import_hash_set,
+            get_hash_sets,
+            delete_hash_set,
+            get_scan_hash_matches,
+            get_document_metadata,
+            get_saved_searches,
+            create_saved_search,
+            update_saved_search,
+            set_saved_search_alert,
+            delete_saved_search,
+            test_watchlists,
+            verify_copy,
+            verify_device,
+            export_device_manifest,
+            verify_device_manifest,
+            get_activity_analytics,
+            get_activity_heatmap,
+            get_device_growth,
+            get_device_baseline,
+            get_device_rules,
+            get_rule_overrides,
+            set_device_rule_override,
+            clear_device_rule_override,
+            get_machines,
+            export_oplog,
+            merge_oplog,
+            get_cloud_backup_status,
+            configure_cloud_backup,
+            run_cloud_backup_now,
+            list_cloud_backups,
+            restore_cloud_backup,
+            get_secrets_status,
+            get_trust_list_status,
+            generate_trust_list_key,
+            configure_trust_list_keys,
+            export_signed_trust_list,
+            import_signed_trust_list,
+            get_session_summaries,
+            get_device_stats,
+            get_evidence,
+            copy_snapshot_to_clipboard,
+            set_case_reference,
+            get_case_references,
+            get_case,
+            add_device_attachment,
+            get_device_attachments,
+            get_device_attachment_content,
+            delete_device_attachment,
+            get_backup_profile,
+            set_backup_profile,
+            delete_backup_profile,
+            get_backup_history,
+            run_backup_now,
+            get_sync_conflicts,
+            resolve_sync_conflict,
+            get_device_volumes,
+            merge_devices,
+            split_device,
+            rekey_devices,
+            get_approval_status,
+            configure_approval,
+            grant_temporary_access,
+            get_access_grants,
+            revoke_access_grant,
+            get_kiosk_status,
+            unlock_configuration,
+            lock_configuration,
+            generate_report,
+            export_report,
+            export_report_pdf,
+            export_xlsx,
+            export_timeline,
+            export_scan,
+            get_reports,
+            get_report_content,
+            get_settings,
+            get_locale_bundle,
+            run_diagnostics,
+            get_events_since,
+            get_service_status,
+            set_setting,
+            get_plugins,
+            set_plugins,
+            set_device_privacy,
+            redact_history,
+            retry_db_init,
+            get_outbox,
+            retry_outbox_message,
+            test_notification_channel,
+            delete_outbox_message,
+            get_script_hooks,
+            set_script_hooks,
+            test_script_hook,
+            get_audit_log,
             minimize_window,
             toggle_maximize_window,
             close_window,
             start_dragging,
+            set_always_on_top,
+            set_compact_mode,
+            get_window_mode,
         ])
+        .on_window_event(|window, event| {
+            // Cerrar la ventana la oculta en la bandeja; se sale desde su menú
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                if window.label() == "main" && tray::minimize_to_tray(window.app_handle()) {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+        })
         .setup(|app| {
             println!("[App] Setting up USB Manager with persistence...");
+            instance::register(app.handle());
             
             // Obtener directorio de datos de la aplicación
             let app_data_dir = app.path().app_data_dir()
@@ -85,51 +385,90 @@ pub fn run() {
                     .expect("Failed to create app data directory");
             }
             
-            // Inicializar base de datos
-            match init_database(app_data_dir) {
-                Ok(db) => {
-                    println!("[App] Database initialized successfully");
-                    
-                    // Iniciar monitoreo USB con DB
-                    let app_handle = app.handle().clone();
-
-                    let mut monitor_to_start = usb_monitor::UsbMonitor::new();
-                    monitor_to_start.set_db(db.clone());
-                    monitor_to_start.set_app_handle(app_handle.clone());
-                    
-                    let shared_monitor = Arc::new(monitor_to_start);
-                    app.manage(shared_monitor.clone());
-
-                    tauri::async_runtime::spawn(async move {
-                        // Scan inicial
-                        let devices = shared_monitor.scan_devices();
-                        println!("[App] Initial scan found {} devices", devices.len());
-                        {
-                            let mut dev_lock = shared_monitor.devices.lock().unwrap();
-                            *dev_lock = devices;
-                        }
-                        
-                        // Iniciar loop de monitoreo
-                        shared_monitor.start_monitoring_shared().await;
-                    });
+            // La ventana se crea oculta; lanzada al iniciar sesión se queda en la bandeja
+            if !autostart::launched_at_login() {
+                if let Some(window) = app.get_webview_window("main") {
+                    let _ = window.show();
                 }
-                Err(e) => {
+            }
+
+            // Cargar ya la configuración de kiosco: borrar el archivo después no la desbloquea
+            kiosk::enabled();
+
+            // Con un servicio en marcha la interfaz usa su base de datos y no monitoriza por su cuenta
+            let service_link = service::detect();
+
+            // Inicializar base de datos del perfil activo
+            let db_path = service_link
+                .and_then(|link| link.database.clone())
+                .unwrap_or_else(|| profiles::active_db_path(&app_data_dir));
+            if let Some(dir) = db_path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+
+            // Sin archivo utilizable se sigue en memoria hasta que `retry_db_init` lo consiga abrir
+            let db = Database::open(db_path.clone())
+                .or_else(|e| {
                     eprintln!("[App] Failed to initialize database: {}", e);
-                    eprintln!("[App] Continuing without persistence...");
-                    
-                    let app_handle = app.handle().clone();
-                    let mut monitor_to_start = usb_monitor::UsbMonitor::new();
-                    monitor_to_start.set_app_handle(app_handle.clone());
-                    
-                    let shared_monitor = Arc::new(monitor_to_start);
-                    app.manage(shared_monitor.clone());
-
-                    tauri::async_runtime::spawn(async move {
-                        shared_monitor.start_monitoring_shared().await;
-                    });
+                    eprintln!("[App] Continuing with an in-memory database until it can be restored...");
+                    Database::open_in_memory(db_path)
+                })
+                .map(Arc::new)?;
+            println!("[App] Database initialized successfully");
+            secrets::migrate(&db);
+            plugins::reload(&db);
+
+            // Informes periódicos, cola de notificaciones, vencimiento de accesos temporales y copias en la nube
+            if service_link.is_none() {
+                tauri::async_runtime::spawn(reports::run_report_scheduler(db.clone()));
+                tauri::async_runtime::spawn(outbox::run_outbox_worker(db.clone()));
+                tauri::async_runtime::spawn(grants::run_grant_scheduler(db.clone(), Some(app.handle().clone())));
+                tauri::async_runtime::spawn(cloud_backup::run_cloud_backup_scheduler(db.clone()));
+            }
+
+            // Iniciar monitoreo USB con DB
+            let mut monitor_to_start = usb_monitor::UsbMonitor::new(db);
+            monitor_to_start.set_app_handle(app.handle().clone());
+            if usb_backend::simulation_enabled() {
+                monitor_to_start.enable_simulation();
+            }
+
+            let shared_monitor = Arc::new(monitor_to_start);
+            app.manage(AppState::new(shared_monitor.clone()));
+
+            // Icono de la bandeja: la monitorización sigue con la ventana cerrada
+            if let Err(e) = tray::init(app.handle()) {
+                println!("[Tray] Failed to create tray icon: {}", e);
+            }
+
+            // Canal IPC propio para scripts y otras herramientas (con servicio se usa el suyo). También con
+            // servicio se abre, porque es el que usa otra instancia de la interfaz para mostrar esta ventana.
+            let ipc_monitor = shared_monitor.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = ipc::serve(ipc::user_endpoint(), ipc_monitor).await {
+                    println!("[IPC] Server stopped: {}", e);
                 }
+            });
+
+            if service_link.is_some() {
+                println!("[App] Background service is monitoring; GUI monitor not started");
+                return Ok(());
             }
-            
+
+            tauri::async_runtime::spawn(async move {
+                // Scan inicial
+                let devices = shared_monitor.scan_devices();
+                println!("[App] Initial scan found {} devices", devices.len());
+                {
+                    let mut dev_lock = shared_monitor.devices.lock().unwrap();
+                    *dev_lock = devices;
+                }
+                shared_monitor.resume_interrupted_scans();
+
+                // Iniciar loop de monitoreo
+                shared_monitor.start_monitoring_shared().await;
+            });
+
             Ok(())
         })
         .run(tauri::generate_context!())
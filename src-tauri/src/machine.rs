@@ -0,0 +1,82 @@
+use serde::Serialize;
+use std::sync::OnceLock;
+use crate::state::AppState;
+
+/// Identidad del equipo donde se ejecuta la app; se guarda en dispositivos y eventos para
+/// poder atribuirlos cuando se fusionan bases de datos de varios equipos
+#[derive(Debug, Serialize, Clone)]
+pub struct MachineIdentity {
+    pub machine_id: String,
+    pub hostname: Option<String>,
+}
+
+static MACHINE: OnceLock<MachineIdentity> = OnceLock::new();
+
+pub fn current() -> &'static MachineIdentity {
+    MACHINE.get_or_init(|| {
+        let hostname = sysinfo::System::host_name().filter(|h| !h.is_empty());
+        // Sin ID del sistema se usa el nombre del equipo, que al menos distingue máquinas
+        let machine_id = os_machine_id()
+            .or_else(|| hostname.as_ref().map(|h| format!("host-{}", h.to_lowercase())))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        println!("[Machine] id={} hostname={:?}", machine_id, hostname);
+        MachineIdentity { machine_id, hostname }
+    })
+}
+
+// HKLM\SOFTWARE\Microsoft\Cryptography\MachineGuid (vista de 64 bits)
+#[cfg(windows)]
+fn os_machine_id() -> Option<String> {
+    use winreg::enums::{HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_64KEY};
+    use winreg::RegKey;
+
+    RegKey::predef(HKEY_LOCAL_MACHINE)
+        .open_subkey_with_flags("SOFTWARE\\Microsoft\\Cryptography", KEY_READ | KEY_WOW64_64KEY)
+        .and_then(|key| key.get_value::<String, _>("MachineGuid"))
+        .ok()
+        .map(|id| id.trim().to_lowercase())
+        .filter(|id| !id.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+fn os_machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+// IOPlatformUUID del registro de IOKit
+#[cfg(target_os = "macos")]
+fn os_machine_id() -> Option<String> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("IOPlatformUUID"))
+        .and_then(|line| line.split('"').nth(3))
+        .map(|id| id.to_lowercase())
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "macos")))]
+fn os_machine_id() -> Option<String> {
+    None
+}
+
+#[tauri::command]
+pub async fn get_machines(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_machines() {
+        Ok(machines) => Ok(serde_json::json!({
+            "success": true,
+            "current": current(),
+            "machines": machines,
+        })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
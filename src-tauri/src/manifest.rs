@@ -0,0 +1,278 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use walkdir::WalkDir;
+use crate::compare::{relative_path, scan_root};
+use crate::db::{Device, FileSnapshot, HashMode};
+use crate::hashing::sha256_file;
+use crate::state::AppState;
+use crate::trust_list::{self, TrustListSettings};
+use crate::verify::verify_against_manifest;
+
+const MANIFEST_FORMAT: &str = "usb-manager-manifest";
+const MANIFEST_VERSION: u32 = 1;
+
+/// Identidad del dispositivo cuyo contenido describe el manifiesto
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ManifestDevice {
+    pub serial_number: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub total_capacity: Option<i64>,
+    pub volume_serial: Option<String>,
+    pub volume_label: Option<String>,
+}
+
+impl From<&Device> for ManifestDevice {
+    fn from(device: &Device) -> Self {
+        ManifestDevice {
+            serial_number: device.serial_number.clone(),
+            vendor_id: device.vendor_id,
+            product_id: device.product_id,
+            name: device.name.clone(),
+            manufacturer: device.manufacturer.clone(),
+            total_capacity: device.total_capacity,
+            volume_serial: device.volume_serial.clone(),
+            volume_label: device.volume_label.clone(),
+        }
+    }
+}
+
+/// Archivo o carpeta del manifiesto, con la ruta relativa a la raíz del dispositivo ('/' como separador)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ManifestEntry {
+    pub path: String,
+    #[serde(default)]
+    pub is_folder: bool,
+    #[serde(default)]
+    pub size: i64,
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Manifest {
+    pub device: ManifestDevice,
+    pub machine_id: String,
+    pub created_at: DateTime<Utc>,
+    /// Escaneo del que sale, o ninguno si se calculó con el dispositivo montado
+    #[serde(default)]
+    pub activity_log_id: Option<i64>,
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// Archivo de un manifiesto. Si está firmado, la firma cubre el formato, la versión y `manifest` tal como se serializa.
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestFile {
+    format: String,
+    version: u32,
+    manifest: Manifest,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+fn signed_message(manifest: &Manifest) -> Result<Vec<u8>, String> {
+    let mut message = format!("{}/{}\n", MANIFEST_FORMAT, MANIFEST_VERSION).into_bytes();
+    message.extend(serde_json::to_vec(manifest).map_err(|e| e.to_string())?);
+    Ok(message)
+}
+
+// Recorrer el dispositivo montado calculando el SHA-256 de cada archivo
+fn entries_from_mount(mount_point: &str) -> Vec<ManifestEntry> {
+    WalkDir::new(mount_point)
+        .follow_links(false)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = relative_path(&entry.path().to_string_lossy(), mount_point);
+            if path.is_empty() {
+                return None;
+            }
+            let is_folder = entry.file_type().is_dir();
+            let size = if is_folder { 0 } else { entry.metadata().map(|m| m.len() as i64).unwrap_or(0) };
+            let sha256 = if is_folder {
+                None
+            } else {
+                sha256_file(entry.path())
+                    .map_err(|e| println!("[Manifest] Error hashing {:?}: {}", entry.path(), e))
+                    .ok()
+            };
+            Some(ManifestEntry { path, is_folder, size, sha256 })
+        })
+        .collect()
+}
+
+// Un escaneo guardado; los hashes por muestra no sirven para verificar en otro equipo
+fn entries_from_scan(snapshots: &[FileSnapshot], root: &str) -> Vec<ManifestEntry> {
+    let mut entries: Vec<ManifestEntry> = snapshots
+        .iter()
+        .map(|s| ManifestEntry {
+            path: relative_path(&s.file_path, root),
+            is_folder: s.is_folder,
+            size: s.file_size,
+            sha256: s.sha256.clone().filter(|_| s.hash_mode.unwrap_or(HashMode::Full) == HashMode::Full),
+        })
+        .filter(|e| !e.path.is_empty())
+        .collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries
+}
+
+/// Exportar el manifiesto de un dispositivo: su identidad y la lista de archivos con sus hashes.
+/// Se calcula con el dispositivo montado, o sale del escaneo `activity_log_id`. Con `sign` se firma
+/// con la clave de este equipo (la de las listas de confianza).
+#[tauri::command]
+pub async fn export_device_manifest(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+    path: String,
+    activity_log_id: Option<i64>,
+    sign: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let device = db
+        .get_device(&device_id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Device not found: {}", device_id))?;
+
+    let entries = match activity_log_id {
+        Some(id) => {
+            let scan = db
+                .get_scan_info(id)
+                .map_err(|e| format!("Database error: {}", e))?
+                .filter(|scan| scan.device_id == device_id)
+                .ok_or_else(|| format!("Scan {} not found for device {}", id, device_id))?;
+            let snapshots = db.get_file_snapshots(id).map_err(|e| format!("Database error: {}", e))?;
+            entries_from_scan(&snapshots, &scan_root(&scan, &snapshots))
+        }
+        None => {
+            let mount_point = state.monitor
+                .mount_point_for(&device_id)
+                .ok_or_else(|| format!("Device is not connected: {}", device_id))?;
+            tauri::async_runtime::spawn_blocking(move || entries_from_mount(&mount_point))
+                .await
+                .map_err(|e| format!("Manifest task failed: {}", e))?
+        }
+    };
+
+    let manifest = Manifest {
+        device: ManifestDevice::from(&device),
+        machine_id: crate::machine::current().machine_id.clone(),
+        created_at: Utc::now(),
+        activity_log_id,
+        entries,
+    };
+    let (public_key, signature) = if sign.unwrap_or(false) {
+        let settings = TrustListSettings::load(&db);
+        let (key, signature) = trust_list::sign(&settings, &signed_message(&manifest)?)
+            .ok_or("No signing key; create one with generate_trust_list_key")?;
+        (Some(key), Some(signature))
+    } else {
+        (None, None)
+    };
+
+    let count = manifest.entries.len();
+    let file = ManifestFile {
+        format: MANIFEST_FORMAT.to_string(),
+        version: MANIFEST_VERSION,
+        manifest,
+        public_key,
+        signature,
+    };
+    let content = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    println!("[Manifest] Exported manifest of {} ({} entries) to {}", device_id, count, path);
+    Ok(serde_json::json!({ "success": true, "path": path, "entries": count, "signed": file.signature.is_some() }))
+}
+
+/// Verificar un dispositivo conectado contra un manifiesto exportado, de este equipo o de otro.
+/// Con `require_signature` se rechaza si no está firmado con una clave dada de alta en este equipo.
+#[tauri::command]
+pub async fn verify_device_manifest(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+    path: String,
+    require_signature: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let file: ManifestFile = serde_json::from_str(&content).map_err(|e| format!("Invalid manifest: {}", e))?;
+    if file.format != MANIFEST_FORMAT {
+        return Err("Invalid manifest: unknown format".to_string());
+    }
+    if file.version > MANIFEST_VERSION {
+        return Err(format!("Unsupported manifest version {}", file.version));
+    }
+
+    // Una firma presente tiene que ser válida; si falta solo se rechaza cuando se exige
+    let signed_by = match (&file.public_key, &file.signature) {
+        (Some(public_key), Some(signature)) => {
+            let settings = TrustListSettings::load(&db);
+            let key = trust_list::verify_signature(&settings, public_key, signature, &signed_message(&file.manifest)?)
+                .map_err(|e| format!("Invalid manifest: {}", e))?;
+            Some(key)
+        }
+        _ if require_signature.unwrap_or(false) => return Err("The manifest is not signed".to_string()),
+        _ => None,
+    };
+
+    let mount_point = state.monitor
+        .mount_point_for(&device_id)
+        .ok_or_else(|| format!("Device is not connected: {}", device_id))?;
+    let device = db.get_device(&device_id).map_err(|e| format!("Database error: {}", e))?;
+    let expected = &file.manifest.device;
+    let device_matches = device.as_ref().is_some_and(|d| {
+        d.serial_number == expected.serial_number
+            && d.vendor_id == expected.vendor_id
+            && d.product_id == expected.product_id
+            && (expected.volume_serial.is_none() || d.volume_serial == expected.volume_serial)
+    });
+
+    let snapshots: Vec<FileSnapshot> = file
+        .manifest
+        .entries
+        .iter()
+        .map(|e| FileSnapshot {
+            id: None,
+            activity_log_id: 0,
+            file_path: e.path.clone(),
+            file_name: e.path.rsplit('/').next().unwrap_or_default().to_string(),
+            file_extension: None,
+            file_size: e.size,
+            is_folder: e.is_folder,
+            sha256: e.sha256.clone(),
+            hash_mode: e.sha256.as_ref().map(|_| HashMode::Full),
+        })
+        .collect();
+    println!("[Manifest] Verifying {} at {} against {}", device_id, mount_point, path);
+    let result = tauri::async_runtime::spawn_blocking(move || verify_against_manifest(Path::new(&mount_point), &snapshots, ""))
+        .await
+        .map_err(|e| format!("Verification task failed: {}", e))?;
+    let intact = result.added.is_empty() && result.missing.is_empty() && result.altered.is_empty();
+
+    Ok(serde_json::json!({
+        "success": true,
+        "intact": intact,
+        "device_matches": device_matches,
+        "signed_by": signed_by,
+        "manifest": {
+            "device": file.manifest.device,
+            "machine_id": file.manifest.machine_id,
+            "created_at": file.manifest.created_at,
+        },
+        "stats": {
+            "checked": result.checked,
+            "added": result.added.len(),
+            "missing": result.missing.len(),
+            "altered": result.altered.len(),
+            "size_only": result.size_only.len(),
+        },
+        "result": result,
+    }))
+}
@@ -0,0 +1,131 @@
+use chrono::{Datelike, Local, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use crate::db::{Alert, AlertSeverity, Database};
+use crate::events;
+use crate::ipc;
+use crate::state::AppState;
+
+pub const NOTIFICATION_SETTINGS_KEY: &str = "notifications";
+/// Evento para que la interfaz muestre el aviso y reproduzca `sound`; no se emite en horario de no molestar
+pub const NOTIFICATION_EVENT: &str = "alert-notification";
+
+/// Sonido de cada severidad: nombre de un sonido de la interfaz o ruta a un archivo (None = sin sonido)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertSounds {
+    pub low: Option<String>,
+    pub medium: Option<String>,
+    pub high: Option<String>,
+    pub critical: Option<String>,
+}
+
+impl Default for AlertSounds {
+    fn default() -> Self {
+        Self {
+            low: None,
+            medium: Some("chime".to_string()),
+            high: Some("alert".to_string()),
+            critical: Some("alarm".to_string()),
+        }
+    }
+}
+
+impl AlertSounds {
+    pub fn for_severity(&self, severity: AlertSeverity) -> Option<&str> {
+        match severity {
+            AlertSeverity::Low => self.low.as_deref(),
+            AlertSeverity::Medium => self.medium.as_deref(),
+            AlertSeverity::High => self.high.as_deref(),
+            AlertSeverity::Critical => self.critical.as_deref(),
+        }
+    }
+}
+
+/// Horario de no molestar (hora local). Si el fin es anterior al inicio, cruza la medianoche
+/// y el día que cuenta es el del inicio.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DoNotDisturb {
+    pub enabled: bool,
+    pub start_hour: u32,
+    pub start_minute: u32,
+    pub end_hour: u32,
+    pub end_minute: u32,
+    /// Días en que se aplica (vacío = todos)
+    pub days: Vec<Weekday>,
+}
+
+impl Default for DoNotDisturb {
+    fn default() -> Self {
+        Self { enabled: false, start_hour: 22, start_minute: 0, end_hour: 7, end_minute: 0, days: Vec::new() }
+    }
+}
+
+impl DoNotDisturb {
+    pub fn is_active_at(&self, now: NaiveDateTime) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let (Some(start), Some(end)) = (
+            NaiveTime::from_hms_opt(self.start_hour, self.start_minute, 0),
+            NaiveTime::from_hms_opt(self.end_hour, self.end_minute, 0),
+        ) else {
+            println!("[Notifications] Invalid do-not-disturb schedule, ignored");
+            return false;
+        };
+        let applies = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let time = now.time();
+        let today = now.weekday();
+
+        if start <= end {
+            applies(today) && time >= start && time < end
+        } else if time >= start {
+            applies(today)
+        } else {
+            time < end && applies(today.pred())
+        }
+    }
+}
+
+/// Sonidos y horario de no molestar, guardados en settings bajo la clave `notifications`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Sin sonidos (los avisos se siguen mostrando)
+    pub muted: bool,
+    pub sounds: AlertSounds,
+    pub do_not_disturb: DoNotDisturb,
+}
+
+impl NotificationSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(NOTIFICATION_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+/// Avisar de una alerta ya guardada con su sonido, salvo en horario de no molestar.
+/// La alerta se guarda y se notifica con `usb-alert` igualmente; esto solo decide el aviso y el sonido.
+pub fn announce(db: &Database, app_handle: Option<&AppHandle>, alert: &Alert) {
+    let settings = NotificationSettings::load(db);
+    if settings.do_not_disturb.is_active_at(Local::now().naive_local()) {
+        println!("[Notifications] Do not disturb: alert {} not announced", alert.id);
+        return;
+    }
+
+    let sound = if settings.muted { None } else { settings.sounds.for_severity(alert.severity) };
+    let payload = serde_json::json!({ "alert": alert, "sound": sound });
+    ipc::publish(NOTIFICATION_EVENT, &payload);
+    if let Some(app_handle) = app_handle {
+        events::emit(app_handle, NOTIFICATION_EVENT, payload);
+    }
+}
+
+/// Si el horario de no molestar está activo ahora y la configuración efectiva de los avisos
+#[tauri::command]
+pub async fn get_notification_status(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let settings = NotificationSettings::load(&db);
+    let do_not_disturb = settings.do_not_disturb.is_active_at(Local::now().naive_local());
+    Ok(serde_json::json!({ "success": true, "do_not_disturb": do_not_disturb, "settings": settings }))
+}
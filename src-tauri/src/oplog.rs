@@ -0,0 +1,118 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::approval::{self, Approval};
+use crate::audit;
+use crate::db::{Database, OplogEntry};
+use crate::kiosk;
+use crate::state::AppState;
+
+const OPLOG_FORMAT: &str = "usb-manager-oplog";
+const OPLOG_VERSION: u32 = 1;
+const MERGED_AUDIT_ACTION: &str = "OPLOG_MERGED";
+
+/// Archivo de exportación del oplog: las operaciones de este equipo y las que ya se fusionaron de otros
+#[derive(Debug, Serialize, Deserialize)]
+struct OplogFile {
+    format: String,
+    version: u32,
+    machine_id: String,
+    exported_at: String,
+    ops: Vec<OplogEntry>,
+}
+
+/// Exportar el oplog a un archivo JSON. Con `since_seq` (el `last_seq` de una exportación anterior)
+/// solo se exportan las operaciones nuevas.
+#[tauri::command]
+pub async fn export_oplog(state: tauri::State<'_, AppState>, path: String, since_seq: Option<i64>) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let ops = db
+        .get_oplog(since_seq.unwrap_or(0))
+        .map_err(|e| format!("Database error: {}", e))?;
+    let last_seq = ops.last().map(|op| op.seq).or(since_seq).unwrap_or(0);
+    let count = ops.len();
+
+    let file = OplogFile {
+        format: OPLOG_FORMAT.to_string(),
+        version: OPLOG_VERSION,
+        machine_id: crate::machine::current().machine_id.clone(),
+        exported_at: Utc::now().to_rfc3339(),
+        ops,
+    };
+    let content = serde_json::to_string(&file).map_err(|e| e.to_string())?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    println!("[Oplog] Exported {} operations to {}", count, path);
+    Ok(serde_json::json!({ "success": true, "path": path, "operations": count, "last_seq": last_seq }))
+}
+
+/// Si una operación cambia la confianza de un dispositivo de forma que necesita aprobación
+pub fn needs_approval(db: &Database, op: &OplogEntry) -> bool {
+    op.trust_change()
+        .is_some_and(|level| approval::needs_approval(db, std::slice::from_ref(&op.entity_id), level))
+}
+
+/// Fusionar el oplog exportado de otro equipo. Se puede repetir con el mismo archivo sin duplicar nada.
+/// Sin `approval`, los cambios de confianza que necesitan aprobación no se fusionan (salen en `held`)
+/// y se pueden fusionar más tarde repitiendo con el código.
+#[tauri::command]
+pub async fn merge_oplog(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    approval: Option<Approval>,
+) -> Result<serde_json::Value, String> {
+    // Fusionar puede cambiar la confianza de los dispositivos
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let file: OplogFile = serde_json::from_str(&content).map_err(|e| format!("Invalid oplog file: {}", e))?;
+    if file.format != OPLOG_FORMAT {
+        return Err("Invalid oplog file: unknown format".to_string());
+    }
+    if file.version > OPLOG_VERSION {
+        return Err(format!("Unsupported oplog version {}", file.version));
+    }
+
+    let (held, mut ops): (Vec<OplogEntry>, Vec<OplogEntry>) =
+        file.ops.into_iter().partition(|op| needs_approval(&db, op));
+    let mut approved = Vec::new();
+    if let Some(approval) = approval.as_ref().filter(|_| !held.is_empty()) {
+        for op in &held {
+            let level = op.trust_change().unwrap_or_default();
+            let method = approval::authorize(&db, std::slice::from_ref(&op.entity_id), level, Some(approval))?;
+            let previous = db.get_device_trust(&op.entity_id).unwrap_or_default();
+            approved.push((op.entity_id.clone(), previous, level, method));
+        }
+    }
+    let held = if approved.is_empty() {
+        held.into_iter().map(|op| op.entity_id).collect()
+    } else {
+        // El orden importa: el último cambio de confianza de cada dispositivo es el que gana
+        ops.extend(held);
+        ops.sort_by_key(|op| op.seq);
+        Vec::new()
+    };
+
+    let mut summary = db
+        .merge_oplog(&ops)
+        .map_err(|e| format!("Database error: {}", e))?;
+    summary.held = held;
+    for (device_id, previous, level, method) in approved {
+        if let Some(method) = method {
+            approval::record_approved(&db, &[(device_id, previous)], level, method, approval.as_ref());
+        }
+    }
+    audit::record(
+        &db,
+        MERGED_AUDIT_ACTION,
+        None,
+        &serde_json::json!({
+            "path": path,
+            "machine_id": file.machine_id,
+            "exported_at": file.exported_at,
+            "summary": summary,
+        }),
+    );
+
+    Ok(serde_json::json!({ "success": true, "machine_id": file.machine_id, "summary": summary }))
+}
@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+/// Usuario interactivo del sistema en el momento de un evento
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct OsSession {
+    pub user: Option<String>,
+    /// ID de sesión de Windows (consola activa); no disponible en otros sistemas
+    pub session_id: Option<u32>,
+}
+
+impl OsSession {
+    /// Sesión de consola activa. En Windows se consulta WTS, de modo que el resultado es correcto
+    /// aunque el proceso se ejecute como servicio bajo otra cuenta.
+    #[cfg(windows)]
+    pub fn current() -> Self {
+        use windows_sys::core::PWSTR;
+        use windows_sys::Win32::System::RemoteDesktop::{
+            WTSFreeMemory, WTSGetActiveConsoleSessionId, WTSQuerySessionInformationW, WTSUserName,
+            WTS_CURRENT_SERVER_HANDLE,
+        };
+
+        let session_id = unsafe { WTSGetActiveConsoleSessionId() };
+        // 0xFFFFFFFF: no hay sesión de consola (p. ej. durante un cambio de usuario)
+        if session_id == u32::MAX {
+            return Self::from_env();
+        }
+
+        let mut buffer: PWSTR = std::ptr::null_mut();
+        let mut bytes: u32 = 0;
+        let user = unsafe {
+            if WTSQuerySessionInformationW(WTS_CURRENT_SERVER_HANDLE, session_id, WTSUserName, &mut buffer, &mut bytes) != 0
+                && !buffer.is_null()
+            {
+                let len = (bytes as usize / 2).saturating_sub(1);
+                let name = String::from_utf16_lossy(std::slice::from_raw_parts(buffer, len));
+                WTSFreeMemory(buffer as *mut _);
+                Some(name.trim_end_matches('\0').to_string()).filter(|n| !n.is_empty())
+            } else {
+                None
+            }
+        };
+
+        Self {
+            user: user.or_else(|| Self::from_env().user),
+            session_id: Some(session_id),
+        }
+    }
+
+    #[cfg(not(windows))]
+    pub fn current() -> Self {
+        Self::from_env()
+    }
+
+    fn from_env() -> Self {
+        let user = ["SUDO_USER", "USER", "LOGNAME", "USERNAME"]
+            .iter()
+            .find_map(|key| std::env::var(key).ok())
+            .filter(|u| !u.is_empty());
+
+        Self { user, session_id: None }
+    }
+}
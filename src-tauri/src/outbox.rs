@@ -0,0 +1,184 @@
+use chrono::Utc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use crate::db::{Alert, AlertSeverity, Database, OutboxMessage, OutboxStatus};
+use crate::i18n::{Message, DEFAULT_LANG};
+use crate::kiosk;
+use crate::plugins;
+use crate::routing;
+use crate::state::AppState;
+
+/// Canal de los plugins de notificación: `plugin:<nombre>`
+pub const PLUGIN_CHANNEL_PREFIX: &str = "plugin:";
+
+const WORKER_INTERVAL: Duration = Duration::from_secs(15);
+const BATCH_SIZE: i64 = 20;
+// Tras este número de intentos el mensaje queda como `dead`
+const MAX_ATTEMPTS: i64 = 10;
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 3600;
+const TEST_ALERT_TYPE: &str = "TEST_NOTIFICATION";
+
+static WAKE: OnceLock<Notify> = OnceLock::new();
+
+fn wake_signal() -> &'static Notify {
+    WAKE.get_or_init(Notify::new)
+}
+
+/// Despertar al worker para que entregue ya lo recién encolado
+pub fn wake() {
+    wake_signal().notify_one();
+}
+
+// Espera exponencial antes del siguiente intento: 30s, 1m, 2m... hasta 1h
+fn backoff_secs(attempts: i64) -> i64 {
+    let exponent = attempts.clamp(0, 16) as u32;
+    (BASE_BACKOFF_SECS * 2i64.pow(exponent)).min(MAX_BACKOFF_SECS)
+}
+
+fn deliver(db: &Database, message: &OutboxMessage) -> Result<(), String> {
+    let alert: Alert = serde_json::from_value(message.payload.clone()).map_err(|e| format!("Invalid payload: {}", e))?;
+    deliver_alert(db, &message.channel, &alert)
+}
+
+fn deliver_alert(db: &Database, channel: &str, alert: &Alert) -> Result<(), String> {
+    if channel == routing::WEBHOOK_CHANNEL {
+        return routing::post_webhook(db, alert);
+    }
+    if let Some(name) = channel.strip_prefix(PLUGIN_CHANNEL_PREFIX) {
+        let hook = plugins::notifier(name).ok_or_else(|| format!("Notifier '{}' is not configured", name))?;
+        return hook.on_alert(alert);
+    }
+
+    Err(format!("Unknown channel '{}'", channel))
+}
+
+/// Intentar entregar los mensajes pendientes cuyo turno ha llegado. Devuelve cuántos se han entregado.
+pub fn process_due(db: &Database) -> usize {
+    let messages = match db.get_due_outbox(Utc::now(), BATCH_SIZE) {
+        Ok(messages) => messages,
+        Err(e) => {
+            println!("[DB] Error reading outbox: {}", e);
+            return 0;
+        }
+    };
+
+    let mut delivered = 0;
+    for message in messages {
+        let result = match deliver(db, &message) {
+            Ok(_) => {
+                delivered += 1;
+                db.delete_outbox(message.id).map(|_| ())
+            }
+            Err(e) => {
+                let attempts = message.attempts + 1;
+                let next_attempt = (attempts < MAX_ATTEMPTS)
+                    .then(|| Utc::now() + chrono::Duration::seconds(backoff_secs(message.attempts)));
+                match next_attempt {
+                    Some(at) => println!("[Outbox] {} #{} failed (attempt {}), retrying at {}: {}", message.channel, message.id, attempts, at, e),
+                    None => println!("[Outbox] {} #{} failed {} times, giving up: {}", message.channel, message.id, attempts, e),
+                }
+                db.record_outbox_failure(message.id, &e, next_attempt)
+            }
+        };
+        if let Err(e) = result {
+            println!("[DB] Error updating outbox: {}", e);
+        }
+    }
+
+    delivered
+}
+
+/// Bucle de entrega de la cola; lo pendiente de ejecuciones anteriores se reintenta al arrancar
+pub async fn run_outbox_worker(db: Arc<Database>) {
+    println!("[Outbox] Worker started.");
+    loop {
+        let worker_db = db.clone();
+        let _ = tokio::task::spawn_blocking(move || process_due(&worker_db)).await;
+
+        tokio::select! {
+            _ = tokio::time::sleep(WORKER_INTERVAL) => {}
+            _ = wake_signal().notified() => {}
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn get_outbox(state: tauri::State<'_, AppState>, status: Option<OutboxStatus>, limit: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_outbox(status, limit) {
+        Ok(messages) => Ok(serde_json::json!({ "success": true, "messages": messages })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Reintentar ya un mensaje (también los `dead`)
+#[tauri::command]
+pub async fn retry_outbox_message(state: tauri::State<'_, AppState>, id: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.retry_outbox(id) {
+        Ok(updated) => {
+            wake();
+            Ok(serde_json::json!({ "success": updated }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn delete_outbox_message(state: tauri::State<'_, AppState>, id: i64) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = &state.db;
+    match db.delete_outbox(id) {
+        Ok(deleted) => Ok(serde_json::json!({ "success": deleted })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Enviar una alerta de prueba por un canal (`webhook`, `plugin:<nombre>` o solo el nombre del plugin) sin pasar
+/// por la cola, para comprobar una integración (webhook, correo, MQTT, syslog...) sin conectar ningún dispositivo
+#[tauri::command]
+pub async fn test_notification_channel(state: tauri::State<'_, AppState>, channel_id: String) -> Result<serde_json::Value, String> {
+    let channel = if channel_id == routing::WEBHOOK_CHANNEL || channel_id.starts_with(PLUGIN_CHANNEL_PREFIX) {
+        channel_id
+    } else {
+        format!("{}{}", PLUGIN_CHANNEL_PREFIX, channel_id)
+    };
+
+    let message = Message::new("alert.test_notification", serde_json::json!({ "channel": channel }));
+    let alert = Alert {
+        id: 0,
+        device_id: None,
+        activity_log_id: None,
+        alert_type: TEST_ALERT_TYPE.to_string(),
+        severity: AlertSeverity::Low,
+        message: message.render(DEFAULT_LANG),
+        message_key: Some(message.key.clone()),
+        message_params: Some(message.params.clone()),
+        created_at: Utc::now(),
+        acknowledged: false,
+    };
+
+    let db = state.db.clone();
+    let test_channel = channel.clone();
+    let (result, latency) = tokio::task::spawn_blocking(move || {
+        let started = Instant::now();
+        let result = deliver_alert(&db, &test_channel, &alert);
+        (result, started.elapsed())
+    })
+    .await
+    .map_err(|e| format!("Test task failed: {}", e))?;
+
+    match &result {
+        Ok(_) => println!("[Outbox] Test notification delivered through {} in {} ms", channel, latency.as_millis()),
+        Err(e) => println!("[Outbox] Test notification through {} failed: {}", channel, e),
+    }
+    Ok(serde_json::json!({
+        "success": true,
+        "channel": channel,
+        "delivered": result.is_ok(),
+        "latency_ms": latency.as_millis() as u64,
+        "error": result.err(),
+    }))
+}
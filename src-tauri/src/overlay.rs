@@ -0,0 +1,168 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use tauri::{AppHandle, Emitter, LogicalSize, Manager, PhysicalSize};
+use crate::events;
+use crate::i18n::Message;
+
+// Modo compacto: la ventana principal pasa a ser una tira pequeña siempre encima con la actividad reciente
+const COMPACT_WIDTH: f64 = 380.0;
+const COMPACT_HEIGHT: f64 = 160.0;
+// Tamaño mínimo normal, el mismo de tauri.conf.json
+const MIN_WIDTH: f64 = 800.0;
+const MIN_HEIGHT: f64 = 600.0;
+const TICKER_CAPACITY: usize = 20;
+/// Evento con las últimas entradas del ticker, emitido mientras el modo compacto está activo
+pub const TICKER_EVENT: &str = "overlay-ticker";
+const MODE_EVENT: &str = "window-mode";
+
+/// Línea del ticker: el mensaje va localizable, como el resto de textos que genera el backend
+#[derive(Debug, Serialize, Clone)]
+pub struct TickerEntry {
+    pub event: String,
+    pub device_id: Option<String>,
+    pub message: Message,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct WindowMode {
+    compact: bool,
+    // Elección del usuario; en modo compacto la ventana va siempre encima
+    always_on_top: bool,
+    // Tamaño antes de entrar en modo compacto, para restaurarlo
+    normal_size: Option<PhysicalSize<u32>>,
+}
+
+static MODE: OnceLock<Mutex<WindowMode>> = OnceLock::new();
+static TICKER: OnceLock<Mutex<VecDeque<TickerEntry>>> = OnceLock::new();
+
+fn mode() -> &'static Mutex<WindowMode> {
+    MODE.get_or_init(|| Mutex::new(WindowMode::default()))
+}
+
+fn ticker() -> &'static Mutex<VecDeque<TickerEntry>> {
+    TICKER.get_or_init(|| Mutex::new(VecDeque::with_capacity(TICKER_CAPACITY)))
+}
+
+fn text(payload: &serde_json::Value, field: &str) -> Option<String> {
+    payload.get(field).and_then(|v| v.as_str()).map(str::to_string)
+}
+
+// Mensaje para el ticker de los eventos que interesan; el resto se ignora
+fn ticker_entry(event: &str, payload: &serde_json::Value) -> Option<TickerEntry> {
+    let (device_id, message) = match event {
+        "usb-connected" | "usb-disconnected" => {
+            let device_id = text(payload, "serial_number").or_else(|| text(payload, "id"));
+            let name = text(payload, "product_name").or_else(|| device_id.clone()).unwrap_or_default();
+            let key = if event == "usb-connected" { "ticker.connected" } else { "ticker.disconnected" };
+            (device_id, Message::new(key, serde_json::json!({ "device": name })))
+        }
+        "usb-alert" => {
+            let message = match text(payload, "message_key") {
+                Some(key) => Message::new(&key, payload.get("message_params").cloned().unwrap_or_default()),
+                None => Message::new("ticker.alert", serde_json::json!({ "message": text(payload, "message") })),
+            };
+            (text(payload, "device_id"), message)
+        }
+        "file-copy-detected" | "usb-session-summary" | "idle-eject-warning" | "device-auto-ejected" => {
+            let message: Message = serde_json::from_value(payload.get("message")?.clone()).ok()?;
+            (text(payload, "device_id").or_else(|| text(&message.params, "device")), message)
+        }
+        "file-taken-detected" => {
+            let evidence = payload.get("evidence")?;
+            let device_id = text(evidence, "device_id");
+            let message = Message::new(
+                "ticker.file_taken",
+                serde_json::json!({ "device": device_id, "file": text(evidence, "file_name") }),
+            );
+            (device_id, message)
+        }
+        _ => return None,
+    };
+    Some(TickerEntry { event: event.to_string(), device_id, message, timestamp: Utc::now() })
+}
+
+/// Llamado por `events::emit`: añadir el evento al ticker y, en modo compacto, enviar la lista actualizada
+pub fn push(app_handle: &AppHandle, event: &str, payload: &serde_json::Value) {
+    let Some(entry) = ticker_entry(event, payload) else {
+        return;
+    };
+    let entries: Vec<TickerEntry> = {
+        let mut ticker = ticker().lock().unwrap();
+        if ticker.len() == TICKER_CAPACITY {
+            ticker.pop_back();
+        }
+        ticker.push_front(entry);
+        ticker.iter().cloned().collect()
+    };
+    if mode().lock().unwrap().compact {
+        let _ = app_handle.emit(TICKER_EVENT, serde_json::json!({ "entries": entries }));
+    }
+}
+
+fn mode_json(mode: &WindowMode) -> serde_json::Value {
+    serde_json::json!({ "compact": mode.compact, "always_on_top": mode.always_on_top })
+}
+
+/// Mantener la ventana principal por encima de las demás aplicaciones
+#[tauri::command]
+pub fn set_always_on_top(app: AppHandle, enabled: bool) -> Result<serde_json::Value, String> {
+    let window = app.get_webview_window("main").ok_or("Main window not found")?;
+    let mut mode = mode().lock().unwrap();
+    mode.always_on_top = enabled;
+    // En modo compacto sigue encima; la elección se aplica al salir
+    if !mode.compact {
+        window.set_always_on_top(enabled).map_err(|e| e.to_string())?;
+    }
+    let state = mode_json(&mode);
+    drop(mode);
+    events::emit(&app, MODE_EVENT, &state);
+    Ok(serde_json::json!({ "success": true, "mode": state }))
+}
+
+/// Cambiar la ventana principal a la tira compacta con la actividad reciente (o volver al tamaño normal).
+/// La interfaz cambia de vista con el evento `window-mode` y recibe el ticker con `overlay-ticker`.
+#[tauri::command]
+pub fn set_compact_mode(app: AppHandle, enabled: bool) -> Result<serde_json::Value, String> {
+    let window = app.get_webview_window("main").ok_or("Main window not found")?;
+    let mut mode = mode().lock().unwrap();
+    if mode.compact != enabled {
+        if enabled {
+            mode.normal_size = window.inner_size().ok();
+            window.set_min_size(None::<LogicalSize<f64>>).map_err(|e| e.to_string())?;
+            window.set_size(LogicalSize::new(COMPACT_WIDTH, COMPACT_HEIGHT)).map_err(|e| e.to_string())?;
+            window.set_always_on_top(true).map_err(|e| e.to_string())?;
+            let _ = window.set_skip_taskbar(true);
+        } else {
+            window.set_min_size(Some(LogicalSize::new(MIN_WIDTH, MIN_HEIGHT))).map_err(|e| e.to_string())?;
+            match mode.normal_size.take() {
+                Some(size) => window.set_size(size),
+                None => window.set_size(LogicalSize::new(MIN_WIDTH, MIN_HEIGHT)),
+            }
+            .map_err(|e| e.to_string())?;
+            window.set_always_on_top(mode.always_on_top).map_err(|e| e.to_string())?;
+            let _ = window.set_skip_taskbar(false);
+        }
+        mode.compact = enabled;
+        println!("[Overlay] Compact mode {}", if enabled { "on" } else { "off" });
+    }
+    let state = mode_json(&mode);
+    drop(mode);
+
+    events::emit(&app, MODE_EVENT, &state);
+    if enabled {
+        let entries: Vec<TickerEntry> = ticker().lock().unwrap().iter().cloned().collect();
+        let _ = app.emit(TICKER_EVENT, serde_json::json!({ "entries": entries }));
+    }
+    Ok(serde_json::json!({ "success": true, "mode": state }))
+}
+
+/// Modo de la ventana y entradas actuales del ticker (la más reciente primero)
+#[tauri::command]
+pub fn get_window_mode() -> Result<serde_json::Value, String> {
+    let state = mode_json(&mode().lock().unwrap());
+    let entries: Vec<TickerEntry> = ticker().lock().unwrap().iter().cloned().collect();
+    Ok(serde_json::json!({ "success": true, "mode": state, "ticker": entries }))
+}
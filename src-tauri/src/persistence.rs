@@ -0,0 +1,52 @@
+use tauri::AppHandle;
+use crate::audit;
+use crate::events;
+use crate::i18n::Message;
+use crate::oplog;
+use crate::plugins;
+use crate::secrets;
+use crate::state::AppState;
+
+/// La base de datos ha pasado de memoria al archivo
+pub const DATABASE_RESTORED_EVENT: &str = "database-restored";
+const DATABASE_RESTORED_AUDIT_ACTION: &str = "DATABASE_RESTORED";
+
+/// Volver a abrir el archivo de la base de datos cuando al arrancar no se pudo (permisos, disco lleno...).
+/// Los dispositivos, la actividad y las alertas guardados en memoria se pasan al archivo; los escaneos
+/// hechos mientras tanto no se conservan.
+#[tauri::command]
+pub async fn retry_db_init(state: tauri::State<'_, AppState>, app: AppHandle) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let path = db.path();
+    if !db.is_in_memory() {
+        return Ok(serde_json::json!({ "success": true, "in_memory": false, "path": path }));
+    }
+
+    let task_db = db.clone();
+    let summary = tauri::async_runtime::spawn_blocking(move || task_db.flush_to_file(oplog::needs_approval))
+        .await
+        .map_err(|e| format!("Database task failed: {}", e))?
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    secrets::migrate(&db);
+    plugins::reload(&db);
+    // Las sesiones abiertas apuntan a filas de la base de datos en memoria: se vuelven a registrar
+    state.monitor.reset_session_state();
+
+    println!("[DB] Database restored at {:?} ({} buffered changes saved)", path, summary.applied);
+    audit::record(&db, DATABASE_RESTORED_AUDIT_ACTION, None, &serde_json::json!({ "path": path, "summary": summary }));
+    events::emit(
+        &app,
+        DATABASE_RESTORED_EVENT,
+        serde_json::json!({
+            "path": path,
+            "summary": summary,
+            "message": Message::new(
+                "database.restored",
+                serde_json::json!({ "count": summary.applied, "path": path.to_string_lossy() }),
+            ),
+        }),
+    );
+
+    Ok(serde_json::json!({ "success": true, "in_memory": false, "path": path, "summary": summary }))
+}
@@ -0,0 +1,344 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::db::{Alert, AlertSeverity, Database};
+use crate::i18n::Message;
+use crate::ipc;
+use crate::kiosk;
+use crate::outbox;
+use crate::service::service_link;
+use crate::state::AppState;
+
+pub const PLUGIN_SETTINGS_KEY: &str = "plugins";
+const PLUGIN_ALERT: &str = "PLUGIN_FINDING";
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Escaneo terminado que se pasa a los hooks de escáner
+#[derive(Debug, Serialize, Clone)]
+pub struct ScanContext {
+    pub device_id: String,
+    pub activity_id: i64,
+    pub mount_point: String,
+    pub files_scanned: usize,
+    pub total_size: i64,
+}
+
+/// Hallazgo de un hook de escáner; se guarda como alerta `PLUGIN_FINDING`
+#[derive(Debug, Deserialize, Clone)]
+pub struct HookFinding {
+    #[serde(default = "default_severity")]
+    pub severity: AlertSeverity,
+    pub message: String,
+}
+
+fn default_severity() -> AlertSeverity {
+    AlertSeverity::Medium
+}
+
+/// Se ejecuta al terminar el escaneo de una unidad
+pub trait ScannerHook: Send + Sync {
+    fn name(&self) -> &str;
+    fn on_scan_complete(&self, scan: &ScanContext) -> Result<Vec<HookFinding>, String>;
+}
+
+/// Recibe cada alerta nueva (correo, chat, SIEM...)
+pub trait NotifierHook: Send + Sync {
+    fn name(&self) -> &str;
+    fn on_alert(&self, alert: &Alert) -> Result<(), String>;
+}
+
+#[derive(Default)]
+pub struct HookRegistry {
+    scanners: Vec<Arc<dyn ScannerHook>>,
+    notifiers: Vec<Arc<dyn NotifierHook>>,
+}
+
+impl HookRegistry {
+    pub fn register_scanner(&mut self, hook: Arc<dyn ScannerHook>) {
+        self.scanners.push(hook);
+    }
+
+    pub fn register_notifier(&mut self, hook: Arc<dyn NotifierHook>) {
+        self.notifiers.push(hook);
+    }
+}
+
+static REGISTRY: RwLock<HookRegistry> = RwLock::new(HookRegistry {
+    scanners: Vec::new(),
+    notifiers: Vec::new(),
+});
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginKind {
+    Scanner,
+    Notifier,
+}
+
+/// Plugin externo: un ejecutable que recibe el evento como JSON por stdin
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PluginConfig {
+    pub name: String,
+    pub kind: PluginKind,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct PluginSettings {
+    pub plugins: Vec<PluginConfig>,
+}
+
+impl PluginSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(PLUGIN_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, plugin) in self.plugins.iter().enumerate() {
+            if plugin.name.trim().is_empty() {
+                return Err("Plugin name cannot be empty".to_string());
+            }
+            if plugin.command.trim().is_empty() {
+                return Err(format!("Plugin '{}' has no command", plugin.name));
+            }
+            if self.plugins[..i].iter().any(|p| p.name == plugin.name) {
+                return Err(format!("Duplicate plugin name '{}'", plugin.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resultado de un proceso lanzado por un plugin o un script
+#[derive(Debug, Serialize, Clone)]
+pub struct ProcessOutput {
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// Leer una tubería en un hilo aparte para que el proceso no se bloquee con ella llena
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut out = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut out);
+        }
+        out
+    })
+}
+
+/// Lanzar un proceso, escribir `input` en su stdin y esperarlo como mucho `timeout`; pasado el plazo se mata
+pub fn run_process(command: &mut Command, input: &str, timeout: Duration) -> Result<ProcessOutput, String> {
+    let started = Instant::now();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start {:?}: {}", command.get_program(), e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // Un proceso que no lee stdin no es un error
+        let _ = stdin.write_all(input.as_bytes());
+    }
+
+    let stdout = read_pipe(child.stdout.take());
+    let stderr = read_pipe(child.stderr.take());
+
+    let deadline = started + timeout;
+    let (status, timed_out) = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break (Some(status), false),
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                break (child.wait().ok(), true);
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+
+    Ok(ProcessOutput {
+        exit_code: status.and_then(|s| s.code()),
+        success: !timed_out && status.map(|s| s.success()).unwrap_or(false),
+        timed_out,
+        duration_ms: started.elapsed().as_millis() as u64,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+struct ExternalPlugin {
+    config: PluginConfig,
+}
+
+impl ExternalPlugin {
+    fn run(&self, input: &serde_json::Value) -> Result<String, String> {
+        let mut command = Command::new(&self.config.command);
+        command.args(&self.config.args);
+        let output = run_process(&mut command, &format!("{}\n", input), Duration::from_secs(self.config.timeout_secs))?;
+
+        if output.timed_out {
+            return Err(format!("Timed out after {}s", self.config.timeout_secs));
+        }
+        if !output.success {
+            return Err(format!("Exited with code {:?}: {}", output.exit_code, output.stderr.trim()));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl ScannerHook for ExternalPlugin {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    // Salida: un hallazgo JSON por línea, p.ej. {"severity": "HIGH", "message": "..."}
+    fn on_scan_complete(&self, scan: &ScanContext) -> Result<Vec<HookFinding>, String> {
+        let out = self.run(&serde_json::json!({ "hook": "scan_complete", "scan": scan }))?;
+
+        let mut findings = Vec::new();
+        for line in out.lines().filter(|l| !l.trim().is_empty()) {
+            match serde_json::from_str::<HookFinding>(line) {
+                Ok(finding) => findings.push(finding),
+                Err(e) => println!("[Plugins] {}: ignoring output line: {}", self.config.name, e),
+            }
+        }
+        Ok(findings)
+    }
+}
+
+impl NotifierHook for ExternalPlugin {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn on_alert(&self, alert: &Alert) -> Result<(), String> {
+        self.run(&serde_json::json!({ "hook": "alert", "alert": alert })).map(|_| ())
+    }
+}
+
+/// Volver a cargar los plugins configurados en la base de datos activa
+pub fn reload(db: &Database) {
+    let mut registry = HookRegistry::default();
+    for config in PluginSettings::load(db).plugins.into_iter().filter(|p| p.enabled) {
+        let kind = config.kind;
+        let plugin = Arc::new(ExternalPlugin { config });
+        match kind {
+            PluginKind::Scanner => registry.register_scanner(plugin),
+            PluginKind::Notifier => registry.register_notifier(plugin),
+        }
+    }
+
+    println!(
+        "[Plugins] Loaded {} scanner and {} notifier hooks",
+        registry.scanners.len(),
+        registry.notifiers.len()
+    );
+    *REGISTRY.write().unwrap() = registry;
+}
+
+/// Ejecutar los hooks de escáner y guardar sus hallazgos como alertas. Bloquea mientras corren los plugins.
+pub fn run_scanners(db: &Arc<Database>, app_handle: Option<&AppHandle>, scan: &ScanContext) {
+    let scanners = REGISTRY.read().unwrap().scanners.clone();
+
+    for hook in scanners {
+        let findings = match hook.on_scan_complete(scan) {
+            Ok(findings) => findings,
+            Err(e) => {
+                println!("[Plugins] Scanner '{}' failed: {}", hook.name(), e);
+                continue;
+            }
+        };
+
+        for finding in findings {
+            raise_alert(
+                db,
+                app_handle,
+                Some(&scan.device_id),
+                Some(scan.activity_id),
+                PLUGIN_ALERT,
+                finding.severity,
+                Message::new(
+                    "alert.plugin",
+                    serde_json::json!({ "plugin": hook.name(), "device": scan.device_id, "message": finding.message }),
+                ),
+            );
+        }
+    }
+}
+
+/// Hook de notificación cargado con ese nombre
+pub fn notifier(name: &str) -> Option<Arc<dyn NotifierHook>> {
+    REGISTRY.read().unwrap().notifiers.iter().find(|hook| hook.name() == name).cloned()
+}
+
+/// Encolar una alerta nueva para cada hook de notificación; la entrega y los reintentos los hace el outbox
+pub fn notify(db: &Database, alert: &Alert) {
+    let notifiers = REGISTRY.read().unwrap().notifiers.clone();
+    if notifiers.is_empty() {
+        return;
+    }
+
+    let payload = serde_json::to_value(alert).unwrap_or_default();
+    for hook in notifiers {
+        let channel = format!("{}{}", outbox::PLUGIN_CHANNEL_PREFIX, hook.name());
+        if let Err(e) = db.enqueue_outbox(&channel, &payload) {
+            println!("[DB] Error queueing notification for '{}': {}", hook.name(), e);
+        }
+    }
+    outbox::wake();
+}
+
+#[tauri::command]
+pub async fn get_plugins(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    Ok(serde_json::json!({ "success": true, "plugins": PluginSettings::load(db).plugins }))
+}
+
+#[tauri::command]
+pub async fn set_plugins(state: tauri::State<'_, AppState>, plugins: Vec<PluginConfig>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let settings = PluginSettings { plugins };
+    settings.validate()?;
+    let value = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+
+    // Con servicio, son sus plugins los que se ejecutan
+    if let Some(link) = service_link() {
+        ipc::call(&link.endpoint, "set_setting", serde_json::json!({ "key": PLUGIN_SETTINGS_KEY, "value": value, "unlock_token": kiosk::session_token() })).await?;
+        return Ok(serde_json::json!({ "success": true, "plugins": settings.plugins }));
+    }
+
+    let db = &state.db;
+    match db.set_setting(PLUGIN_SETTINGS_KEY, &value) {
+        Ok(_) => {
+            reload(db);
+            Ok(serde_json::json!({ "success": true, "plugins": settings.plugins }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,234 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::approval::random_bytes;
+use crate::audit;
+use crate::cloud_backup::hex;
+use crate::db::{Database, FileSnapshot};
+use crate::kiosk;
+use crate::secrets::{self, SecretFields};
+use crate::state::AppState;
+use crate::write_block;
+
+pub const PRIVACY_SETTINGS_KEY: &str = "privacy";
+/// Clave de los hashes de las rutas anonimizadas, aparte de `privacy` para que guardar ese ajuste no la cambie
+pub const PATH_KEY_SETTINGS_KEY: &str = "privacy_path_key";
+const PATH_KEY_LEN: usize = 32;
+const DEVICE_PRIVACY_AUDIT_ACTION: &str = "DEVICE_PRIVACY_CHANGED";
+const HISTORY_REDACTED_AUDIT_ACTION: &str = "HISTORY_REDACTED";
+// Lo que queda en lugar de cada valor con `mode: strip`
+const STRIPPED: &str = "redacted";
+// Caracteres hexadecimales del hash que sustituye a cada ruta
+const PATH_HASH_LEN: usize = 32;
+
+/// Privacidad del inventario, guardada en settings bajo la clave `privacy`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrivacySettings {
+    /// Guardar solo la forma de los archivos (extensión, tamaño y un hash de la ruta) en lugar de nombres y rutas
+    pub redact_paths: bool,
+}
+
+impl PrivacySettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(PRIVACY_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+/// Clave secreta de esta instalación para los hashes de las rutas: sin ella no se pueden comprobar nombres
+/// candidatos contra los hashes guardados. Se crea la primera vez y se guarda en el llavero.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathKey {
+    pub key: String,
+}
+
+impl SecretFields for PathKey {
+    fn secret_fields(&mut self) -> Vec<(&'static str, &mut String)> {
+        vec![("privacy_path_key", &mut self.key)]
+    }
+}
+
+// Clave de la base de datos abierta: leer el llavero en cada archivo sería demasiado lento
+static PATH_KEY: Mutex<Option<(PathBuf, Vec<u8>)>> = Mutex::new(None);
+
+/// Clave de los hashes de rutas de la base de datos abierta, creándola si aún no existe
+pub fn path_key(db: &Database) -> Vec<u8> {
+    let mut cached = PATH_KEY.lock().unwrap();
+    let db_path = db.path();
+    if let Some((_, key)) = cached.as_ref().filter(|(path, _)| *path == db_path) {
+        return key.clone();
+    }
+
+    let mut stored: PathKey = db.get_setting(PATH_KEY_SETTINGS_KEY).ok().flatten().unwrap_or_default();
+    secrets::reveal_all(&mut stored);
+    if stored.key.is_empty() {
+        // Si había una y no se puede leer, las rutas nuevas dejan de coincidir con las ya guardadas
+        println!("[Privacy] Creating a new key for redacted paths");
+        stored.key = hex(&random_bytes(PATH_KEY_LEN));
+        let mut sealed = stored.clone();
+        secrets::seal_all(db, &mut sealed);
+        if let Err(e) = db.set_setting(PATH_KEY_SETTINGS_KEY, &sealed) {
+            println!("[DB] Error saving {}: {}", PATH_KEY_SETTINGS_KEY, e);
+        }
+    }
+    let key = stored.key.into_bytes();
+    *cached = Some((db_path, key.clone()));
+    key
+}
+
+pub fn check_writable(key: &str) -> Result<(), String> {
+    if key == PATH_KEY_SETTINGS_KEY {
+        return Err("The path redaction key cannot be changed".to_string());
+    }
+    Ok(())
+}
+
+/// Ocultar la clave al listar los ajustes
+pub fn redact(settings: &mut serde_json::Map<String, serde_json::Value>) {
+    settings.remove(PATH_KEY_SETTINGS_KEY);
+}
+
+/// Si las rutas de un dispositivo se guardan anonimizadas: su propio ajuste o, si no tiene, el global
+pub fn redacts_paths(db: &Database, device_id: &str) -> bool {
+    db.get_device(device_id)
+        .ok()
+        .flatten()
+        .and_then(|device| device.redact_paths)
+        .unwrap_or_else(|| PrivacySettings::load(db).redact_paths)
+}
+
+/// Ruta y nombre anonimizados: `<raíz>/<HMAC de la ruta relativa>[.ext]`, con la clave de `path_key`. El hash
+/// no cambia entre escaneos, así que se pueden comparar, pero no deja ver nombres ni carpetas. La raíz se deja tal cual.
+pub fn redact_path(key: &[u8], path: &Path, root: &str, extension: Option<&str>) -> (String, String) {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    if relative.is_empty() {
+        let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().to_string();
+        return (path.to_string_lossy().to_string(), name);
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(relative.as_bytes());
+    let hash = hex(&mac.finalize().into_bytes());
+    let hash = &hash[..PATH_HASH_LEN];
+    let name = match extension {
+        Some(extension) => format!("{}.{}", hash, extension),
+        None => hash.to_string(),
+    };
+    (Path::new(root).join(&name).to_string_lossy().to_string(), name)
+}
+
+pub fn redact_snapshot(key: &[u8], snapshot: &FileSnapshot, root: &str) -> FileSnapshot {
+    let extension = snapshot.file_extension.as_deref().filter(|_| !snapshot.is_folder);
+    let (file_path, file_name) = redact_path(key, Path::new(&snapshot.file_path), root, extension);
+    FileSnapshot { file_path, file_name, ..snapshot.clone() }
+}
+
+/// Anonimizar (`redact_paths: true`) o no las rutas de un dispositivo, o volver al ajuste global (`null`).
+/// Se aplica desde el próximo escaneo; lo ya guardado no cambia.
+#[tauri::command]
+pub async fn set_device_privacy(state: tauri::State<'_, AppState>, device_id: String, redact_paths: Option<bool>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let updated = db
+        .set_device_redact_paths(&device_id, redact_paths)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if !updated {
+        return Err(format!("Device not found: {}", device_id));
+    }
+    audit::record(&db, DEVICE_PRIVACY_AUDIT_ACTION, Some(&device_id), &serde_json::json!({ "redact_paths": redact_paths }));
+    Ok(serde_json::json!({
+        "success": true,
+        "device_id": device_id,
+        "redact_paths": redacts_paths(&db, &device_id),
+    }))
+}
+
+/// Cómo se anonimiza cada valor en `redact_history`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactMode {
+    /// Hash con una clave aleatoria que se descarta al terminar: los valores iguales siguen siendo iguales
+    /// (se pueden contar archivos o usuarios distintos) pero no se pueden recuperar
+    #[default]
+    Hash,
+    /// Todos los valores se sustituyen por "redacted"
+    Strip,
+}
+
+/// Qué anonimizar en `redact_history`: sin `device_id`, todos los dispositivos; sin `before`, todo el historial
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactOptions {
+    pub device_id: Option<String>,
+    pub before: Option<DateTime<Utc>>,
+    /// Nombres y rutas de archivo (snapshots, eventos, evidencias, puntos de montaje y alertas)
+    pub paths: bool,
+    /// Usuarios del sistema de la actividad y autores de documentos
+    pub usernames: bool,
+    pub mode: RedactMode,
+}
+
+impl Default for RedactOptions {
+    fn default() -> Self {
+        Self { device_id: None, before: None, paths: true, usernames: true, mode: RedactMode::Hash }
+    }
+}
+
+/// Anonimizar de forma irreversible las rutas y los usuarios del historial ya guardado, conservando recuentos
+/// y tamaños para estadísticas tras una revisión de privacidad. Los informes generados y el registro de
+/// auditoría no se modifican.
+#[tauri::command]
+pub async fn redact_history(state: tauri::State<'_, AppState>, options: Option<RedactOptions>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let options = options.unwrap_or_default();
+    if !options.paths && !options.usernames {
+        return Err("Nothing to redact: enable paths or usernames".to_string());
+    }
+
+    let key = uuid::Uuid::new_v4().to_string();
+    let redact = move |value: &str| -> String {
+        match options.mode {
+            RedactMode::Hash => {
+                let hash = format!("{:x}", Sha256::digest(format!("{}:{}", key, value).as_bytes()));
+                hash[..PATH_HASH_LEN].to_string()
+            }
+            RedactMode::Strip => STRIPPED.to_string(),
+        }
+    };
+    let task_db = db.clone();
+    let task_options = options.clone();
+    let task_redact = redact.clone();
+    let summary = tauri::async_runtime::spawn_blocking(move || {
+        task_db.redact_history(
+            task_options.device_id.as_deref(),
+            task_options.before,
+            task_options.paths.then_some(&task_redact as &dyn Fn(&str) -> String),
+            task_options.usernames.then_some(&task_redact as &dyn Fn(&str) -> String),
+        )
+    })
+    .await
+    .map_err(|e| format!("Redaction task failed: {}", e))?
+    .map_err(|e| format!("Database error: {}", e))?;
+
+    // Los archivos en cuarentena no están en la base de datos, pero su nombre es el del archivo bloqueado
+    let quarantined: usize = if options.paths {
+        summary.activity_ids.iter().map(|&id| write_block::redact_quarantine(&db, id, &redact)).sum()
+    } else {
+        0
+    };
+
+    audit::record(&db, HISTORY_REDACTED_AUDIT_ACTION, options.device_id.as_deref(), &serde_json::json!({
+        "before": options.before,
+        "paths": options.paths,
+        "usernames": options.usernames,
+        "mode": options.mode,
+        "summary": summary,
+        "quarantined": quarantined,
+    }));
+    Ok(serde_json::json!({ "success": true, "summary": summary, "quarantined": quarantined }))
+}
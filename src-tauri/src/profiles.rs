@@ -0,0 +1,192 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use crate::events;
+use crate::i18n::Message;
+use crate::kiosk;
+use crate::plugins;
+use crate::secrets;
+use crate::state::AppState;
+
+pub const DEFAULT_PROFILE: &str = "default";
+const REGISTRY_FILE: &str = "profiles.json";
+const DB_FILE: &str = "usb_manager.db";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Lista de perfiles guardada en `profiles.json` dentro del directorio de datos
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProfileRegistry {
+    pub active: String,
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for ProfileRegistry {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: vec![Profile {
+                name: DEFAULT_PROFILE.to_string(),
+                created_at: Utc::now(),
+            }],
+        }
+    }
+}
+
+impl ProfileRegistry {
+    pub fn load(app_data_dir: &Path) -> Self {
+        let path = app_data_dir.join(REGISTRY_FILE);
+
+        match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                println!("[Profiles] Invalid registry, using default: {}", e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, app_data_dir: &Path) -> Result<(), String> {
+        let path = app_data_dir.join(REGISTRY_FILE);
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, content).map_err(|e| format!("Failed to save profiles: {}", e))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.profiles.iter().any(|p| p.name == name)
+    }
+
+    /// Ruta del archivo de base de datos de un perfil.
+    /// El perfil por defecto conserva la ubicación original para no perder datos previos.
+    pub fn db_path(app_data_dir: &Path, name: &str) -> PathBuf {
+        if name == DEFAULT_PROFILE {
+            return app_data_dir.join(DB_FILE);
+        }
+
+        app_data_dir
+            .join("profiles")
+            .join(profile_dir_name(name))
+            .join(DB_FILE)
+    }
+}
+
+// Nombre de carpeta seguro a partir del nombre visible del perfil
+fn profile_dir_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Ruta de la base de datos del perfil activo
+pub fn active_db_path(app_data_dir: &Path) -> PathBuf {
+    let registry = ProfileRegistry::load(app_data_dir);
+    ProfileRegistry::db_path(app_data_dir, &registry.active)
+}
+
+fn app_data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
+
+#[tauri::command]
+pub async fn list_profiles(state: tauri::State<'_, AppState>, app: AppHandle) -> Result<serde_json::Value, String> {
+    let registry = ProfileRegistry::load(&app_data_dir(&app)?);
+    let database_path = state.db.path().to_string_lossy().to_string();
+
+    Ok(serde_json::json!({
+        "success": true,
+        "active": registry.active,
+        "profiles": registry.profiles,
+        "database_path": database_path,
+    }))
+}
+
+#[tauri::command]
+pub async fn create_profile(app: AppHandle, name: String) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+
+    let data_dir = app_data_dir(&app)?;
+    let mut registry = ProfileRegistry::load(&data_dir);
+
+    let dir_name = profile_dir_name(&name);
+    if registry.profiles.iter().any(|p| p.name == name || profile_dir_name(&p.name) == dir_name) {
+        return Err(format!("Profile already exists: {}", name));
+    }
+
+    let db_path = ProfileRegistry::db_path(&data_dir, &name);
+    if let Some(dir) = db_path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create profile directory: {}", e))?;
+    }
+
+    let profile = Profile {
+        name: name.clone(),
+        created_at: Utc::now(),
+    };
+    registry.profiles.push(profile.clone());
+    registry.save(&data_dir)?;
+
+    println!("[Profiles] Created profile: {}", name);
+    Ok(serde_json::json!({ "success": true, "profile": profile }))
+}
+
+#[tauri::command]
+pub async fn switch_profile(
+    state: tauri::State<'_, AppState>,
+    app: AppHandle,
+    name: String,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    if crate::service::service_link().is_some() {
+        return Err("Profiles are managed by the background service".to_string());
+    }
+
+    let data_dir = app_data_dir(&app)?;
+    let mut registry = ProfileRegistry::load(&data_dir);
+
+    if !registry.contains(&name) {
+        return Err(format!("Unknown profile: {}", name));
+    }
+
+    let db = state.db.clone();
+    // Lo guardado en memoria se perdería al cambiar de archivo
+    if db.is_in_memory() {
+        return Err("Database is running in memory: restore it with retry_db_init first".to_string());
+    }
+    let db_path = ProfileRegistry::db_path(&data_dir, &name);
+    if let Some(dir) = db_path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create profile directory: {}", e))?;
+    }
+
+    db.reopen(db_path).map_err(|e| format!("Database error: {}", e))?;
+    secrets::migrate(&db);
+    plugins::reload(&db);
+
+    registry.active = name.clone();
+    registry.save(&data_dir)?;
+
+    // Los dispositivos conectados se vuelven a registrar en el nuevo perfil
+    state.monitor.reset_session_state();
+
+    println!("[Profiles] Switched to profile: {}", name);
+    events::emit(
+        &app,
+        "profile-switched",
+        serde_json::json!({
+            "profile": name,
+            "message": Message::new("profile.switched", serde_json::json!({ "profile": name })),
+        }),
+    );
+
+    Ok(serde_json::json!({ "success": true, "active": name }))
+}
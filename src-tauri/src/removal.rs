@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use crate::db::EventType;
+
+// Tiempo tras la última escritura en que el sistema puede tener datos sin volcar al volumen
+// (Linux vuelca las páginas sucias a los 30 s; Windows con caché de escritura, antes)
+const DIRTY_WINDOW: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct RemovalState {
+    last_write: Option<Instant>,
+    ejected: bool,
+}
+
+// device_id -> escrituras y expulsión desde la conexión
+static STATE: OnceLock<Mutex<HashMap<String, RemovalState>>> = OnceLock::new();
+
+fn state() -> &'static Mutex<HashMap<String, RemovalState>> {
+    STATE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Anotar una escritura en el dispositivo (creación o modificación vista por el watcher)
+pub fn note_write(device_id: &str) {
+    let mut state = state().lock().unwrap();
+    let entry = state.entry(device_id.to_string()).or_default();
+    entry.last_write = Some(Instant::now());
+    entry.ejected = false;
+}
+
+/// Anotar que el volumen se expulsó desde la aplicación: la siguiente desconexión es limpia
+pub fn note_eject(device_id: &str) {
+    state().lock().unwrap().entry(device_id.to_string()).or_default().ejected = true;
+}
+
+/// Tipo de evento con que se registra la desconexión: retirada sin expulsar si no se expulsó
+/// desde la aplicación y hubo escrituras hace menos de `DIRTY_WINDOW`. Olvida el estado del dispositivo.
+pub fn classify(device_id: &str) -> EventType {
+    let Some(state) = state().lock().unwrap().remove(device_id) else {
+        return EventType::Disconnect;
+    };
+    let dirty = state.last_write.is_some_and(|at| at.elapsed() < DIRTY_WINDOW);
+    if !state.ejected && dirty {
+        EventType::UnsafeRemoval
+    } else {
+        EventType::Disconnect
+    }
+}
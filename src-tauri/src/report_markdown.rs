@@ -0,0 +1,221 @@
+use chrono::{DateTime, Local, Utc};
+use crate::reports::{format_bytes, SummaryReport};
+
+// Máximo de eventos de la línea de tiempo; los informes para tickets deben ser breves
+const MAX_TIMELINE_ROWS: usize = 100;
+
+fn local_date(d: &DateTime<Utc>) -> String {
+    d.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// Secciones del informe como tablas (título, cabeceras, filas), compartidas por Markdown y HTML
+struct Section {
+    title: &'static str,
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+    note: Option<String>,
+}
+
+fn title(report: &SummaryReport) -> String {
+    match report.device_id {
+        Some(ref device_id) => format!("USB activity report: {}", device_id),
+        None => "USB activity report".to_string(),
+    }
+}
+
+fn summary_lines(report: &SummaryReport) -> Vec<(String, String)> {
+    let counts = &report.alert_counts;
+    vec![
+        (
+            "Period".into(),
+            format!("{} - {}", local_date(&report.period_start), local_date(&report.period_end)),
+        ),
+        ("Connections".into(), report.total_connections.to_string()),
+        ("New devices".into(), report.new_devices.len().to_string()),
+        (
+            "Alerts".into(),
+            format!(
+                "{} (critical {}, high {}, medium {}, low {})",
+                report.alerts.len(),
+                counts.critical,
+                counts.high,
+                counts.medium,
+                counts.low
+            ),
+        ),
+        ("Generated".into(), local_date(&report.generated_at)),
+    ]
+}
+
+fn sections(report: &SummaryReport) -> Vec<Section> {
+    let device_name = |id: &str, name: &Option<String>| name.clone().unwrap_or_else(|| id.to_string());
+
+    let timeline_note = (report.timeline.len() > MAX_TIMELINE_ROWS)
+        .then(|| format!("{} more events not shown.", report.timeline.len() - MAX_TIMELINE_ROWS));
+
+    vec![
+        Section {
+            title: "Devices",
+            headers: &["Device", "Serial", "Connections"],
+            rows: report
+                .connections_by_device
+                .iter()
+                .map(|c| {
+                    vec![
+                        device_name(&c.device_id, &c.device_name),
+                        c.device_id.clone(),
+                        c.connections.to_string(),
+                    ]
+                })
+                .collect(),
+            note: None,
+        },
+        Section {
+            title: "New devices",
+            headers: &["Device", "VID:PID", "Serial"],
+            rows: report
+                .new_devices
+                .iter()
+                .map(|d| {
+                    vec![
+                        device_name(&d.serial_number, &d.name),
+                        format!("{:04x}:{:04x}", d.vendor_id, d.product_id),
+                        d.serial_number.clone(),
+                    ]
+                })
+                .collect(),
+            note: None,
+        },
+        Section {
+            title: "Alerts",
+            headers: &["Date", "Severity", "Message"],
+            rows: report
+                .alerts
+                .iter()
+                .map(|a| vec![local_date(&a.created_at), a.severity.as_str().to_string(), a.message.clone()])
+                .collect(),
+            note: None,
+        },
+        Section {
+            title: "Biggest scans",
+            headers: &["Date", "Device", "Size", "Files"],
+            rows: report
+                .biggest_transfers
+                .iter()
+                .map(|t| {
+                    vec![
+                        local_date(&t.timestamp),
+                        device_name(&t.device_id, &t.device_name),
+                        format_bytes(t.total_bytes),
+                        t.total_files.to_string(),
+                    ]
+                })
+                .collect(),
+            note: None,
+        },
+        Section {
+            title: "Timeline",
+            headers: &["Date", "Event", "Device", "User"],
+            rows: report
+                .timeline
+                .iter()
+                .take(MAX_TIMELINE_ROWS)
+                .map(|e| {
+                    vec![
+                        local_date(&e.timestamp),
+                        e.event_type.as_str().to_string(),
+                        e.device_id.clone(),
+                        e.os_user.clone().unwrap_or_default(),
+                    ]
+                })
+                .collect(),
+            note: timeline_note,
+        },
+    ]
+}
+
+fn escape_markdown(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render_markdown(report: &SummaryReport) -> String {
+    let mut out = format!("# {}\n\n", title(report));
+
+    for (label, value) in summary_lines(report) {
+        out.push_str(&format!("- **{}:** {}\n", label, escape_markdown(&value)));
+    }
+
+    for section in sections(report) {
+        out.push_str(&format!("\n## {}\n\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str("_None._\n");
+            continue;
+        }
+
+        out.push_str(&format!("| {} |\n", section.headers.join(" | ")));
+        out.push_str(&format!("|{}\n", "---|".repeat(section.headers.len())));
+        for row in &section.rows {
+            let cells: Vec<String> = row.iter().map(|c| escape_markdown(c)).collect();
+            out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+        if let Some(note) = section.note {
+            out.push_str(&format!("\n_{}_\n", note));
+        }
+    }
+
+    out
+}
+
+/// HTML autocontenido (estilos en línea) para pegar en wikis o adjuntar a tickets
+pub fn render_html(report: &SummaryReport) -> String {
+    let title = escape_html(&title(report));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body{{font-family:sans-serif;margin:2em;color:#222}}\
+         table{{border-collapse:collapse;margin-bottom:1em}}\
+         th,td{{border:1px solid #ccc;padding:4px 8px;text-align:left;font-size:14px}}\
+         th{{background:#f0f0f0}}</style>\n</head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+        title, title
+    );
+
+    for (label, value) in summary_lines(report) {
+        out.push_str(&format!("<li><strong>{}:</strong> {}</li>\n", label, escape_html(&value)));
+    }
+    out.push_str("</ul>\n");
+
+    for section in sections(report) {
+        out.push_str(&format!("<h2>{}</h2>\n", section.title));
+        if section.rows.is_empty() {
+            out.push_str("<p><em>None.</em></p>\n");
+            continue;
+        }
+
+        out.push_str("<table>\n<tr>");
+        for header in section.headers {
+            out.push_str(&format!("<th>{}</th>", header));
+        }
+        out.push_str("</tr>\n");
+        for row in &section.rows {
+            out.push_str("<tr>");
+            for cell in row {
+                out.push_str(&format!("<td>{}</td>", escape_html(cell)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+        if let Some(note) = section.note {
+            out.push_str(&format!("<p><em>{}</em></p>\n", escape_html(&note)));
+        }
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
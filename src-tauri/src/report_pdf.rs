@@ -0,0 +1,304 @@
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use printpdf::{
+    BuiltinFont, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point, Rect,
+    Rgb,
+};
+use std::collections::BTreeMap;
+use crate::db::EventType;
+use crate::reports::{format_bytes, SummaryReport};
+
+// A4 en milímetros
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 18.0;
+const LINE_HEIGHT: f32 = 5.0;
+const CHART_HEIGHT: f32 = 45.0;
+const MAX_TIMELINE_ROWS: usize = 500;
+
+fn rgb(r: f32, g: f32, b: f32) -> Color {
+    Color::Rgb(Rgb::new(r, g, b, None))
+}
+
+fn local_date(d: &DateTime<Utc>) -> String {
+    d.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+}
+
+// Las fuentes integradas de PDF no tienen métricas disponibles: se recorta por número de caracteres
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max_chars.saturating_sub(3)).collect();
+        format!("{}...", cut)
+    }
+}
+
+/// Escritor de páginas con cursor vertical y salto de página automático
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    font: IndirectFontRef,
+    bold: IndirectFontRef,
+    layer: PdfLayerReference,
+    y: f32,
+    pages: usize,
+}
+
+impl PdfWriter {
+    fn new(title: &str) -> Result<Self, String> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        let font = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| e.to_string())?;
+        let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| e.to_string())?;
+        let layer = doc.get_page(page).get_layer(layer);
+
+        Ok(Self {
+            doc,
+            font,
+            bold,
+            layer,
+            y: PAGE_HEIGHT - MARGIN,
+            pages: 1,
+        })
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+        self.pages += 1;
+    }
+
+    fn ensure_space(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+    }
+
+    fn text_at(&self, text: &str, size: f32, x: f32, y: f32, bold: bool) {
+        let font = if bold { &self.bold } else { &self.font };
+        self.layer.use_text(text, size, Mm(x), Mm(y), font);
+    }
+
+    fn line(&mut self, text: &str, size: f32, bold: bool) {
+        self.ensure_space(LINE_HEIGHT);
+        self.text_at(text, size, MARGIN, self.y, bold);
+        self.y -= LINE_HEIGHT;
+    }
+
+    // Fila de tabla: cada celda con su posición x relativa al margen
+    fn row(&mut self, cells: &[(f32, String)], bold: bool) {
+        self.ensure_space(LINE_HEIGHT);
+        for (x, text) in cells {
+            self.text_at(text, 9.0, MARGIN + x, self.y, bold);
+        }
+        self.y -= LINE_HEIGHT;
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.ensure_space(LINE_HEIGHT * 4.0);
+        self.y -= LINE_HEIGHT;
+        self.text_at(text, 13.0, MARGIN, self.y, true);
+        self.y -= 2.0;
+        self.rule();
+        self.y -= LINE_HEIGHT;
+    }
+
+    fn rule(&self) {
+        self.layer.set_outline_color(rgb(0.7, 0.7, 0.7));
+        self.layer.set_outline_thickness(0.5);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(self.y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(self.y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    /// Gráfico de barras simple con etiquetas bajo cada barra
+    fn bar_chart(&mut self, bars: &[(String, f64)], value_label: impl Fn(f64) -> String) {
+        if bars.is_empty() {
+            self.line("No data for this period.", 9.0, false);
+            return;
+        }
+
+        self.ensure_space(CHART_HEIGHT + LINE_HEIGHT * 3.0);
+        let width = PAGE_WIDTH - MARGIN * 2.0;
+        let base = self.y - CHART_HEIGHT;
+        let max = bars.iter().map(|(_, v)| *v).fold(0.0, f64::max).max(1.0);
+        let slot = width / bars.len() as f32;
+        let bar_width = (slot * 0.7).max(0.5);
+        // Con muchas barras solo se etiqueta una de cada N
+        let label_every = ((bars.len() as f32 * 14.0 / width).ceil() as usize).max(1);
+
+        self.layer.set_fill_color(rgb(0.22, 0.47, 0.78));
+        for (i, (label, value)) in bars.iter().enumerate() {
+            let x = MARGIN + slot * i as f32 + (slot - bar_width) / 2.0;
+            let height = (*value / max) as f32 * (CHART_HEIGHT - LINE_HEIGHT);
+            if height > 0.0 {
+                self.layer.add_rect(Rect::new(Mm(x), Mm(base), Mm(x + bar_width), Mm(base + height)));
+            }
+
+            if i % label_every == 0 {
+                self.layer.set_fill_color(rgb(0.0, 0.0, 0.0));
+                self.text_at(&truncate(label, 10), 6.0, x, base - 4.0, false);
+                if bars.len() <= 16 {
+                    self.text_at(&value_label(*value), 6.0, x, base + height + 1.0, false);
+                }
+                self.layer.set_fill_color(rgb(0.22, 0.47, 0.78));
+            }
+        }
+        self.layer.set_fill_color(rgb(0.0, 0.0, 0.0));
+
+        self.layer.set_outline_color(rgb(0.3, 0.3, 0.3));
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(base)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(base)), false),
+            ],
+            is_closed: false,
+        });
+
+        self.y = base - LINE_HEIGHT * 2.0;
+    }
+
+    fn finish(self) -> Result<Vec<u8>, String> {
+        self.doc.save_to_bytes().map_err(|e| e.to_string())
+    }
+}
+
+// Conexiones por día (hora local) a partir de la línea de tiempo
+fn connections_per_day(report: &SummaryReport) -> Vec<(String, f64)> {
+    let mut days: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+    for event in &report.timeline {
+        if matches!(event.event_type, EventType::Connect) {
+            *days.entry(event.timestamp.with_timezone(&Local).date_naive()).or_default() += 1.0;
+        }
+    }
+
+    days.into_iter()
+        .map(|(day, count)| (day.format("%m-%d").to_string(), count))
+        .collect()
+}
+
+/// Generar el informe resumen como PDF (A4, fuentes integradas)
+pub fn render_pdf(report: &SummaryReport) -> Result<Vec<u8>, String> {
+    let mut pdf = PdfWriter::new("USB Manager - Activity Report")?;
+
+    pdf.line("USB Manager - Activity Report", 18.0, true);
+    pdf.y -= 2.0;
+    pdf.line(
+        &format!("Period: {} - {}", local_date(&report.period_start), local_date(&report.period_end)),
+        10.0,
+        false,
+    );
+    if let Some(ref device_id) = report.device_id {
+        pdf.line(&format!("Device: {}", device_id), 10.0, false);
+    }
+    pdf.line(&format!("Generated: {}", local_date(&report.generated_at)), 10.0, false);
+
+    // Resumen
+    let counts = &report.alert_counts;
+    pdf.heading("Summary");
+    pdf.row(&[(0.0, "Connections".into()), (60.0, report.total_connections.to_string())], false);
+    pdf.row(&[(0.0, "Devices seen".into()), (60.0, report.connections_by_device.len().to_string())], false);
+    pdf.row(&[(0.0, "New devices".into()), (60.0, report.new_devices.len().to_string())], false);
+    pdf.row(
+        &[
+            (0.0, "Alerts".into()),
+            (
+                60.0,
+                format!(
+                    "{} (critical {}, high {}, medium {}, low {})",
+                    report.alerts.len(),
+                    counts.critical,
+                    counts.high,
+                    counts.medium,
+                    counts.low
+                ),
+            ),
+        ],
+        false,
+    );
+
+    // Dispositivos
+    pdf.heading("Devices");
+    pdf.row(&[(0.0, "Device".into()), (100.0, "Serial".into()), (160.0, "Connections".into())], true);
+    for count in &report.connections_by_device {
+        let new = report.new_devices.iter().any(|d| d.serial_number == count.device_id);
+        let name = count.device_name.clone().unwrap_or_else(|| count.device_id.clone());
+        pdf.row(
+            &[
+                (0.0, truncate(&if new { format!("{} (new)", name) } else { name }, 50)),
+                (100.0, truncate(&count.device_id, 30)),
+                (160.0, count.connections.to_string()),
+            ],
+            false,
+        );
+    }
+
+    pdf.heading("Connections per day");
+    pdf.bar_chart(&connections_per_day(report), |v| format!("{}", v as i64));
+
+    // Escaneos
+    pdf.heading("Biggest scans");
+    let transfers: Vec<(String, f64)> = report
+        .biggest_transfers
+        .iter()
+        .map(|t| (t.device_name.clone().unwrap_or_else(|| t.device_id.clone()), t.total_bytes as f64))
+        .collect();
+    pdf.bar_chart(&transfers, |v| format_bytes(v as i64));
+    pdf.row(
+        &[(0.0, "Date".into()), (35.0, "Device".into()), (120.0, "Size".into()), (150.0, "Files".into())],
+        true,
+    );
+    for transfer in &report.biggest_transfers {
+        pdf.row(
+            &[
+                (0.0, local_date(&transfer.timestamp)),
+                (35.0, truncate(&transfer.device_name.clone().unwrap_or_else(|| transfer.device_id.clone()), 45)),
+                (120.0, format_bytes(transfer.total_bytes)),
+                (150.0, transfer.total_files.to_string()),
+            ],
+            false,
+        );
+    }
+
+    // Alertas
+    pdf.heading("Alerts");
+    if report.alerts.is_empty() {
+        pdf.line("No alerts in this period.", 9.0, false);
+    }
+    for alert in &report.alerts {
+        pdf.row(
+            &[
+                (0.0, local_date(&alert.created_at)),
+                (35.0, alert.severity.as_str().to_string()),
+                (57.0, truncate(&alert.message, 75)),
+            ],
+            false,
+        );
+    }
+
+    // Línea de tiempo
+    pdf.heading("Timeline");
+    for event in report.timeline.iter().take(MAX_TIMELINE_ROWS) {
+        pdf.row(
+            &[
+                (0.0, local_date(&event.timestamp)),
+                (35.0, event.event_type.as_str().to_string()),
+                (65.0, truncate(&event.device_id, 60)),
+            ],
+            false,
+        );
+    }
+    if report.timeline.len() > MAX_TIMELINE_ROWS {
+        pdf.line(
+            &format!("... {} more events not shown", report.timeline.len() - MAX_TIMELINE_ROWS),
+            9.0,
+            false,
+        );
+    }
+
+    println!("[Reports] PDF rendered: {} pages", pdf.pages);
+    pdf.finish()
+}
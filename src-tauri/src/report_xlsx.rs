@@ -0,0 +1,166 @@
+use chrono::{DateTime, Local, Utc};
+use rust_xlsxwriter::{ColNum, Format, Workbook, Worksheet, XlsxError};
+use crate::db::{ActivityLog, Database, Device, DeviceSort, FileSnapshot};
+use crate::reports::ReportScope;
+use crate::state::AppState;
+
+fn xlsx_err(e: XlsxError) -> String {
+    format!("XLSX error: {}", e)
+}
+
+fn local_datetime(d: &DateTime<Utc>) -> chrono::NaiveDateTime {
+    d.with_timezone(&Local).naive_local()
+}
+
+/// Cabecera en negrita, fila congelada y autofiltro sobre el rango de datos
+fn write_header(sheet: &mut Worksheet, columns: &[(&str, f64)], rows: usize) -> Result<(), XlsxError> {
+    let bold = Format::new().set_bold();
+    for (col, (title, width)) in columns.iter().enumerate() {
+        sheet.write_string_with_format(0, col as ColNum, *title, &bold)?;
+        sheet.set_column_width(col as ColNum, *width)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofilter(0, 0, rows as u32, (columns.len() - 1) as ColNum)?;
+    Ok(())
+}
+
+fn write_devices(sheet: &mut Worksheet, devices: &[Device]) -> Result<(), XlsxError> {
+    sheet.set_name("Devices")?;
+    write_header(
+        sheet,
+        &[
+            ("Serial number", 28.0),
+            ("Name", 30.0),
+            ("Manufacturer", 24.0),
+            ("Vendor ID", 11.0),
+            ("Product ID", 11.0),
+            ("Capacity (bytes)", 18.0),
+            ("Trust", 10.0),
+        ],
+        devices.len(),
+    )?;
+
+    let bytes = Format::new().set_num_format("#,##0");
+    for (i, device) in devices.iter().enumerate() {
+        let row = i as u32 + 1;
+        sheet.write_string(row, 0, &device.serial_number)?;
+        sheet.write_string(row, 1, device.name.as_deref().unwrap_or(""))?;
+        sheet.write_string(row, 2, device.manufacturer.as_deref().unwrap_or(""))?;
+        // VID/PID se escriben como texto hexadecimal, igual que en el resto de la app
+        sheet.write_string(row, 3, format!("{:04x}", device.vendor_id))?;
+        sheet.write_string(row, 4, format!("{:04x}", device.product_id))?;
+        if let Some(capacity) = device.total_capacity {
+            sheet.write_number_with_format(row, 5, capacity as f64, &bytes)?;
+        }
+        sheet.write_string(row, 6, device.trust_level.as_str())?;
+    }
+    Ok(())
+}
+
+fn write_activity(sheet: &mut Worksheet, events: &[ActivityLog], devices: &[Device]) -> Result<(), XlsxError> {
+    sheet.set_name("Activity")?;
+    write_header(
+        sheet,
+        &[
+            ("ID", 8.0),
+            ("Timestamp", 20.0),
+            ("Event", 13.0),
+            ("Device", 28.0),
+            ("Device name", 30.0),
+            ("User", 20.0),
+        ],
+        events.len(),
+    )?;
+
+    let datetime = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+    for (i, event) in events.iter().enumerate() {
+        let row = i as u32 + 1;
+        let name = devices
+            .iter()
+            .find(|d| d.serial_number == event.device_id)
+            .and_then(|d| d.name.as_deref())
+            .unwrap_or("");
+
+        sheet.write_number(row, 0, event.id as f64)?;
+        sheet.write_datetime_with_format(row, 1, local_datetime(&event.timestamp), &datetime)?;
+        sheet.write_string(row, 2, event.event_type.as_str())?;
+        sheet.write_string(row, 3, &event.device_id)?;
+        sheet.write_string(row, 4, name)?;
+        sheet.write_string(row, 5, event.os_user.as_deref().unwrap_or(""))?;
+    }
+    Ok(())
+}
+
+fn write_files(sheet: &mut Worksheet, snapshots: &[FileSnapshot]) -> Result<(), XlsxError> {
+    sheet.set_name("Files")?;
+    write_header(
+        sheet,
+        &[
+            ("Path", 60.0),
+            ("Name", 30.0),
+            ("Extension", 10.0),
+            ("Size (bytes)", 16.0),
+            ("Folder", 8.0),
+            ("SHA-256", 66.0),
+        ],
+        snapshots.len(),
+    )?;
+
+    let bytes = Format::new().set_num_format("#,##0");
+    for (i, snapshot) in snapshots.iter().enumerate() {
+        let row = i as u32 + 1;
+        sheet.write_string(row, 0, &snapshot.file_path)?;
+        sheet.write_string(row, 1, &snapshot.file_name)?;
+        sheet.write_string(row, 2, snapshot.file_extension.as_deref().unwrap_or(""))?;
+        sheet.write_number_with_format(row, 3, snapshot.file_size as f64, &bytes)?;
+        sheet.write_boolean(row, 4, snapshot.is_folder)?;
+        sheet.write_string(row, 5, snapshot.sha256.as_deref().unwrap_or(""))?;
+    }
+    Ok(())
+}
+
+/// Libro con una hoja por sección: dispositivos, actividad y contenido de un escaneo
+pub fn render_xlsx(db: &Database, scope: &ReportScope, activity_log_id: Option<i64>) -> Result<Vec<u8>, String> {
+    let (from, to) = scope.bounds();
+    let device_id = scope.device_id.as_deref();
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+
+    let mut devices = db.get_devices(DeviceSort::default()).map_err(db_err)?;
+    if let Some(device_id) = device_id {
+        devices.retain(|d| d.serial_number == device_id);
+    }
+    let events = db.get_activity_between(from, to, device_id).map_err(db_err)?;
+
+    // Sin escaneo explícito se usa el último del dispositivo filtrado, si lo hay
+    let scan_id = match (activity_log_id, device_id) {
+        (Some(id), _) => Some(id),
+        (None, Some(device_id)) => db.get_latest_scan(device_id).map_err(db_err)?.map(|s| s.activity_log_id),
+        (None, None) => None,
+    };
+
+    let mut workbook = Workbook::new();
+    write_devices(workbook.add_worksheet(), &devices).map_err(xlsx_err)?;
+    write_activity(workbook.add_worksheet(), &events, &devices).map_err(xlsx_err)?;
+    if let Some(scan_id) = scan_id {
+        let snapshots = db.get_file_snapshots(scan_id).map_err(db_err)?;
+        write_files(workbook.add_worksheet(), &snapshots).map_err(xlsx_err)?;
+    }
+
+    workbook.save_to_buffer().map_err(xlsx_err)
+}
+
+#[tauri::command]
+pub async fn export_xlsx(
+    state: tauri::State<'_, AppState>,
+    scope: ReportScope,
+    activity_log_id: Option<i64>,
+    path: String,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    let content = render_xlsx(&db, &scope, activity_log_id)?;
+    std::fs::write(&path, &content).map_err(|e| format!("Failed to write workbook: {}", e))?;
+
+    println!("[Reports] Exported XLSX to {}", path);
+    Ok(serde_json::json!({ "success": true, "path": path, "bytes": content.len() }))
+}
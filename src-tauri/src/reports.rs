@@ -0,0 +1,521 @@
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, TimeZone, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::db::{
+    ActivityLog, Alert, AlertSeverity, Database, Device, DeviceConnectionCount, TransferSummary,
+};
+use crate::cloud_backup;
+use crate::i18n::{Message, DEFAULT_LANG};
+use crate::outbox;
+use crate::routing::RoutingSettings;
+use crate::state::AppState;
+
+pub const REPORT_SCHEDULE_KEY: &str = "report_schedule";
+const REPORT_LAST_RUN_KEY: &str = "report_schedule_last_run";
+const TOP_TRANSFERS: i64 = 10;
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60);
+const REPORT_EMAIL_ALERT: &str = "SCHEDULED_REPORT";
+
+/// Alcance de un informe: rango de fechas y, opcionalmente, un solo dispositivo
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ReportScope {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub device_id: Option<String>,
+}
+
+impl ReportScope {
+    pub fn bounds(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let from = self.from.unwrap_or_else(|| Utc.timestamp_opt(0, 0).unwrap());
+        let to = self.to.unwrap_or_else(Utc::now);
+        (from, to)
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct AlertCounts {
+    pub low: usize,
+    pub medium: usize,
+    pub high: usize,
+    pub critical: usize,
+}
+
+/// Datos de un informe resumen; todos los formatos de exportación se generan desde aquí
+#[derive(Debug, Serialize, Clone)]
+pub struct SummaryReport {
+    pub generated_at: DateTime<Utc>,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub device_id: Option<String>,
+    pub total_connections: i64,
+    pub new_devices: Vec<Device>,
+    pub connections_by_device: Vec<DeviceConnectionCount>,
+    pub alert_counts: AlertCounts,
+    pub alerts: Vec<Alert>,
+    pub biggest_transfers: Vec<TransferSummary>,
+    pub timeline: Vec<ActivityLog>,
+}
+
+pub fn build_summary_report(db: &Database, scope: &ReportScope) -> Result<SummaryReport, String> {
+    let (from, to) = scope.bounds();
+    let device_id = scope.device_id.as_deref();
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+
+    let new_devices = db.get_new_devices(from, to, device_id).map_err(db_err)?;
+    let connections_by_device = db.get_connection_counts(from, to, device_id).map_err(db_err)?;
+    let alerts = db.get_alerts_between(from, to, device_id).map_err(db_err)?;
+    let biggest_transfers = db.get_largest_scans(from, to, device_id, TOP_TRANSFERS).map_err(db_err)?;
+    let timeline = db.get_activity_between(from, to, device_id).map_err(db_err)?;
+
+    let mut alert_counts = AlertCounts::default();
+    for alert in &alerts {
+        match alert.severity {
+            AlertSeverity::Low => alert_counts.low += 1,
+            AlertSeverity::Medium => alert_counts.medium += 1,
+            AlertSeverity::High => alert_counts.high += 1,
+            AlertSeverity::Critical => alert_counts.critical += 1,
+        }
+    }
+
+    Ok(SummaryReport {
+        generated_at: Utc::now(),
+        period_start: from,
+        period_end: to,
+        device_id: scope.device_id.clone(),
+        total_connections: connections_by_device.iter().map(|c| c.connections).sum(),
+        new_devices,
+        connections_by_device,
+        alert_counts,
+        alerts,
+        biggest_transfers,
+        timeline,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    Json,
+    #[default]
+    Text,
+    Pdf,
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Text => "text",
+            ReportFormat::Pdf => "pdf",
+            ReportFormat::Markdown => "markdown",
+            ReportFormat::Html => "html",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Json => "json",
+            ReportFormat::Text => "txt",
+            ReportFormat::Pdf => "pdf",
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, ReportFormat::Pdf)
+    }
+}
+
+pub fn render_report(report: &SummaryReport, format: ReportFormat) -> Result<Vec<u8>, String> {
+    match format {
+        ReportFormat::Json => serde_json::to_vec_pretty(report).map_err(|e| e.to_string()),
+        ReportFormat::Text => Ok(render_text(report).into_bytes()),
+        ReportFormat::Pdf => crate::report_pdf::render_pdf(report),
+        ReportFormat::Markdown => Ok(crate::report_markdown::render_markdown(report).into_bytes()),
+        ReportFormat::Html => Ok(crate::report_markdown::render_html(report).into_bytes()),
+    }
+}
+
+pub fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
+
+fn render_text(report: &SummaryReport) -> String {
+    let mut out = String::new();
+    let date = |d: &DateTime<Utc>| d.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string();
+
+    out.push_str("USB Manager - Activity Report\n");
+    out.push_str(&format!("Period: {} - {}\n", date(&report.period_start), date(&report.period_end)));
+    if let Some(ref device_id) = report.device_id {
+        out.push_str(&format!("Device: {}\n", device_id));
+    }
+    out.push_str(&format!("Generated: {}\n\n", date(&report.generated_at)));
+
+    out.push_str(&format!("Connections: {}\n", report.total_connections));
+    for count in &report.connections_by_device {
+        out.push_str(&format!(
+            "  {:<40} {}\n",
+            count.device_name.clone().unwrap_or_else(|| count.device_id.clone()),
+            count.connections
+        ));
+    }
+
+    out.push_str(&format!("\nNew devices: {}\n", report.new_devices.len()));
+    for device in &report.new_devices {
+        out.push_str(&format!(
+            "  {} ({:04x}:{:04x}) {}\n",
+            device.name.clone().unwrap_or_default(),
+            device.vendor_id,
+            device.product_id,
+            device.serial_number
+        ));
+    }
+
+    let counts = &report.alert_counts;
+    out.push_str(&format!(
+        "\nAlerts: {} (critical {}, high {}, medium {}, low {})\n",
+        report.alerts.len(),
+        counts.critical,
+        counts.high,
+        counts.medium,
+        counts.low
+    ));
+    for alert in &report.alerts {
+        out.push_str(&format!(
+            "  [{}] {} {}\n",
+            alert.severity.as_str(),
+            date(&alert.created_at),
+            alert.message
+        ));
+    }
+
+    out.push_str("\nBiggest transfers:\n");
+    for transfer in &report.biggest_transfers {
+        out.push_str(&format!(
+            "  {} {:<40} {} ({} files)\n",
+            date(&transfer.timestamp),
+            transfer.device_name.clone().unwrap_or_else(|| transfer.device_id.clone()),
+            format_bytes(transfer.total_bytes),
+            transfer.total_files
+        ));
+    }
+
+    out.push_str("\nTimeline:\n");
+    for event in &report.timeline {
+        out.push_str(&format!(
+            "  {} {:<10} {}\n",
+            date(&event.timestamp),
+            event.event_type.as_str(),
+            event.device_id
+        ));
+    }
+
+    out
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFrequency {
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportDelivery {
+    /// Guardar el informe en la tabla `reports`
+    Store,
+    /// Escribir el archivo en `folder`
+    Folder,
+    /// Guardarlo y enviarlo por correo con el plugin de notificación `email_plugin` (ajuste `routing`)
+    Email,
+}
+
+/// Programación del informe periódico, guardada en settings bajo `report_schedule`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReportSchedule {
+    pub enabled: bool,
+    pub frequency: ReportFrequency,
+    pub weekday: Weekday,
+    pub day_of_month: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub format: ReportFormat,
+    pub delivery: ReportDelivery,
+    pub folder: Option<String>,
+}
+
+impl Default for ReportSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: ReportFrequency::Weekly,
+            weekday: Weekday::Mon,
+            day_of_month: 1,
+            hour: 8,
+            minute: 0,
+            format: ReportFormat::Text,
+            delivery: ReportDelivery::Store,
+            folder: None,
+        }
+    }
+}
+
+impl ReportSchedule {
+    // Última ocurrencia programada que ya pasó, con el inicio del periodo que cubre
+    fn latest_occurrence(&self, now: DateTime<Local>) -> Option<(DateTime<Local>, DateTime<Local>)> {
+        let at = |date: NaiveDate| {
+            date.and_hms_opt(self.hour.min(23), self.minute.min(59), 0)
+                .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        };
+
+        match self.frequency {
+            ReportFrequency::Weekly => {
+                let days_back = (now.weekday().num_days_from_monday() + 7
+                    - self.weekday.num_days_from_monday())
+                    % 7;
+                let mut occurrence = at(now.date_naive() - ChronoDuration::days(days_back as i64))?;
+                if occurrence > now {
+                    occurrence -= ChronoDuration::days(7);
+                }
+                Some((occurrence, occurrence - ChronoDuration::days(7)))
+            }
+            ReportFrequency::Monthly => {
+                let day = self.day_of_month.clamp(1, 28);
+                let this_month = NaiveDate::from_ymd_opt(now.year(), now.month(), day)?;
+                let mut occurrence = at(this_month)?;
+                if occurrence > now {
+                    occurrence = at(this_month.checked_sub_months(chrono::Months::new(1))?)?;
+                }
+                let start = at(occurrence.date_naive().checked_sub_months(chrono::Months::new(1))?)?;
+                Some((occurrence, start))
+            }
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self.frequency {
+            ReportFrequency::Weekly => "weekly",
+            ReportFrequency::Monthly => "monthly",
+        }
+    }
+}
+
+// El plugin de correo recibe alertas: el informe va como una alerta con el archivo en sus parámetros
+// (en base64 si el formato es binario)
+fn report_email(report: &SummaryReport, kind: &str, format: ReportFormat, content: &[u8]) -> Alert {
+    let file_name = format!(
+        "usb-report-{}-{}.{}",
+        kind,
+        report.period_end.with_timezone(&Local).format("%Y%m%d"),
+        format.extension()
+    );
+    let (encoding, content) = if format.is_binary() {
+        ("base64", cloud_backup::base64(content))
+    } else {
+        ("utf-8", String::from_utf8_lossy(content).to_string())
+    };
+    let message = Message::new(
+        "report.email",
+        serde_json::json!({
+            "kind": kind,
+            "from": report.period_start.with_timezone(&Local).format("%Y-%m-%d").to_string(),
+            "to": report.period_end.with_timezone(&Local).format("%Y-%m-%d").to_string(),
+        }),
+    );
+    let mut params = message.params.clone();
+    params["file_name"] = serde_json::json!(file_name);
+    params["format"] = serde_json::json!(format.as_str());
+    params["encoding"] = serde_json::json!(encoding);
+    params["content"] = serde_json::json!(content);
+
+    Alert {
+        id: 0,
+        device_id: None,
+        activity_log_id: None,
+        alert_type: REPORT_EMAIL_ALERT.to_string(),
+        severity: AlertSeverity::Low,
+        message: message.render(DEFAULT_LANG),
+        message_key: Some(message.key),
+        message_params: Some(params),
+        created_at: Utc::now(),
+        acknowledged: false,
+    }
+}
+
+/// Entregar un informe: guardarlo en la base de datos, escribirlo en una carpeta o enviarlo por correo
+fn deliver_report(
+    db: &Database,
+    report: &SummaryReport,
+    kind: &str,
+    format: ReportFormat,
+    delivery: ReportDelivery,
+    folder: Option<&str>,
+) -> Result<i64, String> {
+    let content = render_report(report, format)?;
+
+    match delivery {
+        ReportDelivery::Store => db
+            .save_report(kind, report.period_start, report.period_end, format.as_str(), Some(&content), None)
+            .map_err(|e| format!("Database error: {}", e)),
+        ReportDelivery::Folder => {
+            let folder = folder.ok_or("No report folder configured")?;
+            std::fs::create_dir_all(folder).map_err(|e| format!("Failed to create report folder: {}", e))?;
+
+            let file_name = format!(
+                "usb-report-{}-{}.{}",
+                kind,
+                report.period_end.with_timezone(&Local).format("%Y%m%d"),
+                format.extension()
+            );
+            let path = PathBuf::from(folder).join(file_name);
+            std::fs::write(&path, &content).map_err(|e| format!("Failed to write report: {}", e))?;
+
+            let path = path.to_string_lossy().to_string();
+            db.save_report(kind, report.period_start, report.period_end, format.as_str(), None, Some(&path))
+                .map_err(|e| format!("Database error: {}", e))
+        }
+        ReportDelivery::Email => {
+            // Pasa por la cola de notificaciones, con sus reintentos si el correo falla
+            let plugin = RoutingSettings::load(db).email_plugin.ok_or("No email_plugin configured")?;
+            let id = db
+                .save_report(kind, report.period_start, report.period_end, format.as_str(), Some(&content), None)
+                .map_err(|e| format!("Database error: {}", e))?;
+            let payload = serde_json::to_value(report_email(report, kind, format, &content)).map_err(|e| e.to_string())?;
+            db.enqueue_outbox(&format!("{}{}", outbox::PLUGIN_CHANNEL_PREFIX, plugin), &payload)
+                .map_err(|e| format!("Database error: {}", e))?;
+            outbox::wake();
+            Ok(id)
+        }
+    }
+}
+
+fn run_due_report(db: &Database) {
+    let schedule: ReportSchedule = db.get_setting(REPORT_SCHEDULE_KEY).ok().flatten().unwrap_or_default();
+    if !schedule.enabled {
+        return;
+    }
+
+    let Some((occurrence, period_start)) = schedule.latest_occurrence(Local::now()) else {
+        return;
+    };
+    let occurrence = occurrence.with_timezone(&Utc);
+
+    let last_run: Option<DateTime<Utc>> = db.get_setting(REPORT_LAST_RUN_KEY).ok().flatten();
+    if last_run.is_some_and(|last| last >= occurrence) {
+        return;
+    }
+
+    let scope = ReportScope {
+        from: Some(period_start.with_timezone(&Utc)),
+        to: Some(occurrence),
+        device_id: None,
+    };
+
+    println!("[Reports] Generating {} report for {:?}", schedule.kind(), scope);
+    let result = build_summary_report(db, &scope).and_then(|report| {
+        deliver_report(
+            db,
+            &report,
+            schedule.kind(),
+            schedule.format,
+            schedule.delivery,
+            schedule.folder.as_deref(),
+        )
+    });
+
+    match result {
+        Ok(id) => println!("[Reports] Scheduled report generated: id={}", id),
+        Err(e) => println!("[Reports] Scheduled report failed: {}", e),
+    }
+
+    // Se marca como ejecutado aunque falle, para no reintentar cada minuto
+    let _ = db.set_setting(REPORT_LAST_RUN_KEY, &occurrence);
+}
+
+/// Bucle del programador de informes periódicos
+pub async fn run_report_scheduler(db: Arc<Database>) {
+    println!("[Reports] Scheduler started.");
+    loop {
+        run_due_report(&db);
+        tokio::time::sleep(SCHEDULER_INTERVAL).await;
+    }
+}
+
+#[tauri::command]
+pub async fn generate_report(state: tauri::State<'_, AppState>, scope: ReportScope, format: Option<ReportFormat>) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let format = format.unwrap_or_default();
+    if format.is_binary() {
+        return Err(format!("{} reports must be exported to a file", format.as_str()));
+    }
+
+    let report = build_summary_report(&db, &scope)?;
+    let content = render_report(&report, format)?;
+
+    Ok(serde_json::json!({
+        "success": true,
+        "format": format,
+        "content": String::from_utf8_lossy(&content),
+    }))
+}
+
+#[tauri::command]
+pub async fn export_report(state: tauri::State<'_, AppState>, scope: ReportScope, format: Option<ReportFormat>, path: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let format = format.unwrap_or_default();
+
+    let report = build_summary_report(&db, &scope)?;
+    let content = render_report(&report, format)?;
+    std::fs::write(&path, &content).map_err(|e| format!("Failed to write report: {}", e))?;
+
+    println!("[Reports] Exported {} report to {}", format.as_str(), path);
+    Ok(serde_json::json!({ "success": true, "path": path, "bytes": content.len() }))
+}
+
+#[tauri::command]
+pub async fn export_report_pdf(state: tauri::State<'_, AppState>, scope: ReportScope, path: String) -> Result<serde_json::Value, String> {
+    export_report(state, scope, Some(ReportFormat::Pdf), path).await
+}
+
+#[tauri::command]
+pub async fn get_reports(state: tauri::State<'_, AppState>, limit: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_reports(limit) {
+        Ok(reports) => Ok(serde_json::json!({ "success": true, "reports": reports })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn get_report_content(state: tauri::State<'_, AppState>, report_id: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_report_content(report_id) {
+        Ok(content) => {
+            // Los formatos binarios (PDF) se devuelven como bytes
+            let content = content.map(|c| match String::from_utf8(c) {
+                Ok(text) => serde_json::json!(text),
+                Err(e) => serde_json::json!(e.into_bytes()),
+            });
+            Ok(serde_json::json!({ "success": true, "report_id": report_id, "content": content }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
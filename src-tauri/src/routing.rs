@@ -0,0 +1,201 @@
+use chrono::{Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+use crate::db::{Alert, AlertSeverity, Database, TrustLevel};
+use crate::i18n::{Message, DEFAULT_LANG};
+use crate::notifications::{self, NotificationSettings};
+use crate::outbox;
+use crate::plugins;
+use crate::rules;
+use crate::secrets::{self, SecretFields};
+
+pub const ROUTING_SETTINGS_KEY: &str = "routing";
+/// Canal de la cola para el webhook configurado en `routing`
+pub const WEBHOOK_CHANNEL: &str = "webhook";
+const NOTIFICATION_TITLE: &str = "USB Manager";
+const CONNECTED_ALERT: &str = "DEVICE_CONNECTED";
+const UNTRUSTED_CONNECTED_ALERT: &str = "UNTRUSTED_DEVICE_CONNECTED";
+
+/// Tipos de evento cuyos avisos se pueden dirigir a canales concretos
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoutedEvent {
+    Connect,
+    UntrustedConnect,
+    LargeCopy,
+    ExecutableWritten,
+}
+
+impl RoutedEvent {
+    /// Evento al que pertenece una alerta guardada; las demás alertas no se enrutan
+    pub fn for_alert(alert_type: &str) -> Option<Self> {
+        match alert_type {
+            "BLOCKED_DEVICE_CONNECTED" => Some(RoutedEvent::UntrustedConnect),
+            rules::WRITE_THRESHOLD_ALERT => Some(RoutedEvent::LargeCopy),
+            rules::EXECUTABLE_WRITTEN_ALERT => Some(RoutedEvent::ExecutableWritten),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    /// Aviso de la interfaz (`alert-notification`), con su sonido
+    Toast,
+    /// Notificación del sistema operativo
+    OsNotification,
+    /// Correo, enviado por el plugin de notificación `email_plugin`
+    Email,
+    /// POST con la alerta en JSON a `webhook_url`
+    Webhook,
+    None,
+}
+
+/// Canales de cada tipo de evento, guardados en settings bajo la clave `routing`.
+/// Solo decide los avisos: los eventos y las alertas se guardan siempre.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingSettings {
+    /// Los eventos sin ruta siguen como siempre: las alertas con aviso en la interfaz y en todos los
+    /// plugins de notificación, las conexiones sin aviso
+    pub routes: HashMap<RoutedEvent, Vec<Channel>>,
+    pub webhook_url: Option<String>,
+    pub email_plugin: Option<String>,
+}
+
+impl SecretFields for RoutingSettings {
+    fn secret_fields(&mut self) -> Vec<(&'static str, &mut String)> {
+        self.webhook_url.iter_mut().map(|url| ("routing.webhook_url", url)).collect()
+    }
+}
+
+impl RoutingSettings {
+    pub fn load(db: &Database) -> Self {
+        let mut settings: Self = db.get_setting(ROUTING_SETTINGS_KEY).ok().flatten().unwrap_or_default();
+        secrets::reveal_all(&mut settings);
+        settings.webhook_url = settings.webhook_url.filter(|url| !url.is_empty());
+        settings
+    }
+}
+
+/// Preparar el ajuste `routing` recibido por `set_setting` para guardarlo: la URL del webhook (que puede llevar
+/// un token) va al llavero. Sin `webhook_url` se conserva la guardada, porque al listar los ajustes no aparece;
+/// con una cadena vacía se quita.
+pub fn seal_setting(db: &Database, value: &serde_json::Value) -> Result<serde_json::Value, String> {
+    let mut settings: RoutingSettings =
+        serde_json::from_value(value.clone()).map_err(|e| format!("Invalid routing settings: {}", e))?;
+    if settings.webhook_url.is_none() {
+        settings.webhook_url = RoutingSettings::load(db).webhook_url;
+    }
+    secrets::seal_all(db, &mut settings);
+    serde_json::to_value(&settings).map_err(|e| e.to_string())
+}
+
+/// Ocultar la URL del webhook al listar los ajustes
+pub fn redact(settings: &mut serde_json::Map<String, serde_json::Value>) {
+    if let Some(serde_json::Value::Object(routing)) = settings.get_mut(ROUTING_SETTINGS_KEY) {
+        routing.remove("webhook_url");
+    }
+}
+
+/// Avisar de una alerta ya guardada por los canales de su tipo de evento
+pub fn dispatch(db: &Database, app_handle: Option<&AppHandle>, alert: &Alert) {
+    let settings = RoutingSettings::load(db);
+    match RoutedEvent::for_alert(&alert.alert_type).and_then(|event| settings.routes.get(&event)) {
+        Some(channels) => send(db, app_handle, alert, &settings, channels),
+        None => {
+            plugins::notify(db, alert);
+            notifications::announce(db, app_handle, alert);
+        }
+    }
+}
+
+fn send(db: &Database, app_handle: Option<&AppHandle>, alert: &Alert, settings: &RoutingSettings, channels: &[Channel]) {
+    let mut queued = false;
+    for channel in channels {
+        match channel {
+            Channel::Toast => notifications::announce(db, app_handle, alert),
+            Channel::OsNotification => show_os_notification(db, app_handle, alert),
+            Channel::Email => match settings.email_plugin.as_deref() {
+                Some(name) => queued |= enqueue(db, &format!("{}{}", outbox::PLUGIN_CHANNEL_PREFIX, name), alert),
+                None => println!("[Routing] No email_plugin configured, email for {} skipped", alert.alert_type),
+            },
+            Channel::Webhook => queued |= enqueue(db, WEBHOOK_CHANNEL, alert),
+            Channel::None => {}
+        }
+    }
+    if queued {
+        outbox::wake();
+    }
+}
+
+fn enqueue(db: &Database, channel: &str, alert: &Alert) -> bool {
+    let payload = serde_json::to_value(alert).unwrap_or_default();
+    match db.enqueue_outbox(channel, &payload) {
+        Ok(_) => true,
+        Err(e) => {
+            println!("[DB] Error queueing notification for '{}': {}", channel, e);
+            false
+        }
+    }
+}
+
+// La notificación del sistema también respeta el horario de no molestar
+fn show_os_notification(db: &Database, app_handle: Option<&AppHandle>, alert: &Alert) {
+    let Some(app_handle) = app_handle else { return };
+    if NotificationSettings::load(db).do_not_disturb.is_active_at(Local::now().naive_local()) {
+        return;
+    }
+    if let Err(e) = app_handle.notification().builder().title(NOTIFICATION_TITLE).body(&alert.message).show() {
+        println!("[Routing] Failed to show system notification: {}", e);
+    }
+}
+
+/// Avisar de una conexión, si `connect` o `untrusted-connect` tienen ruta. No crea ninguna alerta
+/// (la conexión ya queda en el historial); los dispositivos bloqueados se avisan con su propia alerta.
+pub fn announce_connection(db: &Database, app_handle: Option<&AppHandle>, device_id: &str, activity_id: i64, trust: TrustLevel) {
+    let (event, alert_type, severity, key) = match trust {
+        TrustLevel::Trusted => (RoutedEvent::Connect, CONNECTED_ALERT, AlertSeverity::Low, "alert.device_connected"),
+        TrustLevel::Unknown => (
+            RoutedEvent::UntrustedConnect,
+            UNTRUSTED_CONNECTED_ALERT,
+            AlertSeverity::Medium,
+            "alert.untrusted_device_connected",
+        ),
+        TrustLevel::Blocked => return,
+    };
+    let settings = RoutingSettings::load(db);
+    let Some(channels) = settings.routes.get(&event) else { return };
+
+    let message = Message::new(key, serde_json::json!({ "device": device_id }));
+    let alert = Alert {
+        id: 0,
+        device_id: Some(device_id.to_string()),
+        activity_log_id: Some(activity_id),
+        alert_type: alert_type.to_string(),
+        severity,
+        message: message.render(DEFAULT_LANG),
+        message_key: Some(message.key.clone()),
+        message_params: Some(message.params.clone()),
+        created_at: Utc::now(),
+        acknowledged: false,
+    };
+    send(db, app_handle, &alert, &settings, channels);
+}
+
+/// Enviar una alerta al webhook configurado
+pub fn post_webhook(db: &Database, alert: &Alert) -> Result<(), String> {
+    let url = RoutingSettings::load(db).webhook_url.filter(|url| !url.trim().is_empty()).ok_or("No webhook_url configured")?;
+    let body = serde_json::to_vec(alert).map_err(|e| e.to_string())?;
+    ureq::request("POST", &url)
+        .set("Content-Type", "application/json")
+        .send_bytes(&body)
+        .map_err(|e| match e {
+            ureq::Error::Status(code, _) => format!("HTTP {}", code),
+            ureq::Error::Transport(e) => format!("Connection failed: {}", e),
+        })?;
+    Ok(())
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::anomaly::AnomalySettings;
+use crate::db::{AlertSeverity, Database, RuleKind};
+use crate::i18n::Message;
+use crate::kiosk;
+use crate::state::AppState;
+
+pub const RULE_SETTINGS_KEY: &str = "rules";
+pub const WRITE_THRESHOLD_ALERT: &str = "WRITE_THRESHOLD_EXCEEDED";
+const STORAGE_QUOTA_ALERT: &str = "STORAGE_QUOTA_EXCEEDED";
+pub const EXECUTABLE_WRITTEN_ALERT: &str = "EXECUTABLE_WRITTEN";
+// Extensiones que cuentan como ejecutables al escribirse en la unidad
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "dll", "msi", "com", "scr", "bat", "cmd", "ps1", "vbs", "jar", "sh", "apk", "app", "dmg", "pkg", "lnk",
+];
+
+/// Umbrales globales de las reglas que no pertenecen a la detección de anomalías
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RuleSettings {
+    /// Bytes escritos en una sesión a partir de los cuales se alerta (None = sin límite)
+    pub session_write_bytes: Option<i64>,
+    /// Porcentaje ocupado de la unidad a partir del cual se alerta al escanearla (None = sin límite)
+    pub storage_used_percent: Option<f64>,
+}
+
+impl RuleSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(RULE_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+/// Umbral efectivo de una regla para un dispositivo: el propio si tiene uno, si no el global.
+/// `None` significa que la regla no se aplica.
+pub fn threshold(db: &Database, device_id: &str, rule: RuleKind, global: Option<f64>) -> Option<f64> {
+    match db.get_rule_override(device_id, rule) {
+        Ok(Some(rule_override)) => rule_override.threshold,
+        Ok(None) => global,
+        Err(e) => {
+            println!("[Rules] Database error: {}", e);
+            global
+        }
+    }
+}
+
+fn global_threshold(db: &Database, rule: RuleKind) -> Option<f64> {
+    match rule {
+        RuleKind::SessionWriteBytes => RuleSettings::load(db).session_write_bytes.map(|b| b as f64),
+        RuleKind::ScanVolumeZScore => Some(AnomalySettings::load(db).volume_z_score),
+        RuleKind::UnusualHourShare => Some(AnomalySettings::load(db).min_hour_share),
+        RuleKind::StorageUsedPercent => RuleSettings::load(db).storage_used_percent,
+    }
+}
+
+/// Alertar si lo escrito en la sesión supera el umbral del dispositivo.
+/// Devuelve true si se ha alertado, para no repetir la alerta en la misma sesión.
+pub fn check_session_writes(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    device_id: &str,
+    activity_id: i64,
+    bytes_written: i64,
+) -> bool {
+    let global = global_threshold(db, RuleKind::SessionWriteBytes);
+    let limit = match threshold(db, device_id, RuleKind::SessionWriteBytes, global) {
+        Some(limit) => limit,
+        None => return false,
+    };
+    if (bytes_written as f64) <= limit {
+        return false;
+    }
+
+    raise_alert(
+        db,
+        app_handle,
+        Some(device_id),
+        Some(activity_id),
+        WRITE_THRESHOLD_ALERT,
+        AlertSeverity::High,
+        Message::new(
+            "alert.write_threshold",
+            serde_json::json!({ "device": device_id, "bytes": bytes_written, "limit": limit.round() as i64 }),
+        ),
+    );
+    true
+}
+
+/// Alertar si el archivo creado en la unidad es un ejecutable. `file_name` es el nombre tal como se guarda
+/// (anonimizado si el dispositivo lo está). Devuelve true si se ha alertado, para hacerlo una vez por sesión.
+pub fn check_executable_written(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    device_id: &str,
+    activity_id: i64,
+    path: &Path,
+    file_name: &str,
+) -> bool {
+    let executable = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| EXECUTABLE_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+    if !executable {
+        return false;
+    }
+
+    raise_alert(
+        db,
+        app_handle,
+        Some(device_id),
+        Some(activity_id),
+        EXECUTABLE_WRITTEN_ALERT,
+        AlertSeverity::Medium,
+        Message::new("alert.executable_written", serde_json::json!({ "device": device_id, "file": file_name })),
+    );
+    true
+}
+
+/// Alertar si la unidad escaneada está más llena que el porcentaje del dispositivo
+/// (p.ej. discos de copia de seguridad que se van llenando sin que nadie lo note)
+pub fn check_storage_usage(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    device_id: &str,
+    activity_id: i64,
+    used_bytes: Option<i64>,
+    total_capacity: Option<i64>,
+) {
+    let (Some(used), Some(total)) = (used_bytes, total_capacity) else { return };
+    if total <= 0 {
+        return;
+    }
+    let global = global_threshold(db, RuleKind::StorageUsedPercent);
+    let Some(limit) = threshold(db, device_id, RuleKind::StorageUsedPercent, global) else { return };
+    let percent = used as f64 * 100.0 / total as f64;
+    if percent <= limit {
+        return;
+    }
+
+    raise_alert(
+        db,
+        app_handle,
+        Some(device_id),
+        Some(activity_id),
+        STORAGE_QUOTA_ALERT,
+        AlertSeverity::Medium,
+        Message::new(
+            "alert.storage_quota",
+            serde_json::json!({
+                "device": device_id,
+                "percent": percent.round() as i64,
+                "free": total - used,
+                "limit": limit.round() as i64,
+            }),
+        ),
+    );
+}
+
+#[tauri::command]
+pub async fn get_device_rules(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    let overrides = db
+        .get_rule_overrides(Some(&device_id))
+        .map_err(|e| format!("Database error: {}", e))?;
+
+    // Umbral efectivo de cada regla junto a su origen
+    let rules: Vec<serde_json::Value> = [
+        RuleKind::SessionWriteBytes,
+        RuleKind::ScanVolumeZScore,
+        RuleKind::UnusualHourShare,
+        RuleKind::StorageUsedPercent,
+    ]
+    .into_iter()
+        .map(|rule| {
+            let global = global_threshold(&db, rule);
+            let device_override = overrides.iter().find(|o| o.rule == rule);
+            serde_json::json!({
+                "rule": rule,
+                "global": global,
+                "overridden": device_override.is_some(),
+                "threshold": device_override.map_or(global, |o| o.threshold),
+            })
+        })
+        .collect();
+
+    Ok(serde_json::json!({ "success": true, "device_id": device_id, "rules": rules }))
+}
+
+#[tauri::command]
+pub async fn get_rule_overrides(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_rule_overrides(None) {
+        Ok(overrides) => Ok(serde_json::json!({ "success": true, "overrides": overrides })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Sobrescribir el umbral de una regla para un dispositivo; `threshold: null` la desactiva para él
+#[tauri::command]
+pub async fn set_device_rule_override(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+    rule: RuleKind,
+    threshold: Option<f64>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    if threshold.is_some_and(|t| !t.is_finite() || t < 0.0) {
+        return Err("Threshold must be a non-negative number".to_string());
+    }
+    if rule == RuleKind::StorageUsedPercent && threshold.is_some_and(|t| t > 100.0) {
+        return Err("Storage threshold is a percentage (0-100)".to_string());
+    }
+
+    let db = &state.db;
+    match db.set_rule_override(&device_id, rule, threshold) {
+        Ok(_) => Ok(serde_json::json!({ "success": true })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn clear_device_rule_override(state: tauri::State<'_, AppState>, device_id: String, rule: RuleKind) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = &state.db;
+    match db.delete_rule_override(&device_id, rule) {
+        Ok(deleted) => Ok(serde_json::json!({ "success": deleted })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::db::{AlertSeverity, Database};
+use crate::i18n::Message;
+use crate::kiosk;
+use crate::search::FileQuery;
+use crate::state::AppState;
+
+const SAVED_SEARCH_ALERT: &str = "SAVED_SEARCH_MATCH";
+// Rutas de ejemplo incluidas en el mensaje de la alerta
+const MATCH_SAMPLE: i64 = 3;
+
+// Una búsqueda guardada necesita nombre y una consulta válida con al menos un filtro
+fn validate(name: &str, query: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Saved search name cannot be empty".to_string());
+    }
+    if FileQuery::parse(query)?.is_empty() {
+        return Err("Search query cannot be empty".to_string());
+    }
+    Ok(())
+}
+
+/// Evaluar las búsquedas guardadas con alerta contra los archivos de un escaneo recién terminado
+pub fn check_scan(db: &Arc<Database>, app_handle: Option<&AppHandle>, device_id: &str, activity_id: i64) {
+    let searches = match db.get_saved_searches(true) {
+        Ok(searches) => searches,
+        Err(e) => {
+            println!("[DB] Error loading saved searches: {}", e);
+            return;
+        }
+    };
+
+    for search in searches {
+        let query = match FileQuery::parse(&search.query) {
+            Ok(query) => query,
+            Err(e) => {
+                println!("[Search] Skipping saved search '{}': {}", search.name, e);
+                continue;
+            }
+        };
+
+        let matches = match db.search_files(&query, Some(activity_id), MATCH_SAMPLE) {
+            Ok(matches) => matches,
+            Err(e) => {
+                println!("[DB] Error evaluating saved search '{}': {}", search.name, e);
+                continue;
+            }
+        };
+        if matches.is_empty() {
+            continue;
+        }
+
+        let paths: Vec<&str> = matches.iter().map(|m| m.file_path.as_str()).collect();
+        raise_alert(
+            db,
+            app_handle,
+            Some(device_id),
+            Some(activity_id),
+            SAVED_SEARCH_ALERT,
+            search.alert_severity,
+            Message::new(
+                "alert.saved_search",
+                serde_json::json!({ "search": search.name, "device": device_id, "paths": paths.join(", ") }),
+            ),
+        );
+    }
+}
+
+#[tauri::command]
+pub async fn get_saved_searches(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_saved_searches(false) {
+        Ok(searches) => Ok(serde_json::json!({ "success": true, "searches": searches })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn create_saved_search(
+    state: tauri::State<'_, AppState>,
+    name: String,
+    query: String,
+    filters: Option<serde_json::Value>,
+    alert_enabled: Option<bool>,
+    alert_severity: Option<AlertSeverity>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    validate(&name, &query)?;
+
+    let db = &state.db;
+    match db.create_saved_search(
+        name.trim(),
+        &query,
+        filters.as_ref(),
+        alert_enabled.unwrap_or(false),
+        alert_severity.unwrap_or(AlertSeverity::Medium),
+    ) {
+        Ok(id) => Ok(serde_json::json!({ "success": true, "id": id })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn update_saved_search(
+    state: tauri::State<'_, AppState>,
+    id: i64,
+    name: String,
+    query: String,
+    filters: Option<serde_json::Value>,
+    alert_enabled: Option<bool>,
+    alert_severity: Option<AlertSeverity>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    validate(&name, &query)?;
+
+    let db = &state.db;
+    match db.update_saved_search(
+        id,
+        name.trim(),
+        &query,
+        filters.as_ref(),
+        alert_enabled.unwrap_or(false),
+        alert_severity.unwrap_or(AlertSeverity::Medium),
+    ) {
+        Ok(updated) => Ok(serde_json::json!({ "success": updated })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Convertir una búsqueda guardada en regla de alerta ("avisar si aparece un archivo así en cualquier unidad") o desactivarla
+#[tauri::command]
+pub async fn set_saved_search_alert(
+    state: tauri::State<'_, AppState>,
+    id: i64,
+    enabled: bool,
+    severity: Option<AlertSeverity>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = &state.db;
+    match db.set_saved_search_alert(id, enabled, severity) {
+        Ok(updated) => Ok(serde_json::json!({ "success": updated })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn delete_saved_search(state: tauri::State<'_, AppState>, id: i64) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = &state.db;
+    match db.delete_saved_search(id) {
+        Ok(deleted) => Ok(serde_json::json!({ "success": deleted })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use crate::compare::relative_path;
+use crate::db::{Database, FileSnapshot, ScanInfo};
+use crate::state::AppState;
+
+// Archivos leídos de la base de datos por vuelta; el bloqueo se suelta entre páginas
+const PAGE_SIZE: i64 = 2000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedFile<'a> {
+    path: &'a str,
+    relative_path: String,
+    name: &'a str,
+    extension: Option<&'a str>,
+    size: i64,
+    is_folder: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash_mode: Option<&'static str>,
+}
+
+fn exported<'a>(snapshot: &'a FileSnapshot, root: &str, include_hashes: bool) -> ExportedFile<'a> {
+    ExportedFile {
+        path: &snapshot.file_path,
+        relative_path: relative_path(&snapshot.file_path, root),
+        name: &snapshot.file_name,
+        extension: snapshot.file_extension.as_deref(),
+        size: snapshot.file_size,
+        is_folder: snapshot.is_folder,
+        sha256: snapshot.sha256.as_deref().filter(|_| include_hashes),
+        hash_mode: snapshot.hash_mode.filter(|_| include_hashes).map(|m| m.as_str()),
+    }
+}
+
+// Recorrer los archivos del escaneo por páginas
+fn for_each_snapshot(
+    db: &Database,
+    activity_id: i64,
+    mut f: impl FnMut(&FileSnapshot) -> Result<(), String>,
+) -> Result<usize, String> {
+    let mut after_id = 0;
+    let mut count = 0;
+    loop {
+        let page = db
+            .get_file_snapshots_page(activity_id, after_id, PAGE_SIZE)
+            .map_err(|e| format!("Database error: {}", e))?;
+        for snapshot in &page {
+            f(snapshot)?;
+        }
+        count += page.len();
+        match page.last().and_then(|s| s.id) {
+            Some(id) if page.len() as i64 == PAGE_SIZE => after_id = id,
+            _ => return Ok(count),
+        }
+    }
+}
+
+fn write_csv(db: &Database, scan: &ScanInfo, root: &str, include_hashes: bool, out: BufWriter<File>) -> Result<usize, String> {
+    let mut writer = csv::Writer::from_writer(out);
+    let mut header = vec!["path", "relative_path", "name", "extension", "size", "is_folder"];
+    if include_hashes {
+        header.extend(["sha256", "hash_mode"]);
+    }
+    writer.write_record(&header).map_err(|e| e.to_string())?;
+
+    let count = for_each_snapshot(db, scan.activity_log_id, |snapshot| {
+        let file = exported(snapshot, root, include_hashes);
+        let size = file.size.to_string();
+        let mut record = vec![
+            file.path,
+            file.relative_path.as_str(),
+            file.name,
+            file.extension.unwrap_or_default(),
+            size.as_str(),
+            if file.is_folder { "true" } else { "false" },
+        ];
+        if include_hashes {
+            record.push(file.sha256.unwrap_or_default());
+            record.push(file.hash_mode.unwrap_or_default());
+        }
+        writer.write_record(&record).map_err(|e| e.to_string())
+    })?;
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+// {"scan": {...}, "files": [...]}, escribiendo cada archivo según se lee
+fn write_json(db: &Database, scan: &ScanInfo, root: &str, include_hashes: bool, mut out: BufWriter<File>) -> Result<usize, String> {
+    let io_err = |e: std::io::Error| e.to_string();
+    out.write_all(b"{\"scan\":").map_err(io_err)?;
+    serde_json::to_writer(&mut out, &serde_json::json!({
+        "activity_log_id": scan.activity_log_id,
+        "device_id": scan.device_id,
+        "timestamp": scan.timestamp,
+        "mount_point": scan.mount_point,
+    }))
+    .map_err(|e| e.to_string())?;
+    out.write_all(b",\"files\":[").map_err(io_err)?;
+
+    let mut first = true;
+    let count = for_each_snapshot(db, scan.activity_log_id, |snapshot| {
+        if !first {
+            out.write_all(b",").map_err(io_err)?;
+        }
+        first = false;
+        out.write_all(b"\n").map_err(io_err)?;
+        serde_json::to_writer(&mut out, &exported(snapshot, root, include_hashes)).map_err(|e| e.to_string())
+    })?;
+
+    out.write_all(b"\n]}\n").map_err(io_err)?;
+    out.flush().map_err(io_err)?;
+    Ok(count)
+}
+
+/// Exportar el contenido de un solo escaneo (CSV o JSON) para compartir el inventario de una unidad.
+/// Los archivos se leen y escriben por partes, así que sirve también para escaneos muy grandes.
+#[tauri::command]
+pub async fn export_scan(
+    state: tauri::State<'_, AppState>,
+    activity_id: i64,
+    format: ScanExportFormat,
+    path: String,
+    include_hashes: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let scan = db
+        .get_scan_info(activity_id)
+        .map_err(|e| format!("Database error: {}", e))?
+        .ok_or_else(|| format!("Scan not found: {}", activity_id))?;
+    let include_hashes = include_hashes.unwrap_or(false);
+    let root = scan.mount_point.clone().unwrap_or_default();
+
+    let target = path.clone();
+    let count = tauri::async_runtime::spawn_blocking(move || {
+        let file = File::create(&target).map_err(|e| format!("Failed to create {}: {}", target, e))?;
+        let out = BufWriter::new(file);
+        match format {
+            ScanExportFormat::Csv => write_csv(&db, &scan, &root, include_hashes, out),
+            ScanExportFormat::Json => write_json(&db, &scan, &root, include_hashes, out),
+        }
+    })
+    .await
+    .map_err(|e| format!("Export task failed: {}", e))??;
+
+    println!("[Export] Scan {} exported to {} ({} entries)", activity_id, path, count);
+    Ok(serde_json::json!({ "success": true, "path": path, "entries": count, "format": format }))
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+use tokio::sync::oneshot;
+use crate::events;
+use crate::file_scanner::DEFAULT_MAX_CONCURRENT_SCANS;
+use crate::ipc;
+use crate::state::AppState;
+
+/// Evento con la posición en la cola de un escaneo pendiente (0 = empieza ahora)
+pub const QUEUE_EVENT: &str = "usb-scan-queue";
+
+/// Escaneo en curso o en espera
+#[derive(Debug, Serialize, Clone)]
+pub struct ScanSlot {
+    pub device_id: String,
+    pub activity_id: i64,
+}
+
+struct Pending {
+    slot: ScanSlot,
+    start: oneshot::Sender<()>,
+}
+
+struct Scheduler {
+    limit: usize,
+    running: Vec<ScanSlot>,
+    queue: VecDeque<Pending>,
+    app_handle: Option<AppHandle>,
+}
+
+/// Cola FIFO de escaneos: como mucho `limit` a la vez, para que un hub con varias memorias no sature la base de datos
+pub struct ScanQueue {
+    scheduler: Mutex<Scheduler>,
+}
+
+impl Default for ScanQueue {
+    fn default() -> Self {
+        Self {
+            scheduler: Mutex::new(Scheduler {
+                limit: DEFAULT_MAX_CONCURRENT_SCANS,
+                running: Vec::new(),
+                queue: VecDeque::new(),
+                app_handle: None,
+            }),
+        }
+    }
+}
+
+fn notify(app_handle: Option<&AppHandle>, slot: &ScanSlot, position: usize, running: usize) {
+    let payload = serde_json::json!({
+        "device_id": slot.device_id,
+        "activity_id": slot.activity_id,
+        "position": position,
+        "running": running,
+    });
+    ipc::publish(QUEUE_EVENT, &payload);
+    if let Some(app_handle) = app_handle {
+        events::emit(app_handle, QUEUE_EVENT, payload);
+    }
+}
+
+impl Scheduler {
+    // Arrancar los pendientes que quepan y avisar de la nueva posición de los que siguen esperando
+    fn dispatch(&mut self) {
+        while self.running.len() < self.limit {
+            let Some(pending) = self.queue.pop_front() else { break };
+            // Si ya nadie espera (dispositivo retirado), se descarta
+            if pending.start.send(()).is_ok() {
+                notify(self.app_handle.as_ref(), &pending.slot, 0, self.running.len() + 1);
+                self.running.push(pending.slot);
+            }
+        }
+        for (i, pending) in self.queue.iter().enumerate() {
+            notify(self.app_handle.as_ref(), &pending.slot, i + 1, self.running.len());
+        }
+    }
+}
+
+/// Turno de escaneo; al soltarlo entra el siguiente de la cola
+pub struct ScanPermit {
+    slot: ScanSlot,
+    queue: Arc<ScanQueue>,
+}
+
+impl Drop for ScanPermit {
+    fn drop(&mut self) {
+        let mut scheduler = self.queue.scheduler.lock().unwrap();
+        if let Some(i) = scheduler
+            .running
+            .iter()
+            .position(|s| s.activity_id == self.slot.activity_id && s.device_id == self.slot.device_id)
+        {
+            scheduler.running.remove(i);
+        }
+        scheduler.dispatch();
+    }
+}
+
+impl ScanQueue {
+    /// Esperar turno para escanear. `limit` es el máximo de escaneos simultáneos (ajuste `scan.max_concurrent_scans`).
+    /// Devuelve None si el escaneo se ha cancelado mientras esperaba.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        device_id: &str,
+        activity_id: i64,
+        limit: usize,
+        app_handle: Option<AppHandle>,
+    ) -> Option<ScanPermit> {
+        let slot = ScanSlot { device_id: device_id.to_string(), activity_id };
+        let (start, started) = oneshot::channel();
+        {
+            let mut scheduler = self.scheduler.lock().unwrap();
+            scheduler.limit = limit.max(1);
+            if app_handle.is_some() {
+                scheduler.app_handle = app_handle;
+            }
+            scheduler.queue.push_back(Pending { slot: slot.clone(), start });
+            scheduler.dispatch();
+        }
+
+        started.await.ok()?;
+        Some(ScanPermit { slot, queue: self.clone() })
+    }
+
+    /// Quitar de la cola los escaneos pendientes de un dispositivo desconectado
+    pub fn cancel(&self, device_id: &str) {
+        let mut scheduler = self.scheduler.lock().unwrap();
+        let before = scheduler.queue.len();
+        scheduler.queue.retain(|pending| pending.slot.device_id != device_id);
+        if scheduler.queue.len() != before {
+            println!("[Scanner] Queued scan for {} cancelled", device_id);
+            scheduler.dispatch();
+        }
+    }
+
+    /// Si el dispositivo tiene un escaneo en curso o en espera
+    pub fn is_scanning(&self, device_id: &str) -> bool {
+        let scheduler = self.scheduler.lock().unwrap();
+        scheduler.running.iter().any(|s| s.device_id == device_id)
+            || scheduler.queue.iter().any(|p| p.slot.device_id == device_id)
+    }
+}
+
+#[tauri::command]
+pub async fn get_scan_queue(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let scheduler = state.scans.scheduler.lock().unwrap();
+    let pending: Vec<&ScanSlot> = scheduler.queue.iter().map(|p| &p.slot).collect();
+    Ok(serde_json::json!({
+        "success": true,
+        "limit": scheduler.limit,
+        "running": scheduler.running,
+        "pending": pending,
+    }))
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::audit;
+use crate::db::Database;
+use crate::ipc;
+use crate::kiosk;
+use crate::plugins::{run_process, ProcessOutput};
+use crate::service::service_link;
+use crate::state::AppState;
+
+pub const SCRIPT_SETTINGS_KEY: &str = "scripts";
+const SCRIPT_AUDIT_ACTION: &str = "SCRIPT_RUN";
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+// Salida guardada por stream en el registro de auditoría
+const MAX_CAPTURE_BYTES: usize = 4096;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ScriptEvent {
+    #[serde(rename = "on_connect")]
+    Connect,
+    #[serde(rename = "on_disconnect")]
+    Disconnect,
+    #[serde(rename = "on_scan_complete")]
+    ScanComplete,
+    #[serde(rename = "on_alert")]
+    Alert,
+}
+
+impl ScriptEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScriptEvent::Connect => "on_connect",
+            ScriptEvent::Disconnect => "on_disconnect",
+            ScriptEvent::ScanComplete => "on_scan_complete",
+            ScriptEvent::Alert => "on_alert",
+        }
+    }
+}
+
+/// Comando de shell que se ejecuta con cada evento del tipo indicado
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScriptHook {
+    pub name: String,
+    pub event: ScriptEvent,
+    pub command: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT_SECS
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ScriptSettings {
+    pub hooks: Vec<ScriptHook>,
+}
+
+impl ScriptSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(SCRIPT_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, hook) in self.hooks.iter().enumerate() {
+            if hook.name.trim().is_empty() {
+                return Err("Script name cannot be empty".to_string());
+            }
+            if hook.command.trim().is_empty() {
+                return Err(format!("Script '{}' has no command", hook.name));
+            }
+            if self.hooks[..i].iter().any(|h| h.name == hook.name) {
+                return Err(format!("Duplicate script name '{}'", hook.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+// USB_MANAGER_EVENT, USB_MANAGER_PAYLOAD (JSON) y un USB_MANAGER_<CAMPO> por cada campo simple del evento
+fn script_env(event: ScriptEvent, payload: &serde_json::Value) -> Vec<(String, String)> {
+    let mut env = vec![
+        ("USB_MANAGER_EVENT".to_string(), event.as_str().to_string()),
+        ("USB_MANAGER_PAYLOAD".to_string(), payload.to_string()),
+    ];
+
+    if let Some(fields) = payload.as_object() {
+        for (key, value) in fields {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::Bool(b) => b.to_string(),
+                _ => continue,
+            };
+            env.push((format!("USB_MANAGER_{}", key.to_uppercase()), value));
+        }
+    }
+    env
+}
+
+fn truncate(text: &str) -> &str {
+    if text.len() <= MAX_CAPTURE_BYTES {
+        return text;
+    }
+    let mut end = MAX_CAPTURE_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+// Ejecutar un script y dejar constancia de la ejecución y su salida en el registro de auditoría
+fn run_hook(
+    db: &Database,
+    hook: &ScriptHook,
+    device_id: Option<&str>,
+    payload: &serde_json::Value,
+) -> Result<ProcessOutput, String> {
+    let mut command = shell(&hook.command);
+    command.envs(script_env(hook.event, payload));
+    let result = run_process(&mut command, &format!("{}\n", payload), Duration::from_secs(hook.timeout_secs));
+
+    let mut details = serde_json::json!({
+        "script": hook.name,
+        "event": hook.event,
+        "command": hook.command,
+    });
+    match &result {
+        Ok(output) => {
+            println!(
+                "[Scripts] {} ({}) exited with {:?} in {}ms{}",
+                hook.name,
+                hook.event.as_str(),
+                output.exit_code,
+                output.duration_ms,
+                if output.timed_out { " (timed out)" } else { "" }
+            );
+            details["exit_code"] = serde_json::json!(output.exit_code);
+            details["timed_out"] = serde_json::json!(output.timed_out);
+            details["duration_ms"] = serde_json::json!(output.duration_ms);
+            details["stdout"] = serde_json::json!(truncate(&output.stdout));
+            details["stderr"] = serde_json::json!(truncate(&output.stderr));
+        }
+        Err(e) => {
+            println!("[Scripts] {} ({}) failed: {}", hook.name, hook.event.as_str(), e);
+            details["error"] = serde_json::json!(e);
+        }
+    }
+    audit::record(db, SCRIPT_AUDIT_ACTION, device_id, &details);
+
+    result
+}
+
+/// Lanzar en segundo plano los scripts configurados para el evento
+pub fn fire<S: Serialize>(db: &Arc<Database>, event: ScriptEvent, device_id: Option<&str>, payload: &S) {
+    let hooks: Vec<ScriptHook> = ScriptSettings::load(db)
+        .hooks
+        .into_iter()
+        .filter(|hook| hook.enabled && hook.event == event)
+        .collect();
+    if hooks.is_empty() {
+        return;
+    }
+
+    let payload = serde_json::to_value(payload).unwrap_or_default();
+    let device_id = device_id.map(String::from);
+    let db = db.clone();
+    std::thread::spawn(move || {
+        for hook in hooks {
+            let _ = run_hook(&db, &hook, device_id.as_deref(), &payload);
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_script_hooks(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    Ok(serde_json::json!({ "success": true, "hooks": ScriptSettings::load(db).hooks }))
+}
+
+#[tauri::command]
+pub async fn set_script_hooks(state: tauri::State<'_, AppState>, hooks: Vec<ScriptHook>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let settings = ScriptSettings { hooks };
+    settings.validate()?;
+    let value = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+
+    // Con servicio, los scripts se ejecutan en él
+    if let Some(link) = service_link() {
+        ipc::call(&link.endpoint, "set_setting", serde_json::json!({ "key": SCRIPT_SETTINGS_KEY, "value": value, "unlock_token": kiosk::session_token() })).await?;
+        return Ok(serde_json::json!({ "success": true, "hooks": settings.hooks }));
+    }
+
+    let db = &state.db;
+    match db.set_setting(SCRIPT_SETTINGS_KEY, &value) {
+        Ok(_) => Ok(serde_json::json!({ "success": true, "hooks": settings.hooks })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Ejecutar ahora un script con un evento de ejemplo para comprobar que funciona
+#[tauri::command]
+pub async fn test_script_hook(state: tauri::State<'_, AppState>, hook: ScriptHook, payload: Option<serde_json::Value>) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let payload = payload.unwrap_or_else(|| serde_json::json!({ "test": true }));
+
+    let output = tauri::async_runtime::spawn_blocking(move || run_hook(&db, &hook, None, &payload))
+        .await
+        .map_err(|e| format!("Script failed: {}", e))??;
+
+    Ok(serde_json::json!({ "success": output.success, "output": output }))
+}
@@ -0,0 +1,222 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use crate::state::AppState;
+
+const DEFAULT_LOCATE_LIMIT: i64 = 500;
+
+/// Filtros de `search_files` obtenidos de una consulta como
+/// `ext:docx size>10mb device:"Backup stick" after:2024-01-01 informe`
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct FileQuery {
+    /// Palabras sueltas: deben aparecer en el nombre del archivo (`*` como comodín)
+    pub terms: Vec<String>,
+    pub extensions: Vec<String>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    /// Número de serie, nombre o apodo del dispositivo
+    pub device: Option<String>,
+    pub path: Option<String>,
+    pub sha256: Option<String>,
+    /// Solo archivos cuyo hash está (true) o no está (false) en las listas de conocidos
+    pub known: Option<bool>,
+    /// Escaneos desde este instante (incluido)
+    pub after: Option<DateTime<Utc>>,
+    /// Escaneos anteriores a este instante
+    pub before: Option<DateTime<Utc>>,
+}
+
+impl FileQuery {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let mut parsed = FileQuery::default();
+
+        for token in tokenize(query)? {
+            if let Some(rest) = token.strip_prefix("size").filter(|r| r.starts_with(['<', '>', '=', ':'])) {
+                parse_size_filter(rest, &mut parsed)?;
+                continue;
+            }
+
+            let (key, value) = match token.split_once(':') {
+                Some((key, value)) if !key.is_empty() && !key.contains('*') => (key.to_lowercase(), value),
+                _ => {
+                    parsed.terms.push(token);
+                    continue;
+                }
+            };
+            if value.is_empty() {
+                return Err(format!("Missing value for '{}:'", key));
+            }
+
+            match key.as_str() {
+                "ext" => parsed.extensions.extend(
+                    value
+                        .split(',')
+                        .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                        .filter(|e| !e.is_empty()),
+                ),
+                "device" => parsed.device = Some(value.to_string()),
+                "path" => parsed.path = Some(value.to_string()),
+                "name" => parsed.terms.push(value.to_string()),
+                "hash" | "sha256" => parsed.sha256 = Some(value.to_lowercase()),
+                "known" => parsed.known = Some(parse_bool(value)?),
+                "after" => parsed.after = Some(parse_date(value)?),
+                "before" => parsed.before = Some(parse_date(value)?),
+                _ => return Err(format!("Unknown search filter '{}:'", key)),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+            && self.extensions.is_empty()
+            && self.min_size.is_none()
+            && self.max_size.is_none()
+            && self.device.is_none()
+            && self.path.is_none()
+            && self.sha256.is_none()
+            && self.known.is_none()
+            && self.after.is_none()
+            && self.before.is_none()
+    }
+}
+
+// Separar por espacios respetando las comillas: device:"Backup stick" es un solo token
+fn tokenize(query: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quote in search query".to_string());
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+// ">10mb", ">=1gb", "<500kb", ":2mb" o "=2mb"
+fn parse_size_filter(rest: &str, query: &mut FileQuery) -> Result<(), String> {
+    let (op, value) = ["<=", ">=", "<", ">", "=", ":"]
+        .iter()
+        .find_map(|op| rest.strip_prefix(op).map(|value| (*op, value)))
+        .ok_or_else(|| format!("Invalid size filter 'size{}'", rest))?;
+    let bytes = parse_size(value)?;
+
+    match op {
+        ">" => query.min_size = Some(bytes + 1),
+        ">=" => query.min_size = Some(bytes),
+        "<" => query.max_size = Some(bytes - 1),
+        "<=" => query.max_size = Some(bytes),
+        _ => {
+            query.min_size = Some(bytes);
+            query.max_size = Some(bytes);
+        }
+    }
+    Ok(())
+}
+
+// "10mb", "1.5gb", "512" (bytes); unidades en base 1024
+fn parse_size(value: &str) -> Result<i64, String> {
+    let value = value.to_lowercase();
+    let split = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier: i64 = match unit {
+        "" | "b" => 1,
+        "k" | "kb" => 1024,
+        "m" | "mb" => 1024 * 1024,
+        "g" | "gb" => 1024 * 1024 * 1024,
+        "t" | "tb" => 1024 * 1024 * 1024 * 1024,
+        _ => return Err(format!("Unknown size unit '{}'", unit)),
+    };
+    let number: f64 = number.parse().map_err(|_| format!("Invalid size '{}'", value))?;
+
+    Ok((number * multiplier as f64) as i64)
+}
+
+// "yes"/"no" (también true/false, 1/0)
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "yes" | "true" | "1" => Ok(true),
+        "no" | "false" | "0" => Ok(false),
+        _ => Err(format!("Invalid value '{}', expected yes or no", value)),
+    }
+}
+
+// Fecha (AAAA-MM-DD, medianoche UTC) o fecha y hora RFC 3339
+fn parse_date(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD", value))
+}
+
+/// Un SHA-256 en hexadecimal tiene 64 caracteres
+pub fn looks_like_sha256(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// "¿Qué dispositivo tiene este archivo?": busca por nombre o SHA-256 en todos los escaneos
+#[tauri::command]
+pub async fn locate_file(state: tauri::State<'_, AppState>, name_or_hash: String, limit: Option<i64>) -> Result<serde_json::Value, String> {
+    let query = name_or_hash.trim();
+    if query.is_empty() {
+        return Err("Search query cannot be empty".to_string());
+    }
+
+    let db = &state.db;
+    let by_hash = looks_like_sha256(query);
+    match db.locate_file(query, by_hash, limit.unwrap_or(DEFAULT_LOCATE_LIMIT)) {
+        Ok(locations) => {
+            let mut devices: Vec<&str> = locations.iter().map(|l| l.device_id.as_str()).collect();
+            devices.sort();
+            devices.dedup();
+
+            Ok(serde_json::json!({
+                "success": true,
+                "query": query,
+                "matched_by": if by_hash { "sha256" } else { "name" },
+                "device_count": devices.len(),
+                "locations": locations,
+            }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Búsqueda en todos los escaneos con el lenguaje de consulta de `FileQuery`
+#[tauri::command]
+pub async fn search_files(state: tauri::State<'_, AppState>, query: String, limit: Option<i64>) -> Result<serde_json::Value, String> {
+    let filters = FileQuery::parse(&query)?;
+    if filters.is_empty() {
+        return Err("Search query cannot be empty".to_string());
+    }
+
+    let db = &state.db;
+    match db.search_files(&filters, None, limit.unwrap_or(DEFAULT_LOCATE_LIMIT)) {
+        Ok(results) => Ok(serde_json::json!({
+            "success": true,
+            "query": query,
+            "filters": filters,
+            "count": results.len(),
+            "results": results,
+        })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
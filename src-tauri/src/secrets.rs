@@ -0,0 +1,133 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::approval::{ApprovalSettings, APPROVAL_SETTINGS_KEY};
+use crate::cloud_backup::{CloudBackupSettings, CLOUD_BACKUP_SETTINGS_KEY};
+use crate::db::Database;
+use crate::privacy::{PathKey, PATH_KEY_SETTINGS_KEY};
+use crate::routing::{RoutingSettings, ROUTING_SETTINGS_KEY};
+use crate::trust_list::{TrustListSettings, TRUST_LIST_SETTINGS_KEY};
+
+// Servicio con el que se guardan las entradas en el llavero del sistema
+const KEYCHAIN_SERVICE: &str = "usb-manager";
+// Prefijo del valor que sustituye al secreto en los ajustes: "keychain:<ámbito>/<nombre>"
+const KEYCHAIN_REF_PREFIX: &str = "keychain:";
+// Cada base de datos (perfil) guarda sus secretos con su propio ámbito
+const SCOPE_SETTINGS_KEY: &str = "secrets_scope";
+
+/// Ajustes con campos secretos (contraseñas, tokens, claves de cifrado). Al guardarlos, cada secreto va al
+/// llavero del sistema (Administrador de credenciales en Windows, Keychain en macOS, Secret Service en Linux)
+/// y en los ajustes solo queda una referencia a su entrada.
+pub trait SecretFields {
+    /// Campos secretos con el nombre de su entrada en el llavero
+    fn secret_fields(&mut self) -> Vec<(&'static str, &mut String)>;
+}
+
+fn keychain_error(e: keyring::Error) -> String {
+    format!("Keychain error: {}", e)
+}
+
+fn entry(name: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, name).map_err(keychain_error)
+}
+
+fn scope(db: &Database) -> String {
+    if let Some(scope) = db.get_setting::<String>(SCOPE_SETTINGS_KEY).ok().flatten() {
+        return scope;
+    }
+    let scope = uuid::Uuid::new_v4().to_string();
+    let _ = db.set_setting(SCOPE_SETTINGS_KEY, &scope);
+    scope
+}
+
+fn is_reference(value: &str) -> bool {
+    value.starts_with(KEYCHAIN_REF_PREFIX)
+}
+
+/// Guardar un secreto en el llavero y dejar en su lugar la referencia. Sin llavero disponible
+/// (p. ej. el servicio de Linux, que no tiene sesión gráfica) el valor se queda como estaba y devuelve false.
+pub fn seal(db: &Database, name: &str, value: &mut String) -> bool {
+    if value.is_empty() || is_reference(value) {
+        return false;
+    }
+    // La base de datos es del servicio, que no puede leer el llavero de este usuario: el servicio
+    // pasará el secreto a su propio llavero al arrancar
+    if crate::service::service_link().is_some() {
+        return false;
+    }
+
+    let entry_name = format!("{}/{}", scope(db), name);
+    match entry(&entry_name).and_then(|e| e.set_password(value).map_err(keychain_error)) {
+        Ok(_) => {
+            *value = format!("{}{}", KEYCHAIN_REF_PREFIX, entry_name);
+            true
+        }
+        Err(e) => {
+            println!("[Secrets] {}; '{}' is kept in the settings", e, name);
+            false
+        }
+    }
+}
+
+/// Cambiar una referencia por el secreto guardado en el llavero. Si no se puede leer (otro equipo,
+/// entrada borrada) queda vacío; los valores en claro de versiones anteriores se dejan tal cual.
+pub fn reveal(value: &mut String) {
+    let Some(entry_name) = value.strip_prefix(KEYCHAIN_REF_PREFIX) else { return };
+    match entry(entry_name).and_then(|e| e.get_password().map_err(keychain_error)) {
+        Ok(secret) => *value = secret,
+        Err(e) => {
+            println!("[Secrets] Failed to read '{}': {}", entry_name, e);
+            value.clear();
+        }
+    }
+}
+
+/// Devuelve si se ha pasado algún secreto al llavero
+pub fn seal_all<T: SecretFields>(db: &Database, settings: &mut T) -> bool {
+    let mut sealed = false;
+    for (name, value) in settings.secret_fields() {
+        sealed |= seal(db, name, value);
+    }
+    sealed
+}
+
+pub fn reveal_all<T: SecretFields>(settings: &mut T) {
+    for (_, value) in settings.secret_fields() {
+        reveal(value);
+    }
+}
+
+// Pasar al llavero los secretos de un ajuste que aún los tenga en claro
+fn migrate_setting<T: SecretFields + Serialize + DeserializeOwned>(db: &Database, key: &str) {
+    let Some(mut settings) = db.get_setting::<T>(key).ok().flatten() else { return };
+    if !seal_all(db, &mut settings) {
+        return;
+    }
+
+    match db.set_setting(key, &settings) {
+        Ok(_) => println!("[Secrets] Moved '{}' secrets to the keychain", key),
+        Err(e) => println!("[DB] Error saving {}: {}", key, e),
+    }
+}
+
+/// Pasar al llavero los secretos que versiones anteriores guardaban en claro en los ajustes
+pub fn migrate(db: &Database) {
+    migrate_setting::<ApprovalSettings>(db, APPROVAL_SETTINGS_KEY);
+    migrate_setting::<CloudBackupSettings>(db, CLOUD_BACKUP_SETTINGS_KEY);
+    migrate_setting::<PathKey>(db, PATH_KEY_SETTINGS_KEY);
+    migrate_setting::<RoutingSettings>(db, ROUTING_SETTINGS_KEY);
+    migrate_setting::<TrustListSettings>(db, TRUST_LIST_SETTINGS_KEY);
+}
+
+#[tauri::command]
+pub async fn get_secrets_status() -> Result<serde_json::Value, String> {
+    // Leer una entrada que no existe dice si hay llavero sin tocar ningún secreto real
+    let available = entry("__probe").and_then(|e| match e.get_password() {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(keychain_error(e)),
+    });
+    Ok(serde_json::json!({
+        "success": true,
+        "keychain": available.is_ok(),
+        "error": available.err(),
+    }))
+}
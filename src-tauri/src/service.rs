@@ -0,0 +1,343 @@
+use serde::Serialize;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use crate::db::Database;
+use crate::usb_monitor::UsbMonitor;
+use crate::{cloud_backup, grants, ipc, kiosk, outbox, plugins, profiles, reports, secrets};
+
+pub const SERVICE_NAME: &str = "USBManager";
+const DETECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Carpeta de datos del servicio, común a todos los usuarios del equipo
+pub fn service_data_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("USB_MANAGER_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    if cfg!(windows) {
+        std::env::var("ProgramData")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(r"C:\ProgramData"))
+            .join(SERVICE_NAME)
+    } else {
+        PathBuf::from("/var/lib/usb-manager")
+    }
+}
+
+/// Núcleo de monitorización sin interfaz: base de datos, monitor USB, informes programados
+/// y el canal IPC por el que se conecta la interfaz. Termina cuando `shutdown` se completa.
+pub async fn run_core(data_dir: PathBuf, shutdown: impl Future<Output = ()>) -> Result<(), String> {
+    std::fs::create_dir_all(&data_dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    let db_path = profiles::active_db_path(&data_dir);
+    if let Some(dir) = db_path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+
+    let db = Database::open(db_path).map(Arc::new).map_err(|e| format!("Failed to initialize database: {}", e))?;
+    println!("[Service] Database initialized at {:?}", db.path());
+    secrets::migrate(&db);
+    plugins::reload(&db);
+    kiosk::enabled();
+
+    let scheduler = tokio::spawn(reports::run_report_scheduler(db.clone()));
+    let outbox_worker = tokio::spawn(outbox::run_outbox_worker(db.clone()));
+    let grant_scheduler = tokio::spawn(grants::run_grant_scheduler(db.clone(), None));
+    let cloud_backup_scheduler = tokio::spawn(cloud_backup::run_cloud_backup_scheduler(db.clone()));
+
+    let monitor = Arc::new(UsbMonitor::new(db));
+    // Igual que la interfaz: lo ya conectado al arrancar no cuenta como conexión nueva
+    *monitor.devices.lock().unwrap() = monitor.scan_devices();
+    monitor.resume_interrupted_scans();
+    let monitoring = tokio::spawn(monitor.clone().start_monitoring_shared());
+
+    let endpoint = ipc::service_endpoint();
+    let ipc_monitor = monitor.clone();
+    let server = tokio::spawn(async move {
+        if let Err(e) = ipc::serve(endpoint, ipc_monitor).await {
+            println!("[IPC] Server stopped: {}", e);
+        }
+    });
+
+    shutdown.await;
+    println!("[Service] Shutting down");
+
+    server.abort();
+    monitoring.abort();
+    scheduler.abort();
+    outbox_worker.abort();
+    grant_scheduler.abort();
+    cloud_backup_scheduler.abort();
+    // Cerrar las sesiones abiertas para que la próxima conexión se registre como nueva
+    monitor.reset_session_state();
+
+    Ok(())
+}
+
+// Ctrl+C o SIGTERM (systemd)
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+/// Ejecutar el núcleo en primer plano (`--daemon`, p.ej. bajo systemd)
+pub fn run_daemon(data_dir: PathBuf) -> i32 {
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("[Service] Failed to start runtime: {}", e);
+            return 1;
+        }
+    };
+
+    match runtime.block_on(run_core(data_dir, shutdown_signal())) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("[Service] {}", e);
+            1
+        }
+    }
+}
+
+/// Argumentos de línea de comandos del modo servicio. Devuelve el código de salida
+/// si se ha ejecutado alguno, o None para arrancar la interfaz normalmente.
+///
+///   --daemon [--data-dir DIR]   núcleo en primer plano (systemd)
+///   --service                   arranque desde el gestor de servicios de Windows
+///   --install-service / --uninstall-service (Windows)
+///   --kiosk-token TOKEN         bloquear la configuración con un token de desbloqueo ("" para quitarlo)
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    let data_dir = args
+        .iter()
+        .position(|a| a == "--data-dir")
+        .and_then(|i| args.get(i + 1))
+        .map(PathBuf::from)
+        .unwrap_or_else(service_data_dir);
+
+    let command = args.iter().skip(1).find(|a| a.starts_with("--") && a.as_str() != "--data-dir")?;
+    match command.as_str() {
+        "--daemon" => Some(run_daemon(data_dir)),
+        "--kiosk-token" => {
+            let token = args.iter().position(|a| a == "--kiosk-token").and_then(|i| args.get(i + 1));
+            match kiosk::write_token(token.map(String::as_str)) {
+                Ok(path) => {
+                    println!("[Kiosk] Unlock token {} ({:?})", if token.is_some_and(|t| !t.is_empty()) { "set" } else { "removed" }, path);
+                    Some(0)
+                }
+                Err(e) => {
+                    eprintln!("[Kiosk] {}", e);
+                    Some(1)
+                }
+            }
+        }
+        #[cfg(windows)]
+        "--service" => Some(windows::run_service()),
+        #[cfg(windows)]
+        "--install-service" => Some(windows::install()),
+        #[cfg(windows)]
+        "--uninstall-service" => Some(windows::uninstall()),
+        _ => None,
+    }
+}
+
+/// Servicio detectado al arrancar la interfaz
+#[derive(Debug, Serialize, Clone)]
+pub struct ServiceLink {
+    pub endpoint: String,
+    pub version: Option<String>,
+    pub database: Option<PathBuf>,
+}
+
+static SERVICE_LINK: OnceLock<Option<ServiceLink>> = OnceLock::new();
+
+/// Comprobar (una sola vez) si hay un servicio escuchando en el canal IPC
+pub fn detect() -> Option<&'static ServiceLink> {
+    SERVICE_LINK
+        .get_or_init(|| {
+            let endpoint = ipc::service_endpoint();
+            let pong = tauri::async_runtime::block_on(async {
+                tokio::time::timeout(DETECT_TIMEOUT, ipc::call(&endpoint, "ping", serde_json::json!({}))).await
+            });
+
+            match pong {
+                Ok(Ok(info)) => {
+                    println!("[Service] Connected to service at {}", endpoint);
+                    Some(ServiceLink {
+                        endpoint,
+                        version: info.get("version").and_then(|v| v.as_str()).map(String::from),
+                        database: info.get("database").and_then(|v| v.as_str()).map(PathBuf::from),
+                    })
+                }
+                _ => None,
+            }
+        })
+        .as_ref()
+}
+
+/// Servicio al que está conectada la interfaz, si lo hay
+pub fn service_link() -> Option<&'static ServiceLink> {
+    SERVICE_LINK.get().and_then(|link| link.as_ref())
+}
+
+#[tauri::command]
+pub async fn get_service_status() -> Result<serde_json::Value, String> {
+    match service_link() {
+        Some(link) => {
+            // Comprobar que sigue vivo
+            let alive = ipc::call(&link.endpoint, "ping", serde_json::json!({})).await.is_ok();
+            Ok(serde_json::json!({ "success": true, "connected": true, "alive": alive, "service": link }))
+        }
+        None => Ok(serde_json::json!({ "success": true, "connected": false, "endpoint": ipc::service_endpoint() })),
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::OsString;
+    use std::time::Duration;
+    use windows_service::service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode, ServiceInfo,
+        ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    };
+    use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+    use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+    use windows_service::{define_windows_service, service_dispatcher};
+    use super::{run_core, service_data_dir, SERVICE_NAME};
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    pub fn run_service() -> i32 {
+        match service_dispatcher::start(SERVICE_NAME, ffi_service_main) {
+            Ok(_) => 0,
+            Err(e) => {
+                eprintln!("[Service] Failed to start service dispatcher: {}", e);
+                1
+            }
+        }
+    }
+
+    fn status(state: ServiceState, accept: ServiceControlAccept, exit_code: u32) -> ServiceStatus {
+        ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: accept,
+            exit_code: ServiceExitCode::Win32(exit_code),
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        }
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let mut stop_tx = Some(stop_tx);
+
+        let handler = move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                if let Some(tx) = stop_tx.take() {
+                    let _ = tx.send(());
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        };
+
+        let status_handle = match service_control_handler::register(SERVICE_NAME, handler) {
+            Ok(handle) => handle,
+            Err(e) => {
+                eprintln!("[Service] Failed to register control handler: {}", e);
+                return;
+            }
+        };
+
+        let _ = status_handle.set_service_status(status(
+            ServiceState::Running,
+            ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            0,
+        ));
+
+        let exit_code = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => match runtime.block_on(run_core(service_data_dir(), async {
+                let _ = stop_rx.await;
+            })) {
+                Ok(_) => 0,
+                Err(e) => {
+                    eprintln!("[Service] {}", e);
+                    1
+                }
+            },
+            Err(_) => 1,
+        };
+
+        let _ = status_handle.set_service_status(status(ServiceState::Stopped, ServiceControlAccept::empty(), exit_code));
+    }
+
+    pub fn install() -> i32 {
+        let result = (|| -> windows_service::Result<()> {
+            let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CREATE_SERVICE)?;
+            let info = ServiceInfo {
+                name: OsString::from(SERVICE_NAME),
+                display_name: OsString::from("USB Manager monitoring service"),
+                service_type: ServiceType::OWN_PROCESS,
+                start_type: ServiceStartType::AutoStart,
+                error_control: ServiceErrorControl::Normal,
+                executable_path: std::env::current_exe().map_err(windows_service::Error::Winapi)?,
+                launch_arguments: vec![OsString::from("--service")],
+                dependencies: vec![],
+                account_name: None, // LocalSystem
+                account_password: None,
+            };
+            let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)?;
+            service.set_description("Logs USB storage activity even when no user is logged in")?;
+            Ok(())
+        })();
+
+        match result {
+            Ok(_) => {
+                println!("[Service] Installed {}", SERVICE_NAME);
+                0
+            }
+            Err(e) => {
+                eprintln!("[Service] Failed to install service: {}", e);
+                1
+            }
+        }
+    }
+
+    pub fn uninstall() -> i32 {
+        let result = (|| -> windows_service::Result<()> {
+            let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+            let service = manager.open_service(SERVICE_NAME, ServiceAccess::DELETE)?;
+            service.delete()
+        })();
+
+        match result {
+            Ok(_) => {
+                println!("[Service] Uninstalled {}", SERVICE_NAME);
+                0
+            }
+            Err(e) => {
+                eprintln!("[Service] Failed to uninstall service: {}", e);
+                1
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::AppHandle;
+use crate::db::{Database, FileEvent, FileEventKind, SessionSummary};
+use crate::events;
+use crate::i18n::Message;
+use crate::ipc;
+use crate::state::AppState;
+use crate::transfers;
+
+// Estado final de una ruta tras todos sus eventos en la sesión
+#[derive(Default)]
+struct PathState {
+    created: bool,
+    removed: bool,
+    last_size: Option<i64>,
+}
+
+/// Contar archivos añadidos, eliminados y modificados y los bytes escritos a partir de los eventos del watcher.
+/// Un archivo creado y borrado en la misma sesión no cuenta en ninguno de los grupos.
+fn summarize_file_events(events: &[FileEvent], summary: &mut SessionSummary) {
+    let mut paths: HashMap<&str, PathState> = HashMap::new();
+    for event in events {
+        let state = paths.entry(event.file_path.as_str()).or_default();
+        match event.kind {
+            FileEventKind::Created => {
+                state.created = true;
+                state.removed = false;
+                state.last_size = event.file_size;
+            }
+            FileEventKind::Modified => {
+                state.removed = false;
+                state.last_size = event.file_size.or(state.last_size);
+            }
+            FileEventKind::Removed => state.removed = true,
+        }
+    }
+
+    for state in paths.values() {
+        match (state.created, state.removed) {
+            (true, false) => summary.files_added += 1,
+            (false, true) => summary.files_removed += 1,
+            (false, false) => summary.files_modified += 1,
+            (true, true) => {}
+        }
+        if !state.removed {
+            summary.bytes_written += state.last_size.unwrap_or(0);
+        }
+    }
+}
+
+pub fn summarize_session(
+    db: &Database,
+    device_id: &str,
+    connect_activity_id: i64,
+    disconnect_activity_id: Option<i64>,
+) -> Result<SessionSummary, rusqlite::Error> {
+    let connected_at = db.get_activity_timestamp(connect_activity_id)?;
+    let disconnected_at = match disconnect_activity_id {
+        Some(id) => db.get_activity_timestamp(id)?,
+        None => chrono::Utc::now(),
+    };
+
+    let mut summary = SessionSummary {
+        connect_activity_id,
+        disconnect_activity_id,
+        device_id: device_id.to_string(),
+        connected_at,
+        disconnected_at,
+        duration_secs: (disconnected_at - connected_at).num_seconds().max(0),
+        ..Default::default()
+    };
+
+    summarize_file_events(&db.get_file_events(connect_activity_id)?, &mut summary);
+    summary.alerts_raised = db.count_session_alerts(device_id, connect_activity_id, connected_at, disconnected_at)?;
+
+    Ok(summary)
+}
+
+/// Calcular y guardar el resumen de la sesión que termina, y notificarlo con `usb-session-summary`
+pub fn record_session_summary(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    device_id: &str,
+    connect_activity_id: i64,
+    disconnect_activity_id: Option<i64>,
+) {
+    let summary = match summarize_session(db, device_id, connect_activity_id, disconnect_activity_id)
+        .and_then(|summary| db.save_session_summary(&summary).map(|_| summary))
+    {
+        Ok(summary) => summary,
+        Err(e) => {
+            println!("[DB] Error recording session summary: {}", e);
+            return;
+        }
+    };
+    transfers::record_session(db, &summary);
+
+    println!(
+        "[Session] {} ended after {}s: +{} -{} ~{} files, {} bytes written, {} alerts",
+        device_id,
+        summary.duration_secs,
+        summary.files_added,
+        summary.files_removed,
+        summary.files_modified,
+        summary.bytes_written,
+        summary.alerts_raised
+    );
+
+    // El resumen va con su mensaje localizable en el mismo objeto
+    let mut payload = serde_json::to_value(&summary).unwrap_or_default();
+    payload["message"] = serde_json::json!(Message::new(
+        "session.ended",
+        serde_json::json!({
+            "device": device_id,
+            "duration": summary.duration_secs,
+            "added": summary.files_added,
+            "removed": summary.files_removed,
+            "modified": summary.files_modified,
+            "bytes": summary.bytes_written,
+        }),
+    ));
+    ipc::publish("usb-session-summary", &payload);
+    if let Some(app_handle) = app_handle {
+        events::emit(app_handle, "usb-session-summary", payload);
+    }
+}
+
+#[tauri::command]
+pub async fn get_session_summaries(state: tauri::State<'_, AppState>, device_id: Option<String>, limit: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_session_summaries(device_id.as_deref(), limit) {
+        Ok(sessions) => Ok(serde_json::json!({ "success": true, "sessions": sessions })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,53 @@
+use crate::approval;
+use crate::cloud_backup;
+use crate::ipc;
+use crate::kiosk;
+use crate::plugins::{self, PLUGIN_SETTINGS_KEY};
+use crate::privacy;
+use crate::routing::{self, ROUTING_SETTINGS_KEY};
+use crate::service::service_link;
+use crate::state::AppState;
+use crate::trust_list;
+
+#[tauri::command]
+pub async fn get_settings(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_all_settings() {
+        Ok(mut settings) => {
+            approval::redact(&mut settings);
+            cloud_backup::redact(&mut settings);
+            privacy::redact(&mut settings);
+            routing::redact(&mut settings);
+            trust_list::redact(&mut settings);
+            Ok(serde_json::json!({ "success": true, "settings": settings }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn set_setting(state: tauri::State<'_, AppState>, key: String, value: serde_json::Value) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    approval::check_writable(&key)?;
+    cloud_backup::check_writable(&key)?;
+    privacy::check_writable(&key)?;
+    trust_list::check_writable(&key)?;
+
+    // La base de datos del servicio es suya: los cambios se piden por IPC
+    if let Some(link) = service_link() {
+        ipc::call(&link.endpoint, "set_setting", serde_json::json!({ "key": key, "value": value, "unlock_token": kiosk::session_token() })).await?;
+        return Ok(serde_json::json!({ "success": true, "key": key, "value": value }));
+    }
+
+    let db = &state.db;
+    let stored = if key == ROUTING_SETTINGS_KEY { routing::seal_setting(db, &value)? } else { value.clone() };
+    match db.set_setting(&key, &stored) {
+        Ok(_) => {
+            if key == PLUGIN_SETTINGS_KEY {
+                plugins::reload(db);
+            }
+            Ok(serde_json::json!({ "success": true, "key": key, "value": value }))
+        }
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,193 @@
+use notify::{Event, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::AppHandle;
+use crate::db::{Database, EvidenceRecord, HashMode};
+use crate::events;
+use crate::hashing::sha256_file;
+use crate::ipc;
+use crate::state::AppState;
+
+pub const SHADOW_COPY_SETTINGS_KEY: &str = "shadow_copy";
+const EVIDENCE_DIR: &str = "evidence";
+const DEFAULT_MAX_COPY_BYTES: i64 = 100 * 1024 * 1024;
+
+/// Registro de archivos llevados de un dispositivo al equipo mientras está conectado
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShadowCopySettings {
+    pub enabled: bool,
+    /// Carpetas del equipo donde se vigila la llegada de archivos (vacío = Descargas y Escritorio del usuario)
+    pub host_folders: Vec<String>,
+    /// Guardar una copia del archivo además del hash y los metadatos
+    pub copy_files: bool,
+    /// Tamaño máximo de las copias; los archivos mayores solo se registran
+    pub max_copy_bytes: i64,
+}
+
+impl Default for ShadowCopySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host_folders: Vec::new(),
+            copy_files: false,
+            max_copy_bytes: DEFAULT_MAX_COPY_BYTES,
+        }
+    }
+}
+
+impl ShadowCopySettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(SHADOW_COPY_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+
+    fn folders(&self) -> Vec<PathBuf> {
+        if !self.host_folders.is_empty() {
+            return self.host_folders.iter().map(PathBuf::from).collect();
+        }
+
+        let home = std::env::var_os("USERPROFILE").or_else(|| std::env::var_os("HOME"));
+        home.map(|home| vec![Path::new(&home).join("Downloads"), Path::new(&home).join("Desktop")])
+            .unwrap_or_default()
+    }
+}
+
+// Copia del archivo en la carpeta de evidencias de la sesión, junto a la base de datos activa
+fn store_copy(db: &Database, path: &Path, activity_id: i64) -> Result<PathBuf, String> {
+    let data_dir = db.path().parent().map(Path::to_path_buf).unwrap_or_default();
+    let dir = data_dir.join(EVIDENCE_DIR).join(activity_id.to_string());
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create evidence folder: {}", e))?;
+
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let target = dir.join(format!("{}_{}", chrono::Utc::now().format("%Y%m%d%H%M%S"), file_name));
+    std::fs::copy(path, &target).map_err(|e| format!("Failed to copy evidence: {}", e))?;
+
+    Ok(target)
+}
+
+// Un archivo nuevo en el equipo viene del dispositivo si su escaneo tiene uno con el mismo nombre y tamaño
+// (y el mismo hash, si el escaneo lo calculó). Devuelve true si se ha registrado.
+fn handle_host_file(
+    path: &Path,
+    device_id: &str,
+    activity_id: i64,
+    settings: &ShadowCopySettings,
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+) -> bool {
+    let file_name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => return false,
+    };
+    let size = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.len() as i64,
+        Err(_) => return false,
+    };
+
+    // Mientras la copia no termina el tamaño no coincide, y se espera al siguiente evento
+    let source = match db.find_snapshot_by_name_size(activity_id, &file_name, size) {
+        Ok(Some(source)) => source,
+        _ => return false,
+    };
+    let sha256 = sha256_file(path).ok();
+    // Un hash por muestra no se puede comparar con el del archivo entero
+    let expected = source.sha256.as_ref().filter(|_| source.hash_mode != Some(HashMode::Sampled));
+    if let (Some(expected), Some(actual)) = (expected, &sha256) {
+        if expected != actual {
+            return false;
+        }
+    }
+
+    let evidence_path = if settings.copy_files && size <= settings.max_copy_bytes {
+        match store_copy(db, path, activity_id) {
+            Ok(target) => Some(target.to_string_lossy().to_string()),
+            Err(e) => {
+                println!("[ShadowCopy] {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let record = EvidenceRecord {
+        id: None,
+        device_id: device_id.to_string(),
+        activity_log_id: activity_id,
+        source_path: source.file_path,
+        host_path: path.to_string_lossy().to_string(),
+        file_name,
+        file_size: size,
+        sha256,
+        evidence_path,
+        created_at: None,
+    };
+    match db.insert_evidence(&record) {
+        Ok(id) => {
+            println!("[ShadowCopy] {} taken from {} to {}", record.source_path, device_id, record.host_path);
+            let payload = serde_json::json!({ "id": id, "evidence": record });
+            ipc::publish("file-taken-detected", &payload);
+            if let Some(app_handle) = app_handle {
+                events::emit(app_handle, "file-taken-detected", payload);
+            }
+            true
+        }
+        Err(e) => {
+            println!("[DB] Error saving evidence: {}", e);
+            false
+        }
+    }
+}
+
+/// Vigilar las carpetas del equipo durante la sesión de un dispositivo. `None` si está desactivado.
+pub fn watch_host(
+    device_id: String,
+    activity_id: i64,
+    db: Arc<Database>,
+    app_handle: Option<AppHandle>,
+) -> Option<notify::RecommendedWatcher> {
+    let settings = ShadowCopySettings::load(&db);
+    if !settings.enabled {
+        return None;
+    }
+    let folders = settings.folders();
+
+    // Cada archivo del equipo se registra una sola vez por sesión
+    let recorded: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+        Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+            for path in event.paths {
+                if !path.is_file() || recorded.lock().unwrap().contains(&path) {
+                    continue;
+                }
+                if handle_host_file(&path, &device_id, activity_id, &settings, &db, app_handle.as_ref()) {
+                    recorded.lock().unwrap().insert(path);
+                }
+            }
+        }
+        Ok(_) => {}
+        Err(e) => println!("[ShadowCopy] Error: {:?}", e),
+    })
+    .map_err(|e| println!("[ShadowCopy] No se pudo iniciar: {}", e))
+    .ok()?;
+
+    for folder in folders.iter().filter(|f| f.is_dir()) {
+        match watcher.watch(folder, RecursiveMode::Recursive) {
+            Ok(_) => println!("[ShadowCopy] Vigilando: {}", folder.display()),
+            Err(e) => println!("[ShadowCopy] No se pudo vigilar {}: {}", folder.display(), e),
+        }
+    }
+
+    Some(watcher)
+}
+
+#[tauri::command]
+pub async fn get_evidence(state: tauri::State<'_, AppState>, device_id: Option<String>, limit: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_evidence(device_id.as_deref(), limit) {
+        Ok(records) => Ok(serde_json::json!({ "success": true, "evidence": records })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,19 @@
+use std::sync::Arc;
+use crate::db::Database;
+use crate::scan_queue::ScanQueue;
+use crate::usb_monitor::UsbMonitor;
+
+/// Estado de la aplicación, gestionado por Tauri e inyectado en los comandos. La base de datos y la cola
+/// de escaneos son las mismas que usa el monitor, así que no hay otra forma de llegar a ellas que pueda
+/// quedarse atrás. Los ajustes se leen de `db` en cada uso (`*Settings::load`).
+pub struct AppState {
+    pub db: Arc<Database>,
+    pub monitor: Arc<UsbMonitor>,
+    pub scans: Arc<ScanQueue>,
+}
+
+impl AppState {
+    pub fn new(monitor: Arc<UsbMonitor>) -> Self {
+        Self { db: monitor.db.clone(), scans: monitor.scans.clone(), monitor }
+    }
+}
@@ -0,0 +1,295 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::db::{AlertSeverity, Database, EventType, FileEventKind};
+use crate::reports::ReportScope;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelineFormat {
+    /// Formato "body" de mactime (The Sleuth Kit)
+    Body,
+    /// JSONL compatible con la importación de Timesketch
+    Jsonl,
+    /// ArcSight Common Event Format, una línea por evento
+    Cef,
+    /// QRadar Log Event Extended Format 1.0, una línea por evento
+    Leef,
+}
+
+const SIEM_VENDOR: &str = "USBManager";
+const SIEM_PRODUCT: &str = "USB Manager";
+
+// Qué marca de tiempo MACB representa una entrada en el formato body
+#[derive(Debug, Clone, Copy)]
+enum TimeRole {
+    All,
+    Modified,
+    Changed,
+    Born,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TimelineEntry {
+    pub datetime: DateTime<Utc>,
+    pub timestamp_desc: String,
+    pub message: String,
+    pub source: &'static str,
+    pub device_id: String,
+    pub activity_log_id: Option<i64>,
+    pub path: Option<String>,
+    pub size: Option<i64>,
+    /// Solo en las alertas
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<AlertSeverity>,
+    // Identificador del tipo de evento en CEF/LEEF ("CONNECT", "FILE_CREATED", el tipo de alerta...)
+    #[serde(skip)]
+    event_id: String,
+    #[serde(skip)]
+    role: TimeRole,
+}
+
+/// Reunir eventos de conexión, escaneos, cambios de archivo y alertas del alcance en una sola línea de tiempo
+pub fn collect_timeline(db: &Database, scope: &ReportScope) -> Result<Vec<TimelineEntry>, String> {
+    let (from, to) = scope.bounds();
+    let device_id = scope.device_id.as_deref();
+    let db_err = |e: rusqlite::Error| format!("Database error: {}", e);
+
+    let mut entries = Vec::new();
+
+    for event in db.get_activity_between(from, to, device_id).map_err(db_err)? {
+        let action = match event.event_type {
+            EventType::Connect => "USB device connected",
+            EventType::Disconnect => "USB device disconnected",
+            EventType::UnsafeRemoval => "USB device removed without ejecting",
+        };
+        let mut message = format!("{}: {}", action, event.device_id);
+        if let Some(ref user) = event.os_user {
+            message.push_str(&format!(" (user {})", user));
+        }
+        if let Some(ref port) = event.port_path {
+            message.push_str(&format!(" on port {}", port));
+        }
+        if let Some(ref serial) = event.volume_serial {
+            message.push_str(&format!(" (volume {})", serial));
+        }
+
+        entries.push(TimelineEntry {
+            datetime: event.timestamp,
+            timestamp_desc: action.to_string(),
+            message,
+            source: "activity",
+            device_id: event.device_id,
+            activity_log_id: Some(event.id),
+            path: None,
+            size: None,
+            severity: None,
+            event_id: event.event_type.as_str().to_string(),
+            role: TimeRole::All,
+        });
+    }
+
+    for scan in db.get_scans_between(from, to, device_id).map_err(db_err)? {
+        entries.push(TimelineEntry {
+            datetime: scan.timestamp,
+            timestamp_desc: "Scan completed".to_string(),
+            message: format!(
+                "Scan of {} completed: {} files, {} bytes",
+                scan.device_name.as_deref().unwrap_or(&scan.device_id),
+                scan.total_files,
+                scan.total_bytes
+            ),
+            source: "scan",
+            device_id: scan.device_id,
+            activity_log_id: Some(scan.activity_log_id),
+            path: None,
+            size: Some(scan.total_bytes),
+            severity: None,
+            event_id: "SCAN_COMPLETE".to_string(),
+            role: TimeRole::All,
+        });
+    }
+
+    for event in db.get_file_events_between(from, to, device_id).map_err(db_err)? {
+        let (desc, event_id, role) = match event.kind {
+            FileEventKind::Created => ("File created", "FILE_CREATED", TimeRole::Born),
+            FileEventKind::Modified => ("File modified", "FILE_MODIFIED", TimeRole::Modified),
+            FileEventKind::Removed => ("File removed", "FILE_REMOVED", TimeRole::Changed),
+        };
+
+        entries.push(TimelineEntry {
+            datetime: event.created_at,
+            timestamp_desc: desc.to_string(),
+            message: format!("{} on {}: {}", desc, event.device_id, event.file_path),
+            source: "file_event",
+            device_id: event.device_id,
+            activity_log_id: Some(event.activity_log_id),
+            path: Some(event.file_path),
+            size: event.file_size,
+            severity: None,
+            event_id: event_id.to_string(),
+            role,
+        });
+    }
+
+    for alert in db.get_alerts_between(from, to, device_id).map_err(db_err)? {
+        entries.push(TimelineEntry {
+            datetime: alert.created_at,
+            timestamp_desc: format!("Alert {}", alert.alert_type),
+            message: alert.message,
+            source: "alert",
+            device_id: alert.device_id.unwrap_or_default(),
+            activity_log_id: alert.activity_log_id,
+            path: None,
+            size: None,
+            severity: Some(alert.severity),
+            event_id: alert.alert_type,
+            role: TimeRole::All,
+        });
+    }
+
+    entries.sort_by_key(|e| e.datetime);
+    Ok(entries)
+}
+
+// MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime
+fn body_line(entry: &TimelineEntry) -> String {
+    let ts = entry.datetime.timestamp();
+    let (atime, mtime, ctime, crtime) = match entry.role {
+        TimeRole::All => (ts, ts, ts, ts),
+        TimeRole::Modified => (0, ts, 0, 0),
+        TimeRole::Changed => (0, 0, ts, 0),
+        TimeRole::Born => (0, 0, 0, ts),
+    };
+    let name = match entry.path {
+        Some(ref path) => format!("{} ({})", path, entry.timestamp_desc),
+        None => format!("[USB] {}", entry.message),
+    };
+
+    format!(
+        "0|{}|0|{}|0|0|{}|{}|{}|{}|{}",
+        name.replace('|', "/"),
+        if entry.path.is_some() { "r/rrw-rw-rw-" } else { "-/----------" },
+        entry.size.unwrap_or(0),
+        atime,
+        mtime,
+        ctime,
+        crtime
+    )
+}
+
+// Gravedad 0-10 de CEF y LEEF: los eventos que no son alertas son informativos
+fn siem_severity(entry: &TimelineEntry) -> u8 {
+    match entry.severity {
+        None => 1,
+        Some(AlertSeverity::Low) => 3,
+        Some(AlertSeverity::Medium) => 5,
+        Some(AlertSeverity::High) => 8,
+        Some(AlertSeverity::Critical) => 10,
+    }
+}
+
+// Cabecera CEF/LEEF: barra invertida y barra vertical escapadas
+fn escape_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+// Valor de extensión CEF: barra invertida, '=' y saltos de línea escapados
+fn escape_cef(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+// CEF:Version|Device Vendor|Device Product|Device Version|Signature ID|Name|Severity|Extension
+fn cef_line(entry: &TimelineEntry) -> String {
+    let mut extension = vec![
+        format!("rt={}", entry.datetime.timestamp_millis()),
+        format!("cat={}", entry.source),
+        format!("msg={}", escape_cef(&entry.message)),
+        format!("cs1Label=usbDevice cs1={}", escape_cef(&entry.device_id)),
+    ];
+    if let Some(activity_id) = entry.activity_log_id {
+        extension.push(format!("cn1Label=activityId cn1={}", activity_id));
+    }
+    if let Some(ref path) = entry.path {
+        extension.push(format!("filePath={}", escape_cef(path)));
+    }
+    if let Some(size) = entry.size {
+        extension.push(format!("fsize={}", size));
+    }
+
+    format!(
+        "CEF:0|{}|{}|{}|{}|{}|{}|{}",
+        SIEM_VENDOR,
+        SIEM_PRODUCT,
+        env!("CARGO_PKG_VERSION"),
+        escape_header(&entry.event_id),
+        escape_header(&entry.timestamp_desc),
+        siem_severity(entry),
+        extension.join(" ")
+    )
+}
+
+// Valor de atributo LEEF: sin tabuladores ni saltos de línea, que separan atributos y eventos
+fn clean_leef(value: &str) -> String {
+    value.replace(['\t', '\r', '\n'], " ")
+}
+
+// LEEF:1.0|Vendor|Product|Version|EventID| seguido de atributos separados por tabuladores
+fn leef_line(entry: &TimelineEntry) -> String {
+    let mut attributes = vec![
+        format!("devTime={}", entry.datetime.format("%b %d %Y %H:%M:%S%.3f UTC")),
+        format!("cat={}", entry.source),
+        format!("sev={}", siem_severity(entry)),
+        format!("msg={}", clean_leef(&entry.message)),
+        format!("usbDevice={}", clean_leef(&entry.device_id)),
+    ];
+    if let Some(activity_id) = entry.activity_log_id {
+        attributes.push(format!("activityId={}", activity_id));
+    }
+    if let Some(ref path) = entry.path {
+        attributes.push(format!("filePath={}", clean_leef(path)));
+    }
+    if let Some(size) = entry.size {
+        attributes.push(format!("fileSize={}", size));
+    }
+
+    format!(
+        "LEEF:1.0|{}|{}|{}|{}|{}",
+        SIEM_VENDOR,
+        SIEM_PRODUCT,
+        env!("CARGO_PKG_VERSION"),
+        escape_header(&entry.event_id),
+        attributes.join("\t")
+    )
+}
+
+pub fn render_timeline(entries: &[TimelineEntry], format: TimelineFormat) -> Result<String, String> {
+    let mut out = String::new();
+    for entry in entries {
+        let line = match format {
+            TimelineFormat::Body => body_line(entry),
+            TimelineFormat::Jsonl => serde_json::to_string(entry).map_err(|e| e.to_string())?,
+            TimelineFormat::Cef => cef_line(entry),
+            TimelineFormat::Leef => leef_line(entry),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub async fn export_timeline(state: tauri::State<'_, AppState>, scope: ReportScope, format: TimelineFormat, path: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+
+    let entries = collect_timeline(&db, &scope)?;
+    let content = render_timeline(&entries, format)?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write timeline: {}", e))?;
+
+    println!("[Timeline] Exported {} entries to {}", entries.len(), path);
+    Ok(serde_json::json!({ "success": true, "path": path, "entries": entries.len() }))
+}
@@ -0,0 +1,81 @@
+use crate::compare::{diff_snapshots, scan_root};
+use crate::db::{Database, SessionSummary};
+use crate::state::AppState;
+
+/// Sumar a los contadores del dispositivo lo que el watcher vio escribir durante la sesión
+pub fn record_session(db: &Database, summary: &SessionSummary) {
+    let files = summary.files_added + summary.files_modified;
+    if files == 0 && summary.bytes_written == 0 {
+        return;
+    }
+    if let Err(e) = db.add_session_transfer(&summary.device_id, files, summary.bytes_written) {
+        println!("[DB] Error updating transfer totals: {}", e);
+    }
+}
+
+// Archivos nuevos o cambiados y sus bytes entre el escaneo anterior y el de `activity_id`.
+// El escaneo anterior incluye lo que el watcher añadió en su sesión, así que eso no se cuenta dos veces.
+fn scan_delta(db: &Database, device_id: &str, activity_id: i64) -> Result<Option<(i64, i64)>, rusqlite::Error> {
+    let scans = db.get_recent_scans(device_id, 2)?;
+    let (latest, previous) = match scans.as_slice() {
+        [latest, previous] if latest.activity_log_id == activity_id => (latest, previous),
+        _ => return Ok(None),
+    };
+
+    let previous_snaps = db.get_file_snapshots(previous.activity_log_id)?;
+    let latest_snaps = db.get_file_snapshots(latest.activity_log_id)?;
+    let diff = diff_snapshots(
+        &previous_snaps,
+        &scan_root(previous, &previous_snaps),
+        &latest_snaps,
+        &scan_root(latest, &latest_snaps),
+    );
+
+    let written: Vec<i64> = diff
+        .only_in_b
+        .iter()
+        .chain(diff.modified.iter())
+        .filter(|entry| !entry.is_folder)
+        .map(|entry| entry.size_b.unwrap_or(0))
+        .collect();
+
+    Ok(Some((written.len() as i64, written.iter().sum())))
+}
+
+/// Sumar a los contadores lo que cambió en la unidad desde el escaneo anterior (escrituras fuera de este equipo)
+pub fn record_scan(db: &Database, device_id: &str, activity_id: i64) {
+    let (files, bytes) = match scan_delta(db, device_id, activity_id) {
+        Ok(Some(delta)) => delta,
+        Ok(None) => return,
+        Err(e) => {
+            println!("[DB] Error comparing scans for transfer totals: {}", e);
+            return;
+        }
+    };
+
+    match db.add_scan_transfer(device_id, activity_id, files, bytes) {
+        Ok(true) => println!("[Transfers] {}: {} files, {} bytes written since previous scan", device_id, files, bytes),
+        Ok(false) => {}
+        Err(e) => println!("[DB] Error updating transfer totals: {}", e),
+    }
+}
+
+/// Estadísticas de un dispositivo: registro, primera/última vez visto, volumen total escrito
+/// y cuántas veces se retiró sin expulsar
+#[tauri::command]
+pub async fn get_device_stats(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    let device = db.get_device(&device_id).map_err(|e| format!("Database error: {}", e))?;
+    let (clean_removals, unsafe_removals) =
+        db.get_removal_counts(&device_id).map_err(|e| format!("Database error: {}", e))?;
+    match db.get_device_transfer_stats(&device_id) {
+        Ok(transfers) => Ok(serde_json::json!({
+            "success": true,
+            "device": device,
+            "transfers": transfers,
+            "clean_removals": clean_removals,
+            "unsafe_removals": unsafe_removals,
+        })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Manager};
+use crate::db::Database;
+use crate::eject;
+use crate::events;
+use crate::i18n::{Message, DEFAULT_LANG};
+use crate::service::service_link;
+use crate::state::AppState;
+use crate::usb_monitor::ConnectionKind;
+
+pub const TRAY_SETTINGS_KEY: &str = "tray";
+const TRAY_ID: &str = "main";
+const RECENT_ALERTS: i64 = 5;
+
+const OPEN_ID: &str = "open";
+const PAUSE_ID: &str = "pause";
+const QUIT_ID: &str = "quit";
+const EJECT_PREFIX: &str = "eject:";
+const ALERT_PREFIX: &str = "alert:";
+
+/// Opciones de la bandeja del sistema guardadas en settings bajo la clave `tray`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraySettings {
+    /// Al cerrar la ventana se oculta en la bandeja y la monitorización sigue (por defecto sí)
+    pub minimize_to_tray: Option<bool>,
+    /// Idioma del menú de la bandeja ("en" si no se indica)
+    pub language: Option<String>,
+}
+
+impl TraySettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(TRAY_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+/// Si al cerrar la ventana hay que ocultarla en vez de salir
+pub fn minimize_to_tray(app: &AppHandle) -> bool {
+    app.try_state::<AppState>()
+        .map(|state| TraySettings::load(&state.db).minimize_to_tray.unwrap_or(true))
+        .unwrap_or(true)
+}
+
+fn language(app: &AppHandle) -> String {
+    app.try_state::<AppState>()
+        .and_then(|state| TraySettings::load(&state.db).language)
+        .unwrap_or_else(|| DEFAULT_LANG.to_string())
+}
+
+fn text(lang: &str, key: &str, params: serde_json::Value) -> String {
+    Message::new(key, params).render(lang)
+}
+
+// Dispositivos conectados con su submenú de acciones, alertas sin revisar y acciones generales
+fn build_menu(app: &AppHandle, lang: &str) -> tauri::Result<Menu<tauri::Wry>> {
+    let state = app.try_state::<AppState>();
+    let monitor = state.as_ref().map(|state| &state.monitor);
+    let devices = monitor.map(|m| m.devices.lock().unwrap().clone()).unwrap_or_default();
+    let paused = monitor.is_some_and(|m| m.is_paused());
+
+    let menu = Menu::new(app)?;
+    let header = text(lang, "tray.connected", serde_json::json!({ "count": devices.len() }));
+    menu.append(&MenuItem::with_id(app, "devices", header, false, None::<&str>)?)?;
+    for device in &devices {
+        let name = device
+            .product_name
+            .clone()
+            .or_else(|| device.volume_label.clone())
+            .unwrap_or_else(|| device.id.clone());
+        let label = match &device.mount_point {
+            Some(mount_point) => format!("{} ({})", name, mount_point),
+            None if device.connection == ConnectionKind::ChargeOnly => {
+                format!("{} ({})", name, text(lang, "tray.charge_only", serde_json::json!({})))
+            }
+            None => name,
+        };
+        let submenu = Submenu::with_id(app, format!("device:{}", device.id), label, true)?;
+        let can_eject = device.serial_number.is_some() && device.mount_point.is_some();
+        let eject_id = format!("{}{}", EJECT_PREFIX, device.serial_number.clone().unwrap_or_default());
+        submenu.append(&MenuItem::with_id(app, eject_id, text(lang, "tray.eject", serde_json::json!({})), can_eject, None::<&str>)?)?;
+        menu.append(&submenu)?;
+    }
+
+    let alerts = state
+        .as_ref()
+        .and_then(|state| state.db.get_alerts(RECENT_ALERTS, false).ok())
+        .unwrap_or_default();
+    let alerts_menu = Submenu::with_id(
+        app,
+        "alerts",
+        text(lang, "tray.recent_alerts", serde_json::json!({ "count": alerts.len() })),
+        !alerts.is_empty(),
+    )?;
+    for alert in &alerts {
+        let message = match &alert.message_key {
+            Some(key) => text(lang, key, alert.message_params.clone().unwrap_or_default()),
+            None => alert.message.clone(),
+        };
+        let label = format!("[{}] {}", alert.severity.as_str(), message.chars().take(80).collect::<String>());
+        alerts_menu.append(&MenuItem::with_id(app, format!("{}{}", ALERT_PREFIX, alert.id), label, true, None::<&str>)?)?;
+    }
+
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+    menu.append(&alerts_menu)?;
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+    menu.append(&CheckMenuItem::with_id(
+        app,
+        PAUSE_ID,
+        text(lang, "tray.pause", serde_json::json!({})),
+        monitor.is_some() && service_link().is_none(),
+        paused,
+        None::<&str>,
+    )?)?;
+    menu.append(&MenuItem::with_id(app, OPEN_ID, text(lang, "tray.open", serde_json::json!({})), true, None::<&str>)?)?;
+    menu.append(&MenuItem::with_id(app, QUIT_ID, text(lang, "tray.quit", serde_json::json!({})), true, None::<&str>)?)?;
+    Ok(menu)
+}
+
+fn tooltip(lang: &str, devices: usize, paused: bool) -> String {
+    if paused {
+        text(lang, "tray.tooltip_paused", serde_json::json!({}))
+    } else {
+        text(lang, "tray.tooltip", serde_json::json!({ "count": devices }))
+    }
+}
+
+/// Mostrar y enfocar la ventana principal (también si estaba oculta en la bandeja)
+pub fn show_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+fn on_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref();
+    if let Some(device_id) = id.strip_prefix(EJECT_PREFIX) {
+        let Some(monitor) = app.try_state::<AppState>().map(|state| state.monitor.clone()) else {
+            return;
+        };
+        let device_id = device_id.to_string();
+        std::thread::spawn(move || {
+            if let Err(e) = eject::eject(&monitor, &device_id) {
+                println!("[Tray] Failed to eject {}: {}", device_id, e);
+            }
+        });
+        return;
+    }
+    if let Some(alert_id) = id.strip_prefix(ALERT_PREFIX) {
+        // La interfaz abre la alerta al recibir el evento
+        show_window(app);
+        if let Ok(alert_id) = alert_id.parse::<i64>() {
+            events::emit(app, "tray-open-alert", serde_json::json!({ "id": alert_id }));
+        }
+        return;
+    }
+
+    match id {
+        OPEN_ID => show_window(app),
+        QUIT_ID => app.exit(0),
+        PAUSE_ID => {
+            let Some(state) = app.try_state::<AppState>() else {
+                return;
+            };
+            let paused = !state.monitor.is_paused();
+            if let Err(e) = state.monitor.set_paused(paused) {
+                println!("[Tray] Cannot change monitoring state: {}", e);
+                // El check ya cambió al pulsar: volver a mostrar el estado real
+                refresh(app);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Crear el icono de la bandeja. Un clic izquierdo muestra la ventana; el menú, con el derecho.
+pub fn init(app: &AppHandle) -> tauri::Result<()> {
+    let lang = language(app);
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(tooltip(&lang, 0, false))
+        .menu(&build_menu(app, &lang)?)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                show_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+    println!("[Tray] Tray icon created");
+    Ok(())
+}
+
+/// Rehacer el menú y el texto de la bandeja tras conectar o desconectar dispositivos, alertas o pausas
+pub fn refresh(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let lang = language(app);
+    match build_menu(app, &lang) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => println!("[Tray] Failed to build menu: {}", e),
+    }
+    if let Some(state) = app.try_state::<AppState>() {
+        let devices = state.monitor.devices.lock().unwrap().len();
+        let _ = tray.set_tooltip(Some(tooltip(&lang, devices, state.monitor.is_paused())));
+    }
+}
@@ -0,0 +1,326 @@
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use crate::approval::{random_bytes, Approval};
+use crate::audit;
+use crate::cloud_backup::hex;
+use crate::db::{Database, DeviceSort, TrustLevel};
+use crate::importer::{self, WhitelistEntry};
+use crate::kiosk;
+use crate::secrets::{self, SecretFields};
+use crate::state::AppState;
+
+pub const TRUST_LIST_SETTINGS_KEY: &str = "trust_list";
+const TRUST_LIST_FORMAT: &str = "usb-manager-trust-list";
+const TRUST_LIST_VERSION: u32 = 1;
+const KEY_GENERATED_AUDIT_ACTION: &str = "TRUST_LIST_KEY_GENERATED";
+const KEYS_CONFIGURED_AUDIT_ACTION: &str = "TRUST_LIST_KEYS_CONFIGURED";
+const IMPORTED_AUDIT_ACTION: &str = "TRUST_LIST_IMPORTED";
+const REJECTED_AUDIT_ACTION: &str = "TRUST_LIST_REJECTED";
+
+/// Clave pública de un administrador cuyas listas firmadas se aceptan
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrustedKey {
+    /// Clave pública Ed25519 en hex
+    pub public_key: String,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Firma y verificación de listas de confianza. Solo se cambia con `generate_trust_list_key` y
+/// `configure_trust_list_keys`: con `set_setting` cualquiera podría añadir su propia clave.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TrustListSettings {
+    /// Clave privada Ed25519 (hex) con la que este equipo firma sus listas; va al llavero del sistema
+    pub signing_key: Option<String>,
+    pub trusted_keys: Vec<TrustedKey>,
+    /// Fecha de la última lista importada de cada clave, para no volver a aceptar una anterior
+    pub last_issued: HashMap<String, DateTime<Utc>>,
+}
+
+impl SecretFields for TrustListSettings {
+    fn secret_fields(&mut self) -> Vec<(&'static str, &mut String)> {
+        self.signing_key.iter_mut().map(|key| ("trust_list.signing_key", key)).collect()
+    }
+}
+
+impl TrustListSettings {
+    pub fn load(db: &Database) -> Self {
+        let mut settings: Self = db.get_setting(TRUST_LIST_SETTINGS_KEY).ok().flatten().unwrap_or_default();
+        secrets::reveal_all(&mut settings);
+        settings.signing_key = settings.signing_key.filter(|k| !k.is_empty());
+        settings
+    }
+
+    fn save(&self, db: &Database) -> rusqlite::Result<()> {
+        let mut sealed = self.clone();
+        secrets::seal_all(db, &mut sealed);
+        db.set_setting(TRUST_LIST_SETTINGS_KEY, &sealed)
+    }
+
+    fn signing_key(&self) -> Option<SigningKey> {
+        self.signing_key.as_deref().and_then(parse_hex).map(|bytes| SigningKey::from_bytes(&bytes))
+    }
+
+    fn public_key(&self) -> Option<String> {
+        self.signing_key().map(|key| hex(key.verifying_key().as_bytes()))
+    }
+}
+
+/// Contenido firmado de una lista: los dispositivos aprobados y quién y cuándo la emitió
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrustList {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub issuer: String,
+    pub issued_at: DateTime<Utc>,
+    pub devices: Vec<WhitelistEntry>,
+}
+
+/// Archivo de una lista firmada. La firma cubre el formato, la versión y `list` tal como se serializa.
+#[derive(Debug, Serialize, Deserialize)]
+struct SignedTrustList {
+    format: String,
+    version: u32,
+    list: TrustList,
+    public_key: String,
+    signature: String,
+}
+
+fn signed_message(list: &TrustList) -> Result<Vec<u8>, String> {
+    let mut message = format!("{}/{}\n", TRUST_LIST_FORMAT, TRUST_LIST_VERSION).into_bytes();
+    message.extend(serde_json::to_vec(list).map_err(|e| e.to_string())?);
+    Ok(message)
+}
+
+fn parse_hex<const N: usize>(text: &str) -> Option<[u8; N]> {
+    let text = text.trim();
+    if text.len() != N * 2 || !text.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn parse_public_key(text: &str) -> Result<VerifyingKey, String> {
+    parse_hex(text)
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| format!("Invalid public key '{}'", text))
+}
+
+/// Firmar `message` con la clave de este equipo. Devuelve la clave pública y la firma en hex.
+pub fn sign(settings: &TrustListSettings, message: &[u8]) -> Option<(String, String)> {
+    let signing_key = settings.signing_key()?;
+    let signature = signing_key.sign(message);
+    Some((hex(signing_key.verifying_key().as_bytes()), hex(&signature.to_bytes())))
+}
+
+/// Comprobar una firma hecha con una de las claves dadas de alta en este equipo. Devuelve la clave normalizada.
+pub fn verify_signature(settings: &TrustListSettings, public_key: &str, signature: &str, message: &[u8]) -> Result<String, String> {
+    let public_key = parse_public_key(public_key)?;
+    let key_hex = hex(public_key.as_bytes());
+    if !settings.trusted_keys.iter().any(|k| k.public_key == key_hex) {
+        return Err("signed with a key that is not trusted on this machine".to_string());
+    }
+
+    let signature = parse_hex::<64>(signature)
+        .map(|bytes| Signature::from_bytes(&bytes))
+        .ok_or("malformed signature")?;
+    public_key
+        .verify_strict(message, &signature)
+        .map_err(|_| "the signature does not match the content".to_string())?;
+    Ok(key_hex)
+}
+
+// Comprobar formato, clave y firma. Devuelve la clave pública normalizada
+fn verify(file: &SignedTrustList, settings: &TrustListSettings) -> Result<String, String> {
+    if file.format != TRUST_LIST_FORMAT {
+        return Err("Invalid trust list: unknown format".to_string());
+    }
+    if file.version > TRUST_LIST_VERSION {
+        return Err(format!("Unsupported trust list version {}", file.version));
+    }
+
+    let key_hex = verify_signature(settings, &file.public_key, &file.signature, &signed_message(&file.list)?)
+        .map_err(|e| format!("Invalid trust list: {}", e))?;
+
+    // Volver a importar una lista antigua devolvería la confianza a dispositivos que ya se quitaron
+    if let Some(last) = settings.last_issued.get(&key_hex) {
+        if file.list.issued_at < *last {
+            return Err(format!("The trust list is older than the last one imported from this key ({})", last.to_rfc3339()));
+        }
+    }
+    Ok(key_hex)
+}
+
+/// `set_setting` no puede cambiar las claves de las listas firmadas
+pub fn check_writable(key: &str) -> Result<(), String> {
+    if key == TRUST_LIST_SETTINGS_KEY {
+        return Err("Trust list settings can only be changed with configure_trust_list_keys".to_string());
+    }
+    Ok(())
+}
+
+/// Ocultar la clave privada al listar los ajustes
+pub fn redact(settings: &mut serde_json::Map<String, serde_json::Value>) {
+    settings.remove(TRUST_LIST_SETTINGS_KEY);
+}
+
+#[tauri::command]
+pub async fn get_trust_list_status(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let settings = TrustListSettings::load(&db);
+    Ok(serde_json::json!({
+        "success": true,
+        "public_key": settings.public_key(),
+        "trusted_keys": settings.trusted_keys,
+        "last_issued": settings.last_issued,
+    }))
+}
+
+/// Crear la clave con la que este equipo firma sus listas. Devuelve la clave pública para darla de alta en los demás.
+#[tauri::command]
+pub async fn generate_trust_list_key(state: tauri::State<'_, AppState>, replace: Option<bool>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let mut settings = TrustListSettings::load(&db);
+    if settings.signing_key.is_some() && !replace.unwrap_or(false) {
+        return Err("A signing key already exists; pass replace to create a new one".to_string());
+    }
+
+    settings.signing_key = Some(hex(&random_bytes(32)));
+    settings.save(&db).map_err(|e| format!("Database error: {}", e))?;
+    let public_key = settings.public_key();
+    audit::record(&db, KEY_GENERATED_AUDIT_ACTION, None, &serde_json::json!({ "public_key": public_key }));
+
+    Ok(serde_json::json!({ "success": true, "public_key": public_key }))
+}
+
+/// Claves públicas cuyas listas firmadas se aceptan en este equipo (sustituye a las anteriores)
+#[tauri::command]
+pub async fn configure_trust_list_keys(state: tauri::State<'_, AppState>, keys: Vec<TrustedKey>) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let mut settings = TrustListSettings::load(&db);
+
+    let mut trusted_keys: Vec<TrustedKey> = Vec::new();
+    for key in keys {
+        let public_key = hex(parse_public_key(&key.public_key)?.as_bytes());
+        if !trusted_keys.iter().any(|k| k.public_key == public_key) {
+            trusted_keys.push(TrustedKey { public_key, label: key.label.filter(|l| !l.trim().is_empty()) });
+        }
+    }
+    settings.trusted_keys = trusted_keys;
+    settings.save(&db).map_err(|e| format!("Database error: {}", e))?;
+    audit::record(&db, KEYS_CONFIGURED_AUDIT_ACTION, None, &serde_json::json!({ "trusted_keys": settings.trusted_keys }));
+
+    Ok(serde_json::json!({ "success": true, "trusted_keys": settings.trusted_keys }))
+}
+
+/// Exportar los dispositivos de confianza (o solo `device_ids`) como lista firmada con la clave de este equipo
+#[tauri::command]
+pub async fn export_signed_trust_list(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    name: Option<String>,
+    device_ids: Option<Vec<String>>,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let settings = TrustListSettings::load(&db);
+    if settings.signing_key.is_none() {
+        return Err("No signing key; create one with generate_trust_list_key".to_string());
+    }
+
+    let mut devices: Vec<WhitelistEntry> = db
+        .get_devices(DeviceSort::default())
+        .map_err(|e| format!("Database error: {}", e))?
+        .into_iter()
+        .filter(|d| d.trust_level == TrustLevel::Trusted)
+        .filter(|d| device_ids.as_ref().is_none_or(|ids| ids.contains(&d.serial_number)))
+        .map(|d| WhitelistEntry {
+            serial_number: d.serial_number,
+            vendor_id: Some(d.vendor_id).filter(|v| *v != 0),
+            product_id: Some(d.product_id).filter(|p| *p != 0),
+            nickname: d.nickname.or(d.name),
+        })
+        .collect();
+    devices.sort_by(|a, b| a.serial_number.cmp(&b.serial_number));
+
+    let list = TrustList {
+        name: name.filter(|n| !n.trim().is_empty()),
+        issuer: crate::machine::current().machine_id.clone(),
+        issued_at: Utc::now(),
+        devices,
+    };
+    let (public_key, signature) = sign(&settings, &signed_message(&list)?).ok_or("Invalid signing key")?;
+    let count = list.devices.len();
+    let file = SignedTrustList {
+        format: TRUST_LIST_FORMAT.to_string(),
+        version: TRUST_LIST_VERSION,
+        list,
+        public_key,
+        signature,
+    };
+    let content = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+
+    println!("[TrustList] Exported {} trusted devices to {}", count, path);
+    Ok(serde_json::json!({ "success": true, "path": path, "devices": count, "public_key": file.public_key }))
+}
+
+/// Importar una lista firmada: solo se aplica si la firma es válida y la clave está dada de alta en este equipo
+#[tauri::command]
+pub async fn import_signed_trust_list(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    dry_run: Option<bool>,
+    approval: Option<Approval>,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let dry_run = dry_run.unwrap_or(false);
+    let mut settings = TrustListSettings::load(&db);
+
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let file: SignedTrustList = serde_json::from_str(&content).map_err(|e| format!("Invalid trust list: {}", e))?;
+    let key = match verify(&file, &settings) {
+        Ok(key) => key,
+        Err(e) => {
+            audit::record(
+                &db,
+                REJECTED_AUDIT_ACTION,
+                None,
+                &serde_json::json!({ "path": path, "public_key": file.public_key, "issuer": file.list.issuer, "reason": e }),
+            );
+            return Err(e);
+        }
+    };
+
+    let summary = importer::apply_whitelist(&db, &file.list.devices, dry_run, approval.as_ref())?;
+    if !dry_run {
+        settings.last_issued.insert(key.clone(), file.list.issued_at);
+        settings.save(&db).map_err(|e| format!("Database error: {}", e))?;
+        audit::record(
+            &db,
+            IMPORTED_AUDIT_ACTION,
+            None,
+            &serde_json::json!({
+                "path": path,
+                "name": file.list.name,
+                "issuer": file.list.issuer,
+                "issued_at": file.list.issued_at,
+                "public_key": key,
+                "added": summary.added,
+                "updated": summary.updated,
+                "unchanged": summary.unchanged,
+            }),
+        );
+    }
+
+    Ok(serde_json::json!({ "success": true, "list": { "name": file.list.name, "issuer": file.list.issuer, "issued_at": file.list.issued_at }, "summary": summary }))
+}
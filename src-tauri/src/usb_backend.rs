@@ -0,0 +1,839 @@
+use rusb::{Context, Device, DeviceList};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use sysinfo::{Disk, Disks};
+use crate::db::{Database, MediaType, StringDescriptors};
+use crate::usb_monitor::{ConnectionKind, DeviceVolume, UsbDevice};
+
+/// Origen de la lista de unidades conectadas. El monitor compara listas sucesivas para
+/// detectar conexiones y desconexiones, así que cualquier implementación sirve para
+/// alimentar el flujo completo (registro, escaneo y watcher).
+pub trait UsbBackend: Send + Sync {
+    /// `db` solo se usa para leer ajustes (tiempos de lectura de los descriptores)
+    fn scan_devices(&self, db: &Database) -> Vec<UsbDevice>;
+}
+
+// Idiomas como máximo que se leen de un dispositivo (cada lectura puede agotar su tiempo)
+const MAX_DESCRIPTOR_LANGUAGES: usize = 8;
+pub const DESCRIPTOR_SETTINGS_KEY: &str = "usb_descriptors";
+const DEFAULT_LANGUAGES_TIMEOUT_MS: u64 = 200;
+const DEFAULT_STRING_TIMEOUT_MS: u64 = 100;
+const DEFAULT_RETRIES: u32 = 2;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 50;
+
+/// Lectura de los descriptores USB, guardada en settings bajo la clave `usb_descriptors`.
+/// Algunos dispositivos tardan en responder o fallan la primera vez y se quedan sin nombre.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DescriptorSettings {
+    /// Tiempo máximo de la lectura de idiomas y de cada texto
+    pub languages_timeout_ms: Option<u64>,
+    pub string_timeout_ms: Option<u64>,
+    /// Reintentos de cada lectura que falla; la espera empieza en `retry_backoff_ms` y se duplica en cada uno
+    pub retries: Option<u32>,
+    pub retry_backoff_ms: Option<u64>,
+}
+
+impl DescriptorSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(DESCRIPTOR_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+struct DescriptorTiming {
+    languages_timeout: Duration,
+    string_timeout: Duration,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl From<&DescriptorSettings> for DescriptorTiming {
+    fn from(settings: &DescriptorSettings) -> Self {
+        DescriptorTiming {
+            languages_timeout: Duration::from_millis(settings.languages_timeout_ms.unwrap_or(DEFAULT_LANGUAGES_TIMEOUT_MS)),
+            string_timeout: Duration::from_millis(settings.string_timeout_ms.unwrap_or(DEFAULT_STRING_TIMEOUT_MS)),
+            retries: settings.retries.unwrap_or(DEFAULT_RETRIES),
+            backoff: Duration::from_millis(settings.retry_backoff_ms.unwrap_or(DEFAULT_RETRY_BACKOFF_MS)),
+        }
+    }
+}
+
+// Sin permiso o sin soporte no cambia al reintentar: es lo habitual en hubs, teclados
+// y demás dispositivos que se recorren en cada sondeo
+fn is_permanent(error: &rusb::Error) -> bool {
+    matches!(error, rusb::Error::Access | rusb::Error::NotSupported | rusb::Error::NotFound | rusb::Error::InvalidParam)
+}
+
+// Repetir una lectura que falla con espera creciente
+fn with_retries<T>(timing: &DescriptorTiming, mut read: impl FnMut() -> rusb::Result<T>) -> rusb::Result<T> {
+    let mut backoff = timing.backoff;
+    let mut attempt = 0;
+    loop {
+        match read() {
+            Err(e) if !is_permanent(&e) && attempt < timing.retries => {
+                attempt += 1;
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Idioma principal del usuario como LANGID primario de USB/Windows (los 10 bits bajos), p.ej. 0x0A para español
+#[cfg(windows)]
+fn user_primary_language() -> Option<u16> {
+    use windows_sys::Win32::Globalization::GetUserDefaultUILanguage;
+
+    // SAFETY: sin parámetros; devuelve el LANGID de la interfaz del usuario
+    let lang_id = unsafe { GetUserDefaultUILanguage() };
+    Some(lang_id & 0x3FF).filter(|&primary| primary != 0)
+}
+
+// En el resto, el idioma del locale (LC_ALL, LC_MESSAGES o LANG: "es_ES.UTF-8" -> "es")
+#[cfg(not(windows))]
+fn user_primary_language() -> Option<u16> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+    let code = locale.split(['_', '.', '@', '-']).next()?.to_lowercase();
+    let primary = match code.as_str() {
+        "ar" => 0x01,
+        "zh" => 0x04,
+        "cs" => 0x05,
+        "da" => 0x06,
+        "de" => 0x07,
+        "el" => 0x08,
+        "en" => 0x09,
+        "es" => 0x0A,
+        "fi" => 0x0B,
+        "fr" => 0x0C,
+        "he" => 0x0D,
+        "hu" => 0x0E,
+        "it" => 0x10,
+        "ja" => 0x11,
+        "ko" => 0x12,
+        "nl" => 0x13,
+        "no" | "nb" => 0x14,
+        "pl" => 0x15,
+        "pt" => 0x16,
+        "ru" => 0x19,
+        "sv" => 0x1D,
+        "tr" => 0x1F,
+        "uk" => 0x22,
+        _ => return None,
+    };
+    Some(primary)
+}
+
+fn preferred_language() -> Option<u16> {
+    static PREFERRED: OnceLock<Option<u16>> = OnceLock::new();
+    *PREFERRED.get_or_init(user_primary_language)
+}
+
+/// Descriptores en el idioma del usuario si el dispositivo lo declara; si no, en el primero que declara
+fn preferred_descriptors(descriptors: &[StringDescriptors]) -> Option<&StringDescriptors> {
+    preferred_language()
+        .and_then(|primary| descriptors.iter().find(|d| d.language_id & 0x3FF == primary))
+        .or_else(|| descriptors.first())
+}
+
+#[derive(Clone)]
+struct RusbDetails {
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    descriptors: Vec<StringDescriptors>,
+    // Con alguna interfaz de datos; None si es un hub o no se pudo leer su configuración
+    data: Option<bool>,
+    // Sin fallos pasajeros: se puede guardar en caché
+    complete: bool,
+}
+
+// El resultado de una lectura; los fallos pasajeros dejan los datos como incompletos
+fn keep<T>(result: rusb::Result<T>, complete: &mut bool) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            if !is_permanent(&e) {
+                *complete = false;
+            }
+            None
+        }
+    }
+}
+
+// Un dispositivo enumerado cuyas interfaces no tienen ningún endpoint (solo el de control) no puede
+// transferir datos: cable o puerto de solo carga, o un teléfono en modo "solo carga".
+// La configuración se lee sin abrir el dispositivo; en Windows libusb la tiene de la enumeración.
+fn has_data_interfaces(device: &Device<Context>, device_desc: &rusb::DeviceDescriptor) -> Option<bool> {
+    if device_desc.class_code() == rusb::constants::LIBUSB_CLASS_HUB {
+        return None;
+    }
+    let config = device.active_config_descriptor().ok()?;
+    let data = config
+        .interfaces()
+        .flat_map(|interface| interface.descriptors())
+        .any(|setting| setting.num_endpoints() > 0);
+    Some(data)
+}
+
+fn get_rusb_details(device: &Device<Context>, timing: &DescriptorTiming) -> RusbDetails {
+    let mut details =
+        RusbDetails { vendor_id: 0, product_id: 0, serial: None, descriptors: Vec::new(), data: None, complete: true };
+    let device_desc = match device.device_descriptor() {
+        Ok(d) => d,
+        Err(_) => return details,
+    };
+    details.vendor_id = device_desc.vendor_id();
+    details.product_id = device_desc.product_id();
+    details.data = has_data_interfaces(device, &device_desc);
+
+    let mut complete = true;
+    let Some(handle) = keep(with_retries(timing, || device.open()), &mut complete) else {
+        details.complete = complete;
+        return details;
+    };
+    let Some(langs) = keep(with_retries(timing, || handle.read_languages(timing.languages_timeout)), &mut complete) else {
+        details.complete = complete;
+        return details;
+    };
+    let mut read = |lang_id, index: Option<u8>| {
+        let idx = index?;
+        keep(with_retries(timing, || handle.read_string_descriptor(lang_id, idx, timing.string_timeout)), &mut complete)
+    };
+
+    // Todos los idiomas que declara: fabricante y producto pueden venir solo en el suyo (japonés, chino...)
+    for lang in langs.iter().take(MAX_DESCRIPTOR_LANGUAGES) {
+        let product = read(*lang, device_desc.product_string_index());
+        let manufacturer = read(*lang, device_desc.manufacturer_string_index());
+        if product.is_some() || manufacturer.is_some() {
+            details.descriptors.push(StringDescriptors { language_id: lang.lang_id(), manufacturer, product });
+        }
+    }
+    // El número de serie no depende del idioma
+    if let Some(lang) = langs.first() {
+        details.serial = read(*lang, device_desc.serial_number_string_index());
+    }
+
+    details.complete = complete;
+    details
+}
+
+// Clave de caché: una dirección nueva en el bus es otra conexión
+type DeviceKey = (u8, u8, u16, u16);
+
+fn device_key(device: &Device<Context>) -> DeviceKey {
+    let (vid, pid) = device.device_descriptor().map(|d| (d.vendor_id(), d.product_id())).unwrap_or((0, 0));
+    (device.bus_number(), device.address(), vid, pid)
+}
+
+// Bus y cadena de puertos al estilo de Linux: "1-3.2" = bus 1, puerto 3 del root hub, puerto 2 del hub
+fn port_path(device: &Device<Context>) -> Option<String> {
+    let ports = device.port_numbers().ok().filter(|ports| !ports.is_empty())?;
+    let chain: Vec<String> = ports.iter().map(|p| p.to_string()).collect();
+    Some(format!("{}-{}", device.bus_number(), chain.join(".")))
+}
+
+/// Número de serie y etiqueta del volumen montado en `mount_point` (`disk_name` es el dispositivo, p.ej. /dev/sdb1)
+#[cfg(windows)]
+fn volume_info(mount_point: &str, _disk_name: &str) -> (Option<String>, Option<String>) {
+    use windows_sys::Win32::Storage::FileSystem::GetVolumeInformationW;
+
+    let root: Vec<u16> = mount_point.encode_utf16().chain(std::iter::once(0)).collect();
+    let mut serial: u32 = 0;
+    let mut label = [0u16; 261];
+    // SAFETY: `root` termina en NUL, `label` tiene la longitud indicada y el resto de búferes van a null con tamaño 0
+    let ok = unsafe {
+        GetVolumeInformationW(
+            root.as_ptr(),
+            label.as_mut_ptr(),
+            label.len() as u32,
+            &mut serial,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if ok == 0 {
+        return (None, None);
+    }
+
+    let length = label.iter().position(|&c| c == 0).unwrap_or(label.len());
+    let label = String::from_utf16_lossy(&label[..length]);
+    (
+        Some(format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF)),
+        Some(label).filter(|l| !l.is_empty()),
+    )
+}
+
+// En Linux el UUID del sistema de archivos (en FAT/exFAT es el mismo número de serie que muestra Windows)
+// y la etiqueta de /dev/disk/by-label
+#[cfg(target_os = "linux")]
+fn volume_info(_mount_point: &str, disk_name: &str) -> (Option<String>, Option<String>) {
+    let device = match std::fs::canonicalize(disk_name) {
+        Ok(device) => device,
+        Err(_) => return (None, None),
+    };
+    let link_name = |dir: &str| {
+        std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .find(|entry| std::fs::canonicalize(entry.path()).ok().as_ref() == Some(&device))
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+    };
+
+    (
+        link_name("/dev/disk/by-uuid").map(|uuid| windows_serial(&uuid.to_uppercase())),
+        link_name("/dev/disk/by-label").map(|label| unescape_label(&label)),
+    )
+}
+
+// NTFS tiene un serie de 64 bits ("1A2B3C4D5E6F7A8B"); Windows muestra solo los 32 bits bajos ("5E6F-7A8B"),
+// que es lo que aparece en sus propios registros
+#[cfg(target_os = "linux")]
+fn windows_serial(uuid: &str) -> String {
+    if uuid.len() == 16 && uuid.chars().all(|c| c.is_ascii_hexdigit()) {
+        format!("{}-{}", &uuid[8..12], &uuid[12..16])
+    } else {
+        uuid.to_string()
+    }
+}
+
+// udev escapa los espacios y otros caracteres de la etiqueta como "\x20"
+#[cfg(target_os = "linux")]
+fn unescape_label(label: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = label.as_bytes();
+    while !rest.is_empty() {
+        if rest.len() >= 4 && rest.starts_with(b"\\x") {
+            if let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(&rest[2..4]), 16) {
+                bytes.push(byte);
+                rest = &rest[4..];
+                continue;
+            }
+        }
+        bytes.push(rest[0]);
+        rest = &rest[1..];
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn volume_info(_mount_point: &str, _disk_name: &str) -> (Option<String>, Option<String>) {
+    (None, None)
+}
+
+/// Tipo de unidad según el sistema. Solo las extraíbles se correlacionan con dispositivos USB y se escanean:
+/// un recurso de red o una unidad de la nube asociada por error se escanearía a través de la red.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DriveType {
+    Removable,
+    Fixed,
+    Network,
+}
+
+// Sistemas de archivos de red y de almacenamiento en la nube, aunque se monten con letra de unidad o bajo /media
+const NETWORK_FILE_SYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb", "smb2", "smb3", "smbfs", "afpfs", "webdav", "davfs", "9p", "afs", "ceph",
+    "glusterfs", "sshfs", "rclone", "onedriver", "googledrivefs", "gvfsd-fuse",
+];
+
+fn drive_type(disk: &Disk) -> DriveType {
+    // "fuse.sshfs", "fuse.rclone"...: cuenta el tipo real
+    let file_system = disk.file_system().to_string_lossy().to_lowercase();
+    let file_system = file_system.rsplit('.').next().unwrap_or_default();
+    if NETWORK_FILE_SYSTEMS.contains(&file_system) {
+        return DriveType::Network;
+    }
+    os_drive_type(disk)
+}
+
+/// Volumen extraíble local (no de red ni de la nube), el único que se correlaciona y escanea
+pub fn is_removable_drive(disk: &Disk) -> bool {
+    drive_type(disk) == DriveType::Removable
+}
+
+#[cfg(windows)]
+fn os_drive_type(disk: &Disk) -> DriveType {
+    use windows_sys::Win32::Storage::FileSystem::GetDriveTypeW;
+    use windows_sys::Win32::System::WindowsProgramming::{DRIVE_REMOTE, DRIVE_REMOVABLE};
+
+    let root: Vec<u16> = disk.mount_point().as_os_str().to_string_lossy().encode_utf16().chain(std::iter::once(0)).collect();
+    // SAFETY: `root` termina en NUL
+    match unsafe { GetDriveTypeW(root.as_ptr()) } {
+        DRIVE_REMOVABLE => DriveType::Removable,
+        DRIVE_REMOTE => DriveType::Network,
+        _ => DriveType::Fixed,
+    }
+}
+
+// En Linux un montaje que no sale de un dispositivo de bloques (/dev/...) no es un disco local
+#[cfg(target_os = "linux")]
+fn os_drive_type(disk: &Disk) -> DriveType {
+    if !disk.name().to_string_lossy().starts_with("/dev/") {
+        DriveType::Network
+    } else if disk.is_removable() {
+        DriveType::Removable
+    } else {
+        DriveType::Fixed
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn os_drive_type(disk: &Disk) -> DriveType {
+    if disk.is_removable() {
+        DriveType::Removable
+    } else {
+        DriveType::Fixed
+    }
+}
+
+/// Bus de un volumen y, en las tarjetas, el número de serie (CID) y el nombre de la propia tarjeta
+struct Media {
+    media_type: MediaType,
+    card_serial: Option<String>,
+    card_name: Option<String>,
+}
+
+// En Linux por sysfs: las tarjetas de un lector integrado (mmcblk) no son USB y el kernel no siempre las marca extraíbles
+#[cfg(target_os = "linux")]
+fn media_info(disk: &Disk) -> Option<Media> {
+    let device = std::fs::canonicalize(disk.name()).ok()?;
+    let name = device.file_name()?.to_string_lossy().to_string();
+    let mut block = std::fs::canonicalize(std::path::Path::new("/sys/class/block").join(name)).ok()?;
+    // Partición (sdb1, mmcblk0p1): los datos del dispositivo están en el disco
+    if block.join("partition").exists() {
+        block = block.parent()?.to_path_buf();
+    }
+    let read = |file: &str| {
+        std::fs::read_to_string(block.join("device").join(file))
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    // "MMC" también es la eMMC interna del equipo: solo cuentan las tarjetas SD
+    if read("type").as_deref() == Some("SD") {
+        return Some(Media {
+            media_type: MediaType::SdCard,
+            card_serial: read("serial").map(|serial| serial.trim_start_matches("0x").to_uppercase()),
+            card_name: read("name"),
+        });
+    }
+    block.to_string_lossy().contains("/usb").then_some(Media {
+        media_type: MediaType::Usb,
+        card_serial: None,
+        card_name: None,
+    })
+}
+
+// En Windows por el tipo de bus del disco que contiene el volumen
+#[cfg(windows)]
+fn media_info(disk: &Disk) -> Option<Media> {
+    use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        BusTypeMmc, BusTypeSd, BusTypeUsb, CreateFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+    };
+    use windows_sys::Win32::System::Ioctl::{
+        PropertyStandardQuery, StorageDeviceProperty, IOCTL_STORAGE_QUERY_PROPERTY, STORAGE_DEVICE_DESCRIPTOR,
+        STORAGE_PROPERTY_QUERY,
+    };
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    let mount_point = disk.mount_point().to_string_lossy();
+    let letter = mount_point.chars().next().filter(|c| c.is_ascii_alphabetic())?;
+    let path: Vec<u16> = format!("\\\\.\\{}:", letter).encode_utf16().chain(std::iter::once(0)).collect();
+    // SAFETY: `path` termina en NUL; la consulta de propiedades no necesita permisos de lectura
+    let handle = unsafe {
+        CreateFileW(
+            path.as_ptr(),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    let query = STORAGE_PROPERTY_QUERY {
+        PropertyId: StorageDeviceProperty,
+        QueryType: PropertyStandardQuery,
+        AdditionalParameters: [0],
+    };
+    // SAFETY: consulta y descriptor con sus tamaños reales; el handle se cierra justo después
+    let mut descriptor: STORAGE_DEVICE_DESCRIPTOR = unsafe { std::mem::zeroed() };
+    let mut returned = 0u32;
+    let ok = unsafe {
+        let ok = DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_QUERY_PROPERTY,
+            &query as *const _ as *const _,
+            std::mem::size_of::<STORAGE_PROPERTY_QUERY>() as u32,
+            &mut descriptor as *mut _ as *mut _,
+            std::mem::size_of::<STORAGE_DEVICE_DESCRIPTOR>() as u32,
+            &mut returned,
+            std::ptr::null_mut(),
+        );
+        CloseHandle(handle);
+        ok
+    };
+    if ok == 0 {
+        return None;
+    }
+
+    let media_type = match descriptor.BusType {
+        bus if bus == BusTypeSd || bus == BusTypeMmc => MediaType::SdCard,
+        bus if bus == BusTypeUsb => MediaType::Usb,
+        _ => return None,
+    };
+    Some(Media { media_type, card_serial: None, card_name: None })
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn media_info(_disk: &Disk) -> Option<Media> {
+    None
+}
+
+/// Backend real: unidades extraíbles de sysinfo enriquecidas con los descriptores de libusb.
+/// Los descriptores solo se leen de los dispositivos nuevos (o cuya lectura falló): abrir cada dispositivo
+/// en cada sondeo lo despierta y es lento con muchos periféricos.
+#[derive(Default)]
+pub struct RusbBackend {
+    cache: Mutex<HashMap<DeviceKey, RusbDetails>>,
+}
+
+impl UsbBackend for RusbBackend {
+    fn scan_devices(&self, db: &Database) -> Vec<UsbDevice> {
+        let mut final_list = Vec::new();
+        
+        let disks = Disks::new_with_refreshed_list();
+        
+        let settings = DescriptorSettings::load(db);
+        let timing = DescriptorTiming::from(&settings);
+        let mut rusb_devices = Vec::new();
+        if let Ok(context) = Context::new() {
+            if let Ok(list) = DeviceList::new_with_context(context) {
+                let mut cache = self.cache.lock().unwrap();
+                let mut present = HashSet::new();
+                for device in list.iter() {
+                    let key = device_key(&device);
+                    present.insert(key);
+                    let details = match cache.get(&key) {
+                        Some(details) if details.complete => details.clone(),
+                        _ => {
+                            let details = get_rusb_details(&device, &timing);
+                            cache.insert(key, details.clone());
+                            details
+                        }
+                    };
+                    rusb_devices.push((device, details));
+                }
+                cache.retain(|key, _| present.contains(key));
+            }
+        }
+
+        let owners = DiskOwners::new(&rusb_devices);
+        let mut matched = HashSet::new();
+        for disk in &disks {
+            let drive_type = drive_type(disk);
+            if drive_type == DriveType::Network {
+                continue;
+            }
+            // Los lectores de tarjetas integrados no siempre dan el volumen como extraíble
+            let media = media_info(disk);
+            let is_card = media.as_ref().is_some_and(|m| m.media_type == MediaType::SdCard);
+            if drive_type == DriveType::Removable || is_card {
+                let mount_point = disk.mount_point().to_string_lossy().to_string();
+                let disk_name = disk.name().to_string_lossy().to_string();
+                
+                let mut vid = 0;
+                let mut pid = 0;
+                let mut product_name = if disk_name.is_empty() { "USB Drive".to_string() } else { disk_name.clone() };
+                let mut manufacturer = "Generic Storage".to_string();
+                let mut serial = None;
+                let mut port = None;
+                let mut descriptors = Vec::new();
+                let mut volume_guid = None;
+                let mut block_device = None;
+
+                if let Some(owner) = owners.find(disk) {
+                    vid = owner.vendor_id;
+                    pid = owner.product_id;
+                    serial = owner.serial;
+                    volume_guid = owner.volume_guid;
+                    block_device = owner.block_device;
+                    if let Some(index) = owner.rusb_index {
+                        let (r_device, details) = &rusb_devices[index];
+                        if let Some(preferred) = preferred_descriptors(&details.descriptors) {
+                            if let Some(p) = &preferred.product { product_name = p.clone(); }
+                            if let Some(m) = &preferred.manufacturer { manufacturer = m.clone(); }
+                        }
+                        port = port_path(r_device);
+                        descriptors = details.descriptors.clone();
+                        matched.insert(index);
+                    }
+                }
+
+                let (volume_serial, volume_label) = volume_info(&mount_point, &disk_name);
+                // Sin descriptor USB: el bus que da el sistema, o un volumen extraíble sin más datos
+                let media_type = if serial.is_some() {
+                    MediaType::Usb
+                } else {
+                    media.as_ref().map(|m| m.media_type).unwrap_or(MediaType::Removable)
+                };
+                if let Some(card) = media.filter(|m| m.media_type == MediaType::SdCard) {
+                    if let Some(name) = card.card_name {
+                        product_name = name;
+                    }
+                    manufacturer = "SD Card".to_string();
+                    // El CID de la tarjeta identifica la tarjeta, no el lector
+                    serial = serial.or(card.card_serial.map(|cid| format!("SD_{}", cid)));
+                }
+                let final_serial = serial.unwrap_or_else(|| {
+                    format!("DISK_{}_{}", mount_point.replace(":", "").replace("\\", ""), disk.total_space())
+                });
+
+                let volume = DeviceVolume {
+                    mount_point: mount_point.clone(),
+                    volume_serial: volume_serial.clone(),
+                    volume_label: volume_label.clone(),
+                    total_space: Some(disk.total_space()),
+                    available_space: Some(disk.available_space()),
+                    volume_guid,
+                    block_device,
+                };
+                final_list.push(UsbDevice {
+                    id: final_serial.clone(),
+                    vendor_id: vid,
+                    product_id: pid,
+                    product_name: Some(product_name),
+                    manufacturer_name: Some(manufacturer),
+                    serial_number: Some(final_serial),
+                    mount_point: Some(mount_point),
+                    total_space: Some(disk.total_space()),
+                    available_space: Some(disk.available_space()),
+                    port_path: port,
+                    volume_serial,
+                    volume_label,
+                    media_type,
+                    volumes: vec![volume],
+                    usb_serial: None,
+                    descriptors,
+                    connection: ConnectionKind::Data,
+                });
+            }
+        }
+
+        // Sin unidad y sin interfaces de datos: se muestran como de solo carga para que se vea
+        // por qué un teléfono "conectado" no tiene punto de montaje ni escaneo
+        for (index, (r_device, details)) in rusb_devices.iter().enumerate() {
+            if matched.contains(&index) || details.data != Some(false) {
+                continue;
+            }
+            final_list.push(charge_only_device(r_device, details));
+        }
+
+        group_volumes(final_list)
+    }
+}
+
+/// Dispositivo USB al que pertenece un volumen
+struct DiskOwner {
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<String>,
+    volume_guid: Option<String>,
+    block_device: Option<String>,
+    // Su entrada en la lista de libusb (nombres y puerto), si se ha encontrado
+    rusb_index: Option<usize>,
+}
+
+/// Correlación de los volúmenes de un sondeo con los dispositivos USB
+struct DiskOwners<'a> {
+    rusb_devices: &'a [(Device<Context>, RusbDetails)],
+    #[cfg(windows)]
+    usb_disks: HashMap<char, crate::usb_windows::UsbDisk>,
+    #[cfg(target_os = "linux")]
+    usb_disks: HashMap<String, crate::usb_linux::UsbDisk>,
+}
+
+impl<'a> DiskOwners<'a> {
+    fn new(rusb_devices: &'a [(Device<Context>, RusbDetails)]) -> Self {
+        Self {
+            rusb_devices,
+            #[cfg(windows)]
+            usb_disks: crate::usb_windows::usb_disks(),
+            #[cfg(target_os = "linux")]
+            usb_disks: crate::usb_linux::usb_disks(),
+        }
+    }
+
+    // En Windows por la cadena de dispositivos del sistema: la letra lleva a su disco y el disco al dispositivo USB
+    // que lo contiene, sin adivinar. libusb solo aporta los nombres y el puerto del dispositivo con el mismo VID/PID
+    // (y número de serie, si lo lee); con varios iguales sin número de serie no se elige ninguno.
+    #[cfg(windows)]
+    fn find(&self, disk: &Disk) -> Option<DiskOwner> {
+        let letter = disk.mount_point().to_string_lossy().chars().next()?.to_ascii_uppercase();
+        let usb = self.usb_disks.get(&letter)?;
+
+        let candidates: Vec<usize> = self
+            .rusb_devices
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, details))| {
+                details.vendor_id == usb.vendor_id
+                    && details.product_id == usb.product_id
+                    && (details.serial.is_none() || usb.serial.is_none() || details.serial == usb.serial)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let rusb_index = match candidates.as_slice() {
+            [index] => Some(*index),
+            _ => None,
+        };
+
+        Some(DiskOwner {
+            vendor_id: usb.vendor_id,
+            product_id: usb.product_id,
+            serial: usb.serial.clone().or_else(|| rusb_index.and_then(|index| self.rusb_devices[index].1.serial.clone())),
+            volume_guid: usb.volume_guid.clone(),
+            block_device: None,
+            rusb_index,
+        })
+    }
+
+    // En Linux por udev: el punto de montaje lleva (por /proc/mounts) a su dispositivo de bloques y este al
+    // dispositivo USB del que cuelga. En libusb es el que está en el mismo puerto, que no se repite.
+    #[cfg(target_os = "linux")]
+    fn find(&self, disk: &Disk) -> Option<DiskOwner> {
+        let usb = self.usb_disks.get(disk.mount_point().to_string_lossy().as_ref())?;
+        let rusb_index = self.rusb_devices.iter().position(|(device, details)| {
+            details.vendor_id == usb.vendor_id
+                && details.product_id == usb.product_id
+                && port_path(device).as_deref() == Some(usb.port_path.as_str())
+        });
+
+        Some(DiskOwner {
+            vendor_id: usb.vendor_id,
+            product_id: usb.product_id,
+            serial: usb.serial.clone().or_else(|| rusb_index.and_then(|index| self.rusb_devices[index].1.serial.clone())),
+            volume_guid: None,
+            block_device: Some(usb.block_device.to_string_lossy().to_string()),
+            rusb_index,
+        })
+    }
+
+    // En el resto, por el número de serie USB dentro del nombre del disco
+    #[cfg(not(any(windows, target_os = "linux")))]
+    fn find(&self, disk: &Disk) -> Option<DiskOwner> {
+        let disk_name = disk.name().to_string_lossy();
+        self.rusb_devices.iter().enumerate().find_map(|(index, (_, details))| {
+            let serial = details.serial.as_deref()?;
+            // Con un nombre vacío `s.contains` acertaría con cualquier dispositivo
+            let found = !serial.is_empty() && !disk_name.is_empty() && (disk_name.contains(serial) || serial.contains(&*disk_name));
+            found.then(|| DiskOwner {
+                vendor_id: details.vendor_id,
+                product_id: details.product_id,
+                serial: details.serial.clone(),
+                volume_guid: None,
+                block_device: None,
+                rusb_index: Some(index),
+            })
+        })
+    }
+}
+
+fn charge_only_device(device: &Device<Context>, details: &RusbDetails) -> UsbDevice {
+    let port = port_path(device);
+    let preferred = preferred_descriptors(&details.descriptors);
+    let id = details.serial.clone().filter(|s| !s.is_empty()).unwrap_or_else(|| {
+        format!("USB_{:04X}_{:04X}_{}", details.vendor_id, details.product_id, port.as_deref().unwrap_or("0"))
+    });
+    UsbDevice {
+        id: id.clone(),
+        vendor_id: details.vendor_id,
+        product_id: details.product_id,
+        product_name: Some(preferred.and_then(|d| d.product.clone()).unwrap_or_else(|| "USB Device".to_string())),
+        manufacturer_name: preferred.and_then(|d| d.manufacturer.clone()),
+        serial_number: Some(id),
+        mount_point: None,
+        total_space: None,
+        available_space: None,
+        port_path: port,
+        volume_serial: None,
+        volume_label: None,
+        media_type: MediaType::Usb,
+        volumes: Vec::new(),
+        usb_serial: None,
+        descriptors: details.descriptors.clone(),
+        connection: ConnectionKind::ChargeOnly,
+    }
+}
+
+// Las particiones de una misma memoria (mismo número de serie en el mismo puerto) son un solo dispositivo
+// con varios volúmenes; el principal es el primero por punto de montaje
+fn group_volumes(mut devices: Vec<UsbDevice>) -> Vec<UsbDevice> {
+    devices.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    let mut grouped: Vec<UsbDevice> = Vec::new();
+    for device in devices {
+        match grouped
+            .iter_mut()
+            .find(|d| d.serial_number == device.serial_number && d.port_path == device.port_path)
+        {
+            Some(primary) => primary.volumes.extend(device.volumes),
+            None => grouped.push(device),
+        }
+    }
+    grouped
+}
+
+/// Backend simulado para demos y pruebas: dispositivos inyectados a mano,
+/// opcionalmente sumados a los de otro backend
+#[derive(Default)]
+pub struct MockBackend {
+    devices: Mutex<Vec<UsbDevice>>,
+    inner: Option<Arc<dyn UsbBackend>>,
+}
+
+impl MockBackend {
+    /// Mantener los dispositivos reales de `inner` y añadir los simulados
+    pub fn wrapping(inner: Arc<dyn UsbBackend>) -> Self {
+        Self { devices: Mutex::new(Vec::new()), inner: Some(inner) }
+    }
+
+    pub fn connect(&self, device: UsbDevice) {
+        let mut devices = self.devices.lock().unwrap();
+        devices.retain(|d| d.serial_number != device.serial_number);
+        devices.push(device);
+    }
+
+    // Devuelve false si el dispositivo no estaba conectado
+    pub fn disconnect(&self, serial_number: &str) -> bool {
+        let mut devices = self.devices.lock().unwrap();
+        let before = devices.len();
+        devices.retain(|d| d.serial_number.as_deref() != Some(serial_number));
+        devices.len() != before
+    }
+}
+
+impl UsbBackend for MockBackend {
+    fn scan_devices(&self, db: &Database) -> Vec<UsbDevice> {
+        let mut devices = self.inner.as_ref().map(|inner| inner.scan_devices(db)).unwrap_or_default();
+        devices.extend(self.devices.lock().unwrap().iter().cloned());
+        devices
+    }
+}
+
+/// La simulación de dispositivos solo está disponible en builds de desarrollo o con USB_MANAGER_DEMO=1
+pub fn simulation_enabled() -> bool {
+    cfg!(debug_assertions) || std::env::var("USB_MANAGER_DEMO").is_ok_and(|v| v == "1")
+}
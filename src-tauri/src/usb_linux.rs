@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Clase de interfaz USB de almacenamiento masivo (usb-storage y uas)
+const MASS_STORAGE_CLASS: &str = "08";
+
+/// Dispositivo USB de almacenamiento del que sale un punto de montaje, según udev
+#[derive(Debug, Clone)]
+pub struct UsbDisk {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub serial: Option<String>,
+    /// Bus y cadena de puertos del dispositivo en sysfs ("1-3.2"), el mismo formato que `port_path`
+    pub port_path: String,
+    /// Dispositivo de bloques montado: la partición (/dev/sdb1) o el disco (/dev/sdb) si no tiene tabla de particiones
+    pub block_device: PathBuf,
+}
+
+// /proc/mounts escapa espacios, tabuladores, saltos de línea y barras invertidas en octal ("\040")
+fn unescape_mount(field: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = field.as_bytes();
+    while !rest.is_empty() {
+        if rest.len() >= 4 && rest[0] == b'\\' {
+            if let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(&rest[1..4]), 8) {
+                bytes.push(byte);
+                rest = &rest[4..];
+                continue;
+            }
+        }
+        bytes.push(rest[0]);
+        rest = &rest[1..];
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+// Dispositivo de bloques -> sus puntos de montaje (uno puede estar montado en varios sitios)
+fn mounts() -> HashMap<PathBuf, Vec<String>> {
+    let mut mounts: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let Ok(content) = std::fs::read_to_string("/proc/mounts") else { return mounts };
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(source), Some(target)) = (fields.next(), fields.next()) else { continue };
+        if !source.starts_with("/dev/") {
+            continue;
+        }
+        // Los montajes por /dev/disk/by-uuid/... apuntan al mismo nodo
+        let source = std::fs::canonicalize(unescape_mount(source)).unwrap_or_else(|_| PathBuf::from(source));
+        mounts.entry(source).or_default().push(unescape_mount(target));
+    }
+    mounts
+}
+
+fn attribute(device: &udev::Device, name: &str) -> Option<String> {
+    device
+        .attribute_value(name)
+        .map(|value| value.to_string_lossy().trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// Solo cuenta si el bloque cuelga de una interfaz de almacenamiento masivo de un dispositivo USB
+// (quedan fuera los discos SATA/NVMe internos y las tarjetas de un lector integrado)
+fn usb_owner(block: &udev::Device) -> Option<(u16, u16, Option<String>, String)> {
+    let interface = block.parent_with_subsystem_devtype("usb", "usb_interface").ok()??;
+    if attribute(&interface, "bInterfaceClass").as_deref() != Some(MASS_STORAGE_CLASS) {
+        return None;
+    }
+    let usb = block.parent_with_subsystem_devtype("usb", "usb_device").ok()??;
+    let vendor_id = u16::from_str_radix(&attribute(&usb, "idVendor")?, 16).ok()?;
+    let product_id = u16::from_str_radix(&attribute(&usb, "idProduct")?, 16).ok()?;
+    Some((vendor_id, product_id, attribute(&usb, "serial"), usb.sysname().to_string_lossy().to_string()))
+}
+
+fn block_devices() -> std::io::Result<Vec<udev::Device>> {
+    let mut enumerator = udev::Enumerator::new()?;
+    enumerator.match_subsystem("block")?;
+    Ok(enumerator.scan_devices()?.collect())
+}
+
+/// Punto de montaje -> dispositivo USB de almacenamiento que lo contiene, recorriendo los dispositivos de bloques
+/// de udev. Los montajes que no salen de un dispositivo USB no aparecen.
+pub fn usb_disks() -> HashMap<String, UsbDisk> {
+    let mut disks = HashMap::new();
+    let mounts = mounts();
+    if mounts.is_empty() {
+        return disks;
+    }
+
+    let devices = match block_devices() {
+        Ok(devices) => devices,
+        Err(e) => {
+            println!("[USB] udev enumeration failed: {}", e);
+            return disks;
+        }
+    };
+
+    for block in devices {
+        // Particiones (sdb1) y discos sin particiones (sdb): los dos salen como bloques con nodo en /dev
+        let Some(block_device) = block.devnode().map(Path::to_path_buf) else { continue };
+        let Some(mount_points) = mounts.get(&block_device) else { continue };
+        let Some((vendor_id, product_id, serial, port_path)) = usb_owner(&block) else { continue };
+
+        for mount_point in mount_points {
+            disks.insert(
+                mount_point.clone(),
+                UsbDisk {
+                    vendor_id,
+                    product_id,
+                    serial: serial.clone(),
+                    port_path: port_path.clone(),
+                    block_device: block_device.clone(),
+                },
+            );
+        }
+    }
+    disks
+}
@@ -1,12 +1,60 @@
-use rusb::{Context, Device, DeviceList};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::collections::HashMap;
 use tauri::{AppHandle, Emitter};
-use sysinfo::Disks;
-use crate::db::{Database, Device as DbDevice, EventType, get_database};
-use crate::file_scanner::FileScanner;
+use tokio::sync::Notify;
+use crate::alerts::raise_alert;
+use crate::anomaly;
+use crate::audit;
+use crate::backup;
+use crate::baseline;
+use crate::doc_metadata;
+use crate::events;
+use crate::flapping::{self, FlappingSettings};
+use crate::hotplug::{self, HotplugSettings};
+use crate::i18n::Message;
+use crate::identity;
+use crate::idle_eject;
+use crate::ipc;
+use crate::kiosk;
+use crate::machine;
+use crate::os_session::OsSession;
+use crate::plugins::{self, ScanContext};
+use crate::privacy;
+use crate::removal;
+use crate::routing;
+use crate::rules;
+use crate::saved_searches;
+use crate::scan_queue::ScanQueue;
+use crate::scripts::{self, ScriptEvent};
+use crate::sessions;
+use crate::shadow_copy;
+use crate::state::AppState;
+use crate::tray;
+use crate::transfers;
+use crate::db::{AlertSeverity, Database, Device as DbDevice, DeviceSort, EventType, FileSnapshot, MediaType, ScanCheckpoint, ScanStatus, StringDescriptors, TrustLevel};
+use crate::file_scanner::{FileScanner, ScanSettings};
 use crate::file_watcher::FileWatcher;
+use crate::usb_backend::{MockBackend, RusbBackend, UsbBackend};
+use crate::watchlists;
+
+const MONITORING_PAUSED_ACTION: &str = "MONITORING_PAUSED";
+const MONITORING_RESUMED_ACTION: &str = "MONITORING_RESUMED";
+const DEVICE_MONITORING_ACTION: &str = "DEVICE_MONITORING_CHANGED";
+// Sondeo del bus sin avisos de libusb, y durante HOTPLUG_SETTLE tras cada aviso
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const HOTPLUG_SETTLE: Duration = Duration::from_secs(10);
+
+/// Si la conexión lleva datos o solo carga (cable de carga, teléfono en modo "solo carga")
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ConnectionKind {
+    #[default]
+    Data,
+    /// Enumerado sin interfaces de datos: no se monta ni se escanea
+    ChargeOnly,
+}
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
 pub struct UsbDevice {
@@ -18,143 +66,226 @@ pub struct UsbDevice {
     pub serial_number: Option<String>,
     pub mount_point: Option<String>,
     pub total_space: Option<u64>,
+    pub available_space: Option<u64>,
+    /// Bus y cadena de puertos, p.ej. "1-3.2" (None si libusb no ve el dispositivo)
+    #[serde(default)]
+    pub port_path: Option<String>,
+    /// Número de serie del volumen montado
+    #[serde(default)]
+    pub volume_serial: Option<String>,
+    /// Etiqueta del volumen montado
+    #[serde(default)]
+    pub volume_label: Option<String>,
+    #[serde(default)]
+    pub media_type: MediaType,
+    /// Volúmenes montados del dispositivo (varias particiones), el de `mount_point` el primero
+    #[serde(default)]
+    pub volumes: Vec<DeviceVolume>,
+    /// Número de serie USB informado, antes de resolver el registro (que puede usar otro identificador)
+    #[serde(default)]
+    pub usb_serial: Option<String>,
+    /// Fabricante y producto en cada idioma que declara; `product_name` y `manufacturer_name` son los del idioma del usuario
+    #[serde(default)]
+    pub descriptors: Vec<StringDescriptors>,
+    #[serde(default)]
+    pub connection: ConnectionKind,
+}
+
+/// Volumen montado de un dispositivo
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct DeviceVolume {
+    pub mount_point: String,
+    pub volume_serial: Option<String>,
+    pub volume_label: Option<String>,
+    pub total_space: Option<u64>,
+    pub available_space: Option<u64>,
+    /// Ruta del volumen en Windows (`\\?\Volume{GUID}\`), que no cambia aunque cambie la letra
+    #[serde(default)]
+    pub volume_guid: Option<String>,
+    /// Dispositivo de bloques en Linux (`/dev/sdb1`)
+    #[serde(default)]
+    pub block_device: Option<String>,
+}
+
+impl UsbDevice {
+    /// Volúmenes montados; los dispositivos simulados pueden traer solo `mount_point`
+    pub fn mounted_volumes(&self) -> Vec<DeviceVolume> {
+        if !self.volumes.is_empty() {
+            return self.volumes.clone();
+        }
+        self.mount_point
+            .iter()
+            .map(|mount_point| DeviceVolume {
+                mount_point: mount_point.clone(),
+                volume_serial: self.volume_serial.clone(),
+                volume_label: self.volume_label.clone(),
+                total_space: self.total_space,
+                available_space: self.available_space,
+                volume_guid: None,
+                block_device: None,
+            })
+            .collect()
+    }
+
+    pub fn mount_points(&self) -> Vec<String> {
+        self.mounted_volumes().into_iter().map(|v| v.mount_point).collect()
+    }
 }
 
 pub struct UsbMonitor {
     pub devices: Arc<Mutex<Vec<UsbDevice>>>,
     pub app_handle: Option<AppHandle>,
-    pub db: Option<Arc<Database>>,
-    pub device_mount_map: Arc<Mutex<HashMap<String, String>>>,
-    pub active_watchers: Arc<Mutex<HashMap<String, notify::RecommendedWatcher>>>,
+    pub db: Arc<Database>,
+    /// device_id -> puntos de montaje de sus volúmenes
+    pub device_mount_map: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    /// device_id -> punto de montaje -> watcher de ese volumen
+    pub active_watchers: Arc<Mutex<HashMap<String, HashMap<String, notify::RecommendedWatcher>>>>,
+    /// Watchers de las carpetas del equipo para registrar lo que se copia desde cada dispositivo
+    pub host_watchers: Arc<Mutex<HashMap<String, notify::RecommendedWatcher>>>,
+    /// device_id -> activity_log_id de la conexión que abrió la sesión en curso
+    pub session_map: Arc<Mutex<HashMap<String, i64>>>,
+    /// Escaneos en curso y en espera de turno
+    pub scans: Arc<ScanQueue>,
+    backend: Arc<dyn UsbBackend>,
+    /// Presente si la simulación de dispositivos está activada (ver `simulate_device_event`)
+    pub simulator: Option<Arc<MockBackend>>,
+    /// Monitorización en pausa: no se detectan conexiones ni desconexiones hasta reanudar
+    paused: AtomicBool,
+    /// Despierta el bucle de monitorización antes de tiempo (avisos de libusb, dispositivos simulados)
+    wake: Arc<Notify>,
+    /// Los cambios llegan por los avisos de libusb y el bus ya no se recorre cada 2 s
+    hotplug: Arc<AtomicBool>,
 }
 
 impl UsbMonitor {
-    pub fn new() -> Self {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self::with_backend(Arc::new(RusbBackend::default()), db)
+    }
+
+    // Monitor sobre otro origen de dispositivos (p.ej. MockBackend en pruebas sin hardware)
+    pub fn with_backend(backend: Arc<dyn UsbBackend>, db: Arc<Database>) -> Self {
         Self {
             devices: Arc::new(Mutex::new(Vec::new())),
             app_handle: None,
-            db: None,
+            db,
             device_mount_map: Arc::new(Mutex::new(HashMap::new())),
             active_watchers: Arc::new(Mutex::new(HashMap::new())),
+            host_watchers: Arc::new(Mutex::new(HashMap::new())),
+            session_map: Arc::new(Mutex::new(HashMap::new())),
+            scans: Arc::new(ScanQueue::default()),
+            backend,
+            simulator: None,
+            paused: AtomicBool::new(false),
+            wake: Arc::new(Notify::new()),
+            hotplug: Arc::new(AtomicBool::new(false)),
         }
     }
 
-    pub fn set_db(&mut self, db: Arc<Database>) {
-        self.db = Some(db);
+    // Sumar dispositivos simulados a los del backend actual
+    pub fn enable_simulation(&mut self) {
+        let simulator = Arc::new(MockBackend::wrapping(self.backend.clone()));
+        self.backend = simulator.clone();
+        self.simulator = Some(simulator);
+        println!("[USB] Device simulation enabled");
     }
 
     pub fn set_app_handle(&mut self, app_handle: AppHandle) {
         self.app_handle = Some(app_handle);
     }
 
-    fn get_rusb_details(device: &Device<Context>) -> (u16, u16, Option<String>, Option<String>, Option<String>) {
-        let device_desc = match device.device_descriptor() {
-            Ok(d) => d,
-            Err(_) => return (0, 0, None, None, None),
-        };
-
-        let vid = device_desc.vendor_id();
-        let pid = device_desc.product_id();
-        let mut product = None;
-        let mut manufacturer = None;
-        let mut serial = None;
-
-        if let Ok(handle) = device.open() {
-            if let Ok(langs) = handle.read_languages(Duration::from_millis(200)) {
-                if let Some(lang_id) = langs.first() {
-                    if let Some(idx) = device_desc.product_string_index() {
-                        product = handle.read_string_descriptor(*lang_id, idx, Duration::from_millis(100)).ok();
-                    }
-                    if let Some(idx) = device_desc.manufacturer_string_index() {
-                        manufacturer = handle.read_string_descriptor(*lang_id, idx, Duration::from_millis(100)).ok();
-                    }
-                    if let Some(idx) = device_desc.serial_number_string_index() {
-                        serial = handle.read_string_descriptor(*lang_id, idx, Duration::from_millis(100)).ok();
-                    }
-                }
-            }
-        }
+    // Olvidar las sesiones en curso para que el próximo ciclo
+    // vuelva a registrar los dispositivos conectados (p.ej. tras cambiar de perfil)
+    pub fn reset_session_state(&self) {
+        self.devices.lock().unwrap().clear();
+        self.device_mount_map.lock().unwrap().clear();
+        self.active_watchers.lock().unwrap().clear();
+        self.host_watchers.lock().unwrap().clear();
+        self.session_map.lock().unwrap().clear();
+    }
 
-        (vid, pid, product, manufacturer, serial)
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
     }
 
-    pub fn scan_devices(&self) -> Vec<UsbDevice> {
-        let mut final_list = Vec::new();
-        
-        let disks = Disks::new_with_refreshed_list();
-        
-        let mut rusb_devices = Vec::new();
-        if let Ok(context) = Context::new() {
-            if let Ok(list) = DeviceList::new_with_context(context) {
-                for device in list.iter() {
-                    let details = Self::get_rusb_details(&device);
-                    rusb_devices.push((device, details));
-                }
-            }
+    /// Pausar o reanudar la monitorización (desde la interfaz o la bandeja). Queda en el registro de auditoría.
+    pub fn set_paused(&self, paused: bool) -> Result<(), String> {
+        kiosk::ensure_unlocked()?;
+        if self.paused.swap(paused, Ordering::Relaxed) == paused {
+            return Ok(());
+        }
+        println!("[USB] Monitoring {}", if paused { "paused" } else { "resumed" });
+        if !paused {
+            self.wake();
         }
 
-        for disk in &disks {
-            if disk.is_removable() {
-                let mount_point = disk.mount_point().to_string_lossy().to_string();
-                let disk_name = disk.name().to_string_lossy().to_string();
-                
-                let mut vid = 0;
-                let mut pid = 0;
-                let mut product_name = if disk_name.is_empty() { "USB Drive".to_string() } else { disk_name.clone() };
-                let mut manufacturer = "Generic Storage".to_string();
-                let mut serial = None;
-
-                for (_, (r_vid, r_pid, r_prod, r_man, r_serial)) in &rusb_devices {
-                    let mut match_found = false;
-                    
-                    if let Some(s) = r_serial {
-                        if !s.is_empty() && (disk_name.contains(s) || s.contains(&disk_name)) {
-                            match_found = true;
-                        }
-                    }
-                    
-                    if match_found {
-                        vid = *r_vid;
-                        pid = *r_pid;
-                        if let Some(p) = r_prod { product_name = p.clone(); }
-                        if let Some(m) = r_man { manufacturer = m.clone(); }
-                        serial = r_serial.clone();
-                        break; 
-                    }
-                }
-
-                let final_serial = serial.unwrap_or_else(|| {
-                    format!("DISK_{}_{}", mount_point.replace(":", "").replace("\\", ""), disk.total_space())
-                });
-
-                final_list.push(UsbDevice {
-                    id: final_serial.clone(),
-                    vendor_id: vid,
-                    product_id: pid,
-                    product_name: Some(product_name),
-                    manufacturer_name: Some(manufacturer),
-                    serial_number: Some(final_serial),
-                    mount_point: Some(mount_point),
-                    total_space: Some(disk.total_space()),
-                });
-            }
+        let action = if paused { MONITORING_PAUSED_ACTION } else { MONITORING_RESUMED_ACTION };
+        audit::record(&self.db, action, None, &serde_json::json!({}));
+        ipc::publish("usb-monitoring", &serde_json::json!({ "paused": paused }));
+        if let Some(ref app_handle) = self.app_handle {
+            events::emit(app_handle, "usb-monitoring", serde_json::json!({ "paused": paused }));
+            tray::refresh(app_handle);
         }
+        Ok(())
+    }
 
-        println!("[USB] Scan finished. Found {} storage devices.", final_list.len());
-        final_list
+    // Punto de montaje actual de un dispositivo conectado
+    pub fn mount_point_for(&self, device_id: &str) -> Option<String> {
+        self.devices
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|d| d.serial_number.as_deref() == Some(device_id))
+            .and_then(|d| d.mount_point.clone())
     }
 
-    fn check_changes(&self) -> (Vec<UsbDevice>, Vec<UsbDevice>) {
-        let current_devices = self.scan_devices();
+    pub fn scan_devices(&self) -> Vec<UsbDevice> {
+        let devices = self.backend.scan_devices(&self.db);
+        println!("[USB] Scan finished. Found {} storage devices.", devices.len());
+        devices
+    }
+
+    // Devuelve los conectados, los desconectados y los que siguen conectados con otros volúmenes montados
+    fn check_changes(&self) -> (Vec<UsbDevice>, Vec<UsbDevice>, Vec<UsbDevice>) {
+        let mut current_devices = self.scan_devices();
         let previous_devices = self.devices.lock().unwrap().clone();
+
+        // El mismo volumen montado en el mismo sitio conserva el registro que se le asignó al conectarse;
+        // solo las unidades nuevas se resuelven contra la base de datos
+        for device in current_devices.iter_mut() {
+            // Cualquiera de sus volúmenes: al montarse otra partición puede cambiar el principal
+            let known = previous_devices.iter().find(|d| {
+                d.mounted_volumes().iter().any(|v| {
+                    device.mount_point.as_deref() == Some(v.mount_point.as_str())
+                        && v.volume_serial == device.volume_serial
+                        && v.total_space == device.total_space
+                })
+            });
+            let resolved = match known {
+                Some(known) => known.serial_number.clone(),
+                None => Some(identity::resolve_device_id(&self.db, device)),
+            };
+            if let Some(resolved) = resolved {
+                device.usb_serial = device.serial_number.clone();
+                device.id = resolved.clone();
+                device.serial_number = Some(resolved);
+            }
+            // Descriptores que no se pudieron leer al conectar: se completan con la primera lectura que funcione
+            if let Some(known) = known {
+                if known.descriptors.is_empty() && !device.descriptors.is_empty() {
+                    backfill_descriptors(&self.db, device);
+                }
+            }
+        }
         
         let mut connected_devices = Vec::new();
         let mut disconnected_devices = Vec::new();
+        let mut remounted_devices = Vec::new();
 
         for device in &current_devices {
-            let is_new = !previous_devices.iter().any(|d| d.serial_number == device.serial_number);
-            if is_new {
-                connected_devices.push(device.clone());
+            match previous_devices.iter().find(|d| d.serial_number == device.serial_number) {
+                None => connected_devices.push(device.clone()),
+                Some(previous) if previous.mount_points() != device.mount_points() => remounted_devices.push(device.clone()),
+                Some(_) => {}
             }
         }
 
@@ -166,71 +297,365 @@ impl UsbMonitor {
         }
 
         *self.devices.lock().unwrap() = current_devices;
-        (connected_devices, disconnected_devices)
+        (connected_devices, disconnected_devices, remounted_devices)
+    }
+
+    // Escanear el volumen en segundo plano (en cola si hay otros escaneos) y procesar el resultado.
+    // Con `resume` se continúa un escaneo interrumpido en lugar de empezar otro.
+    fn spawn_scan(
+        &self,
+        device_id: &str,
+        mount_point: String,
+        activity_id: i64,
+        used_bytes: Option<i64>,
+        total_capacity: Option<i64>,
+        resume: Option<ScanCheckpoint>,
+    ) {
+        let db = &self.db;
+        let scan_settings = ScanSettings::load(db);
+        let scan_limit = scan_settings.concurrency();
+        let stream_batches = scan_settings.stream_batches;
+        let db_clone = db.clone();
+        let app_handle_clone = self.app_handle.clone();
+        let dev_id_clone = device_id.to_string();
+        let scans = self.scans.clone();
+        update_scan_status(db, activity_id, device_id, ScanStatus::Queued, None);
+        tokio::spawn(async move {
+            // Con varias memorias a la vez, los escaneos esperan turno
+            let Some(_permit) = scans.acquire(&dev_id_clone, activity_id, scan_limit, app_handle_clone.clone()).await else {
+                update_scan_status(&db_clone, activity_id, &dev_id_clone, ScanStatus::Canceled, None);
+                return;
+            };
+
+            update_scan_status(&db_clone, activity_id, &dev_id_clone, ScanStatus::Running, None);
+            publish_scan_event(app_handle_clone.as_ref(), "usb-scan-started", serde_json::json!({
+                "device_id": dev_id_clone,
+                "activity_id": activity_id,
+                "mount_point": mount_point,
+                "resumed": resume.is_some(),
+            }));
+            let progress_app = app_handle_clone.clone();
+            let progress = move |checkpoint: &ScanCheckpoint, batch: &[FileSnapshot]| {
+                publish_scan_event(progress_app.as_ref(), "usb-scan-progress", serde_json::json!({
+                    "device_id": checkpoint.device_id,
+                    "activity_id": checkpoint.activity_log_id,
+                    "files": checkpoint.total_files,
+                    "folders": checkpoint.total_folders,
+                    "bytes": checkpoint.total_bytes,
+                }));
+                // Directo a la interfaz: sin límite por ventana ni búfer de reenvío, que guardaría miles de rutas.
+                // Una interfaz recargada a mitad vuelve a consultar el escaneo al terminar.
+                if let Some(app_handle) = progress_app.as_ref().filter(|_| stream_batches) {
+                    let _ = app_handle.emit("usb-scan-batch", serde_json::json!({
+                        "device_id": checkpoint.device_id,
+                        "activity_id": checkpoint.activity_log_id,
+                        "batch": checkpoint.batches,
+                        "files": batch,
+                    }));
+                }
+            };
+
+            let scan = match resume {
+                Some(checkpoint) => FileScanner::resume_scan(checkpoint, db_clone.clone(), progress).await,
+                None => {
+                    println!("[Scanner] Starting scan for {}", mount_point);
+                    FileScanner::scan_and_save(&mount_point, &dev_id_clone, activity_id, db_clone.clone(), progress).await
+                }
+            };
+            match scan {
+                Ok(stats) => {
+                    println!("[Scanner] Scan complete{}", if stats.incremental { " (incremental)" } else { "" });
+                    if let Err(e) = db_clone.record_scan_result(activity_id, &dev_id_clone, &stats, used_bytes, total_capacity) {
+                        println!("[DB] Error recording scan result: {}", e);
+                        update_scan_status(&db_clone, activity_id, &dev_id_clone, ScanStatus::Failed, Some(&e.to_string()));
+                    }
+                    anomaly::check_scan_volume(
+                        &db_clone,
+                        app_handle_clone.as_ref(),
+                        &dev_id_clone,
+                        activity_id,
+                        stats.total_size_bytes,
+                    );
+                    saved_searches::check_scan(&db_clone, app_handle_clone.as_ref(), &dev_id_clone, activity_id);
+                    baseline::check_scan(&db_clone, app_handle_clone.as_ref(), &dev_id_clone, activity_id);
+                    watchlists::check_scan(&db_clone, app_handle_clone.as_ref(), &dev_id_clone, activity_id);
+                    rules::check_storage_usage(
+                        &db_clone,
+                        app_handle_clone.as_ref(),
+                        &dev_id_clone,
+                        activity_id,
+                        used_bytes,
+                        total_capacity,
+                    );
+                    transfers::record_scan(&db_clone, &dev_id_clone, activity_id);
+
+                    // Los plugins externos pueden tardar: fuera del hilo del runtime
+                    let scan = ScanContext {
+                        device_id: dev_id_clone.clone(),
+                        activity_id,
+                        mount_point: mount_point.clone(),
+                        files_scanned: stats.total_files,
+                        total_size: stats.total_size_bytes,
+                    };
+                    let hooks_db = db_clone.clone();
+                    let hooks_app = app_handle_clone.clone();
+                    tokio::task::spawn_blocking(move || {
+                        plugins::run_scanners(&hooks_db, hooks_app.as_ref(), &scan);
+                    });
+                    if ScanSettings::load(&db_clone).extract_document_metadata {
+                        let metadata_db = db_clone.clone();
+                        tokio::task::spawn_blocking(move || doc_metadata::extract_scan(&metadata_db, activity_id));
+                    }
+
+                    let payload = serde_json::json!({
+                        "device_id": dev_id_clone,
+                        "activity_id": activity_id,
+                        "files_scanned": stats.total_files,
+                        "total_size": stats.total_size_bytes,
+                        "message": Message::new("scan.completed", serde_json::json!({
+                            "device": dev_id_clone,
+                            "files": stats.total_files,
+                            "bytes": stats.total_size_bytes,
+                        })),
+                    });
+                    scripts::fire(&db_clone, ScriptEvent::ScanComplete, Some(&dev_id_clone), &payload);
+                    publish_scan_event(app_handle_clone.as_ref(), "usb-scan-complete", payload);
+                }
+                Err(e) => {
+                    println!("[Scanner] Error: {}", e);
+                    update_scan_status(&db_clone, activity_id, &dev_id_clone, ScanStatus::Failed, Some(&e));
+                    publish_scan_event(app_handle_clone.as_ref(), "usb-scan-failed", serde_json::json!({
+                        "device_id": dev_id_clone,
+                        "activity_id": activity_id,
+                        "error": e,
+                    }));
+                }
+            }
+        });
+    }
+
+    // Reconexión rápida con el mismo volumen: la conexión nueva se queda con el inventario del escaneo anterior
+    fn reuse_scan(&self, device_id: &str, source_activity_id: i64, activity_id: i64) -> bool {
+        let db = &self.db;
+        let (files, bytes) = match db.reuse_scan(source_activity_id, activity_id) {
+            Ok(totals) => totals,
+            Err(e) => {
+                println!("[DB] Error reusing scan {}: {}", source_activity_id, e);
+                return false;
+            }
+        };
+        println!("[Scanner] {} reconnected with the same volume, reusing scan {}", device_id, source_activity_id);
+        publish_scan_event(self.app_handle.as_ref(), "usb-scan-complete", serde_json::json!({
+            "device_id": device_id,
+            "activity_id": activity_id,
+            "files_scanned": files,
+            "total_size": bytes,
+            "reused_from": source_activity_id,
+            "message": Message::new("scan.completed", serde_json::json!({
+                "device": device_id,
+                "files": files,
+                "bytes": bytes,
+            })),
+        }));
+        true
+    }
+
+    /// Reanudar los escaneos que quedaron a medias (app cerrada o equipo reiniciado) de los dispositivos
+    /// que siguen conectados en el mismo punto de montaje. Se llama tras el escaneo inicial de dispositivos.
+    pub fn resume_interrupted_scans(&self) {
+        let db = &self.db;
+        // Lo que estaba en cola o en curso al cerrar queda cancelado; los que se reanudan vuelven a la cola
+        if let Err(e) = db.cancel_unfinished_scans() {
+            println!("[DB] Error updating scan status: {}", e);
+        }
+        let checkpoints = match db.get_scan_checkpoints(None) {
+            Ok(checkpoints) => checkpoints,
+            Err(e) => {
+                println!("[DB] Error loading scan checkpoints: {}", e);
+                return;
+            }
+        };
+
+        let devices = self.devices.lock().unwrap().clone();
+        for checkpoint in checkpoints.into_iter().filter(|c| !c.abandoned) {
+            let Some(device) = devices.iter().find(|d| {
+                d.serial_number.as_deref() == Some(checkpoint.device_id.as_str())
+                    && d.mount_point.as_deref() == Some(checkpoint.mount_point.as_str())
+            }) else {
+                continue;
+            };
+            if db.get_device(&checkpoint.device_id).ok().flatten().is_some_and(|d| d.skip_scan) {
+                continue;
+            }
+            let used_bytes = device
+                .total_space
+                .zip(device.available_space)
+                .map(|(total, available)| total.saturating_sub(available) as i64);
+            let device_id = checkpoint.device_id.clone();
+            println!("[Scanner] Resuming interrupted scan of {} (activity {})", device_id, checkpoint.activity_log_id);
+            self.spawn_scan(
+                &device_id,
+                checkpoint.mount_point.clone(),
+                checkpoint.activity_log_id,
+                used_bytes,
+                device.total_space.map(|t| t as i64),
+                Some(checkpoint),
+            );
+        }
     }
 
     fn handle_device_connected(&self, device: &UsbDevice) {
         let device_id = device.serial_number.clone().unwrap_or_default();
 
         println!("[USB] Device Logic Connected: {} (Mount: {:?})", device_id, device.mount_point);
+        idle_eject::touch(&device_id);
 
-        if let Some(ref db) = self.db {
-            let db_device = DbDevice {
-                serial_number: device_id.clone(),
-                vendor_id: device.vendor_id,
-                product_id: device.product_id,
-                name: device.product_name.clone(),
-                manufacturer: device.manufacturer_name.clone(),
-                total_capacity: device.total_space.map(|s| s as i64),
-            };
+        let db = &self.db;
+        let db_device = DbDevice {
+            serial_number: device_id.clone(),
+            vendor_id: device.vendor_id,
+            product_id: device.product_id,
+            name: device.product_name.clone(),
+            manufacturer: device.manufacturer_name.clone(),
+            total_capacity: device.total_space.map(|s| s as i64),
+            trust_level: TrustLevel::Unknown,
+            machine_id: Some(machine::current().machine_id.clone()),
+            nickname: None,
+            volume_serial: device.volume_serial.clone(),
+            volume_label: device.volume_label.clone(),
+            media_type: device.media_type,
+            first_seen: None,
+            last_seen: None,
+            case_reference: None,
+            usb_serial: device.usb_serial.clone(),
+            skip_scan: false,
+            skip_watch: false,
+            redact_paths: None,
+        };
 
-            if let Err(e) = db.upsert_device(&db_device) {
-                println!("[DB] Error upserting device: {}", e);
-            }
+        // Registro anterior con este número de serie, antes de actualizarlo
+        let previous = db.get_device(&device_id).ok().flatten();
+        let used_bytes = device
+            .total_space
+            .zip(device.available_space)
+            .map(|(total, available)| total.saturating_sub(available) as i64);
+        let skip_scan = previous.as_ref().is_some_and(|d| d.skip_scan);
+        let skip_watch = previous.as_ref().is_some_and(|d| d.skip_watch);
+        // Se mira antes de registrar la conexión nueva, que pasaría a ser la última
+        let reusable = device
+            .mount_point
+            .as_ref()
+            .filter(|_| !skip_scan)
+            .and_then(|_| reusable_scan(db, &device_id, device, used_bytes));
+
+        if let Err(e) = db.upsert_device(&db_device) {
+            println!("[DB] Error upserting device: {}", e);
+        }
+        if let Err(e) = db.save_device_descriptors(&device_id, &device.descriptors) {
+            println!("[DB] Error saving device descriptors: {}", e);
+        }
+
+        let session = OsSession::current();
+        match db.create_activity_log(
+            &device_id,
+            EventType::Connect,
+            device.mount_point.as_deref(),
+            device.port_path.as_deref(),
+            device.volume_serial.as_deref(),
+            Some(&session),
+        ) {
+            Ok(activity_id) => {
+                self.session_map.lock().unwrap().insert(device_id.clone(), activity_id);
 
-            match db.create_activity_log(&device_id, EventType::Connect) {
-                Ok(activity_id) => {
-                    if let Some(ref mount) = device.mount_point {
-                        self.device_mount_map.lock().unwrap().insert(device_id.clone(), mount.clone());
-
-                        let mount_point = mount.clone();
-                        let db_clone = db.clone();
-                        let app_handle_clone = self.app_handle.clone();
-                        let dev_id_clone = device_id.clone();
-
-                        match FileWatcher::watch_mount(
-                            mount_point.clone(),
-                            activity_id,
-                            db_clone.clone(),
-                            app_handle_clone.clone().unwrap(),
-                        ) {
-                            Ok(watcher) => {
-                                self.active_watchers.lock().unwrap().insert(device_id.clone(), watcher);
-                            }
-                            Err(e) => println!("[Watcher] No se pudo iniciar: {}", e),
-                        }
-
-                        tokio::spawn(async move {
-                            println!("[Scanner] Starting scan for {}", mount_point);
-                            match FileScanner::scan_and_save(&mount_point, activity_id, db_clone).await {
-                                Ok(stats) => {
-                                    println!("[Scanner] Scan complete");
-                                    if let Some(app_handle) = app_handle_clone {
-                                        let _ = app_handle.emit("usb-scan-complete", serde_json::json!({
-                                            "device_id": dev_id_clone,
-                                            "activity_id": activity_id,
-                                            "files_scanned": stats.total_files,
-                                            "total_size": stats.total_size_bytes,
-                                        }));
-                                    }
-                                }
-                                Err(e) => println!("[Scanner] Error: {}", e),
-                            }
-                        });
+                let trust = db.get_device_trust(&device_id).unwrap_or_default();
+                if trust == TrustLevel::Blocked {
+                    raise_alert(
+                        db,
+                        self.app_handle.as_ref(),
+                        Some(&device_id),
+                        Some(activity_id),
+                        "BLOCKED_DEVICE_CONNECTED",
+                        AlertSeverity::High,
+                        Message::new("alert.blocked_device", serde_json::json!({ "device": device_id })),
+                    );
+                } else {
+                    routing::announce_connection(db, self.app_handle.as_ref(), &device_id, activity_id, trust);
+                }
+                anomaly::check_connection(db, self.app_handle.as_ref(), &device_id, activity_id);
+                identity::check_connection(db, self.app_handle.as_ref(), &db_device, previous.as_ref(), activity_id);
+
+                if let Some(ref mount) = device.mount_point {
+                    backup::on_connect(db, self.app_handle.as_ref(), &device_id, mount, activity_id);
+
+                    let mount_point = mount.clone();
+                    let db_clone = db.clone();
+                    let app_handle_clone = self.app_handle.clone();
+                    let total_capacity = device.total_space.map(|t| t as i64);
+
+                    self.watch_volumes(&device_id, &device.mount_points(), activity_id);
+                    if skip_watch {
+                        println!("[Watcher] File watching disabled for {}", device_id);
+                    } else if let Some(watcher) = shadow_copy::watch_host(
+                        device_id.clone(),
+                        activity_id,
+                        db_clone.clone(),
+                        app_handle_clone.clone(),
+                    ) {
+                        self.host_watchers.lock().unwrap().insert(device_id.clone(), watcher);
+                    }
+
+                    // El inventario es del volumen principal; el resto solo se vigila
+                    let reused = reusable.is_some_and(|source| self.reuse_scan(&device_id, source, activity_id));
+                    if skip_scan {
+                        println!("[Scanner] Scanning disabled for {}", device_id);
+                    } else if !reused {
+                        self.spawn_scan(&device_id, mount_point, activity_id, used_bytes, total_capacity, None);
                     }
                 }
-                Err(e) => println!("[DB] Error creating log: {}", e),
             }
+            Err(e) => println!("[DB] Error creating log: {}", e),
+        }
+    }
+
+    // Un watcher por volumen montado, todos sobre la misma sesión; los de volúmenes que ya no están se detienen
+    fn watch_volumes(&self, device_id: &str, mount_points: &[String], activity_id: i64) {
+        let db = &self.db;
+        // Con la vigilancia desactivada solo se anotan sus volúmenes
+        let skip_watch = db.get_device(device_id).ok().flatten().is_some_and(|d| d.skip_watch);
+        let watched: &[String] = if skip_watch { &[] } else { mount_points };
+        let mut active_watchers = self.active_watchers.lock().unwrap();
+        let watchers = active_watchers.entry(device_id.to_string()).or_default();
+        watchers.retain(|mount_point, _| watched.contains(mount_point));
+
+        for mount_point in watched {
+            if watchers.contains_key(mount_point) {
+                continue;
+            }
+            match FileWatcher::watch_mount(
+                mount_point.clone(),
+                device_id.to_string(),
+                activity_id,
+                db.clone(),
+                self.app_handle.clone(),
+            ) {
+                Ok(watcher) => {
+                    watchers.insert(mount_point.clone(), watcher);
+                }
+                Err(e) => println!("[Watcher] No se pudo iniciar en {}: {}", mount_point, e),
+            }
+        }
+        self.device_mount_map.lock().unwrap().insert(device_id.to_string(), mount_points.to_vec());
+    }
+
+    // Se ha montado o desmontado un volumen de un dispositivo que sigue conectado
+    fn handle_volumes_changed(&self, device: &UsbDevice) {
+        let device_id = device.serial_number.clone().unwrap_or_default();
+        let mount_points = device.mount_points();
+        println!("[USB] Volumes of {} changed: {:?}", device_id, mount_points);
+
+        let activity_id = self.session_map.lock().unwrap().get(&device_id).copied();
+        if let Some(activity_id) = activity_id {
+            self.watch_volumes(&device_id, &mount_points, activity_id);
         }
     }
 
@@ -238,159 +663,359 @@ impl UsbMonitor {
         let device_id = device.serial_number.clone().unwrap_or_default();
         println!("[USB] Device Logic Disconnected: {}", device_id);
 
-        if let Some(ref db) = self.db {
-            let _ = db.create_activity_log(&device_id, EventType::Disconnect);
-            self.device_mount_map.lock().unwrap().remove(&device_id);
+        // Detener el watcher antes de resumir la sesión para que no lleguen más eventos
+        self.active_watchers.lock().unwrap().remove(&device_id);
+        self.host_watchers.lock().unwrap().remove(&device_id);
+        self.scans.cancel(&device_id);
+        idle_eject::forget(&device_id);
+        let event_type = removal::classify(&device_id);
+        if matches!(event_type, EventType::UnsafeRemoval) {
+            println!("[USB] {} removed without ejecting (recent writes may be lost)", device_id);
         }
 
-        self.active_watchers.lock().unwrap().remove(&device_id);
+        let db = &self.db;
+        let disconnect_id = db
+            .create_activity_log(
+                &device_id,
+                event_type,
+                None,
+                device.port_path.as_deref(),
+                device.volume_serial.as_deref(),
+                Some(&OsSession::current()),
+            )
+            .ok();
+        if let Err(e) = db.touch_device_last_seen(&device_id) {
+            println!("[DB] Error updating last seen: {}", e);
+        }
+        self.device_mount_map.lock().unwrap().remove(&device_id);
+
+        let connect_id = self.session_map.lock().unwrap().remove(&device_id);
+        if let Some(connect_id) = connect_id {
+            sessions::record_session_summary(db, self.app_handle.as_ref(), &device_id, connect_id, disconnect_id);
+        }
+    }
+
+    fn report_connected(&self, device: &UsbDevice) {
+        self.handle_device_connected(device);
+        ipc::publish("usb-connected", device);
+        scripts::fire(&self.db, ScriptEvent::Connect, device.serial_number.as_deref(), device);
+        if let Some(ref app_handle) = self.app_handle {
+            events::emit(app_handle, "usb-connected", device);
+        }
+    }
+
+    // Anotar el cambio para detectar conexiones inestables; true si hay que ignorarlo.
+    // Una desconexión con sesión abierta se registra siempre para cerrarla.
+    fn suppress_flapping(&self, device: &UsbDevice, settings: &FlappingSettings, connecting: bool) -> bool {
+        let db = &self.db;
+        let suppress = match flapping::note_transition(&device.id, settings) {
+            flapping::Transition::Normal => return false,
+            flapping::Transition::Started { count } => {
+                flapping::announce(self, db, &device.id, count, settings);
+                true
+            }
+            flapping::Transition::Flapping => true,
+        };
+        suppress && (connecting || !self.session_map.lock().unwrap().contains_key(&device.id))
+    }
+
+    /// Un dispositivo ha dejado de fluctuar: si sigue conectado sin sesión abierta, se registra y escanea
+    pub(crate) fn resume_after_flapping(&self, device_id: &str) {
+        if self.session_map.lock().unwrap().contains_key(device_id) {
+            return;
+        }
+        let device = self.devices.lock().unwrap().iter().find(|d| d.id == device_id).cloned();
+        if let Some(device) = device {
+            self.report_connected(&device);
+            if let Some(ref app_handle) = self.app_handle {
+                tray::refresh(app_handle);
+            }
+        }
     }
 
     pub fn emit_events(&self) {
-        let (connected, disconnected) = self.check_changes();
-        
+        let (connected, disconnected, remounted) = self.check_changes();
+        let flapping = FlappingSettings::load(&self.db);
+
         for device in &connected {
-            self.handle_device_connected(device);
-            if let Some(ref app_handle) = self.app_handle {
-                let _ = app_handle.emit("usb-connected", &device);
+            if !self.suppress_flapping(device, &flapping, true) {
+                self.report_connected(device);
             }
         }
 
         for device in &disconnected {
+            if self.suppress_flapping(device, &flapping, false) {
+                continue;
+            }
             self.handle_device_disconnected(device);
+            ipc::publish("usb-disconnected", device);
+            scripts::fire(&self.db, ScriptEvent::Disconnect, device.serial_number.as_deref(), device);
             if let Some(ref app_handle) = self.app_handle {
-                let _ = app_handle.emit("usb-disconnected", &device);
+                events::emit(app_handle, "usb-disconnected", device);
             }
         }
-    }
 
-    pub async fn start_monitoring(self) {
-        println!("[USB] Monitoring service started.");
-        let monitor = Arc::new(self);
-        loop {
-            monitor.emit_events();
-            tokio::time::sleep(Duration::from_secs(2)).await;
+        for device in &remounted {
+            self.handle_volumes_changed(device);
+            ipc::publish("usb-volumes-changed", device);
+            if let Some(ref app_handle) = self.app_handle {
+                events::emit(app_handle, "usb-volumes-changed", device);
+            }
+        }
+
+        if !connected.is_empty() || !disconnected.is_empty() || !remounted.is_empty() {
+            if let Some(ref app_handle) = self.app_handle {
+                tray::refresh(app_handle);
+            }
         }
     }
 
     pub async fn start_monitoring_shared(self: Arc<Self>) {
         println!("[USB] Monitoring service started (shared).");
+        let settings = HotplugSettings::load(&self.db);
+        if settings.enabled {
+            hotplug::listen(self.wake.clone(), self.hotplug.clone());
+        }
+        let idle_poll = Duration::from_secs(settings.idle_poll_secs).max(POLL_INTERVAL);
+
+        // Tras un aviso se sigue sondeando un rato: el volumen se monta después de que llegue el dispositivo USB
+        let mut settle_until = Instant::now();
         loop {
-            self.emit_events();
-            tokio::time::sleep(Duration::from_secs(2)).await;
+            if !self.is_paused() {
+                self.emit_events();
+                idle_eject::check(&self);
+                flapping::check(&self);
+            }
+            let interval = if self.uses_hotplug() && Instant::now() >= settle_until { idle_poll } else { POLL_INTERVAL };
+            if tokio::time::timeout(interval, self.wake.notified()).await.is_ok() {
+                settle_until = Instant::now() + HOTPLUG_SETTLE;
+            }
         }
     }
+
+    pub fn uses_hotplug(&self) -> bool {
+        self.hotplug.load(Ordering::Relaxed)
+    }
+
+    // Procesar los cambios ya, sin esperar al siguiente ciclo
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
 }
 
 #[tauri::command]
 pub async fn get_connected_devices(
-    monitor: tauri::State<'_, Arc<UsbMonitor>>
+    state: tauri::State<'_, AppState>
 ) -> Result<Vec<UsbDevice>, String> {
-    let devices = monitor.devices.lock().unwrap().clone();
+    let devices = state.monitor.devices.lock().unwrap().clone();
     Ok(devices)
 }
 
+/// Pausar (`paused: true`) o reanudar la monitorización de dispositivos
 #[tauri::command]
-pub async fn start_usb_monitoring(app_handle: AppHandle) -> Result<String, String> {
-    // Este comando ya no es el principal, pero lo mantenemos por compatibilidad
-    // si no se usa el estado compartido
-    let mut monitor = UsbMonitor::new();
-    monitor.set_app_handle(app_handle);
-    
-    let devices = monitor.scan_devices();
-    *monitor.devices.lock().unwrap() = devices;
-    
-    tokio::spawn(async move {
-        monitor.start_monitoring().await;
-    });
-    
+pub async fn set_monitoring_paused(
+    state: tauri::State<'_, AppState>,
+    paused: bool,
+) -> Result<serde_json::Value, String> {
+    state.monitor.set_paused(paused)?;
+    Ok(serde_json::json!({ "success": true, "paused": paused }))
+}
+
+#[tauri::command]
+pub async fn get_monitoring_status(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let in_memory = state.monitor.db.is_in_memory();
+    Ok(serde_json::json!({
+        "success": true,
+        "paused": state.monitor.is_paused(),
+        "in_memory": in_memory,
+        "hotplug": state.monitor.uses_hotplug(),
+    }))
+}
+
+#[tauri::command]
+pub async fn start_usb_monitoring() -> Result<String, String> {
+    // Este comando ya no es el principal, pero lo mantenemos por compatibilidad: el monitor
+    // compartido arranca con la aplicación y un segundo monitor registraría cada conexión dos veces
     Ok("Monitoring started".to_string())
 }
 
 #[tauri::command]
-pub async fn get_device_history(limit: i64) -> Result<serde_json::Value, String> {
-    if let Some(ref db) = get_database() {
-        match db.get_activity_history(limit) {
-            Ok(history) => Ok(serde_json::json!({ "success": true, "history": history })),
-            Err(e) => Err(format!("Database error: {}", e)),
-        }
-    } else {
-        Err("Database not initialized".to_string())
+pub async fn get_device_history(state: tauri::State<'_, AppState>, limit: i64, case_reference: Option<String>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_activity_history(limit, None, case_reference.as_deref()) {
+        Ok(history) => Ok(serde_json::json!({ "success": true, "history": history })),
+        Err(e) => Err(format!("Database error: {}", e)),
     }
 }
 
 #[tauri::command]
-pub async fn get_registered_devices() -> Result<serde_json::Value, String> {
-    if let Some(ref db) = get_database() {
-        match db.get_devices() {
-            Ok(devices) => Ok(serde_json::json!({ "success": true, "devices": devices })),
-            Err(e) => Err(format!("Database error: {}", e)),
-        }
-    } else {
-        Err("Database not initialized".to_string())
+pub async fn get_registered_devices(state: tauri::State<'_, AppState>, sort: Option<DeviceSort>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_devices(sort.unwrap_or_default()) {
+        Ok(devices) => Ok(serde_json::json!({ "success": true, "devices": devices })),
+        Err(e) => Err(format!("Database error: {}", e)),
     }
 }
 
+/// Fabricante y producto en cada idioma que declara el dispositivo (LANGID de USB)
 #[tauri::command]
-pub async fn get_file_snapshots(activity_log_id: i64) -> Result<serde_json::Value, String> {
-     if let Some(ref db) = get_database() {
-        match db.get_file_snapshots(activity_log_id) {
-            Ok(snapshots) => {
-                let (files, folders) = db.get_scan_stats(activity_log_id).unwrap_or((0, 0));
-                Ok(serde_json::json!({
-                    "success": true, 
-                    "snapshots": snapshots,
-                    "stats": { "total_files": files, "total_folders": folders }
-                }))
-            }
-            Err(e) => Err(format!("Database error: {}", e)),
+pub async fn get_device_descriptors(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_device_descriptors(&device_id) {
+        Ok(descriptors) => Ok(serde_json::json!({ "success": true, "descriptors": descriptors })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Activar o desactivar el escaneo de archivos (`scan`) y la vigilancia de cambios (`watch`) de un dispositivo,
+/// p.ej. para una unidad cifrada personal. Las conexiones y desconexiones se siguen registrando.
+/// Desactivar la vigilancia detiene la de una conexión en curso; lo demás se aplica desde la próxima conexión.
+#[tauri::command]
+pub async fn set_device_monitoring(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+    scan: bool,
+    watch: bool,
+) -> Result<serde_json::Value, String> {
+    kiosk::ensure_unlocked()?;
+    let db = state.db.clone();
+    let updated = db
+        .set_device_monitoring(&device_id, !scan, !watch)
+        .map_err(|e| format!("Database error: {}", e))?;
+    if !updated {
+        return Err(format!("Device not found: {}", device_id));
+    }
+    if !watch {
+        state.monitor.active_watchers.lock().unwrap().remove(&device_id);
+        state.monitor.host_watchers.lock().unwrap().remove(&device_id);
+    }
+    audit::record(&db, DEVICE_MONITORING_ACTION, Some(&device_id), &serde_json::json!({ "scan": scan, "watch": watch }));
+    Ok(serde_json::json!({ "success": true, "device_id": device_id, "scan": scan, "watch": watch }))
+}
+
+#[tauri::command]
+pub async fn get_file_snapshots(state: tauri::State<'_, AppState>, activity_log_id: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_file_snapshots(activity_log_id) {
+        Ok(snapshots) => {
+            let (files, folders) = db.get_scan_stats(activity_log_id).unwrap_or((0, 0));
+            Ok(serde_json::json!({
+                "success": true, 
+                "snapshots": snapshots,
+                "stats": { "total_files": files, "total_folders": folders }
+            }))
         }
-    } else {
-        Err("Database not initialized".to_string())
+        Err(e) => Err(format!("Database error: {}", e)),
     }
 }
 
 #[tauri::command]
-pub async fn get_device_files(device_id: String) -> Result<serde_json::Value, String> {
-    if let Some(ref db) = get_database() {
-        match db.get_latest_device_snapshots(&device_id) {
-            Ok((activity_id, snapshots)) => {
-                 let (files, folders) = if activity_id > 0 { db.get_scan_stats(activity_id).unwrap_or((0, 0)) } else { (0,0) };
-                 Ok(serde_json::json!({
-                    "success": true,
-                    "device_id": device_id,
-                    "activity_id": activity_id,
-                    "snapshots": snapshots,
-                    "stats": { "total_files": files, "total_folders": folders }
-                }))
-            }
-            Err(e) => Err(format!("Database error: {}", e)),
+pub async fn get_device_files(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_latest_device_snapshots(&device_id) {
+        Ok((activity_id, snapshots)) => {
+             let (files, folders) = if activity_id > 0 { db.get_scan_stats(activity_id).unwrap_or((0, 0)) } else { (0,0) };
+             Ok(serde_json::json!({
+                "success": true,
+                "device_id": device_id,
+                "activity_id": activity_id,
+                "snapshots": snapshots,
+                "stats": { "total_files": files, "total_folders": folders }
+            }))
         }
-    } else {
-        Err("Database not initialized".to_string())
+        Err(e) => Err(format!("Database error: {}", e)),
     }
 }
 
 #[tauri::command]
-pub async fn get_device_all_scans(device_id: String) -> Result<serde_json::Value, String> {
-    if let Some(ref db) = get_database() {
-        match db.get_all_device_snapshots(&device_id) {
-            Ok(results) => {
-                let scans: Vec<serde_json::Value> = results.into_iter().map(|(id, time, snaps)| {
-                    serde_json::json!({
-                        "activity_id": id,
-                        "timestamp": time,
-                        "snapshot_count": snaps.len(),
-                        "file_count": snaps.iter().filter(|s| !s.is_folder).count(),
-                        "folder_count": snaps.iter().filter(|s| s.is_folder).count(),
-                    })
-                }).collect();
-                Ok(serde_json::json!({ "success": true, "device_id": device_id, "scans": scans }))
-            }
-            Err(e) => Err(format!("Database error: {}", e)),
+pub async fn get_device_all_scans(state: tauri::State<'_, AppState>, device_id: String) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_all_device_snapshots(&device_id) {
+        Ok(results) => {
+            let scans: Vec<serde_json::Value> = results.into_iter().map(|(id, time, snaps)| {
+                serde_json::json!({
+                    "activity_id": id,
+                    "timestamp": time,
+                    "snapshot_count": snaps.len(),
+                    "file_count": snaps.iter().filter(|s| !s.is_folder).count(),
+                    "folder_count": snaps.iter().filter(|s| s.is_folder).count(),
+                })
+            }).collect();
+            Ok(serde_json::json!({ "success": true, "device_id": device_id, "scans": scans }))
         }
-    } else {
-        Err("Database not initialized".to_string())
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+// Guardar el nuevo estado de un escaneo; un fallo solo se registra en el log
+fn update_scan_status(db: &Database, activity_id: i64, device_id: &str, status: ScanStatus, error: Option<&str>) {
+    if let Err(e) = db.set_scan_status(activity_id, device_id, status, error) {
+        println!("[DB] Error updating scan status: {}", e);
+    }
+}
+
+// Guardar el nombre, el fabricante y los descriptores leídos tarde de un dispositivo ya registrado
+fn backfill_descriptors(db: &Database, device: &UsbDevice) {
+    let device_id = device.serial_number.as_deref().unwrap_or(&device.id);
+    let result = db
+        .backfill_device_names(
+            device_id,
+            device.vendor_id,
+            device.product_id,
+            device.product_name.as_deref(),
+            device.manufacturer_name.as_deref(),
+            device.usb_serial.as_deref(),
+        )
+        .and_then(|_| db.save_device_descriptors(device_id, &device.descriptors));
+    match result {
+        Ok(_) => println!("[USB] Descriptors of {} read on a later poll: {:?}", device_id, device.product_name),
+        Err(e) => println!("[DB] Error saving late descriptors: {}", e),
+    }
+}
+
+// Escaneo anterior reutilizable: el de la última conexión, terminado, con desconexión hace menos de
+// `reuse_window_secs` y el mismo volumen (número de serie, capacidad y espacio usado)
+fn reusable_scan(db: &Database, device_id: &str, device: &UsbDevice, used_bytes: Option<i64>) -> Option<i64> {
+    let window = ScanSettings::load(db).reuse_window_secs();
+    // El escaneo anterior puede ser de antes de anonimizar las rutas
+    if window == 0 || privacy::redacts_paths(db, device_id) {
+        return None;
+    }
+    let previous = db.get_reusable_scan(device_id).ok().flatten()?;
+    let recent = previous.secs_since_disconnect.is_some_and(|secs| secs <= window as i64);
+    let same_volume = device.volume_serial.is_some()
+        && previous.volume_serial == device.volume_serial
+        && previous.total_capacity == device.total_space.map(|t| t as i64)
+        && previous.used_bytes.is_some()
+        && previous.used_bytes == used_bytes;
+    (recent && same_volume).then_some(previous.activity_log_id)
+}
+
+// Eventos del ciclo de vida de un escaneo: started, progress, complete y failed
+fn publish_scan_event(app_handle: Option<&AppHandle>, event: &str, payload: serde_json::Value) {
+    ipc::publish(event, &payload);
+    if let Some(app_handle) = app_handle {
+        events::emit(app_handle, event, payload);
+    }
+}
+
+/// Estado de los escaneos recientes (QUEUED, RUNNING, DONE, FAILED, CANCELED)
+#[tauri::command]
+pub async fn get_scan_states(state: tauri::State<'_, AppState>, device_id: Option<String>, limit: i64) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_scan_states(device_id.as_deref(), limit) {
+        Ok(scans) => Ok(serde_json::json!({ "success": true, "scans": scans })),
+        Err(e) => Err(format!("Database error: {}", e)),
+    }
+}
+
+/// Escaneos sin terminar: en curso, pendientes de reanudar o abandonados (snapshot incompleto)
+#[tauri::command]
+pub async fn get_interrupted_scans(state: tauri::State<'_, AppState>, device_id: Option<String>) -> Result<serde_json::Value, String> {
+    let db = &state.db;
+    match db.get_scan_checkpoints(device_id.as_deref()) {
+        Ok(scans) => Ok(serde_json::json!({ "success": true, "scans": scans })),
+        Err(e) => Err(format!("Database error: {}", e)),
     }
 }
 
@@ -399,3 +1024,34 @@ impl PartialEq for UsbDevice {
         self.serial_number == other.serial_number
     }
 }
+
+/// Simular la conexión o desconexión de un dispositivo (solo en modo desarrollo/demo).
+/// El monitor lo procesa enseguida como si fuera real.
+#[tauri::command]
+pub async fn simulate_device_event(
+    device_json: UsbDevice,
+    event_type: EventType,
+    state: tauri::State<'_, AppState>,
+) -> Result<serde_json::Value, String> {
+    let simulator = state
+        .monitor
+        .simulator
+        .as_ref()
+        .ok_or("Device simulation is disabled (set USB_MANAGER_DEMO=1)")?;
+    let serial = device_json
+        .serial_number
+        .clone()
+        .ok_or("Simulated device needs a serial_number")?;
+
+    let changed = match event_type {
+        EventType::Connect => {
+            simulator.connect(device_json);
+            true
+        }
+        EventType::Disconnect | EventType::UnsafeRemoval => simulator.disconnect(&serial),
+    };
+
+    println!("[USB] Simulated {} for {}", event_type.as_str(), serial);
+    state.monitor.wake();
+    Ok(serde_json::json!({ "success": changed, "device_id": serial, "event_type": event_type }))
+}
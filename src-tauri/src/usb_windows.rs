@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use windows_sys::Win32::Devices::DeviceAndDriverInstallation::{
+    CM_Get_Device_IDW, CM_Get_Parent, SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInterfaces,
+    SetupDiGetClassDevsW, SetupDiGetDeviceInterfaceDetailW, CR_SUCCESS, DIGCF_DEVICEINTERFACE, DIGCF_PRESENT,
+    SP_DEVICE_INTERFACE_DATA, SP_DEVICE_INTERFACE_DETAIL_DATA_W, SP_DEVINFO_DATA,
+};
+use windows_sys::Win32::Foundation::{CloseHandle, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, GetLogicalDrives, GetVolumeNameForVolumeMountPointW, FILE_DEVICE_DISK, FILE_SHARE_READ,
+    FILE_SHARE_WRITE, OPEN_EXISTING,
+};
+use windows_sys::Win32::System::Ioctl::{GUID_DEVINTERFACE_DISK, IOCTL_STORAGE_GET_DEVICE_NUMBER, STORAGE_DEVICE_NUMBER};
+use windows_sys::Win32::System::IO::DeviceIoControl;
+
+// Longitud máxima de un identificador de instancia (MAX_DEVICE_ID_LEN) más el NUL
+const DEVICE_ID_LEN: usize = 201;
+// Niveles que se suben desde el disco hasta el dispositivo USB (disco -> USBSTOR/UASPStor -> interfaz -> USB)
+const MAX_PARENT_DEPTH: usize = 8;
+
+/// Dispositivo USB del que cuelga una letra de unidad, según la cadena de dispositivos de Windows
+#[derive(Debug, Clone)]
+pub struct UsbDisk {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Número de serie USB; None si el dispositivo no tiene y Windows ha generado uno
+    pub serial: Option<String>,
+    /// Instancia del dispositivo, p.ej. `USB\VID_0781&PID_5567\4C530001230101116175`
+    pub instance_id: String,
+    /// `\\?\Volume{GUID}\`, que no cambia aunque cambie la letra
+    pub volume_guid: Option<String>,
+}
+
+fn wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+fn from_wide(buffer: &[u16]) -> String {
+    let length = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..length])
+}
+
+// Número del disco (el N de \\.\PhysicalDriveN) de una letra de unidad o de una interfaz de disco
+fn device_number(path: &[u16]) -> Option<u32> {
+    // SAFETY: `path` termina en NUL; la consulta no necesita permisos de lectura y el handle se cierra justo después
+    unsafe {
+        let handle = CreateFileW(
+            path.as_ptr(),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            0,
+            std::ptr::null_mut(),
+        );
+        if handle == INVALID_HANDLE_VALUE {
+            return None;
+        }
+
+        let mut number: STORAGE_DEVICE_NUMBER = std::mem::zeroed();
+        let mut returned = 0u32;
+        let ok = DeviceIoControl(
+            handle,
+            IOCTL_STORAGE_GET_DEVICE_NUMBER,
+            std::ptr::null(),
+            0,
+            &mut number as *mut _ as *mut _,
+            std::mem::size_of::<STORAGE_DEVICE_NUMBER>() as u32,
+            &mut returned,
+            std::ptr::null_mut(),
+        );
+        CloseHandle(handle);
+        (ok != 0 && number.DeviceType == FILE_DEVICE_DISK).then_some(number.DeviceNumber)
+    }
+}
+
+// Número de disco -> nodo del dispositivo, recorriendo las interfaces de disco presentes con SetupAPI
+fn disk_nodes() -> HashMap<u32, u32> {
+    let mut nodes = HashMap::new();
+    // SAFETY: cada estructura lleva su cbSize; el búfer del detalle tiene el tamaño que pide SetupAPI
+    // (en u32 para respetar la alineación de la cabecera) y la lista se libera al terminar
+    unsafe {
+        let set = SetupDiGetClassDevsW(
+            &GUID_DEVINTERFACE_DISK,
+            std::ptr::null(),
+            std::ptr::null_mut(),
+            DIGCF_PRESENT | DIGCF_DEVICEINTERFACE,
+        );
+        if set == INVALID_HANDLE_VALUE as isize {
+            return nodes;
+        }
+
+        let mut index = 0;
+        loop {
+            let mut interface: SP_DEVICE_INTERFACE_DATA = std::mem::zeroed();
+            interface.cbSize = std::mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32;
+            if SetupDiEnumDeviceInterfaces(set, std::ptr::null(), &GUID_DEVINTERFACE_DISK, index, &mut interface) == 0 {
+                break;
+            }
+            index += 1;
+
+            let mut required = 0u32;
+            SetupDiGetDeviceInterfaceDetailW(set, &interface, std::ptr::null_mut(), 0, &mut required, std::ptr::null_mut());
+            if (required as usize) < std::mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() {
+                continue;
+            }
+            let mut buffer = vec![0u32; (required as usize).div_ceil(4)];
+            let detail = buffer.as_mut_ptr() as *mut SP_DEVICE_INTERFACE_DETAIL_DATA_W;
+            (*detail).cbSize = std::mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32;
+            let mut info: SP_DEVINFO_DATA = std::mem::zeroed();
+            info.cbSize = std::mem::size_of::<SP_DEVINFO_DATA>() as u32;
+            if SetupDiGetDeviceInterfaceDetailW(set, &interface, detail, required, std::ptr::null_mut(), &mut info) == 0 {
+                continue;
+            }
+
+            let offset = std::mem::offset_of!(SP_DEVICE_INTERFACE_DETAIL_DATA_W, DevicePath);
+            let path = std::slice::from_raw_parts(
+                (buffer.as_ptr() as *const u8).add(offset) as *const u16,
+                (required as usize - offset) / 2,
+            );
+            let length = path.iter().position(|&c| c == 0).unwrap_or(path.len());
+            let path: Vec<u16> = path[..length].iter().copied().chain(std::iter::once(0)).collect();
+            if let Some(number) = device_number(&path) {
+                nodes.insert(number, info.DevInst);
+            }
+        }
+        SetupDiDestroyDeviceInfoList(set);
+    }
+    nodes
+}
+
+fn instance_id(node: u32) -> Option<String> {
+    let mut buffer = [0u16; DEVICE_ID_LEN];
+    // SAFETY: `buffer` tiene la longitud indicada
+    let result = unsafe { CM_Get_Device_IDW(node, buffer.as_mut_ptr(), buffer.len() as u32, 0) };
+    (result == CR_SUCCESS).then(|| from_wide(&buffer))
+}
+
+// Subir desde el disco hasta el dispositivo USB. En los compuestos el padre del disco es una interfaz
+// (`USB\VID_..&PID_..&MI_00\...`) y el dispositivo con su número de serie está un nivel más arriba.
+fn usb_ancestor(mut node: u32) -> Option<String> {
+    for _ in 0..MAX_PARENT_DEPTH {
+        let mut parent = 0u32;
+        // SAFETY: `parent` es un u32 válido para el resultado
+        if unsafe { CM_Get_Parent(&mut parent, node, 0) } != CR_SUCCESS {
+            return None;
+        }
+        node = parent;
+        let id = instance_id(node)?;
+        if id.to_uppercase().starts_with("USB\\") && !id.to_uppercase().contains("&MI_") {
+            return Some(id);
+        }
+    }
+    None
+}
+
+// `USB\VID_0781&PID_5567\4C530001230101116175` -> (0x0781, 0x5567, serie). Si el dispositivo no tiene número de
+// serie Windows genera uno con '&' (`6&2C4F1A9&0&1`), que depende del puerto y no sirve para identificarlo.
+fn parse_instance_id(id: &str) -> Option<(u16, u16, Option<String>)> {
+    let mut parts = id.split('\\');
+    parts.next()?;
+    let hardware = parts.next()?.to_uppercase();
+    let serial = parts.next().filter(|s| !s.is_empty() && !s.contains('&')).map(str::to_string);
+
+    let field = |prefix: &str| {
+        hardware
+            .split('&')
+            .find_map(|part| part.strip_prefix(prefix))
+            .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+    };
+    Some((field("VID_")?, field("PID_")?, serial))
+}
+
+fn volume_guid(letter: char) -> Option<String> {
+    let root = wide(&format!("{}:\\", letter));
+    let mut buffer = [0u16; 64];
+    // SAFETY: `root` termina en NUL y `buffer` tiene la longitud indicada
+    let ok = unsafe { GetVolumeNameForVolumeMountPointW(root.as_ptr(), buffer.as_mut_ptr(), buffer.len() as u32) };
+    (ok != 0).then(|| from_wide(&buffer))
+}
+
+/// Letra de unidad -> dispositivo USB que la contiene. Las unidades que no cuelgan de un dispositivo USB
+/// (discos internos, lectores de tarjetas integrados) no aparecen.
+pub fn usb_disks() -> HashMap<char, UsbDisk> {
+    let mut disks = HashMap::new();
+    let nodes = disk_nodes();
+    if nodes.is_empty() {
+        return disks;
+    }
+
+    // SAFETY: sin parámetros
+    let drives = unsafe { GetLogicalDrives() };
+    for (bit, letter) in ('A'..='Z').enumerate() {
+        if drives & (1 << bit) == 0 {
+            continue;
+        }
+        let Some(number) = device_number(&wide(&format!("\\\\.\\{}:", letter))) else { continue };
+        let Some(instance_id) = nodes.get(&number).and_then(|&node| usb_ancestor(node)) else { continue };
+        let Some((vendor_id, product_id, serial)) = parse_instance_id(&instance_id) else { continue };
+        disks.insert(
+            letter,
+            UsbDisk { vendor_id, product_id, serial, instance_id, volume_guid: volume_guid(letter) },
+        );
+    }
+    disks
+}
@@ -0,0 +1,240 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Posición del diario de cambios USN de un volumen NTFS al empezar un escaneo
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JournalPosition {
+    pub journal_id: i64,
+    pub next_usn: i64,
+}
+
+/// Posición actual del diario del volumen montado en `mount_point`.
+/// `None` si no es NTFS, no tiene diario o no hay permisos para abrir el volumen.
+#[cfg(windows)]
+pub fn query(mount_point: &str) -> Option<JournalPosition> {
+    let volume = windows::Volume::open(mount_point)?;
+    let data = volume.journal_data()?;
+    Some(JournalPosition {
+        journal_id: data.UsnJournalID as i64,
+        next_usn: data.NextUsn,
+    })
+}
+
+#[cfg(not(windows))]
+pub fn query(_mount_point: &str) -> Option<JournalPosition> {
+    None
+}
+
+/// Carpetas cuyo contenido ha cambiado entre `since` y `until` (rutas absolutas bajo `mount_point`).
+/// `None` si el diario se ha recreado o ya no conserva esos registros: hay que escanear entero.
+#[cfg(windows)]
+pub fn changed_dirs(mount_point: &str, since: JournalPosition, until: JournalPosition) -> Option<HashSet<PathBuf>> {
+    if since.journal_id != until.journal_id {
+        return None;
+    }
+
+    let volume = windows::Volume::open(mount_point)?;
+    let data = volume.journal_data()?;
+    if data.UsnJournalID as i64 != since.journal_id || data.FirstUsn > since.next_usn {
+        return None;
+    }
+
+    // Cada cambio (crear, borrar, renombrar, escribir) se apunta en la carpeta que lo contiene
+    let parents = volume.changed_parents(since, until.next_usn)?;
+    let root = std::path::Path::new(mount_point);
+    Some(
+        parents
+            .into_iter()
+            .filter_map(|reference| volume.path_of(reference))
+            .map(|relative| root.join(relative.trim_start_matches('\\')))
+            .collect(),
+    )
+}
+
+#[cfg(not(windows))]
+pub fn changed_dirs(_mount_point: &str, _since: JournalPosition, _until: JournalPosition) -> Option<HashSet<PathBuf>> {
+    None
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::JournalPosition;
+    use std::collections::HashSet;
+    use windows_sys::Win32::Foundation::{CloseHandle, GENERIC_READ, HANDLE, INVALID_HANDLE_VALUE};
+    use windows_sys::Win32::Storage::FileSystem::{
+        CreateFileW, GetFinalPathNameByHandleW, OpenFileById, FileIdType, FILE_FLAG_BACKUP_SEMANTICS,
+        FILE_ID_DESCRIPTOR, FILE_NAME_NORMALIZED, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE,
+        OPEN_EXISTING, VOLUME_NAME_NONE,
+    };
+    use windows_sys::Win32::System::Ioctl::{
+        FSCTL_QUERY_USN_JOURNAL, FSCTL_READ_USN_JOURNAL, READ_USN_JOURNAL_DATA_V0, USN_JOURNAL_DATA_V0,
+    };
+    use windows_sys::Win32::System::IO::DeviceIoControl;
+
+    const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+    pub struct Volume(HANDLE);
+
+    impl Drop for Volume {
+        fn drop(&mut self) {
+            // SAFETY: el handle se abrió en `open` y solo se cierra aquí
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    impl Volume {
+        // "E:\" -> \\.\E:
+        pub fn open(mount_point: &str) -> Option<Self> {
+            let letter = mount_point.chars().next().filter(|c| c.is_ascii_alphabetic())?;
+            if mount_point.chars().nth(1) != Some(':') {
+                return None;
+            }
+
+            let path: Vec<u16> = format!("\\\\.\\{}:", letter).encode_utf16().chain(std::iter::once(0)).collect();
+            // SAFETY: `path` termina en NUL; sin atributos de seguridad ni plantilla
+            let handle = unsafe {
+                CreateFileW(
+                    path.as_ptr(),
+                    GENERIC_READ,
+                    FILE_SHARE_READ | FILE_SHARE_WRITE,
+                    std::ptr::null(),
+                    OPEN_EXISTING,
+                    0,
+                    std::ptr::null_mut(),
+                )
+            };
+            (handle != INVALID_HANDLE_VALUE).then_some(Volume(handle))
+        }
+
+        pub fn journal_data(&self) -> Option<USN_JOURNAL_DATA_V0> {
+            // SAFETY: la estructura es POD y se pasa con su tamaño real
+            let mut data: USN_JOURNAL_DATA_V0 = unsafe { std::mem::zeroed() };
+            let mut returned = 0u32;
+            let ok = unsafe {
+                DeviceIoControl(
+                    self.0,
+                    FSCTL_QUERY_USN_JOURNAL,
+                    std::ptr::null(),
+                    0,
+                    &mut data as *mut _ as *mut _,
+                    std::mem::size_of::<USN_JOURNAL_DATA_V0>() as u32,
+                    &mut returned,
+                    std::ptr::null_mut(),
+                )
+            };
+            (ok != 0).then_some(data)
+        }
+
+        // Referencias de las carpetas padre de todos los registros en [since, until)
+        pub fn changed_parents(&self, since: JournalPosition, until: i64) -> Option<HashSet<u64>> {
+            let mut parents = HashSet::new();
+            let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+            let mut request = READ_USN_JOURNAL_DATA_V0 {
+                StartUsn: since.next_usn,
+                ReasonMask: u32::MAX,
+                ReturnOnlyOnClose: 0,
+                Timeout: 0,
+                BytesToWaitFor: 0,
+                UsnJournalID: since.journal_id as u64,
+            };
+
+            while request.StartUsn < until {
+                let mut returned = 0u32;
+                // SAFETY: petición y búfer con sus tamaños reales
+                let ok = unsafe {
+                    DeviceIoControl(
+                        self.0,
+                        FSCTL_READ_USN_JOURNAL,
+                        &request as *const _ as *const _,
+                        std::mem::size_of::<READ_USN_JOURNAL_DATA_V0>() as u32,
+                        buffer.as_mut_ptr() as *mut _,
+                        buffer.len() as u32,
+                        &mut returned,
+                        std::ptr::null_mut(),
+                    )
+                };
+                if ok == 0 {
+                    return None;
+                }
+
+                // El búfer empieza con el USN para la siguiente lectura, seguido de registros USN_RECORD_V2
+                let returned = returned as usize;
+                if returned <= 8 {
+                    break;
+                }
+                let next_usn = i64::from_le_bytes(buffer[0..8].try_into().ok()?);
+
+                let mut offset = 8;
+                while offset + 32 <= returned {
+                    let record = &buffer[offset..returned];
+                    let length = u32::from_le_bytes(record[0..4].try_into().ok()?) as usize;
+                    if length == 0 || length > record.len() {
+                        break;
+                    }
+                    let major_version = u16::from_le_bytes(record[4..6].try_into().ok()?);
+                    let usn = i64::from_le_bytes(record[24..32].try_into().ok()?);
+                    if usn >= until {
+                        return Some(parents);
+                    }
+                    if major_version == 2 {
+                        parents.insert(u64::from_le_bytes(record[16..24].try_into().ok()?));
+                    }
+                    offset += length;
+                }
+
+                if next_usn <= request.StartUsn {
+                    break;
+                }
+                request.StartUsn = next_usn;
+            }
+
+            Some(parents)
+        }
+
+        // Ruta dentro del volumen ("\carpeta\sub") de una referencia de archivo; `None` si ya no existe
+        pub fn path_of(&self, reference: u64) -> Option<String> {
+            // SAFETY: descriptor inicializado con su tamaño y el tipo de identificador
+            let mut descriptor: FILE_ID_DESCRIPTOR = unsafe { std::mem::zeroed() };
+            descriptor.dwSize = std::mem::size_of::<FILE_ID_DESCRIPTOR>() as u32;
+            descriptor.Type = FileIdType;
+            descriptor.Anonymous.FileId = reference as i64;
+
+            let handle = unsafe {
+                OpenFileById(
+                    self.0,
+                    &descriptor,
+                    0,
+                    FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                    std::ptr::null(),
+                    FILE_FLAG_BACKUP_SEMANTICS,
+                )
+            };
+            if handle == INVALID_HANDLE_VALUE {
+                return None;
+            }
+            let file = Volume(handle);
+
+            let mut buffer = vec![0u16; 1024];
+            loop {
+                // SAFETY: el búfer tiene la longitud indicada
+                let length = unsafe {
+                    GetFinalPathNameByHandleW(
+                        file.0,
+                        buffer.as_mut_ptr(),
+                        buffer.len() as u32,
+                        FILE_NAME_NORMALIZED | VOLUME_NAME_NONE,
+                    )
+                } as usize;
+                if length == 0 {
+                    return None;
+                }
+                // Si no cabe devuelve el tamaño necesario
+                if length >= buffer.len() {
+                    buffer.resize(length + 1, 0);
+                    continue;
+                }
+                return Some(String::from_utf16_lossy(&buffer[..length]));
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+use crate::compare::{relative_path, scan_root};
+use crate::db::{FileSnapshot, HashMode};
+use crate::hashing::sha256_file;
+use crate::state::AppState;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum VerifyStatus {
+    Match,
+    Mismatch,
+    Missing,
+    Error,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct VerifyResult {
+    pub relative_path: String,
+    pub status: VerifyStatus,
+    pub source_sha256: Option<String>,
+    pub target_sha256: Option<String>,
+    pub error: Option<String>,
+}
+
+fn verify_file(source: &Path, target: &Path, relative_path: String) -> VerifyResult {
+    let source_hash = match sha256_file(source) {
+        Ok(hash) => hash,
+        Err(e) => {
+            return VerifyResult {
+                relative_path,
+                status: VerifyStatus::Error,
+                source_sha256: None,
+                target_sha256: None,
+                error: Some(format!("Cannot read source: {}", e)),
+            }
+        }
+    };
+
+    if !target.exists() {
+        return VerifyResult {
+            relative_path,
+            status: VerifyStatus::Missing,
+            source_sha256: Some(source_hash),
+            target_sha256: None,
+            error: None,
+        };
+    }
+
+    match sha256_file(target) {
+        Ok(target_hash) => VerifyResult {
+            relative_path,
+            status: if target_hash == source_hash { VerifyStatus::Match } else { VerifyStatus::Mismatch },
+            source_sha256: Some(source_hash),
+            target_sha256: Some(target_hash),
+            error: None,
+        },
+        Err(e) => VerifyResult {
+            relative_path,
+            status: VerifyStatus::Error,
+            source_sha256: Some(source_hash),
+            target_sha256: None,
+            error: Some(format!("Cannot read copy: {}", e)),
+        },
+    }
+}
+
+/// Comparar un archivo o carpeta del equipo con su copia en el USB
+pub fn verify_copy_paths(source: &Path, target: &Path) -> Vec<VerifyResult> {
+    if source.is_file() {
+        let name = source.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        return vec![verify_file(source, target, name)];
+    }
+
+    WalkDir::new(source)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            let relative = entry.path().strip_prefix(source).unwrap_or(entry.path());
+            verify_file(entry.path(), &target.join(relative), relative.to_string_lossy().replace('\\', "/"))
+        })
+        .collect()
+}
+
+/// Verificar una copia hacia el USB. `target_path` es relativo a la raíz del dispositivo;
+/// por defecto se busca el mismo nombre en la raíz.
+#[tauri::command]
+pub async fn verify_copy(
+    state: tauri::State<'_, AppState>,
+    source_path: String,
+    device_id: String,
+    target_path: Option<String>,
+) -> Result<serde_json::Value, String> {
+    let mount_point = state.monitor
+        .mount_point_for(&device_id)
+        .ok_or_else(|| format!("Device is not connected: {}", device_id))?;
+
+    let source = PathBuf::from(&source_path);
+    if !source.exists() {
+        return Err(format!("Source does not exist: {}", source_path));
+    }
+
+    let relative_target = match target_path {
+        Some(path) => PathBuf::from(path.trim_start_matches(['/', '\\'])),
+        None => PathBuf::from(source.file_name().ok_or("Source has no file name")?),
+    };
+    let target = Path::new(&mount_point).join(relative_target);
+
+    println!("[Verify] Verifying {:?} against {:?}", source, target);
+    let results = tauri::async_runtime::spawn_blocking(move || verify_copy_paths(&source, &target))
+        .await
+        .map_err(|e| format!("Verification task failed: {}", e))?;
+
+    let count = |status: VerifyStatus| results.iter().filter(|r| r.status == status).count();
+    let (matched, mismatched, missing, errors) = (
+        count(VerifyStatus::Match),
+        count(VerifyStatus::Mismatch),
+        count(VerifyStatus::Missing),
+        count(VerifyStatus::Error),
+    );
+
+    Ok(serde_json::json!({
+        "success": true,
+        "verified": mismatched == 0 && missing == 0 && errors == 0 && matched > 0,
+        "stats": { "matched": matched, "mismatched": mismatched, "missing": missing, "errors": errors },
+        "results": results,
+    }))
+}
+
+/// Archivo que ya no coincide con el manifiesto
+#[derive(Debug, Serialize, Clone)]
+pub struct AlteredFile {
+    pub relative_path: String,
+    pub expected_size: i64,
+    pub actual_size: i64,
+    pub expected_sha256: Option<String>,
+    pub actual_sha256: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DeviceVerification {
+    pub added: Vec<String>,
+    pub missing: Vec<String>,
+    pub altered: Vec<AlteredFile>,
+    /// Archivos sin hash completo en el manifiesto: solo se ha comparado el tamaño
+    pub size_only: Vec<String>,
+    pub checked: usize,
+}
+
+/// Comparar el contenido montado con los snapshots de un escaneo anterior (el manifiesto),
+/// volviendo a calcular el hash de cada archivo que en el manifiesto lo tiene completo
+pub fn verify_against_manifest(mount_point: &Path, manifest: &[FileSnapshot], manifest_root: &str) -> DeviceVerification {
+    let mut expected: BTreeMap<String, &FileSnapshot> = manifest
+        .iter()
+        .map(|s| (relative_path(&s.file_path, manifest_root), s))
+        .filter(|(rel, _)| !rel.is_empty())
+        .collect();
+    let mut result = DeviceVerification::default();
+
+    for entry in WalkDir::new(mount_point).follow_links(false).into_iter().filter_map(|e| e.ok()) {
+        let rel = relative_path(&entry.path().to_string_lossy(), &mount_point.to_string_lossy());
+        if rel.is_empty() {
+            continue;
+        }
+        let is_folder = entry.file_type().is_dir();
+        let Some(snapshot) = expected.remove(&rel) else {
+            result.added.push(rel);
+            continue;
+        };
+        if is_folder || snapshot.is_folder {
+            if is_folder != snapshot.is_folder {
+                result.added.push(rel.clone());
+                result.missing.push(rel);
+            }
+            continue;
+        }
+
+        result.checked += 1;
+        let actual_size = entry.metadata().map(|m| m.len() as i64).unwrap_or(-1);
+        let full_hash = snapshot.sha256.as_ref().filter(|_| snapshot.hash_mode.unwrap_or(HashMode::Full) == HashMode::Full);
+        let actual_sha256 = match full_hash {
+            Some(_) if actual_size == snapshot.file_size => sha256_file(entry.path()).ok(),
+            _ => None,
+        };
+        let altered = actual_size != snapshot.file_size
+            || full_hash.is_some_and(|expected| actual_sha256.as_ref() != Some(expected));
+        if altered {
+            result.altered.push(AlteredFile {
+                relative_path: rel,
+                expected_size: snapshot.file_size,
+                actual_size,
+                expected_sha256: snapshot.sha256.clone(),
+                actual_sha256,
+            });
+        } else if full_hash.is_none() {
+            result.size_only.push(rel);
+        }
+    }
+
+    result.missing.extend(expected.into_keys());
+    result
+}
+
+/// Comprobar que el contenido de un dispositivo conectado sigue igual que en su manifiesto: el escaneo
+/// `activity_log_id` o, por defecto, el último con snapshots anterior a la conexión actual
+#[tauri::command]
+pub async fn verify_device(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+    activity_log_id: Option<i64>,
+) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let mount_point = state.monitor
+        .mount_point_for(&device_id)
+        .ok_or_else(|| format!("Device is not connected: {}", device_id))?;
+    let current_session = state.monitor.session_map.lock().unwrap().get(&device_id).copied();
+
+    let scans = db
+        .get_recent_scans(&device_id, 2)
+        .map_err(|e| format!("Database error: {}", e))?;
+    let manifest = match activity_log_id {
+        Some(id) => db
+            .get_scan_info(id)
+            .map_err(|e| format!("Database error: {}", e))?
+            .filter(|scan| scan.device_id == device_id),
+        None => scans.into_iter().find(|scan| Some(scan.activity_log_id) != current_session),
+    }
+    .ok_or_else(|| format!("No stored manifest for device {}", device_id))?;
+    let snapshots = db
+        .get_file_snapshots(manifest.activity_log_id)
+        .map_err(|e| format!("Database error: {}", e))?;
+    let root = scan_root(&manifest, &snapshots);
+
+    println!("[Verify] Verifying {} at {} against scan {}", device_id, mount_point, manifest.activity_log_id);
+    let result = tauri::async_runtime::spawn_blocking(move || verify_against_manifest(Path::new(&mount_point), &snapshots, &root))
+        .await
+        .map_err(|e| format!("Verification task failed: {}", e))?;
+
+    Ok(serde_json::json!({
+        "success": true,
+        "intact": result.added.is_empty() && result.missing.is_empty() && result.altered.is_empty(),
+        "manifest": manifest,
+        "stats": {
+            "checked": result.checked,
+            "added": result.added.len(),
+            "missing": result.missing.len(),
+            "altered": result.altered.len(),
+            "size_only": result.size_only.len(),
+        },
+        "result": result,
+    }))
+}
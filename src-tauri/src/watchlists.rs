@@ -0,0 +1,156 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::db::{AlertSeverity, Database};
+use crate::i18n::Message;
+use crate::state::AppState;
+
+pub const WATCHLIST_SETTINGS_KEY: &str = "watchlists";
+const WATCHLIST_ALERT: &str = "WATCHLIST_MATCH";
+// Nombres de ejemplo incluidos en el mensaje de la alerta de un escaneo
+const MATCH_SAMPLE: usize = 3;
+
+/// Lista de palabras o expresiones regulares que se buscan en los nombres de archivo (sin distinguir mayúsculas)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Watchlist {
+    pub name: String,
+    pub enabled: bool,
+    /// Palabras ("salary", "confidencial") o, con `regex`, expresiones regulares
+    pub patterns: Vec<String>,
+    pub regex: bool,
+    pub severity: AlertSeverity,
+}
+
+impl Default for Watchlist {
+    fn default() -> Self {
+        Self { name: String::new(), enabled: true, patterns: Vec::new(), regex: false, severity: AlertSeverity::Medium }
+    }
+}
+
+/// Listas de vigilancia, guardadas en settings bajo la clave `watchlists`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchlistSettings {
+    pub watchlists: Vec<Watchlist>,
+}
+
+impl WatchlistSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(WATCHLIST_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+}
+
+struct CompiledWatchlist {
+    name: String,
+    severity: AlertSeverity,
+    patterns: Vec<Regex>,
+}
+
+impl CompiledWatchlist {
+    fn matches(&self, file_name: &str) -> bool {
+        self.patterns.iter().any(|p| p.is_match(file_name))
+    }
+}
+
+// Las palabras se buscan tal cual dentro del nombre; las expresiones inválidas se devuelven aparte
+fn compile(settings: &WatchlistSettings) -> (Vec<CompiledWatchlist>, Vec<String>) {
+    let mut compiled = Vec::new();
+    let mut errors = Vec::new();
+    for watchlist in settings.watchlists.iter().filter(|w| w.enabled) {
+        let mut patterns = Vec::new();
+        for pattern in watchlist.patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let source = if watchlist.regex { pattern.to_string() } else { regex::escape(pattern) };
+            match RegexBuilder::new(&source).case_insensitive(true).build() {
+                Ok(regex) => patterns.push(regex),
+                Err(e) => errors.push(format!("{}: invalid pattern '{}': {}", watchlist.name, pattern, e)),
+            }
+        }
+        if !patterns.is_empty() {
+            compiled.push(CompiledWatchlist { name: watchlist.name.clone(), severity: watchlist.severity, patterns });
+        }
+    }
+    (compiled, errors)
+}
+
+fn load_compiled(db: &Database) -> Vec<CompiledWatchlist> {
+    let (compiled, errors) = compile(&WatchlistSettings::load(db));
+    for error in errors {
+        println!("[Watchlists] Skipping {}", error);
+    }
+    compiled
+}
+
+/// Alertar una vez por lista con los archivos de un escaneo recién terminado cuyo nombre coincide
+pub fn check_scan(db: &Arc<Database>, app_handle: Option<&AppHandle>, device_id: &str, activity_id: i64) {
+    let watchlists = load_compiled(db);
+    if watchlists.is_empty() {
+        return;
+    }
+    let snapshots = match db.get_file_snapshots(activity_id) {
+        Ok(snapshots) => snapshots,
+        Err(e) => {
+            println!("[DB] Error loading snapshots for watchlists: {}", e);
+            return;
+        }
+    };
+
+    for watchlist in &watchlists {
+        let matches: Vec<&str> = snapshots
+            .iter()
+            .filter(|s| !s.is_folder && watchlist.matches(&s.file_name))
+            .map(|s| s.file_path.as_str())
+            .collect();
+        if matches.is_empty() {
+            continue;
+        }
+
+        raise_alert(
+            db,
+            app_handle,
+            Some(device_id),
+            Some(activity_id),
+            WATCHLIST_ALERT,
+            watchlist.severity,
+            Message::new(
+                "alert.watchlist_scan",
+                serde_json::json!({
+                    "watchlist": watchlist.name,
+                    "device": device_id,
+                    "count": matches.len(),
+                    "paths": matches[..matches.len().min(MATCH_SAMPLE)].join(", "),
+                }),
+            ),
+        );
+    }
+}
+
+/// Alertar si un archivo recién creado en la unidad coincide con alguna lista. `file_name` es el nombre real;
+/// `file_path`, la ruta tal como se guarda (anonimizada si el dispositivo lo pide), que es la que va en la alerta.
+pub fn check_file(db: &Arc<Database>, app_handle: Option<&AppHandle>, device_id: &str, activity_id: i64, file_path: &str, file_name: &str) {
+    for watchlist in load_compiled(db).iter().filter(|w| w.matches(file_name)) {
+        raise_alert(
+            db,
+            app_handle,
+            Some(device_id),
+            Some(activity_id),
+            WATCHLIST_ALERT,
+            watchlist.severity,
+            Message::new(
+                "alert.watchlist_file",
+                serde_json::json!({ "watchlist": watchlist.name, "device": device_id, "path": file_path }),
+            ),
+        );
+    }
+}
+
+/// Probar las listas configuradas con un nombre de archivo: qué listas coinciden y qué patrones no son válidos
+#[tauri::command]
+pub async fn test_watchlists(state: tauri::State<'_, AppState>, file_name: String) -> Result<serde_json::Value, String> {
+    let db = state.db.clone();
+    let (compiled, errors) = compile(&WatchlistSettings::load(&db));
+    let matched: Vec<&str> = compiled.iter().filter(|w| w.matches(&file_name)).map(|w| w.name.as_str()).collect();
+    Ok(serde_json::json!({ "success": true, "file_name": file_name, "matched": matched, "errors": errors }))
+}
@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
+use crate::alerts::raise_alert;
+use crate::audit;
+use crate::db::{AlertSeverity, Database};
+use crate::i18n::Message;
+
+pub const WRITE_BLOCK_SETTINGS_KEY: &str = "write_block";
+const WRITE_BLOCK_ALERT: &str = "FILE_WRITE_BLOCKED";
+const WRITE_BLOCK_AUDIT_ACTION: &str = "WRITE_BLOCKED";
+const QUARANTINE_DIR: &str = "quarantine";
+// El archivo se da por escrito cuando su tamaño no cambia entre dos comprobaciones
+const STABLE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const STABLE_CHECKS: u32 = 6;
+
+/// Tipo de archivo reconocido por su contenido, independientemente de la extensión
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectedType {
+    Executable,
+    Archive,
+    Pdf,
+}
+
+impl DetectedType {
+    // Firmas de los primeros bytes del archivo
+    fn detect(header: &[u8]) -> Option<Self> {
+        const SIGNATURES: &[(&[u8], DetectedType)] = &[
+            (b"MZ", DetectedType::Executable),
+            (b"\x7fELF", DetectedType::Executable),
+            (b"\xcf\xfa\xed\xfe", DetectedType::Executable),
+            (b"\xfe\xed\xfa\xcf", DetectedType::Executable),
+            (b"PK\x03\x04", DetectedType::Archive),
+            (b"Rar!\x1a\x07", DetectedType::Archive),
+            (b"7z\xbc\xaf\x27\x1c", DetectedType::Archive),
+            (b"\x1f\x8b", DetectedType::Archive),
+            (b"%PDF", DetectedType::Pdf),
+        ];
+
+        SIGNATURES
+            .iter()
+            .find(|(signature, _)| header.starts_with(signature))
+            .map(|(_, kind)| *kind)
+    }
+}
+
+/// Bloqueo de escritura: los archivos de estos tipos escritos en una unidad vigilada
+/// se copian a la cuarentena y se eliminan de la unidad
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WriteBlockSettings {
+    pub enabled: bool,
+    /// Extensiones sin punto, p.ej. ["exe", "docx"]
+    pub extensions: Vec<String>,
+    /// Tipos detectados por contenido, para no depender de la extensión
+    pub detected_types: Vec<DetectedType>,
+}
+
+impl WriteBlockSettings {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(WRITE_BLOCK_SETTINGS_KEY).ok().flatten().unwrap_or_default()
+    }
+
+    // Motivo del bloqueo, si el archivo coincide con la configuración
+    fn matches(&self, path: &Path) -> Option<String> {
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            let extension = extension.to_lowercase();
+            if self.extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&extension)) {
+                return Some(format!(".{}", extension));
+            }
+        }
+
+        if self.detected_types.is_empty() {
+            return None;
+        }
+        let mut header = [0u8; 8];
+        let read = std::fs::File::open(path).and_then(|mut f| f.read(&mut header)).ok()?;
+        DetectedType::detect(&header[..read])
+            .filter(|kind| self.detected_types.contains(kind))
+            .map(|kind| serde_json::to_value(kind).ok().and_then(|v| v.as_str().map(String::from)).unwrap_or_default())
+    }
+}
+
+// Carpeta de cuarentena de una sesión, junto a la base de datos activa
+fn quarantine_dir(db: &Database, activity_id: i64) -> PathBuf {
+    let data_dir = db.path().parent().map(Path::to_path_buf).unwrap_or_default();
+    data_dir.join(QUARANTINE_DIR).join(activity_id.to_string())
+}
+
+/// Anonimizar el nombre de los archivos en cuarentena de una sesión (`redact_history`). Devuelve cuántos se han renombrado.
+pub fn redact_quarantine(db: &Database, activity_id: i64, redact: &dyn Fn(&str) -> String) -> usize {
+    let Ok(entries) = std::fs::read_dir(quarantine_dir(db, activity_id)) else { return 0 };
+    let mut renamed = 0;
+    for (index, entry) in entries.flatten().enumerate() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // "<fecha>_<nombre>": la fecha se conserva y el índice evita que dos nombres anonimizados choquen
+        let (stamp, file_name) = name.split_once('_').unwrap_or(("", &name));
+        let target = entry.path().with_file_name(format!("{}_{}_{}", stamp, index, redact(file_name)));
+        match std::fs::rename(entry.path(), &target) {
+            Ok(()) => renamed += 1,
+            Err(e) => println!("[WriteBlock] Failed to rename {}: {}", entry.path().display(), e),
+        }
+    }
+    renamed
+}
+
+// Esperar a que el archivo deje de crecer; false si se sigue escribiendo (lo volverá a intentar el siguiente evento)
+fn wait_until_stable(path: &Path) -> bool {
+    let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).ok();
+    let mut last = size(path);
+    for _ in 0..STABLE_CHECKS {
+        std::thread::sleep(STABLE_CHECK_INTERVAL);
+        let current = size(path);
+        if current.is_some() && current == last {
+            return true;
+        }
+        last = current;
+    }
+    false
+}
+
+// Copiar el archivo a la cuarentena (con el nombre de `stored_path`) y borrarlo de la unidad; devuelve la ruta de la copia
+fn quarantine(db: &Database, path: &Path, stored_path: &str, activity_id: i64) -> Result<PathBuf, String> {
+    let dir = quarantine_dir(db, activity_id);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create quarantine folder: {}", e))?;
+
+    let file_name = Path::new(stored_path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let target = dir.join(format!("{}_{}", chrono::Utc::now().format("%Y%m%d%H%M%S"), file_name));
+    std::fs::copy(path, &target).map_err(|e| format!("Failed to copy to quarantine: {}", e))?;
+    std::fs::remove_file(path).map_err(|e| format!("Failed to remove from device: {}", e))?;
+
+    Ok(target)
+}
+
+/// Aplicar el bloqueo a un archivo recién escrito. Devuelve true si se ha eliminado de la unidad, para que el
+/// watcher no lo registre como copia normal; si no se ha podido, se registra y se reintenta con el siguiente evento.
+/// `stored_path` es la ruta tal como se guarda (anonimizada si el dispositivo lo pide), la que va en la alerta y la auditoría.
+pub fn enforce(
+    db: &Arc<Database>,
+    app_handle: Option<&AppHandle>,
+    path: &Path,
+    stored_path: &str,
+    device_id: &str,
+    activity_id: i64,
+) -> bool {
+    let settings = WriteBlockSettings::load(db);
+    if !settings.enabled {
+        return false;
+    }
+    let reason = match settings.matches(path) {
+        Some(reason) => reason,
+        None => return false,
+    };
+
+    // Copiarlo a medio escribir dejaría en la cuarentena un archivo incompleto
+    if !wait_until_stable(path) {
+        println!("[WriteBlock] {} is still being written, will retry", stored_path);
+        return false;
+    }
+
+    let file_path = stored_path.to_string();
+    let result = quarantine(db, path, stored_path, activity_id);
+    let mut details = serde_json::json!({ "path": file_path, "reason": reason });
+    match &result {
+        Ok(target) => {
+            println!("[WriteBlock] Removed {} ({}) from {}", file_path, reason, device_id);
+            details["quarantine_path"] = serde_json::json!(target.to_string_lossy());
+        }
+        Err(e) => {
+            println!("[WriteBlock] Could not block {}: {}", file_path, e);
+            details["error"] = serde_json::json!(e);
+        }
+    }
+    audit::record(db, WRITE_BLOCK_AUDIT_ACTION, Some(device_id), &details);
+
+    raise_alert(
+        db,
+        app_handle,
+        Some(device_id),
+        Some(activity_id),
+        WRITE_BLOCK_ALERT,
+        AlertSeverity::High,
+        Message::new(
+            if result.is_ok() { "alert.write_blocked" } else { "alert.write_block_failed" },
+            serde_json::json!({ "device": device_id, "file": file_path, "reason": reason }),
+        ),
+    );
+    result.is_ok()
+}
@@ -0,0 +1,102 @@
+use std::path::Path;
+
+/// Proceso que tiene abierto el archivo recién creado o modificado (p.ej. "explorer.exe" al copiar,
+/// "powershell.exe" al escribirlo un script). Se consulta al llegar el evento del watcher, así que
+/// una escritura muy corta que ya ha cerrado el archivo queda sin atribuir (`None`).
+#[cfg(windows)]
+pub fn writer_of(path: &Path) -> Option<String> {
+    windows::processes_using(path)
+        .into_iter()
+        // El propio monitor abre el archivo (hashes, bloqueo de escritura): no es quien lo escribió
+        .find(|&pid| pid != std::process::id())
+        .and_then(windows::process_name)
+}
+
+#[cfg(not(windows))]
+pub fn writer_of(_path: &Path) -> Option<String> {
+    None
+}
+
+// Restart Manager: la misma consulta que usa el sistema para decir qué programa tiene un archivo en uso
+#[cfg(windows)]
+mod windows {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{CloseHandle, ERROR_MORE_DATA, ERROR_SUCCESS};
+    use windows_sys::Win32::System::RestartManager::{
+        RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY, RM_PROCESS_INFO,
+    };
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // Procesos a la vez; con más se repite la consulta con el tamaño que indica el sistema
+    const INITIAL_PROCESSES: usize = 8;
+
+    struct Session(u32);
+
+    impl Drop for Session {
+        fn drop(&mut self) {
+            // SAFETY: la sesión se abrió en `processes_using` y solo se cierra aquí
+            unsafe { RmEndSession(self.0) };
+        }
+    }
+
+    pub fn processes_using(path: &Path) -> Vec<u32> {
+        let mut handle = 0u32;
+        let mut key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+        // SAFETY: `key` tiene el tamaño que exige RmStartSession
+        if unsafe { RmStartSession(&mut handle, 0, key.as_mut_ptr()) } != ERROR_SUCCESS {
+            return Vec::new();
+        }
+        let session = Session(handle);
+
+        let file: Vec<u16> = path.as_os_str().encode_wide().chain(std::iter::once(0)).collect();
+        let files = [file.as_ptr()];
+        // SAFETY: un solo nombre terminado en NUL, sin aplicaciones ni servicios
+        let registered = unsafe {
+            RmRegisterResources(session.0, 1, files.as_ptr(), 0, std::ptr::null(), 0, std::ptr::null())
+        };
+        if registered != ERROR_SUCCESS {
+            return Vec::new();
+        }
+
+        let mut capacity = INITIAL_PROCESSES;
+        loop {
+            // SAFETY: RM_PROCESS_INFO es una estructura plana; la lista se rellena como mucho hasta `capacity`
+            let mut processes: Vec<RM_PROCESS_INFO> = vec![unsafe { std::mem::zeroed() }; capacity];
+            let mut needed = 0u32;
+            let mut count = capacity as u32;
+            let mut reasons = 0u32;
+            let result = unsafe { RmGetList(session.0, &mut needed, &mut count, processes.as_mut_ptr(), &mut reasons) };
+            match result {
+                ERROR_SUCCESS => {
+                    return processes[..count as usize].iter().map(|p| p.Process.dwProcessId).collect();
+                }
+                ERROR_MORE_DATA if needed as usize > capacity => capacity = needed as usize,
+                _ => return Vec::new(),
+            }
+        }
+    }
+
+    // Nombre del ejecutable ("explorer.exe") a partir de su ruta completa
+    pub fn process_name(pid: u32) -> Option<String> {
+        // SAFETY: solo se pide información limitada; el handle se cierra antes de salir
+        let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if process.is_null() {
+            return None;
+        }
+        let mut buffer = [0u16; 1024];
+        let mut length = buffer.len() as u32;
+        let ok = unsafe {
+            let ok = QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buffer.as_mut_ptr(), &mut length);
+            CloseHandle(process);
+            ok
+        };
+        if ok == 0 {
+            return None;
+        }
+        let image = String::from_utf16_lossy(&buffer[..length as usize]);
+        Path::new(&image).file_name().map(|name| name.to_string_lossy().to_string())
+    }
+}